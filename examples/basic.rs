@@ -7,15 +7,12 @@ use salvo_express_session::{ExpressSessionHandler, MemoryStore, SessionConfig, S
 async fn index(depot: &mut Depot) -> String {
     let session = depot.session_mut().expect("Session not found");
 
-    // Get current view count
-    let views: i32 = session.get("views").unwrap_or(0);
-
     // Increment view count
-    session.set("views", views + 1);
+    let views = session.incr("views", 1);
 
     format!(
         "Hello! You have viewed this page {} time(s).\nSession ID: {}",
-        views + 1,
+        views,
         session.id()
     )
 }
@@ -0,0 +1,224 @@
+//! Session management endpoints for operators
+//!
+//! [`session_router`] serves, relative to where it is mounted:
+//!
+//! - `GET /?offset=0&limit=50`: page of sessions (ID, user, expiry), sorted by ID
+//! - `GET /{sid}`: one stored session
+//! - `DELETE /{sid}`: destroy a session
+//! - `POST /purge`: remove expired sessions the store still lists
+//!
+//! The router performs no authorization of its own; always mount it behind
+//! a guard that only lets administrators through:
+//!
+//! ```rust,ignore
+//! let router = Router::new()
+//!     .hoop(session_handler)
+//!     .push(
+//!         Router::with_path("admin/sessions")
+//!             .hoop(RequireLogin::new())
+//!             .hoop(require_admin)
+//!             .push(admin::session_router(store.clone())),
+//!     );
+//! ```
+
+use std::sync::Arc;
+
+use salvo_core::http::StatusCode;
+use salvo_core::writing::Json;
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response, Router};
+use serde_json::json;
+
+use crate::auth::user_key;
+use crate::error::SessionError;
+use crate::store::{SessionStore, BATCH_SIZE};
+
+/// Page size when the request doesn't give a limit
+const DEFAULT_LIMIT: usize = 50;
+
+/// Largest accepted page size
+const MAX_LIMIT: usize = 500;
+
+/// Build the session management router over `store`
+///
+/// Built on [`SessionStore::ids`], [`SessionStore::get`] and
+/// [`SessionStore::destroy`]; listing and purging need a store that
+/// implements `ids`.
+pub fn session_router<S: SessionStore>(store: S) -> Router {
+    let store: Arc<dyn SessionStore> = Arc::new(store);
+    let endpoint = |action| SessionAdmin {
+        store: Arc::clone(&store),
+        action,
+    };
+
+    Router::new()
+        .get(endpoint(Action::List))
+        .push(Router::with_path("purge").post(endpoint(Action::Purge)))
+        .push(
+            Router::with_path("{sid}")
+                .get(endpoint(Action::Inspect))
+                .delete(endpoint(Action::Delete)),
+        )
+}
+
+/// Operation served by a [`SessionAdmin`] endpoint
+#[derive(Clone, Copy)]
+enum Action {
+    List,
+    Inspect,
+    Delete,
+    Purge,
+}
+
+/// Handler for one of the management endpoints
+struct SessionAdmin {
+    store: Arc<dyn SessionStore>,
+    action: Action,
+}
+
+impl SessionAdmin {
+    /// List a page of sessions
+    async fn list(&self, req: &Request, res: &mut Response) -> Result<(), SessionError> {
+        let offset = req.query::<usize>("offset").unwrap_or(0);
+        let limit = req
+            .query::<usize>("limit")
+            .unwrap_or(DEFAULT_LIMIT)
+            .min(MAX_LIMIT);
+
+        let mut ids = self.store.ids().await?;
+        ids.sort();
+
+        let page: Vec<String> = ids.iter().skip(offset).take(limit).cloned().collect();
+        let sessions: Vec<_> = page
+            .iter()
+            .zip(self.store.get_many(&page).await?)
+            .filter_map(|(sid, data)| {
+                data.map(|data| {
+                    json!({
+                        "id": sid,
+                        "user": user_key(&data),
+                        "expires": data.cookie.expires,
+                    })
+                })
+            })
+            .collect();
+
+        res.render(Json(json!({
+            "total": ids.len(),
+            "offset": offset,
+            "limit": limit,
+            "sessions": sessions,
+        })));
+        Ok(())
+    }
+
+    /// Show one stored session
+    async fn inspect(&self, req: &Request, res: &mut Response) -> Result<(), SessionError> {
+        let sid = req.param::<String>("sid").unwrap_or_default();
+        match self.store.get(&sid).await? {
+            Some(data) => res.render(Json(data)),
+            None => {
+                res.status_code(StatusCode::NOT_FOUND);
+            }
+        }
+        Ok(())
+    }
+
+    /// Destroy one session
+    async fn delete(&self, req: &Request, res: &mut Response) -> Result<(), SessionError> {
+        let sid = req.param::<String>("sid").unwrap_or_default();
+        self.store.destroy(&sid).await?;
+        res.status_code(StatusCode::NO_CONTENT);
+        Ok(())
+    }
+
+    /// Remove listed sessions the store no longer returns (expired)
+    async fn purge(&self, res: &mut Response) -> Result<(), SessionError> {
+        let mut purged = 0;
+        for batch in self.store.ids().await?.chunks(BATCH_SIZE) {
+            let sessions = self.store.get_many(batch).await?;
+            for (sid, data) in batch.iter().zip(sessions) {
+                if data.is_none() {
+                    self.store.destroy(sid).await?;
+                    purged += 1;
+                }
+            }
+        }
+        res.render(Json(json!({ "purged": purged })));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler for SessionAdmin {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        _depot: &mut Depot,
+        res: &mut Response,
+        _ctrl: &mut FlowCtrl,
+    ) {
+        let result = match self.action {
+            Action::List => self.list(req, res).await,
+            Action::Inspect => self.inspect(req, res).await,
+            Action::Delete => self.delete(req, res).await,
+            Action::Purge => self.purge(res).await,
+        };
+        if let Err(e) = result {
+            tracing::error!("Session admin request failed: {}", e);
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::StatusCode;
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_core::Service;
+
+    use super::session_router;
+    use crate::session::SessionData;
+    use crate::store::{MemoryStore, SessionStore};
+
+    #[tokio::test]
+    async fn test_session_router() {
+        let store = MemoryStore::new();
+        for sid in ["a", "b", "c"] {
+            store
+                .set(sid, &SessionData::new(3600), Some(3600))
+                .await
+                .unwrap();
+        }
+        store
+            .set("gone", &SessionData::new(0), Some(0))
+            .await
+            .unwrap();
+        let service = Service::new(session_router(store.clone()));
+
+        let page: serde_json::Value = TestClient::get("http://127.0.0.1/?offset=1&limit=2")
+            .send(&service)
+            .await
+            .take_json()
+            .await
+            .unwrap();
+        assert_eq!(page["total"], 3);
+        assert_eq!(page["sessions"][0]["id"], "b");
+        assert_eq!(page["sessions"].as_array().unwrap().len(), 2);
+
+        let res = TestClient::delete("http://127.0.0.1/a")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::NO_CONTENT));
+        let res = TestClient::get("http://127.0.0.1/a").send(&service).await;
+        assert_eq!(res.status_code, Some(StatusCode::NOT_FOUND));
+
+        let purged: serde_json::Value = TestClient::post("http://127.0.0.1/purge")
+            .send(&service)
+            .await
+            .take_json()
+            .await
+            .unwrap();
+        // MemoryStore already drops expired sessions when listing
+        assert_eq!(purged["purged"], 0);
+    }
+}
@@ -0,0 +1,186 @@
+//! Session analytics for capacity planning
+//!
+//! [`SessionAnalytics`] samples the store and reports the number of active
+//! sessions, how fast new ones are created and how old they are.
+//!
+//! ```rust,ignore
+//! let analytics = SessionAnalytics::new(store.clone()).start(Duration::from_secs(60));
+//!
+//! // Later, e.g. in a status endpoint
+//! if let Some(stats) = analytics.latest() {
+//!     println!("{} active, {:.1}/min", stats.active, stats.created_per_minute);
+//! }
+//! ```
+//!
+//! Sampling loads every session through [`SessionStore::all`], so keep the
+//! interval long for large stores. Sessions without a creation time (e.g.
+//! created by express-session) count as active but not towards creation
+//! rate or ages.
+
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+
+use crate::error::SessionError;
+use crate::store::SessionStore;
+
+/// Upper bounds of the age distribution buckets
+const AGE_BOUNDS: [Duration; 5] = [
+    Duration::from_secs(60),
+    Duration::from_secs(10 * 60),
+    Duration::from_secs(60 * 60),
+    Duration::from_secs(24 * 60 * 60),
+    Duration::from_secs(7 * 24 * 60 * 60),
+];
+
+/// Number of sessions up to a given age
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AgeBucket {
+    /// Upper bound of the bucket, exclusive; None for older sessions
+    pub max_age: Option<Duration>,
+    /// Sessions in the bucket
+    pub count: usize,
+}
+
+/// One sample of the store
+#[derive(Clone, Debug)]
+pub struct SessionStats {
+    /// When the sample was taken
+    pub sampled_at: DateTime<Utc>,
+    /// Sessions in the store
+    pub active: usize,
+    /// Average number of sessions created per minute over the window
+    pub created_per_minute: f64,
+    /// Sessions by age, youngest bucket first
+    pub ages: Vec<AgeBucket>,
+}
+
+/// Periodic sampler of session statistics
+pub struct SessionAnalytics {
+    store: Arc<dyn SessionStore>,
+    window: Duration,
+    latest: RwLock<Option<SessionStats>>,
+}
+
+impl SessionAnalytics {
+    /// Create a sampler over `store`, with a 10 minute creation rate window
+    pub fn new<S: SessionStore>(store: S) -> Self {
+        Self {
+            store: Arc::new(store),
+            window: Duration::from_secs(10 * 60),
+            latest: RwLock::new(None),
+        }
+    }
+
+    /// Set the window the creation rate is averaged over (default: 10 minutes)
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sample the store every `interval` in a background task
+    ///
+    /// The task stops once the returned handle and all its clones are dropped.
+    pub fn start(self, interval: Duration) -> Arc<Self> {
+        let analytics = Arc::new(self);
+        let weak = Arc::downgrade(&analytics);
+        tokio::spawn(Self::run(weak, interval));
+        analytics
+    }
+
+    /// Sampling loop of [`Self::start`]
+    async fn run(analytics: Weak<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let Some(analytics) = analytics.upgrade() else {
+                return;
+            };
+            if let Err(e) = analytics.sample().await {
+                tracing::error!("Failed to sample sessions: {}", e);
+            }
+        }
+    }
+
+    /// Take a sample now, which also becomes [`Self::latest`]
+    pub async fn sample(&self) -> Result<SessionStats, SessionError> {
+        let sessions = self.store.all().await?;
+        let now = Utc::now();
+
+        let mut ages: Vec<AgeBucket> = AGE_BOUNDS
+            .iter()
+            .map(|bound| AgeBucket {
+                max_age: Some(*bound),
+                count: 0,
+            })
+            .chain(std::iter::once(AgeBucket {
+                max_age: None,
+                count: 0,
+            }))
+            .collect();
+
+        let mut created = 0;
+        for created_at in sessions.iter().filter_map(|data| data.created_at) {
+            let age = (now - created_at).to_std().unwrap_or_default();
+            if age < self.window {
+                created += 1;
+            }
+            let bucket = AGE_BOUNDS
+                .iter()
+                .position(|bound| age < *bound)
+                .unwrap_or(AGE_BOUNDS.len());
+            ages[bucket].count += 1;
+        }
+
+        let minutes = self.window.as_secs_f64() / 60.0;
+        let stats = SessionStats {
+            sampled_at: now,
+            active: sessions.len(),
+            created_per_minute: if minutes > 0.0 {
+                created as f64 / minutes
+            } else {
+                0.0
+            },
+            ages,
+        };
+        *self.latest.write() = Some(stats.clone());
+        Ok(stats)
+    }
+
+    /// Most recent sample, if any was taken
+    pub fn latest(&self) -> Option<SessionStats> {
+        self.latest.read().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionData;
+    use crate::store::MemoryStore;
+
+    #[tokio::test]
+    async fn test_sample() {
+        let store = MemoryStore::new();
+        for (sid, age_secs) in [("a", 30), ("b", 120), ("c", 2 * 3600)] {
+            let mut data = SessionData::new(3600);
+            data.created_at = Some(Utc::now() - chrono::Duration::seconds(age_secs));
+            store.set(sid, &data, Some(3600)).await.unwrap();
+        }
+        let mut legacy = SessionData::new(3600);
+        legacy.created_at = None;
+        store.set("legacy", &legacy, Some(3600)).await.unwrap();
+
+        let analytics = SessionAnalytics::new(store).with_window(Duration::from_secs(300));
+        assert!(analytics.latest().is_none());
+
+        let stats = analytics.sample().await.unwrap();
+        assert_eq!(stats.active, 4);
+        assert_eq!(stats.created_per_minute, 2.0 / 5.0);
+        let counts: Vec<usize> = stats.ages.iter().map(|bucket| bucket.count).collect();
+        assert_eq!(counts, vec![1, 1, 0, 1, 0, 0]);
+        assert_eq!(analytics.latest().unwrap().active, 4);
+    }
+}
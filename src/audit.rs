@@ -0,0 +1,223 @@
+//! Audit log of session changes
+//!
+//! With [`ExpressSessionHandler::with_audit`](crate::ExpressSessionHandler::with_audit),
+//! every save that changes session values produces an [`AuditRecord`]: who
+//! (the logged-in user), when, and which keys changed. Records carry a hash
+//! of the session ID, never the ID itself, and no values.
+//!
+//! ```rust,ignore
+//! let session_handler = ExpressSessionHandler::new(store, config)
+//!     .with_audit(FileAuditSink::new("/var/log/app/session-audit.jsonl"));
+//! ```
+//!
+//! A failing sink is logged and never fails the request.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::error::SessionError;
+use crate::session::SessionData;
+use crate::store::SessionStore;
+
+/// Default retention of [`StoreAuditSink`] records: 90 days
+const DEFAULT_RETENTION: u64 = 90 * 24 * 60 * 60;
+
+/// One audited session save
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AuditRecord {
+    /// When the session was saved
+    pub at: DateTime<Utc>,
+    /// Truncated SHA-256 of the session ID
+    pub session: String,
+    /// User logged into the session, as stored by `Session::login`
+    pub user: Option<String>,
+    /// Keys written or removed by the save
+    pub changed: Vec<String>,
+}
+
+/// Destination of audit records
+#[async_trait]
+pub trait AuditSink: Send + Sync + 'static {
+    /// Persist one record
+    async fn record(&self, record: &AuditRecord) -> Result<(), SessionError>;
+}
+
+/// Sink emitting records as `tracing` events with the `session_audit` target
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingAuditSink;
+
+#[async_trait]
+impl AuditSink for TracingAuditSink {
+    async fn record(&self, record: &AuditRecord) -> Result<(), SessionError> {
+        tracing::info!(
+            target: "session_audit",
+            at = %record.at.to_rfc3339(),
+            session = %record.session,
+            user = ?record.user,
+            changed = ?record.changed,
+            "session changed"
+        );
+        Ok(())
+    }
+}
+
+/// Sink appending records to a file, one JSON object per line
+///
+/// The file is opened for every record, so it can be rotated by moving it.
+#[derive(Clone, Debug)]
+pub struct FileAuditSink {
+    path: PathBuf,
+}
+
+impl FileAuditSink {
+    /// Append to the file at `path`, creating it if needed
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl AuditSink for FileAuditSink {
+    async fn record(&self, record: &AuditRecord) -> Result<(), SessionError> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        let path = self.path.clone();
+
+        let written = tokio::task::spawn_blocking(move || {
+            use std::io::Write;
+
+            // One write per line keeps concurrent appends from interleaving
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)?
+                .write_all(line.as_bytes())
+        })
+        .await
+        .map_err(|e| SessionError::StoreError(format!("Audit write panicked: {}", e)))?;
+
+        written.map_err(|source| SessionError::Io {
+            context: format!("Failed to write {}", self.path.display()),
+            source,
+        })
+    }
+}
+
+/// Sink saving records in a [`SessionStore`], expiring after a retention period
+///
+/// Records are keyed by millisecond timestamp and a random suffix. Use a
+/// store (or key prefix) of its own, so records aren't listed among the
+/// sessions.
+pub struct StoreAuditSink {
+    store: Arc<dyn SessionStore>,
+    retention: u64,
+}
+
+impl StoreAuditSink {
+    /// Save records in `store`, kept for 90 days
+    pub fn new<S: SessionStore>(store: S) -> Self {
+        Self {
+            store: Arc::new(store),
+            retention: DEFAULT_RETENTION,
+        }
+    }
+
+    /// Set how long records are kept, in seconds (default: 90 days)
+    pub fn with_retention(mut self, seconds: u64) -> Self {
+        self.retention = seconds;
+        self
+    }
+}
+
+#[async_trait]
+impl AuditSink for StoreAuditSink {
+    async fn record(&self, record: &AuditRecord) -> Result<(), SessionError> {
+        let key = format!(
+            "{}-{}",
+            record.at.timestamp_millis(),
+            Uuid::new_v4().simple()
+        );
+        let mut entry = SessionData::new(self.retention);
+        entry.set("at", record.at);
+        entry.set("session", &record.session);
+        entry.set("user", &record.user);
+        entry.set("changed", &record.changed);
+        self.store.set(&key, &entry, Some(self.retention)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parking_lot::Mutex;
+    use salvo_core::test::TestClient;
+    use salvo_core::{handler, Depot, Router, Service};
+
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::depot_ext::SessionDepotExt;
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::MemoryStore;
+
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<AuditRecord>>>);
+
+    #[async_trait]
+    impl AuditSink for Collect {
+        async fn record(&self, record: &AuditRecord) -> Result<(), SessionError> {
+            self.0.lock().push(record.clone());
+            Ok(())
+        }
+    }
+
+    #[handler]
+    async fn login(depot: &mut Depot) {
+        let session = depot.session_mut().unwrap();
+        session.login(7, serde_json::Value::Null).unwrap();
+        session.set("theme", "dark");
+    }
+
+    #[tokio::test]
+    async fn test_saves_are_audited() {
+        let sink = Collect::default();
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), SessionConfig::new("secret"))
+            .with_audit(sink.clone());
+        let service = Service::new(Router::new().hoop(handler).get(login));
+
+        let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        let sid = res.cookie("connect.sid").unwrap().value().to_string();
+
+        let records = sink.0.lock().clone();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].user.as_deref(), Some("7"));
+        assert_eq!(records[0].changed, vec!["passport", "theme"]);
+        assert_eq!(records[0].session.len(), 16);
+        assert!(!sid.contains(&records[0].session));
+    }
+
+    #[tokio::test]
+    async fn test_file_sink() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", Uuid::new_v4()));
+        let sink = FileAuditSink::new(&path);
+        let record = AuditRecord {
+            at: Utc::now(),
+            session: "0123456789abcdef".to_string(),
+            user: None,
+            changed: vec!["cart".to_string()],
+        };
+        sink.record(&record).await.unwrap();
+        sink.record(&record).await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let parsed: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(parsed["changed"][0], "cart");
+        assert_eq!(parsed["user"], serde_json::Value::Null);
+    }
+}
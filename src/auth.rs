@@ -0,0 +1,202 @@
+//! Session-based authentication helpers
+//!
+//! The logged-in user is stored like [Passport](https://www.passportjs.org/)
+//! does, under `passport.user`, so Node.js services using Passport with the
+//! same sessions see the same user. Claims given at login (name, roles, ...)
+//! are kept next to it under `passport.claims`.
+//!
+//! ```rust,ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Claims {
+//!     name: String,
+//!     admin: bool,
+//! }
+//!
+//! // After checking the credentials
+//! session.login(user.id, Claims { name: user.name, admin: false })?;
+//!
+//! // Later
+//! let claims: Option<Claims> = session.current_user();
+//!
+//! let router = Router::new()
+//!     .hoop(session_handler)
+//!     .push(Router::with_path("account").hoop(RequireLogin::new()).get(account));
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use salvo_core::http::StatusCode;
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
+
+use crate::depot_ext::SessionDepotExt;
+use crate::error::SessionError;
+use crate::session::{Session, SessionData};
+
+/// Session key holding the authentication state, as used by Passport
+const PASSPORT_KEY: &str = "passport";
+
+/// ID of the user logged into a stored session, as a string
+///
+/// Used to index sessions by user; numeric IDs are formatted as numbers,
+/// other non-string IDs as JSON.
+pub(crate) fn user_key(data: &SessionData) -> Option<String> {
+    match data.data.get(PASSPORT_KEY)?.get("user")? {
+        Value::Null => None,
+        Value::String(user) => Some(user.clone()),
+        user => Some(user.to_string()),
+    }
+}
+
+impl Session {
+    /// Log a user in, storing their ID and claims
+    ///
+    /// The session ID is regenerated (keeping the session data) to prevent
+    /// session fixation, so an ID obtained before login is useless after it.
+    pub fn login<U: Serialize, C: Serialize>(
+        &self,
+        user_id: U,
+        claims: C,
+    ) -> Result<(), SessionError> {
+        let user_id = serde_json::to_value(user_id)?;
+        let claims = serde_json::to_value(claims)?;
+
+        let passport = self.namespace(PASSPORT_KEY);
+        passport.set("user", user_id);
+        if claims.is_null() {
+            passport.remove("claims");
+        } else {
+            passport.set("claims", claims);
+        }
+        self.regenerate_keep_data();
+        Ok(())
+    }
+
+    /// Log the user out, like Passport's `req.logout()`
+    ///
+    /// The session is replaced by a fresh, empty one under a new ID, so
+    /// nothing stored while logged in survives. A remember-me token is
+    /// revoked as well.
+    pub fn logout(&self) {
+        self.regenerate_clear();
+        self.forget_me();
+    }
+
+    /// Check if a user is logged in
+    pub fn is_authenticated(&self) -> bool {
+        self.namespace(PASSPORT_KEY)
+            .get::<Value>("user")
+            .is_some_and(|user| !user.is_null())
+    }
+
+    /// Get the ID of the logged-in user
+    pub fn user_id<U: for<'de> Deserialize<'de>>(&self) -> Option<U> {
+        self.namespace(PASSPORT_KEY).get("user")
+    }
+
+    /// Get the claims given at login, if a user is logged in
+    pub fn current_user<C: for<'de> Deserialize<'de>>(&self) -> Option<C> {
+        if !self.is_authenticated() {
+            return None;
+        }
+        self.namespace(PASSPORT_KEY).get("claims")
+    }
+}
+
+/// Middleware rejecting requests without a logged-in user
+///
+/// Answers with 401 Unauthorized by default. Must be mounted after the
+/// session handler.
+#[derive(Clone, Debug)]
+pub struct RequireLogin {
+    status: StatusCode,
+}
+
+impl RequireLogin {
+    /// Create a guard answering with 401 Unauthorized
+    pub fn new() -> Self {
+        Self {
+            status: StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    /// Set the status code for requests without a logged-in user (default: 401)
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+impl Default for RequireLogin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Handler for RequireLogin {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        let session = match depot.session_required() {
+            Ok(session) => session,
+            Err(e) => {
+                tracing::error!("RequireLogin: {}", e);
+                res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                ctrl.skip_rest();
+                return;
+            }
+        };
+
+        if let Err(e) = session.load().await {
+            tracing::error!("Failed to load session: {}", e);
+        }
+        if !session.is_authenticated() {
+            res.status_code(self.status);
+            ctrl.skip_rest();
+            return;
+        }
+
+        ctrl.call_next(req, depot, res).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use crate::session::{Session, SessionData};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Claims {
+        name: String,
+    }
+
+    #[test]
+    fn test_login_logout() {
+        let session = Session::new("sid".to_string(), SessionData::default(), false);
+        session.set("cart", 2);
+        assert!(!session.is_authenticated());
+        assert_eq!(session.current_user::<Claims>(), None);
+
+        let claims = Claims {
+            name: "Alice".to_string(),
+        };
+        session.login(42, &claims).unwrap();
+        assert!(session.should_regenerate());
+        assert!(session.is_authenticated());
+        assert_eq!(session.user_id::<u64>(), Some(42));
+        assert_eq!(session.current_user::<Claims>(), Some(claims));
+        assert_eq!(session.get_path::<u64>("passport.user"), Some(42));
+        assert_eq!(session.get::<i32>("cart"), Some(2));
+
+        session.logout();
+        assert!(!session.is_authenticated());
+        assert_eq!(session.user_id::<u64>(), None);
+        assert!(session.is_empty());
+    }
+}
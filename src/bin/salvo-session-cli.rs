@@ -0,0 +1,232 @@
+//! Inspect and maintain a session store from the command line
+//!
+//! ```text
+//! salvo-session-cli [--redis URL | --snapshot PATH] [--prefix PREFIX] <command>
+//! ```
+//!
+//! Built with the `cli` feature. Connects to Redis (`--redis`, or the
+//! `REDIS_URL` environment variable) or to a `MemoryStore` snapshot file.
+
+use std::process::ExitCode;
+
+use salvo_express_session::{MemoryStore, SessionError, SessionStore};
+use serde_json::Value;
+
+const USAGE: &str = "\
+Usage: salvo-session-cli [OPTIONS] <COMMAND>
+
+Commands:
+  list            List sessions with their user and expiry
+  show <sid>      Print a session as JSON
+  delete <sid>    Destroy a session
+  purge-expired   Remove expired sessions the store still holds
+  count           Print the number of sessions
+
+Options:
+  --redis <URL>       Redis store URL (default: $REDIS_URL)
+  --snapshot <PATH>   MemoryStore snapshot file
+  --prefix <PREFIX>   Session key prefix (default: sess:)
+  -h, --help          Print this help";
+
+/// Store selected on the command line
+#[derive(Debug, PartialEq)]
+enum Backend {
+    Redis(String),
+    Snapshot(String),
+}
+
+/// Subcommand to run
+#[derive(Debug, PartialEq)]
+enum Command {
+    List,
+    Show(String),
+    Delete(String),
+    PurgeExpired,
+    Count,
+}
+
+/// Parsed command line
+#[derive(Debug, PartialEq)]
+struct Args {
+    backend: Backend,
+    prefix: String,
+    command: Command,
+}
+
+/// Parse the arguments (without the program name)
+fn parse_args(
+    args: impl IntoIterator<Item = String>,
+    redis_url: Option<String>,
+) -> Result<Args, String> {
+    let mut args = args.into_iter();
+    let mut backend = None;
+    let mut prefix = "sess:".to_string();
+    let mut command = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--redis" => backend = Some(Backend::Redis(value("--redis")?)),
+            "--snapshot" => backend = Some(Backend::Snapshot(value("--snapshot")?)),
+            "--prefix" => prefix = value("--prefix")?,
+            "list" => command = Some(Command::List),
+            "show" => command = Some(Command::Show(value("show")?)),
+            "delete" => command = Some(Command::Delete(value("delete")?)),
+            "purge-expired" => command = Some(Command::PurgeExpired),
+            "count" => command = Some(Command::Count),
+            other => return Err(format!("unexpected argument {:?}", other)),
+        }
+    }
+
+    let backend = backend
+        .or(redis_url.map(Backend::Redis))
+        .ok_or("no store given: use --redis, --snapshot or REDIS_URL")?;
+    let command = command.ok_or("no command given")?;
+    Ok(Args {
+        backend,
+        prefix,
+        command,
+    })
+}
+
+/// Connect to the selected store
+async fn open_store(
+    backend: &Backend,
+    prefix: &str,
+) -> Result<Box<dyn SessionStore>, SessionError> {
+    match backend {
+        #[cfg(feature = "redis-store")]
+        Backend::Redis(url) => {
+            let store = salvo_express_session::RedisStore::from_url(url)
+                .await?
+                .with_custom_prefix(prefix);
+            store.ping().await?;
+            Ok(Box::new(store))
+        }
+        #[cfg(not(feature = "redis-store"))]
+        Backend::Redis(_) => Err(SessionError::StoreError(
+            "built without the redis-store feature".to_string(),
+        )),
+        Backend::Snapshot(path) => Ok(Box::new(
+            MemoryStore::with_prefix(prefix).with_snapshot(path),
+        )),
+    }
+}
+
+/// Run a command against the store
+async fn run(store: &dyn SessionStore, command: &Command) -> Result<(), SessionError> {
+    match command {
+        Command::List => {
+            let mut ids = store.ids().await?;
+            ids.sort();
+            for sid in ids {
+                let Some(data) = store.get(&sid).await? else {
+                    continue;
+                };
+                let user = match data.get::<Value>("passport").map(|p| p["user"].clone()) {
+                    Some(Value::Null) | None => "-".to_string(),
+                    Some(Value::String(user)) => user,
+                    Some(user) => user.to_string(),
+                };
+                let expires = data
+                    .cookie
+                    .expires
+                    .map(|expires| expires.to_rfc3339())
+                    .unwrap_or_else(|| "-".to_string());
+                println!("{}\t{}\t{}", sid, user, expires);
+            }
+        }
+        Command::Show(sid) => match store.get(sid).await? {
+            Some(data) => println!("{}", serde_json::to_string_pretty(&data)?),
+            None => return Err(SessionError::NotFound),
+        },
+        Command::Delete(sid) => store.destroy(sid).await?,
+        Command::PurgeExpired => {
+            let mut purged = store.prune_expired(chrono::Utc::now(), usize::MAX).await?;
+            // Stores with native expiry may still list sessions they no longer return
+            for sid in store.ids().await? {
+                if store.get(&sid).await?.is_none() {
+                    store.destroy(&sid).await?;
+                    purged += 1;
+                }
+            }
+            println!("{}", purged);
+        }
+        Command::Count => println!("{}", store.length().await?),
+    }
+    // Write back snapshot changes
+    store.flush().await
+}
+
+fn main() -> ExitCode {
+    let raw: Vec<String> = std::env::args().skip(1).collect();
+    if raw.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let args = match parse_args(raw, std::env::var("REDIS_URL").ok()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let runtime = match tokio::runtime::Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("error: failed to start the runtime: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let result = runtime.block_on(async {
+        let store = open_store(&args.backend, &args.prefix).await?;
+        run(store.as_ref(), &args.command).await
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str], redis_url: Option<&str>) -> Result<Args, String> {
+        parse_args(
+            args.iter().map(|arg| arg.to_string()),
+            redis_url.map(String::from),
+        )
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            parse(
+                &["--prefix", "app:", "show", "abc"],
+                Some("redis://localhost")
+            ),
+            Ok(Args {
+                backend: Backend::Redis("redis://localhost".to_string()),
+                prefix: "app:".to_string(),
+                command: Command::Show("abc".to_string()),
+            })
+        );
+        assert_eq!(
+            parse(
+                &["count", "--snapshot", "s.json"],
+                Some("redis://localhost")
+            )
+            .unwrap()
+            .backend,
+            Backend::Snapshot("s.json".to_string())
+        );
+        assert!(parse(&["count"], None).is_err());
+        assert!(parse(&["--snapshot", "s.json", "show"], None).is_err());
+        assert!(parse(&["--snapshot", "s.json", "frobnicate"], None).is_err());
+    }
+}
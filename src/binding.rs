@@ -0,0 +1,218 @@
+//! Client IP binding
+//!
+//! For high-security deployments, [`IpBinding`] records the client IP when a
+//! session is created and refuses to resume the session from an address
+//! outside the same network, so a stolen cookie is useless elsewhere:
+//!
+//! ```rust,ignore
+//! let config = SessionConfig::new("secret").with_ip_binding(
+//!     IpBinding::new()
+//!         .with_trust_proxy(true)
+//!         .with_ipv4_prefix(24)
+//!         .with_ipv6_prefix(64),
+//! );
+//! ```
+//!
+//! The address is stored as `clientIp` next to the session's cookie.
+//! Sessions without one (e.g. created by Node.js) are bound to the address
+//! they are next saved from. Requests whose address can't be determined are
+//! not checked.
+
+use std::net::IpAddr;
+
+use salvo_core::Request;
+use serde::Deserialize;
+
+/// What happens to a session used from outside its bound network
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BindingAction {
+    /// Continue with a new, empty session under a new ID; the stored
+    /// session stays valid for its original client
+    Regenerate,
+    /// Abort the request with 403 Forbidden
+    ///
+    /// Sessions are then loaded before the route even with `lazy_load`, so
+    /// the request can still be aborted.
+    Reject,
+}
+
+/// Binding of sessions to the client IP they were created from
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct IpBinding {
+    /// Whether to take the client IP from `X-Forwarded-For` (default: false)
+    pub trust_proxy: bool,
+
+    /// Leading bits of an IPv4 address that must match (default: 32)
+    pub ipv4_prefix: u8,
+
+    /// Leading bits of an IPv6 address that must match (default: 128)
+    pub ipv6_prefix: u8,
+
+    /// What happens to a session used from another network (default: Regenerate)
+    pub on_mismatch: BindingAction,
+}
+
+impl Default for IpBinding {
+    fn default() -> Self {
+        Self {
+            trust_proxy: false,
+            ipv4_prefix: 32,
+            ipv6_prefix: 128,
+            on_mismatch: BindingAction::Regenerate,
+        }
+    }
+}
+
+impl IpBinding {
+    /// Bind sessions to the exact client IP
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether to take the client IP from `X-Forwarded-For` (default: false)
+    ///
+    /// Like express's `trust proxy`, the left-most address is used; only
+    /// enable this behind a proxy that sets the header.
+    pub fn with_trust_proxy(mut self, trust_proxy: bool) -> Self {
+        self.trust_proxy = trust_proxy;
+        self
+    }
+
+    /// Set the IPv4 prefix length that must match, e.g. 24 for a /24 (default: 32)
+    pub fn with_ipv4_prefix(mut self, bits: u8) -> Self {
+        self.ipv4_prefix = bits.min(32);
+        self
+    }
+
+    /// Set the IPv6 prefix length that must match, e.g. 64 for a /64 (default: 128)
+    pub fn with_ipv6_prefix(mut self, bits: u8) -> Self {
+        self.ipv6_prefix = bits.min(128);
+        self
+    }
+
+    /// Set what happens to a session used from another network (default: Regenerate)
+    pub fn with_on_mismatch(mut self, action: BindingAction) -> Self {
+        self.on_mismatch = action;
+        self
+    }
+
+    /// The client IP of a request, if known
+    pub fn client_ip(&self, req: &Request) -> Option<IpAddr> {
+        client_ip(req, self.trust_proxy)
+    }
+
+    /// Whether `ip` is within the tolerated network around `bound`
+    pub fn matches(&self, bound: IpAddr, ip: IpAddr) -> bool {
+        match (bound.to_canonical(), ip.to_canonical()) {
+            (IpAddr::V4(bound), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32u32.saturating_sub(self.ipv4_prefix.into()));
+                let mask = mask.unwrap_or(0);
+                u32::from(bound) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(bound), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128u32.saturating_sub(self.ipv6_prefix.into()));
+                let mask = mask.unwrap_or(0);
+                u128::from(bound) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The client IP of a request, optionally from the left-most `X-Forwarded-For` entry
+pub(crate) fn client_ip(req: &Request, trust_proxy: bool) -> Option<IpAddr> {
+    let forwarded = trust_proxy
+        .then(|| req.header::<String>("x-forwarded-for"))
+        .flatten()
+        .and_then(|value| value.split(',').next()?.trim().parse().ok());
+    forwarded
+        .or_else(|| req.remote_addr().clone().into_std().map(|addr| addr.ip()))
+        .map(|ip| ip.to_canonical())
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::StatusCode;
+    use salvo_core::test::TestClient;
+    use salvo_core::{handler, Depot, Router, Service};
+
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::depot_ext::SessionDepotExt;
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::{MemoryStore, SessionStore};
+
+    #[test]
+    fn test_matches() {
+        let binding = IpBinding::new();
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+        assert!(binding.matches(ip("10.0.0.1"), ip("10.0.0.1")));
+        assert!(binding.matches(ip("10.0.0.1"), ip("::ffff:10.0.0.1")));
+        assert!(!binding.matches(ip("10.0.0.1"), ip("10.0.0.2")));
+
+        let binding = binding.with_ipv4_prefix(24).with_ipv6_prefix(64);
+        assert!(binding.matches(ip("10.0.0.1"), ip("10.0.0.200")));
+        assert!(!binding.matches(ip("10.0.0.1"), ip("10.0.1.1")));
+        assert!(binding.matches(ip("2001:db8::1"), ip("2001:db8::ffff")));
+        assert!(!binding.matches(ip("2001:db8::1"), ip("2001:db9::1")));
+        assert!(!binding.matches(ip("10.0.0.1"), ip("2001:db8::1")));
+
+        let any = IpBinding::new().with_ipv4_prefix(0);
+        assert!(any.matches(ip("10.0.0.1"), ip("192.168.0.1")));
+    }
+
+    #[handler]
+    async fn login(depot: &mut Depot) {
+        depot.session_mut().unwrap().set("user", "alice");
+    }
+
+    async fn request(service: &Service, ip: &str, cookie: Option<&str>) -> (StatusCode, String) {
+        let mut req = TestClient::get("http://127.0.0.1/").add_header("x-forwarded-for", ip, true);
+        if let Some(cookie) = cookie {
+            req = req.add_header("cookie", cookie, true);
+        }
+        let res = req.send(service).await;
+        let cookie = res.cookie("connect.sid").map(|c| c.stripped().to_string());
+        (res.status_code.unwrap(), cookie.unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn test_sessions_stay_in_their_network() {
+        let binding = IpBinding::new().with_trust_proxy(true).with_ipv4_prefix(24);
+        let store = MemoryStore::new();
+        let config = SessionConfig::new("secret").with_ip_binding(binding.clone());
+        let handler = ExpressSessionHandler::new(store.clone(), config);
+        let service = Service::new(Router::new().hoop(handler).get(login));
+
+        let (_, cookie) = request(&service, "10.0.0.1, 172.16.0.1", None).await;
+        let sid = store.ids().await.unwrap().pop().unwrap();
+        let data = store.get(&sid).await.unwrap().unwrap();
+        assert_eq!(data.client_ip, Some(IpAddr::from([10, 0, 0, 1])));
+
+        // Same /24: resumed, nothing re-issued
+        let (_, reissued) = request(&service, "10.0.0.7", Some(&cookie)).await;
+        assert!(reissued.is_empty() || reissued == cookie);
+        assert_eq!(store.length().await.unwrap(), 1);
+
+        // Elsewhere: a new session, the original stays
+        let (_, reissued) = request(&service, "192.168.0.1", Some(&cookie)).await;
+        assert!(!reissued.is_empty() && reissued != cookie);
+        assert_eq!(store.length().await.unwrap(), 2);
+
+        // Rejected even when sessions are loaded lazily
+        let binding = binding.with_on_mismatch(BindingAction::Reject);
+        for lazy_load in [false, true] {
+            let config = SessionConfig::new("secret")
+                .with_ip_binding(binding.clone())
+                .with_lazy_load(lazy_load);
+            let handler = ExpressSessionHandler::new(store.clone(), config);
+            let service = Service::new(Router::new().hoop(handler).get(login));
+            let (status, reissued) = request(&service, "192.168.0.1", Some(&cookie)).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            assert!(reissued.is_empty() || reissued == cookie);
+        }
+        assert_eq!(store.length().await.unwrap(), 2);
+    }
+}
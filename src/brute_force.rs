@@ -0,0 +1,222 @@
+//! Tracking of invalid cookie signatures
+//!
+//! A client sending session cookies with invalid signatures is either
+//! misconfigured or guessing signatures. [`SignatureGuard`] counts these
+//! failures per client IP in a [`FailureTracker`], calls a hook when a
+//! client reaches the threshold and can answer its further attempts with
+//! 400 Bad Request:
+//!
+//! ```rust,ignore
+//! let guard = SignatureGuard::new(MemoryFailureTracker::new(Duration::from_secs(600)))
+//!     .with_threshold(20)
+//!     .with_reject(true)
+//!     .with_on_threshold(|ip, count| tracing::warn!(%ip, count, "Cookie signature brute force"));
+//! let session_handler = ExpressSessionHandler::new(store, config).with_signature_guard(guard);
+//! ```
+//!
+//! Requests with a valid cookie, or none, are never counted or rejected.
+//! A tracker shared between instances (e.g. backed by Redis `INCR`) counts
+//! attempts spread across a cluster.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+
+use crate::error::SessionError;
+
+/// Tracked clients above which expired windows are pruned
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Callback invoked when a client reaches the failure threshold
+pub type ThresholdHook = Arc<dyn Fn(IpAddr, u64) + Send + Sync>;
+
+/// Counter of signature failures per client
+#[async_trait]
+pub trait FailureTracker: Send + Sync + 'static {
+    /// Record a failure by `ip`, returning its failures in the current window
+    async fn record(&self, ip: IpAddr) -> Result<u64, SessionError>;
+
+    /// Failures by `ip` in the current window
+    async fn count(&self, ip: IpAddr) -> Result<u64, SessionError>;
+}
+
+/// In-memory [`FailureTracker`] counting failures in fixed windows
+pub struct MemoryFailureTracker {
+    window: Duration,
+    failures: Mutex<HashMap<IpAddr, (Instant, u64)>>,
+}
+
+impl MemoryFailureTracker {
+    /// Count failures over windows of `window`
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl FailureTracker for MemoryFailureTracker {
+    async fn record(&self, ip: IpAddr) -> Result<u64, SessionError> {
+        let mut failures = self.failures.lock();
+        if failures.len() >= PRUNE_THRESHOLD {
+            failures.retain(|_, (start, _)| start.elapsed() < self.window);
+        }
+        let entry = failures.entry(ip).or_insert((Instant::now(), 0));
+        if entry.0.elapsed() >= self.window {
+            *entry = (Instant::now(), 0);
+        }
+        entry.1 += 1;
+        Ok(entry.1)
+    }
+
+    async fn count(&self, ip: IpAddr) -> Result<u64, SessionError> {
+        let failures = self.failures.lock();
+        Ok(failures
+            .get(&ip)
+            .filter(|(start, _)| start.elapsed() < self.window)
+            .map_or(0, |(_, count)| *count))
+    }
+}
+
+/// Counts invalid cookie signatures per client and reacts to brute forcing
+pub struct SignatureGuard {
+    tracker: Arc<dyn FailureTracker>,
+    threshold: u64,
+    reject: bool,
+    trust_proxy: bool,
+    on_threshold: Option<ThresholdHook>,
+}
+
+impl SignatureGuard {
+    /// Count failures in `tracker`, with a threshold of 10
+    pub fn new<T: FailureTracker>(tracker: T) -> Self {
+        Self {
+            tracker: Arc::new(tracker),
+            threshold: 10,
+            reject: false,
+            trust_proxy: false,
+            on_threshold: None,
+        }
+    }
+
+    /// Set the failures per window at which a client counts as brute forcing (default: 10)
+    pub fn with_threshold(mut self, threshold: u64) -> Self {
+        self.threshold = threshold.max(1);
+        self
+    }
+
+    /// Set whether to answer failures past the threshold with 400 Bad Request (default: false)
+    pub fn with_reject(mut self, reject: bool) -> Self {
+        self.reject = reject;
+        self
+    }
+
+    /// Set whether to take the client IP from `X-Forwarded-For` (default: false)
+    pub fn with_trust_proxy(mut self, trust_proxy: bool) -> Self {
+        self.trust_proxy = trust_proxy;
+        self
+    }
+
+    /// Call `hook` with the client IP and count when a client reaches the threshold
+    ///
+    /// Called once per client and window.
+    pub fn with_on_threshold<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(IpAddr, u64) + Send + Sync + 'static,
+    {
+        self.on_threshold = Some(Arc::new(hook));
+        self
+    }
+
+    /// Whether to take the client IP from `X-Forwarded-For`
+    pub(crate) fn trust_proxy(&self) -> bool {
+        self.trust_proxy
+    }
+
+    /// Record a signature failure by `ip`, returning whether to reject the request
+    ///
+    /// Tracker errors are logged and never reject.
+    pub(crate) async fn record(&self, ip: IpAddr) -> bool {
+        let count = match self.tracker.record(ip).await {
+            Ok(count) => count,
+            Err(e) => {
+                tracing::error!("Failed to record signature failure: {}", e);
+                return false;
+            }
+        };
+        if count == self.threshold {
+            tracing::warn!(%ip, count, "Client reached the cookie signature failure threshold");
+            if let Some(hook) = &self.on_threshold {
+                hook(ip, count);
+            }
+        }
+        self.reject && count > self.threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use salvo_core::http::StatusCode;
+    use salvo_core::test::TestClient;
+    use salvo_core::{handler, Router, Service};
+
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::MemoryStore;
+
+    #[tokio::test]
+    async fn test_memory_tracker_windows() {
+        let tracker = MemoryFailureTracker::new(Duration::from_millis(50));
+        let ip = IpAddr::from([10, 0, 0, 1]);
+        assert_eq!(tracker.record(ip).await.unwrap(), 1);
+        assert_eq!(tracker.record(ip).await.unwrap(), 2);
+        assert_eq!(tracker.count(ip).await.unwrap(), 2);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(tracker.count(ip).await.unwrap(), 0);
+        assert_eq!(tracker.record(ip).await.unwrap(), 1);
+    }
+
+    #[handler]
+    async fn hello() -> &'static str {
+        "hello"
+    }
+
+    #[tokio::test]
+    async fn test_brute_force_is_rejected() {
+        let reached = Arc::new(AtomicU64::new(0));
+        let hook_reached = Arc::clone(&reached);
+        let guard = SignatureGuard::new(MemoryFailureTracker::new(Duration::from_secs(60)))
+            .with_threshold(2)
+            .with_reject(true)
+            .with_trust_proxy(true)
+            .with_on_threshold(move |_, count| hook_reached.store(count, Ordering::SeqCst));
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), SessionConfig::new("secret"))
+            .with_signature_guard(guard);
+        let service = Service::new(Router::new().hoop(handler).get(hello));
+
+        let attempt = |ip: &'static str| {
+            TestClient::get("http://127.0.0.1/")
+                .add_header("x-forwarded-for", ip, true)
+                .add_header("cookie", "connect.sid=s:guess.c2lnbmF0dXJl", true)
+                .send(&service)
+        };
+        assert_eq!(attempt("10.0.0.1").await.status_code, Some(StatusCode::OK));
+        assert_eq!(attempt("10.0.0.1").await.status_code, Some(StatusCode::OK));
+        assert_eq!(reached.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            attempt("10.0.0.1").await.status_code,
+            Some(StatusCode::BAD_REQUEST)
+        );
+        // Other clients are unaffected
+        assert_eq!(attempt("10.0.0.2").await.status_code, Some(StatusCode::OK));
+    }
+}
@@ -0,0 +1,94 @@
+//! Session cookies on error pages rendered by catchers
+//!
+//! When a request fails, Salvo's [`Catcher`](salvo_core::catcher::Catcher)
+//! renders the error page after the session handler has finished. Catchers
+//! that build a fresh response drop the Set-Cookie headers of the session,
+//! so a session created by the failed request is saved but never reaches
+//! the client. [`RestoreSessionCookies`] re-applies them once the catcher
+//! is done:
+//!
+//! ```rust,ignore
+//! let service = Service::new(router)
+//!     .catcher(Catcher::default().hoop(RestoreSessionCookies).hoop(error_page));
+//! ```
+//!
+//! Mount it as the catcher's first hoop. The session handler leaves its
+//! cookies for it on every error response unless
+//! [`SessionConfig::with_catcher_cookies`](crate::SessionConfig::with_catcher_cookies)
+//! turns this off. Panics unwinding through the session handler skip its
+//! cookie logic altogether; mount `CatchPanic` after the session handler.
+
+use salvo_core::http::cookie::Cookie;
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
+
+/// Cookies the session handler set on an error response
+pub(crate) struct IssuedCookies(pub(crate) Vec<Cookie<'static>>);
+
+/// Catcher hoop re-applying the session cookies of an error response
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RestoreSessionCookies;
+
+#[async_trait]
+impl Handler for RestoreSessionCookies {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        ctrl.call_next(req, depot, res).await;
+        if let Ok(IssuedCookies(cookies)) = depot.obtain::<IssuedCookies>() {
+            for cookie in cookies {
+                res.add_cookie(cookie.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::catcher::Catcher;
+    use salvo_core::http::StatusCode;
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_core::{handler, Router, Service};
+
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::depot_ext::SessionDepotExt;
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::{MemoryStore, SessionStore};
+
+    #[handler]
+    async fn fail(depot: &mut Depot, res: &mut Response) {
+        depot.session_mut().unwrap().set("attempts", 1);
+        res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    /// Error page replacing the whole response
+    #[handler]
+    async fn error_page(res: &mut Response) {
+        *res = Response::new();
+        res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+        res.render("Something went wrong");
+    }
+
+    #[tokio::test]
+    async fn test_cookies_survive_catcher() {
+        for catcher_cookies in [true, false] {
+            let store = MemoryStore::new();
+            let config = SessionConfig::new("secret").with_catcher_cookies(catcher_cookies);
+            let handler = ExpressSessionHandler::new(store.clone(), config);
+            let service = Service::new(Router::new().hoop(handler).get(fail)).catcher(
+                Catcher::default()
+                    .hoop(RestoreSessionCookies)
+                    .hoop(error_page),
+            );
+
+            let mut res = TestClient::get("http://127.0.0.1/").send(&service).await;
+            assert_eq!(res.take_string().await.unwrap(), "Something went wrong");
+            assert_eq!(res.cookie("connect.sid").is_some(), catcher_cookies);
+            assert_eq!(store.length().await.unwrap(), 1);
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Pluggable cookie signing/verification
+//!
+//! The handler delegates turning a session ID into cookie values (and back)
+//! to a [`CookieCodec`]. The default [`ExpressCodec`] produces the
+//! express-session `s:` format; [`KeygripCodec`] produces the cookie-session
+//! format with a separate `<name>.sig` cookie. Custom formats (versioned,
+//! key-id-tagged, alternate HMACs) can be plugged in by implementing the trait.
+//!
+//! Codecs receive the handler's secrets as [`SigningKey`]s, keyed once when
+//! the handler is built.
+
+use std::borrow::Cow;
+
+use crate::secret::SigningKey;
+use crate::session::SessionData;
+use crate::{cookie_signature, keygrip};
+
+/// Cookie values produced by a codec for a single session ID
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedCookie {
+    /// Value of the session cookie itself
+    pub value: String,
+    /// Value of the companion signature cookie, for codecs that use one
+    pub signature: Option<String>,
+}
+
+impl SignedCookie {
+    /// Create a signed cookie without a companion signature cookie
+    pub fn new<S: Into<String>>(value: S) -> Self {
+        Self {
+            value: value.into(),
+            signature: None,
+        }
+    }
+
+    /// Create a signed cookie with a companion signature cookie
+    pub fn with_signature<S: Into<String>, T: Into<String>>(value: S, signature: T) -> Self {
+        Self {
+            value: value.into(),
+            signature: Some(signature.into()),
+        }
+    }
+}
+
+/// Trait for cookie signing formats
+///
+/// `value` in [`CookieCodec::unsign`] is the raw cookie value as sent by the
+/// client (still URL encoded).
+pub trait CookieCodec: Send + Sync + 'static {
+    /// Sign a session ID for the cookie `name` using `key`
+    fn sign(&self, name: &str, value: &str, key: &SigningKey) -> SignedCookie;
+
+    /// Verify a cookie against any of the keys, returning the session ID
+    fn unsign(&self, name: &str, cookie: &SignedCookie, keys: &[SigningKey]) -> Option<String>;
+
+    /// Name of the companion signature cookie, if this codec uses one
+    fn signature_cookie_name(&self, _name: &str) -> Option<String> {
+        None
+    }
+
+    /// Sign a session ID with access to the session's data (optional)
+    ///
+    /// For codecs embedding session values in the cookie; the default calls
+    /// [`Self::sign`].
+    fn sign_session(
+        &self,
+        name: &str,
+        value: &str,
+        key: &SigningKey,
+        session: &SessionData,
+    ) -> SignedCookie {
+        let _ = session;
+        self.sign(name, value, key)
+    }
+
+    /// Whether a change to these session keys requires re-issuing the cookie (optional)
+    fn reissue_on_change(&self, _changed: &[String]) -> bool {
+        false
+    }
+}
+
+/// express-session format: `s:` + value + `.` + HMAC-SHA256 signature
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExpressCodec;
+
+impl CookieCodec for ExpressCodec {
+    fn sign(&self, _name: &str, value: &str, key: &SigningKey) -> SignedCookie {
+        SignedCookie::new(cookie_signature::sign_with_key(value, key))
+    }
+
+    fn unsign(&self, _name: &str, cookie: &SignedCookie, keys: &[SigningKey]) -> Option<String> {
+        // Most cookies need no decoding; skip the copy for them
+        let value = urlencoding::decode(&cookie.value).unwrap_or(Cow::Borrowed(&cookie.value));
+        cookie_signature::unsign_with_keys(&value, keys)
+    }
+}
+
+/// cookie-session/Keygrip format: raw value plus a separate `<name>.sig`
+/// cookie holding an HMAC-SHA1 signature of `<name>=<value>`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KeygripCodec;
+
+impl CookieCodec for KeygripCodec {
+    fn sign(&self, name: &str, value: &str, key: &SigningKey) -> SignedCookie {
+        SignedCookie::with_signature(value, keygrip::sign_cookie(name, value, key))
+    }
+
+    fn unsign(&self, name: &str, cookie: &SignedCookie, keys: &[SigningKey]) -> Option<String> {
+        let signature = cookie.signature.as_deref()?;
+        // Keygrip signs the raw cookie value, before URL decoding
+        if keygrip::verify_cookie(name, &cookie.value, signature, keys) {
+            Some(url_decode(&cookie.value))
+        } else {
+            None
+        }
+    }
+
+    fn signature_cookie_name(&self, name: &str) -> Option<String> {
+        Some(keygrip::signature_cookie_name(name))
+    }
+}
+
+/// Codec that encrypts the session ID before handing it to an inner codec
+///
+/// The session ID is sealed with AES-256-GCM, so the value is opaque to
+/// clients and intermediaries. The inner codec (default: [`ExpressCodec`])
+/// still signs the ciphertext. Each secret yields separate encryption and
+/// signing keys through HKDF-SHA256, so the cipher and the signature never
+/// share a key.
+///
+/// ```rust,ignore
+/// use salvo_express_session::{EncryptedCodec, ExpressCodec};
+///
+/// let handler = ExpressSessionHandler::new(store, config)
+///     .with_codec(EncryptedCodec::new(ExpressCodec));
+/// ```
+#[cfg(feature = "encryption")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EncryptedCodec<C: CookieCodec = ExpressCodec> {
+    inner: C,
+}
+
+#[cfg(feature = "encryption")]
+impl<C: CookieCodec> EncryptedCodec<C> {
+    /// Wrap an inner codec with encryption
+    pub fn new(inner: C) -> Self {
+        Self { inner }
+    }
+
+    /// Derive a 256-bit key for one purpose from a secret
+    fn derive_key(secret: &str, info: &[u8]) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        hkdf::Hkdf::<sha2::Sha256>::new(None, secret.as_bytes())
+            .expand(info, &mut key)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        key
+    }
+
+    /// Derive the AEAD cipher for a secret
+    fn cipher(secret: &str) -> aes_gcm::Aes256Gcm {
+        use aes_gcm::KeyInit;
+
+        aes_gcm::Aes256Gcm::new(&Self::derive_key(secret, b"session-enc").into())
+    }
+
+    /// Derive the key the inner codec signs with
+    ///
+    /// Derived per call, like the cipher, since HKDF already dominates.
+    fn mac_key(key: &SigningKey) -> SigningKey {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+        SigningKey::new(URL_SAFE_NO_PAD.encode(Self::derive_key(key.secret(), b"session-mac")))
+    }
+
+    /// Encrypt a value, returning base64url(nonce || ciphertext)
+    fn encrypt(value: &str, secret: &str) -> String {
+        use aes_gcm::aead::{Aead, AeadCore, OsRng};
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+        let nonce = aes_gcm::Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = Self::cipher(secret)
+            .encrypt(&nonce, value.as_bytes())
+            .expect("AES-GCM encryption of a session ID cannot fail");
+
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        URL_SAFE_NO_PAD.encode(payload)
+    }
+
+    /// Decrypt a value produced by [`Self::encrypt`] with any of the keys
+    fn decrypt(token: &str, keys: &[SigningKey]) -> Option<String> {
+        use aes_gcm::aead::Aead;
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+        let payload = URL_SAFE_NO_PAD.decode(token).ok()?;
+        // 12-byte nonce followed by at least the 16-byte tag
+        if payload.len() < 12 + 16 {
+            return None;
+        }
+        let (nonce, ciphertext) = payload.split_at(12);
+        let nonce = aes_gcm::Nonce::from_slice(nonce);
+
+        keys.iter().find_map(|key| {
+            let plaintext = Self::cipher(key.secret()).decrypt(nonce, ciphertext).ok()?;
+            String::from_utf8(plaintext).ok()
+        })
+    }
+}
+
+#[cfg(feature = "encryption")]
+impl<C: CookieCodec> CookieCodec for EncryptedCodec<C> {
+    fn sign(&self, name: &str, value: &str, key: &SigningKey) -> SignedCookie {
+        let token = Self::encrypt(value, key.secret());
+        self.inner.sign(name, &token, &Self::mac_key(key))
+    }
+
+    fn unsign(&self, name: &str, cookie: &SignedCookie, keys: &[SigningKey]) -> Option<String> {
+        let mac_keys: Vec<SigningKey> = keys.iter().map(Self::mac_key).collect();
+        let token = self.inner.unsign(name, cookie, &mac_keys)?;
+        Self::decrypt(&token, keys)
+    }
+
+    fn signature_cookie_name(&self, name: &str) -> Option<String> {
+        self.inner.signature_cookie_name(name)
+    }
+}
+
+/// URL decode a cookie value (cookies are URL encoded)
+pub(crate) fn url_decode(value: &str) -> String {
+    match urlencoding::decode(value) {
+        Ok(d) => d.to_string(),
+        Err(_) => value.to_string(),
+    }
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        let codec = EncryptedCodec::new(ExpressCodec);
+        let keys = SigningKey::from_secrets(["secret"]);
+
+        let signed = codec.sign("connect.sid", "session-id", &keys[0]);
+        assert!(!signed.value.contains("session-id"));
+        assert_eq!(
+            codec.unsign("connect.sid", &signed, &keys),
+            Some("session-id".to_string())
+        );
+        assert_eq!(
+            codec.unsign("connect.sid", &signed, &SigningKey::from_secrets(["other"])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_encryption_and_signing_keys_differ() {
+        type Codec = EncryptedCodec<ExpressCodec>;
+        assert_ne!(
+            Codec::derive_key("secret", b"session-enc"),
+            Codec::derive_key("secret", b"session-mac")
+        );
+
+        // The ciphertext isn't signed with the raw secret
+        let keys = SigningKey::from_secrets(["secret"]);
+        let signed = Codec::default().sign("connect.sid", "session-id", &keys[0]);
+        assert_eq!(ExpressCodec.unsign("connect.sid", &signed, &keys), None);
+    }
+}
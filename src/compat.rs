@@ -0,0 +1,389 @@
+//! Migration from `salvo_session` / async-session
+//!
+//! [`AsyncSessionCompat`] reads the cookie and stored format of
+//! [async-session](https://docs.rs/async-session), as used by
+//! `salvo_session`. A request without an express-session cookie but with a
+//! valid `salvo_session` cookie gets a new session holding the old
+//! session's values; the old cookie is expired. Users keep their sessions
+//! across the switch without logging in again.
+//!
+//! ```rust,ignore
+//! let compat = AsyncSessionCompat::new(
+//!     RedisAsyncSessionSource::new(conn, "async-session:"),
+//!     old_secret.as_bytes(),
+//! );
+//! let session_handler = ExpressSessionHandler::new(store, config).with_async_session_compat(compat);
+//! ```
+//!
+//! async-session stores a session under an ID derived from the cookie value
+//! (`base64(blake3(base64_decode(cookie)))`) as JSON with the values
+//! themselves encoded as JSON strings:
+//!
+//! ```text
+//! {"id":"...","expiry":"2024-06-01T12:00:00Z","data":{"user_id":"42","name":"\"alice\""}}
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use salvo_core::http::cookie::{self, time::Duration as CookieDuration};
+use salvo_core::{Request, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::error::SessionError;
+
+/// Length of the base64 HMAC-SHA256 digest prefixed to signed cookie values
+const DIGEST_LEN: usize = 44;
+
+/// Where async-session stored its sessions
+#[async_trait]
+pub trait AsyncSessionSource: Send + Sync + 'static {
+    /// Get the serialized async-session `Session` stored under `id`
+    async fn load(&self, id: &str) -> Result<Option<String>, SessionError>;
+}
+
+/// Session as serialized by async-session
+#[derive(Serialize, Deserialize)]
+pub(crate) struct AsyncSession {
+    pub(crate) id: String,
+    pub(crate) expiry: Option<DateTime<Utc>>,
+    /// Values, each encoded as a JSON string
+    #[serde(default)]
+    pub(crate) data: HashMap<String, String>,
+}
+
+impl AsyncSession {
+    /// Whether the session expired by now
+    pub(crate) fn is_expired(&self) -> bool {
+        self.expiry.is_some_and(|expiry| expiry <= Utc::now())
+    }
+
+    /// Decode the values; values that aren't JSON are kept as strings
+    pub(crate) fn into_values(self) -> impl Iterator<Item = (String, Value)> {
+        self.data.into_iter().map(|(key, value)| {
+            let value = serde_json::from_str(&value).unwrap_or(Value::String(value));
+            (key, value)
+        })
+    }
+}
+
+/// Reader of `salvo_session` cookies and async-session records
+pub struct AsyncSessionCompat {
+    source: Arc<dyn AsyncSessionSource>,
+    signing_key: Vec<u8>,
+    cookie_name: String,
+    cookie_path: String,
+}
+
+impl AsyncSessionCompat {
+    /// Read sessions from `source`, verifying cookies with the `salvo_session` secret
+    ///
+    /// `secret` is the (at least 64 byte) key given to `salvo_session`;
+    /// cookies are signed with its first 32 bytes.
+    pub fn new<S: AsyncSessionSource>(source: S, secret: &[u8]) -> Self {
+        Self {
+            source: Arc::new(source),
+            signing_key: secret[..secret.len().min(32)].to_vec(),
+            cookie_name: "salvo.session.id".to_string(),
+            cookie_path: "/".to_string(),
+        }
+    }
+
+    /// Set the old cookie name (default: "salvo.session.id")
+    pub fn with_cookie_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.cookie_name = name.into();
+        self
+    }
+
+    /// Set the old cookie path, used to expire it (default: "/")
+    pub fn with_cookie_path<S: Into<String>>(mut self, path: S) -> Self {
+        self.cookie_path = path.into();
+        self
+    }
+
+    /// Verify a signed cookie value, returning the value without its signature
+    fn unsign(&self, signed: &str) -> Option<String> {
+        if !signed.is_char_boundary(DIGEST_LEN) {
+            return None;
+        }
+        let (digest, value) = signed.split_at(DIGEST_LEN);
+        let digest = STANDARD.decode(digest).ok()?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.signing_key).ok()?;
+        mac.update(value.as_bytes());
+        mac.verify_slice(&digest).ok()?;
+        Some(value.to_string())
+    }
+
+    /// Load the values of the request's old session
+    ///
+    /// Returns None for a missing, tampered or expired session. Values stored
+    /// as JSON are decoded; others are kept as strings.
+    pub(crate) async fn migrate(
+        &self,
+        req: &Request,
+    ) -> Result<Option<Vec<(String, Value)>>, SessionError> {
+        let Some(signed) = req.cookie(&self.cookie_name) else {
+            return Ok(None);
+        };
+        let Some(id) = self
+            .unsign(signed.value())
+            .and_then(|value| session_id(&value))
+        else {
+            tracing::warn!("Ignoring salvo_session cookie with an invalid signature");
+            return Ok(None);
+        };
+
+        let Some(raw) = self.source.load(&id).await? else {
+            return Ok(None);
+        };
+        let session: AsyncSession = serde_json::from_str(&raw)?;
+        if session.is_expired() {
+            return Ok(None);
+        }
+        Ok(Some(session.into_values().collect()))
+    }
+
+    /// Whether the request carries an old session cookie
+    pub(crate) fn has_cookie(&self, req: &Request) -> bool {
+        req.cookie(&self.cookie_name).is_some()
+    }
+
+    /// Tell the client to drop the old cookie
+    pub(crate) fn expire_cookie(&self, res: &mut Response) {
+        let cookie = cookie::Cookie::build(self.cookie_name.clone())
+            .path(self.cookie_path.clone())
+            .max_age(CookieDuration::ZERO)
+            .build();
+        res.add_cookie(cookie);
+    }
+}
+
+/// Store ID of an async-session cookie value: `base64(blake3(base64_decode(value)))`
+fn session_id(cookie_value: &str) -> Option<String> {
+    let decoded = STANDARD.decode(cookie_value).ok()?;
+    Some(STANDARD.encode(blake3::hash(&decoded)?))
+}
+
+/// async-session records in Redis, as written by async-redis-session
+#[cfg(feature = "redis-store")]
+pub struct RedisAsyncSessionSource {
+    conn: redis::aio::ConnectionManager,
+    prefix: String,
+}
+
+#[cfg(feature = "redis-store")]
+impl RedisAsyncSessionSource {
+    /// Read sessions stored under `prefix + id` (async-redis-session's prefix, often empty)
+    pub fn new<S: Into<String>>(conn: redis::aio::ConnectionManager, prefix: S) -> Self {
+        Self {
+            conn,
+            prefix: prefix.into(),
+        }
+    }
+}
+
+#[cfg(feature = "redis-store")]
+#[async_trait]
+impl AsyncSessionSource for RedisAsyncSessionSource {
+    async fn load(&self, id: &str) -> Result<Option<String>, SessionError> {
+        use redis::AsyncCommands;
+
+        let mut conn = self.conn.clone();
+        Ok(conn.get(format!("{}{}", self.prefix, id)).await?)
+    }
+}
+
+/// BLAKE3 for inputs of up to one chunk (1024 bytes), which covers
+/// async-session's 64-byte cookie values
+mod blake3 {
+    const IV: [u32; 8] = [
+        0x6A09E667, 0xBB67AE85, 0x3C6EF372, 0xA54FF53A, 0x510E527F, 0x9B05688C, 0x1F83D9AB,
+        0x5BE0CD19,
+    ];
+    const MSG_PERMUTATION: [usize; 16] = [2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8];
+    const CHUNK_START: u32 = 1;
+    const CHUNK_END: u32 = 2;
+    const ROOT: u32 = 8;
+    const BLOCK_LEN: usize = 64;
+    const CHUNK_LEN: usize = 1024;
+
+    fn g(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize, mx: u32, my: u32) {
+        state[a] = state[a].wrapping_add(state[b]).wrapping_add(mx);
+        state[d] = (state[d] ^ state[a]).rotate_right(16);
+        state[c] = state[c].wrapping_add(state[d]);
+        state[b] = (state[b] ^ state[c]).rotate_right(12);
+        state[a] = state[a].wrapping_add(state[b]).wrapping_add(my);
+        state[d] = (state[d] ^ state[a]).rotate_right(8);
+        state[c] = state[c].wrapping_add(state[d]);
+        state[b] = (state[b] ^ state[c]).rotate_right(7);
+    }
+
+    fn round(state: &mut [u32; 16], m: &[u32; 16]) {
+        g(state, 0, 4, 8, 12, m[0], m[1]);
+        g(state, 1, 5, 9, 13, m[2], m[3]);
+        g(state, 2, 6, 10, 14, m[4], m[5]);
+        g(state, 3, 7, 11, 15, m[6], m[7]);
+        g(state, 0, 5, 10, 15, m[8], m[9]);
+        g(state, 1, 6, 11, 12, m[10], m[11]);
+        g(state, 2, 7, 8, 13, m[12], m[13]);
+        g(state, 3, 4, 9, 14, m[14], m[15]);
+    }
+
+    /// Compress one block of the first chunk into a new chaining value
+    fn compress(cv: &[u32; 8], block: &[u8], flags: u32) -> [u32; 8] {
+        let mut m = [0u32; 16];
+        let mut padded = [0u8; BLOCK_LEN];
+        padded[..block.len()].copy_from_slice(block);
+        for (word, bytes) in m.iter_mut().zip(padded.chunks_exact(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+
+        let mut state = [
+            cv[0],
+            cv[1],
+            cv[2],
+            cv[3],
+            cv[4],
+            cv[5],
+            cv[6],
+            cv[7],
+            IV[0],
+            IV[1],
+            IV[2],
+            IV[3],
+            0, // chunk counter, low word
+            0, // chunk counter, high word
+            block.len() as u32,
+            flags,
+        ];
+        for i in 0..7 {
+            round(&mut state, &m);
+            if i < 6 {
+                m = MSG_PERMUTATION.map(|j| m[j]);
+            }
+        }
+
+        let mut out = [0u32; 8];
+        for i in 0..8 {
+            out[i] = state[i] ^ state[i + 8];
+        }
+        out
+    }
+
+    /// Hash `input`, or None if it is longer than one chunk
+    pub(super) fn hash(input: &[u8]) -> Option<[u8; 32]> {
+        if input.len() > CHUNK_LEN {
+            return None;
+        }
+        let blocks: Vec<&[u8]> = if input.is_empty() {
+            vec![&[]]
+        } else {
+            input.chunks(BLOCK_LEN).collect()
+        };
+
+        let mut cv = IV;
+        for (i, block) in blocks.iter().enumerate() {
+            let mut flags = if i == 0 { CHUNK_START } else { 0 };
+            if i == blocks.len() - 1 {
+                flags |= CHUNK_END | ROOT;
+            }
+            cv = compress(&cv, block, flags);
+        }
+
+        let mut out = [0u8; 32];
+        for (bytes, word) in out.chunks_exact_mut(4).zip(cv) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parking_lot::Mutex;
+    use salvo_core::http::cookie::Cookie;
+
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn test_blake3() {
+        assert_eq!(
+            hex(&blake3::hash(b"").unwrap()),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+        assert_eq!(
+            hex(&blake3::hash(b"abc").unwrap()),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        // Official test vector: bytes 0, 1, ..., 250, 0, ... of length 1023
+        let input: Vec<u8> = (0..1023).map(|i| (i % 251) as u8).collect();
+        assert_eq!(
+            hex(&blake3::hash(&input).unwrap()),
+            "10108970eeda3eb932baac1428c7a2163b0e924c9a9e25b35bba72b28f70bd11"
+        );
+        assert!(blake3::hash(&[0; 1025]).is_none());
+    }
+
+    #[derive(Default)]
+    struct Records(Mutex<HashMap<String, String>>);
+
+    #[async_trait]
+    impl AsyncSessionSource for Records {
+        async fn load(&self, id: &str) -> Result<Option<String>, SessionError> {
+            Ok(self.0.lock().get(id).cloned())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_migrate() {
+        let secret = [7u8; 64];
+        let cookie_value = STANDARD.encode([1u8; 64]);
+        let records = Records::default();
+        records.0.lock().insert(
+            session_id(&cookie_value).unwrap(),
+            serde_json::json!({
+                "id": session_id(&cookie_value).unwrap(),
+                "expiry": null,
+                "data": { "user_id": "42", "name": "\"alice\"", "raw": "not json" },
+            })
+            .to_string(),
+        );
+        let compat = AsyncSessionCompat::new(records, &secret);
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(&secret[..32]).unwrap();
+        mac.update(cookie_value.as_bytes());
+        let signed = STANDARD.encode(mac.finalize().into_bytes()) + &cookie_value;
+
+        let mut req = Request::new();
+        req.cookies_mut()
+            .add(Cookie::new("salvo.session.id", signed.clone()));
+        let mut values = compat.migrate(&req).await.unwrap().unwrap();
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            values,
+            vec![
+                ("name".to_string(), Value::from("alice")),
+                ("raw".to_string(), Value::from("not json")),
+                ("user_id".to_string(), Value::from(42)),
+            ]
+        );
+
+        let mut req = Request::new();
+        req.cookies_mut().add(Cookie::new(
+            "salvo.session.id",
+            signed.replacen('A', "B", 1),
+        ));
+        assert!(compat.migrate(&req).await.unwrap().is_none());
+    }
+}
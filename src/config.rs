@@ -1,18 +1,68 @@
 //! Session configuration
 
+use std::sync::Arc;
 use std::time::Duration;
 
+use serde::{Deserialize, Deserializer};
+
+use crate::binding::IpBinding;
+use crate::error::ConfigError;
+use crate::session::SessionCookie;
+
+/// Secret used by [`SessionConfig::default`], rejected unless explicitly allowed
+const DEFAULT_SECRET: &str = "keyboard cat";
+
+/// Minimum secret length enforced by strict validation
+const STRONG_SECRET_LEN: usize = 32;
+
 /// Configuration for the session middleware
-#[derive(Clone, Debug)]
+///
+/// Deserializable so it can live in an application config file; missing
+/// fields other than `secrets` take their defaults, and durations accept
+/// seconds or strings like `"2h"`, `"1d"` or `"1h30m"`:
+///
+/// ```toml
+/// secrets = ["new-secret", "old-secret"]
+/// cookie_name = "sid"
+/// cookie_same_site = "strict"
+/// max_age = "1d"
+/// idle_timeout = "30m"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
 pub struct SessionConfig {
     /// Secret key(s) for signing cookies.
     /// The first secret is used for signing new cookies.
     /// All secrets are tried when verifying signatures (for secret rotation).
-    pub secrets: Vec<String>,
+    /// Left empty, rather than the default secret, when missing from a
+    /// deserialized config, so that validation fails.
+    #[serde(default)]
+    pub secrets: Vec<Arc<str>>,
+
+    /// Whether validation also rejects short secrets (default: false)
+    pub strict: bool,
+
+    /// Whether validation accepts the well-known default secret (default: false)
+    /// Only [`SessionConfig::development`] turns this on.
+    pub allow_insecure_default_secret: bool,
 
     /// Name of the session cookie (default: "connect.sid")
     pub cookie_name: String,
 
+    /// Additional cookie names accepted when reading (default: empty)
+    /// Sessions found under a legacy name are re-issued under `cookie_name`
+    pub legacy_cookie_names: Vec<String>,
+
+    /// Whether to expire legacy cookies once re-issued under the primary name (default: true)
+    pub expire_legacy_cookies: bool,
+
+    /// Whether to re-sign cookies that were verified with a rotated secret (default: true)
+    pub resign_rotated: bool,
+
+    /// Whether to leave the session cookies of error responses for
+    /// [`RestoreSessionCookies`](crate::RestoreSessionCookies) (default: true)
+    pub catcher_cookies: bool,
+
     /// Cookie path (default: "/")
     pub cookie_path: String,
 
@@ -25,14 +75,38 @@ pub struct SessionConfig {
     /// Secure flag for cookie (default: false)
     pub cookie_secure: bool,
 
+    /// Whether to force the Secure flag on SameSite=None cookies (default: false)
+    pub auto_secure: bool,
+
     /// SameSite attribute for cookie
     pub cookie_same_site: SameSite,
 
+    /// Whether to emit an `Expires` attribute alongside `Max-Age` (default: true)
+    /// express-session sets `Expires` from the session's cookie expiry
+    pub cookie_expires: bool,
+
+    /// Longest session cookie or token accepted, in bytes (default: 4096)
+    /// Longer values are ignored without being decoded or verified
+    pub max_cookie_length: usize,
+
     /// Max age in seconds (default: None = session cookie)
     /// When None, cookie expires when browser closes (non-persistent cookie)
     /// This is used for both cookie expiry and session TTL in store
+    #[serde(deserialize_with = "duration::deserialize_option")]
     pub max_age: Option<u64>,
 
+    /// Absolute session lifetime in seconds, counted from creation (default: None)
+    /// Rolling and touch may extend the cookie, but the session is forcibly
+    /// expired once this much time has passed since it was created
+    #[serde(deserialize_with = "duration::deserialize_option")]
+    pub absolute_max_age: Option<u64>,
+
+    /// Idle timeout in seconds (default: None)
+    /// The session becomes invalid if it isn't accessed for this long,
+    /// independently of the cookie max age. Tracked as `lastAccess` in the session
+    #[serde(deserialize_with = "duration::deserialize_option")]
+    pub idle_timeout: Option<u64>,
+
     /// Session key prefix in store (default: "sess:")
     pub prefix: String,
 
@@ -43,12 +117,223 @@ pub struct SessionConfig {
     /// Whether to force save on every request (default: false)
     pub resave: bool,
 
+    /// Whether to persist only the changed keys of existing sessions via
+    /// [`SessionStore::patch`](crate::SessionStore::patch) (default: false)
+    pub patch_updates: bool,
+
+    /// What to do with a session whose data was cleared to empty (default: Keep)
+    pub unset: Unset,
+
+    /// Session keys whose change regenerates the session ID (default: empty)
+    pub privilege_keys: Vec<String>,
+
+    /// Binding of sessions to the client IP they were created from (default: None)
+    pub ip_binding: Option<IpBinding>,
+
+    /// Whether to defer the store lookup until the session is first accessed (default: false)
+    /// Requests whose handlers never use the session don't hit the store
+    pub lazy_load: bool,
+
+    /// Whether to defer creating new sessions until data is written (default: false)
+    /// When enabled, anonymous requests get no session ID and no cookie
+    pub lazy: bool,
+
     /// Whether to reset cookie expiry on every request (default: false)
     pub rolling: bool,
+
+    /// Minimum seconds between store touches of an unmodified session
+    /// (default: None - touch on every request), like connect-mongo's `touchAfter`
+    #[serde(deserialize_with = "duration::deserialize_option")]
+    pub touch_after: Option<u64>,
+
+    /// Whether GET and HEAD requests only read the session, never saving,
+    /// touching or renewing it (default: false)
+    pub read_only_safe_methods: bool,
+
+    /// Whether to persist the session and set its cookie before handlers run,
+    /// for long-lived responses (default: false)
+    pub early_save: bool,
+
+    /// Whether to refresh the store TTL of sessions as they are loaded, in
+    /// one round-trip, instead of touching unmodified ones afterwards (default: false)
+    pub touch_on_load: bool,
+
+    /// Only renew a rolling session when its remaining lifetime drops below
+    /// this threshold (default: None - renew every time)
+    pub rolling_threshold: Option<RenewalThreshold>,
+
+    /// Request paths the middleware bypasses entirely (default: empty)
+    /// Entries match exactly, or by prefix when ending in `*` (e.g. "/static/*")
+    pub skip_paths: Vec<String>,
+
+    /// What to do when the store fails to load a session (default: FailOpen)
+    pub store_failure_policy: StoreFailurePolicy,
+
+    /// How concurrent saves of the same session are resolved (default: LastWriteWins)
+    pub conflict_strategy: ConflictStrategy,
+
+    /// Maximum serialized session size in bytes (default: None - unlimited)
+    pub max_size: Option<usize>,
+
+    /// What to do with sessions over `max_size` (default: Reject)
+    pub oversize_policy: OversizePolicy,
+
+    /// Where the session token is read from and written to (default: Cookie)
+    pub transport: SessionTransport,
+
+    /// Request/response header carrying the session token (default: "X-Session-Token")
+    pub header_name: String,
+
+    /// Optional scheme expected before the token in the header,
+    /// e.g. "Session" for `Authorization: Session <token>` (default: None)
+    pub header_scheme: Option<String>,
+
+    /// Whether to return the token in a response header for cookie-less clients (default: false)
+    pub response_header: bool,
+
+    /// Query parameter accepted as a session token fallback on WebSocket
+    /// upgrade requests only (default: None - disabled)
+    pub websocket_query_param: Option<String>,
+
+    /// How session IDs appear in the crate's logs and spans (default: Hash)
+    pub log_redaction: LogRedaction,
+}
+
+impl SameSite {
+    /// Attribute value as stored in express-session cookie data
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SameSite::Strict => "strict",
+            SameSite::Lax => "lax",
+            SameSite::None => "none",
+        }
+    }
+
+    /// Parse an attribute value as stored in express-session cookie data
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "strict" => Some(SameSite::Strict),
+            "lax" => Some(SameSite::Lax),
+            "none" => Some(SameSite::None),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<String> for SameSite {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        SameSite::parse(&value).ok_or_else(|| {
+            format!(
+                "invalid SameSite value {:?}, expected strict, lax or none",
+                value
+            )
+        })
+    }
+}
+
+/// What to do with a session whose data was cleared (express-session `unset`)
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Unset {
+    /// Persist the emptied session as usual
+    Keep,
+    /// Destroy the stored session and remove the cookie
+    Destroy,
+}
+
+/// Policy applied when the session store fails to load a session
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreFailurePolicy {
+    /// Continue with a fresh session that is saved and issued as usual
+    FailOpen,
+    /// Continue with an empty, unauthenticated session that is never saved
+    /// and never replaces the client's cookie
+    FailClosed,
+    /// Abort the request with 503 Service Unavailable
+    Abort,
+}
+
+/// How session IDs appear in logs and tracing spans
+///
+/// Raw session IDs are credentials and are never logged.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRedaction {
+    /// A truncated SHA-256 of the ID, enough to correlate one session's entries
+    Hash,
+    /// No session identifier at all
+    Omit,
+}
+
+/// How a save is resolved when another request saved the same session first
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    /// Overwrite the stored session unconditionally (express-session behavior)
+    LastWriteWins,
+    /// Drop the write and report [`SessionError::Conflict`](crate::SessionError::Conflict)
+    Error,
+    /// Re-read the stored session, apply this request's changed keys on top
+    /// and retry a few times before reporting a conflict
+    RetryMerge,
+}
+
+/// What to do with a session larger than the configured maximum size
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizePolicy {
+    /// Don't write the session and report [`SessionError::TooLarge`](crate::SessionError::TooLarge)
+    Reject,
+    /// Remove the largest values until the session fits, then write it
+    Truncate,
+}
+
+/// Remaining-lifetime threshold below which a rolling session is renewed
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenewalThreshold {
+    /// Renew when fewer than this many seconds of lifetime remain
+    Seconds(#[serde(deserialize_with = "duration::deserialize")] u64),
+    /// Renew when less than this percentage (0-100) of the original max age remains
+    Percent(u8),
+}
+
+/// Transport used to carry the signed session token
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionTransport {
+    /// Session token is only carried in the cookie
+    Cookie,
+    /// Session token is only carried in a header
+    Header,
+    /// Session token is read from the cookie, then from the header
+    CookieAndHeader,
+}
+
+impl SessionTransport {
+    /// Whether the cookie is used by this transport
+    pub fn uses_cookie(&self) -> bool {
+        matches!(
+            self,
+            SessionTransport::Cookie | SessionTransport::CookieAndHeader
+        )
+    }
+
+    /// Whether the header is used by this transport
+    pub fn uses_header(&self) -> bool {
+        matches!(
+            self,
+            SessionTransport::Header | SessionTransport::CookieAndHeader
+        )
+    }
 }
 
 /// SameSite cookie attribute
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
 pub enum SameSite {
     /// Strict - cookie only sent for same-site requests
     Strict,
@@ -61,18 +346,51 @@ pub enum SameSite {
 impl Default for SessionConfig {
     fn default() -> Self {
         Self {
-            secrets: vec!["keyboard cat".to_string()],
+            secrets: vec![Arc::from(DEFAULT_SECRET)],
+            strict: false,
+            allow_insecure_default_secret: false,
             cookie_name: "connect.sid".to_string(),
+            legacy_cookie_names: Vec::new(),
+            expire_legacy_cookies: true,
+            resign_rotated: true,
+            catcher_cookies: true,
             cookie_path: "/".to_string(),
             cookie_domain: None,
             cookie_http_only: true,
             cookie_secure: false,
+            auto_secure: false,
             cookie_same_site: SameSite::Lax,
+            cookie_expires: true,
+            max_cookie_length: 4096,
             max_age: None, // Session cookie by default (like express-session)
+            absolute_max_age: None,
+            idle_timeout: None,
             prefix: "sess:".to_string(),
             save_uninitialized: false,
             resave: false,
+            patch_updates: false,
+            unset: Unset::Keep,
+            privilege_keys: Vec::new(),
+            ip_binding: None,
+            lazy_load: false,
+            lazy: false,
             rolling: false,
+            touch_after: None,
+            read_only_safe_methods: false,
+            early_save: false,
+            touch_on_load: false,
+            rolling_threshold: None,
+            skip_paths: Vec::new(),
+            store_failure_policy: StoreFailurePolicy::FailOpen,
+            conflict_strategy: ConflictStrategy::LastWriteWins,
+            max_size: None,
+            oversize_policy: OversizePolicy::Reject,
+            transport: SessionTransport::Cookie,
+            header_name: "X-Session-Token".to_string(),
+            header_scheme: None,
+            response_header: false,
+            websocket_query_param: None,
+            log_redaction: LogRedaction::Hash,
         }
     }
 }
@@ -81,11 +399,56 @@ impl SessionConfig {
     /// Create a new session configuration with the given secret
     pub fn new<S: Into<String>>(secret: S) -> Self {
         Self {
-            secrets: vec![secret.into()],
+            secrets: vec![Arc::from(secret.into())],
             ..Default::default()
         }
     }
 
+    /// Recommended settings for production deployments
+    ///
+    /// Secure, HttpOnly, `SameSite=Lax` cookies lasting a day, renewed on
+    /// activity once half their lifetime has passed, with strict validation
+    /// (secrets of at least 32 bytes).
+    pub fn production<S: Into<String>>(secret: S) -> Self {
+        Self::new(secret)
+            .with_strict(true)
+            .with_secure(true)
+            .with_http_only(true)
+            .with_same_site(SameSite::Lax)
+            .with_max_age(24 * 60 * 60)
+            .with_rolling(true)
+            .with_rolling_threshold(RenewalThreshold::Percent(50))
+    }
+
+    /// Relaxed settings for local development over plain HTTP
+    ///
+    /// Uses the default secret, which validation then accepts, non-Secure
+    /// cookies and saves every session so it can be inspected in the store.
+    /// Not for production use.
+    pub fn development() -> Self {
+        Self::default()
+            .with_allow_insecure_default_secret(true)
+            .with_secure(false)
+            .with_same_site(SameSite::Lax)
+            .with_max_age(24 * 60 * 60)
+            .with_save_uninitialized(true)
+    }
+
+    /// Set whether validation also rejects secrets shorter than 32 bytes (default: false)
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Set whether validation accepts the well-known default secret (default: false)
+    ///
+    /// Anyone can forge cookies signed with it; only enable this for local
+    /// development.
+    pub fn with_allow_insecure_default_secret(mut self, allow: bool) -> Self {
+        self.allow_insecure_default_secret = allow;
+        self
+    }
+
     /// Create a new session configuration with multiple secrets for rotation
     pub fn with_secrets<I, S>(secrets: I) -> Self
     where
@@ -93,17 +456,141 @@ impl SessionConfig {
         S: Into<String>,
     {
         Self {
-            secrets: secrets.into_iter().map(|s| s.into()).collect(),
+            secrets: secrets.into_iter().map(|s| Arc::from(s.into())).collect(),
             ..Default::default()
         }
     }
 
+    /// Create a session configuration from environment variables
+    ///
+    /// Reads `SESSION_SECRETS` (comma-separated, primary first) or
+    /// `SESSION_SECRET`, plus optional overrides for the defaults:
+    ///
+    /// | Variable | Value |
+    /// |----------|-------|
+    /// | `SESSION_COOKIE_NAME` | cookie name |
+    /// | `SESSION_COOKIE_PATH` | cookie path |
+    /// | `SESSION_COOKIE_DOMAIN` | cookie domain |
+    /// | `SESSION_MAX_AGE` | duration, or `none` for a browser-session cookie |
+    /// | `SESSION_IDLE_TIMEOUT` | duration |
+    /// | `SESSION_ABSOLUTE_MAX_AGE` | duration |
+    /// | `SESSION_SECURE` | boolean |
+    /// | `SESSION_HTTP_ONLY` | boolean |
+    /// | `SESSION_SAME_SITE` | `strict`, `lax` or `none` |
+    /// | `SESSION_PREFIX` | store key prefix |
+    /// | `SESSION_ROLLING` | boolean |
+    /// | `SESSION_RESAVE` | boolean |
+    /// | `SESSION_SAVE_UNINITIALIZED` | boolean |
+    ///
+    /// Durations are seconds or strings like `2h` or `1d`. Booleans accept
+    /// `true`/`false`, `1`/`0`, `yes`/`no` and `on`/`off`.
+    /// The resulting configuration is validated before being returned.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Build a configuration from a variable lookup (see [`Self::from_env`])
+    fn from_vars<F: Fn(&str) -> Option<String>>(var: F) -> Result<Self, ConfigError> {
+        let secrets: Vec<String> = match var("SESSION_SECRETS").or_else(|| var("SESSION_SECRET")) {
+            Some(secrets) => secrets.split(',').map(|s| s.trim().to_string()).collect(),
+            None => return Err(ConfigError::NoSecrets),
+        };
+        let mut config = Self::with_secrets(secrets);
+
+        if let Some(name) = var("SESSION_COOKIE_NAME") {
+            config.cookie_name = name;
+        }
+        if let Some(path) = var("SESSION_COOKIE_PATH") {
+            config.cookie_path = path;
+        }
+        if let Some(domain) = var("SESSION_COOKIE_DOMAIN") {
+            config.cookie_domain = Some(domain);
+        }
+        if let Some(prefix) = var("SESSION_PREFIX") {
+            config.prefix = prefix;
+        }
+        if let Some(value) = var("SESSION_MAX_AGE") {
+            config.max_age = if value.eq_ignore_ascii_case("none") {
+                None
+            } else {
+                Some(parse_env("SESSION_MAX_AGE", value, duration::parse_secs)?)
+            };
+        }
+        if let Some(value) = var("SESSION_IDLE_TIMEOUT") {
+            config.idle_timeout = Some(parse_env("SESSION_IDLE_TIMEOUT", value, |v| {
+                duration::parse_secs(v)
+            })?);
+        }
+        if let Some(value) = var("SESSION_ABSOLUTE_MAX_AGE") {
+            config.absolute_max_age = Some(parse_env("SESSION_ABSOLUTE_MAX_AGE", value, |v| {
+                duration::parse_secs(v)
+            })?);
+        }
+        if let Some(value) = var("SESSION_SAME_SITE") {
+            config.cookie_same_site = parse_env("SESSION_SAME_SITE", value, SameSite::parse)?;
+        }
+
+        let flags: [(&str, &mut bool); 5] = [
+            ("SESSION_SECURE", &mut config.cookie_secure),
+            ("SESSION_HTTP_ONLY", &mut config.cookie_http_only),
+            ("SESSION_ROLLING", &mut config.rolling),
+            ("SESSION_RESAVE", &mut config.resave),
+            ("SESSION_SAVE_UNINITIALIZED", &mut config.save_uninitialized),
+        ];
+        for (name, flag) in flags {
+            if let Some(value) = var(name) {
+                *flag = parse_env(name, value, parse_bool)?;
+            }
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Set whether to re-sign cookies verified with a rotated secret (default: true)
+    ///
+    /// When a cookie only verifies with one of the older secrets, it is
+    /// re-issued signed with the primary secret so old signatures age out.
+    pub fn with_resign_rotated(mut self, resign: bool) -> Self {
+        self.resign_rotated = resign;
+        self
+    }
+
     /// Set the cookie name (default: "connect.sid")
     pub fn with_cookie_name<S: Into<String>>(mut self, name: S) -> Self {
         self.cookie_name = name.into();
         self
     }
 
+    /// Accept sessions from additional (legacy) cookie names
+    ///
+    /// Useful when renaming the cookie: in-flight sessions found under a
+    /// legacy name keep working and are re-issued under the primary name.
+    pub fn with_legacy_cookie_names<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.legacy_cookie_names = names.into_iter().map(|n| n.into()).collect();
+        self
+    }
+
+    /// Set whether to expire legacy cookies once re-issued (default: true)
+    pub fn with_expire_legacy_cookies(mut self, expire: bool) -> Self {
+        self.expire_legacy_cookies = expire;
+        self
+    }
+
+    /// Set whether to leave the session cookies of error responses for catchers (default: true)
+    ///
+    /// Lets [`RestoreSessionCookies`](crate::RestoreSessionCookies) re-apply
+    /// them after a catcher rendered the error page. Turn it off to let
+    /// catchers decide which cookies error pages carry.
+    pub fn with_catcher_cookies(mut self, catcher_cookies: bool) -> Self {
+        self.catcher_cookies = catcher_cookies;
+        self
+    }
+
     /// Set the cookie path (default: "/")
     pub fn with_cookie_path<S: Into<String>>(mut self, path: S) -> Self {
         self.cookie_path = path.into();
@@ -129,11 +616,40 @@ impl SessionConfig {
     }
 
     /// Set the SameSite attribute (default: Lax)
+    ///
+    /// Browsers drop `SameSite=None` cookies without `Secure`; combine it with
+    /// [`Self::with_secure`] or [`Self::with_auto_secure`].
     pub fn with_same_site(mut self, same_site: SameSite) -> Self {
         self.cookie_same_site = same_site;
         self
     }
 
+    /// Set whether to force the Secure flag on SameSite=None cookies (default: false)
+    pub fn with_auto_secure(mut self, auto_secure: bool) -> Self {
+        self.auto_secure = auto_secure;
+        self
+    }
+
+    /// Whether SameSite=None cookies would be issued without Secure
+    pub fn is_insecure_same_site_none(&self) -> bool {
+        self.cookie_same_site == SameSite::None && !self.cookie_secure && !self.auto_secure
+    }
+
+    /// Set whether to emit an `Expires` attribute alongside `Max-Age` (default: true)
+    pub fn with_expires_attribute(mut self, enabled: bool) -> Self {
+        self.cookie_expires = enabled;
+        self
+    }
+
+    /// Set the longest session cookie or token accepted, in bytes (default: 4096)
+    ///
+    /// Browsers don't store cookies over 4096 bytes, so longer ones are
+    /// hostile; they are ignored before any decoding or HMAC computation.
+    pub fn with_max_cookie_length(mut self, bytes: usize) -> Self {
+        self.max_cookie_length = bytes;
+        self
+    }
+
     /// Set max age in seconds
     /// Pass None for session cookie (expires when browser closes)
     pub fn with_max_age(mut self, max_age: impl Into<Option<u64>>) -> Self {
@@ -147,6 +663,24 @@ impl SessionConfig {
         self
     }
 
+    /// Set the absolute session lifetime in seconds, counted from creation
+    ///
+    /// Sessions without a creation time, e.g. created by Node.js, get one
+    /// saved on their next request.
+    pub fn with_absolute_max_age(mut self, secs: impl Into<Option<u64>>) -> Self {
+        self.absolute_max_age = secs.into();
+        self
+    }
+
+    /// Set the idle timeout in seconds
+    ///
+    /// Every access is recorded in the store, so unmodified sessions are
+    /// saved rather than touched while this is enabled.
+    pub fn with_idle_timeout(mut self, secs: impl Into<Option<u64>>) -> Self {
+        self.idle_timeout = secs.into();
+        self
+    }
+
     /// Set the session key prefix in store (default: "sess:")
     pub fn with_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
         self.prefix = prefix.into();
@@ -165,14 +699,592 @@ impl SessionConfig {
         self
     }
 
+    /// Set whether to persist only the changed keys of existing sessions (default: false)
+    ///
+    /// Instead of rewriting the whole record, the handler passes the keys
+    /// that changed during the request to [`SessionStore::patch`](crate::SessionStore::patch),
+    /// letting stores with field-level updates skip unchanged data. Stores
+    /// without such support fall back to a full write.
+    pub fn with_patch_updates(mut self, patch: bool) -> Self {
+        self.patch_updates = patch;
+        self
+    }
+
+    /// Set what happens to a session whose data was cleared to empty (default: Keep)
+    ///
+    /// Mirrors express-session's `unset` option: with [`Unset::Destroy`], a
+    /// handler emptying the session destroys the stored record and removes
+    /// the cookie instead of persisting an empty object.
+    pub fn with_unset(mut self, unset: Unset) -> Self {
+        self.unset = unset;
+        self
+    }
+
+    /// Set session keys whose change regenerates the session ID (default: empty)
+    ///
+    /// Closes session fixation windows without manual
+    /// [`Session::regenerate`](crate::Session::regenerate) calls: when a
+    /// request changes e.g. `.with_privilege_keys(["userId", "role"])` on an
+    /// existing session, the data moves to a new ID in the same response and
+    /// the old record is destroyed.
+    pub fn with_privilege_keys<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.privilege_keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Bind sessions to the client IP they were created from (default: None)
+    ///
+    /// Sessions used from outside the tolerated network are not resumed;
+    /// see [`IpBinding`].
+    pub fn with_ip_binding(mut self, binding: IpBinding) -> Self {
+        self.ip_binding = Some(binding);
+        self
+    }
+
+    /// Set whether to defer the store lookup until the session is first accessed (default: false)
+    ///
+    /// Routes that never use the session (static assets, health checks) then
+    /// never touch the store. The [`Session`](crate::Session) extractor,
+    /// [`SessionDepotExt::load_session`](crate::SessionDepotExt::load_session) and
+    /// the built-in hoops load it; sessions taken from the depot otherwise need
+    /// `session.load().await` before reading, as they read as new and empty
+    /// until then. Changes and removals made without loading are applied on top
+    /// of the stored data after the response. Load failures always fall back to a fresh session; with a non fail-open
+    /// store failure policy that session is not persisted.
+    pub fn with_lazy_load(mut self, lazy_load: bool) -> Self {
+        self.lazy_load = lazy_load;
+        self
+    }
+
+    /// Set whether to defer creating new sessions until data is written (default: false)
+    ///
+    /// No session ID is generated and no cookie is set for requests whose
+    /// handlers never write to the session (unless `save_uninitialized` is set).
+    pub fn with_lazy(mut self, lazy: bool) -> Self {
+        self.lazy = lazy;
+        self
+    }
+
     /// Set whether to reset cookie expiry on every request (default: false)
     pub fn with_rolling(mut self, rolling: bool) -> Self {
         self.rolling = rolling;
         self
     }
 
+    /// Only refresh the store TTL of unmodified sessions once the last touch
+    /// is older than `secs` (default: None - touch on every request)
+    ///
+    /// Touch times are tracked per handler instance, in memory.
+    pub fn with_touch_after(mut self, secs: impl Into<Option<u64>>) -> Self {
+        self.touch_after = secs.into();
+        self
+    }
+
+    /// Set whether GET and HEAD requests only read the session (default: false)
+    ///
+    /// Such requests load the session but never save, touch or renew it,
+    /// saving store writes on read-heavy sites. [`Session::save`](crate::Session::save)
+    /// and [`Session::try_set`](crate::Session::try_set) fail with
+    /// [`SessionError::ReadOnly`](crate::SessionError::ReadOnly); other
+    /// changes are dropped with a warning. Destroying a session (e.g. a
+    /// `GET /logout`) still takes effect. New sessions get no cookie.
+    ///
+    /// Sessions are loaded with a plain store read, even with
+    /// [`with_touch_on_load`](Self::with_touch_on_load). With an
+    /// `idle_timeout`, reads therefore don't count as activity: a client
+    /// that only sends GET and HEAD requests is logged out once the idle
+    /// timeout passes.
+    pub fn with_read_only_safe_methods(mut self, read_only: bool) -> Self {
+        self.read_only_safe_methods = read_only;
+        self
+    }
+
+    /// Set whether to persist the session and set its cookie before handlers run (default: false)
+    ///
+    /// For SSE, long polling and other streaming routes, whose handlers may
+    /// run for minutes: new sessions (with `save_uninitialized`) are stored
+    /// and their cookie issued up front, so concurrent requests of the
+    /// client find them. Changes made
+    /// by the handler are still saved once it returns. Enable it per route
+    /// with [`ExpressSessionHandler::scoped`](crate::ExpressSessionHandler::scoped).
+    pub fn with_early_save(mut self, early_save: bool) -> Self {
+        self.early_save = early_save;
+        self
+    }
+
+    /// Set whether to refresh the store TTL of sessions as they are loaded (default: false)
+    ///
+    /// Saves a round-trip per request for unmodified sessions, using
+    /// [`SessionStore::get_and_touch`](crate::store::SessionStore::get_and_touch)
+    /// (GETEX on Redis). The TTL is refreshed to the full `max_age` rather
+    /// than the cookie's remaining lifetime. Has no effect with `resave`, an
+    /// idle timeout, an absolute max age or a rolling threshold, where the
+    /// TTL depends on the loaded session.
+    pub fn with_touch_on_load(mut self, touch_on_load: bool) -> Self {
+        self.touch_on_load = touch_on_load;
+        self
+    }
+
+    /// Set the rolling renewal threshold (default: None - renew every time)
+    ///
+    /// Reduces Set-Cookie churn and store touches on busy sessions by only
+    /// renewing once the remaining lifetime drops below the threshold.
+    pub fn with_rolling_threshold(mut self, threshold: RenewalThreshold) -> Self {
+        self.rolling_threshold = Some(threshold);
+        self
+    }
+
+    /// Set request paths that bypass cookie parsing and store access entirely
+    ///
+    /// Entries match exactly, or by prefix when ending in `*`:
+    /// `.with_skip_paths(["/health", "/metrics", "/static/*"])`
+    pub fn with_skip_paths<I, S>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.skip_paths = paths.into_iter().map(|p| p.into()).collect();
+        self
+    }
+
+    /// Check if a request path is excluded from session handling
+    pub fn is_skipped_path(&self, path: &str) -> bool {
+        self.skip_paths
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => path.starts_with(prefix),
+                None => path == pattern,
+            })
+    }
+
+    /// Set the store failure policy (default: FailOpen)
+    pub fn with_store_failure_policy(mut self, policy: StoreFailurePolicy) -> Self {
+        self.store_failure_policy = policy;
+        self
+    }
+
+    /// Set how concurrent saves of the same session are resolved (default: LastWriteWins)
+    ///
+    /// With [`ConflictStrategy::Error`] or [`ConflictStrategy::RetryMerge`],
+    /// every save increments the session's `sessionVersion` and uses
+    /// [`SessionStore::compare_and_set`](crate::SessionStore::compare_and_set)
+    /// so that concurrent requests (e.g. parallel AJAX calls) don't silently
+    /// overwrite each other's changes. Last-write-wins saves keep an existing
+    /// version current but don't add one.
+    pub fn with_conflict_strategy(mut self, strategy: ConflictStrategy) -> Self {
+        self.conflict_strategy = strategy;
+        self
+    }
+
+    /// Set the maximum serialized session size in bytes (default: None - unlimited)
+    ///
+    /// Protects the store from handlers accidentally stuffing large values
+    /// into the session. Sessions over the limit are handled according to
+    /// the [`OversizePolicy`]; [`Session::try_set`](crate::Session::try_set)
+    /// rejects a value that would exceed it up front.
+    pub fn with_max_size(mut self, bytes: usize) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Set what happens to sessions over the maximum size (default: Reject)
+    pub fn with_oversize_policy(mut self, policy: OversizePolicy) -> Self {
+        self.oversize_policy = policy;
+        self
+    }
+
+    /// Set the session token transport (default: Cookie)
+    pub fn with_transport(mut self, transport: SessionTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Set the header carrying the session token (default: "X-Session-Token")
+    pub fn with_header_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.header_name = name.into();
+        self
+    }
+
+    /// Set the scheme expected before the token in the header
+    ///
+    /// For example, `.with_header_name("Authorization").with_header_scheme("Session")`
+    /// accepts `Authorization: Session <token>`.
+    pub fn with_header_scheme<S: Into<String>>(mut self, scheme: S) -> Self {
+        self.header_scheme = Some(scheme.into());
+        self
+    }
+
+    /// Set whether to return the token in a response header (default: false)
+    pub fn with_response_header(mut self, enabled: bool) -> Self {
+        self.response_header = enabled;
+        self
+    }
+
+    /// Accept the signed session token from a query parameter on WebSocket upgrade requests
+    ///
+    /// Browsers can't set custom headers on WebSocket handshakes, so this allows
+    /// e.g. `wss://example.com/ws?sid=<token>`. Other requests ignore the parameter.
+    pub fn with_websocket_query_param<S: Into<String>>(mut self, name: S) -> Self {
+        self.websocket_query_param = Some(name.into());
+        self
+    }
+
+    /// Set how session IDs appear in logs and spans (default: Hash)
+    pub fn with_log_redaction(mut self, redaction: LogRedaction) -> Self {
+        self.log_redaction = redaction;
+        self
+    }
+
+    /// Whether new session cookies carry the Secure flag
+    fn issues_secure_cookies(&self) -> bool {
+        self.cookie_secure || (self.auto_secure && self.cookie_same_site == SameSite::None)
+    }
+
+    /// Check the configuration for mistakes that would otherwise misbehave at runtime
+    ///
+    /// Rejects missing, empty or duplicate secrets, the default secret unless
+    /// explicitly allowed, invalid cookie names, and cookie attribute combinations
+    /// that browsers refuse (`SameSite=None` without `Secure`, unmet
+    /// `__Secure-`/`__Host-` prefix requirements).
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.secrets.is_empty() {
+            return Err(ConfigError::NoSecrets);
+        }
+        if self.secrets.iter().any(|s| s.is_empty()) {
+            return Err(ConfigError::EmptySecret);
+        }
+        if (1..self.secrets.len()).any(|i| self.secrets[..i].contains(&self.secrets[i])) {
+            return Err(ConfigError::DuplicateSecret);
+        }
+        if !self.allow_insecure_default_secret
+            && self.secrets.iter().any(|s| &**s == DEFAULT_SECRET)
+        {
+            return Err(ConfigError::DefaultSecret);
+        }
+        if self.strict && self.secrets.iter().any(|s| s.len() < STRONG_SECRET_LEN) {
+            return Err(ConfigError::WeakSecret {
+                min_len: STRONG_SECRET_LEN,
+            });
+        }
+
+        for name in std::iter::once(&self.cookie_name).chain(&self.legacy_cookie_names) {
+            if !is_valid_cookie_name(name) {
+                return Err(ConfigError::InvalidCookieName(name.clone()));
+            }
+        }
+        if self.legacy_cookie_names.contains(&self.cookie_name) {
+            return Err(ConfigError::ConflictingAttributes(format!(
+                "{:?} is both the cookie name and a legacy cookie name",
+                self.cookie_name
+            )));
+        }
+
+        let secure = self.issues_secure_cookies();
+        if self.is_insecure_same_site_none() {
+            return Err(ConfigError::ConflictingAttributes(
+                "SameSite=None requires Secure; use with_secure(true) or with_auto_secure(true)"
+                    .to_string(),
+            ));
+        }
+        if self.cookie_name.starts_with("__Secure-") && !secure {
+            return Err(ConfigError::ConflictingAttributes(
+                "__Secure- cookies require with_secure(true)".to_string(),
+            ));
+        }
+        if self.cookie_name.starts_with("__Host-")
+            && !(secure && self.cookie_path == "/" && self.cookie_domain.is_none())
+        {
+            return Err(ConfigError::ConflictingAttributes(
+                "__Host- cookies require with_secure(true), path \"/\" and no domain".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Build the cookie data for a new session from this configuration
+    pub fn new_session_cookie(&self) -> SessionCookie {
+        SessionCookie {
+            secure: self.issues_secure_cookies(),
+            http_only: self.cookie_http_only,
+            path: self.cookie_path.clone(),
+            domain: self.cookie_domain.clone(),
+            same_site: Some(self.cookie_same_site.as_str().to_string()),
+            ..SessionCookie::with_optional_max_age(self.max_age)
+        }
+    }
+
     /// Get max age as Duration
     pub fn max_age_duration(&self) -> Option<Duration> {
         self.max_age.map(Duration::from_secs)
     }
 }
+
+/// Parse an environment variable value, reporting the variable on failure
+fn parse_env<T>(
+    name: &str,
+    value: String,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> Result<T, ConfigError> {
+    parse(value.trim()).ok_or_else(|| ConfigError::InvalidEnvVar {
+        name: name.to_string(),
+        value,
+    })
+}
+
+/// Parse a boolean flag from an environment variable value
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Check a cookie name against the RFC 6265 token grammar
+fn is_valid_cookie_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?={}".contains(&b))
+}
+
+/// Duration values given as seconds or as strings like "90s", "2h" or "1d12h"
+mod duration {
+    use super::*;
+
+    /// Parse a duration string into seconds
+    ///
+    /// Accepts a plain number of seconds or one or more `<number><unit>`
+    /// segments with units `s`, `m`, `h`, `d` and `w`.
+    pub(crate) fn parse_secs(value: &str) -> Option<u64> {
+        let value = value.trim();
+        if let Ok(secs) = value.parse() {
+            return Some(secs);
+        }
+        if value.is_empty() {
+            return None;
+        }
+
+        let mut total: u64 = 0;
+        let mut rest = value;
+        while !rest.is_empty() {
+            let digits = rest.find(|c: char| !c.is_ascii_digit())?;
+            if digits == 0 {
+                return None;
+            }
+            let amount: u64 = rest[..digits].parse().ok()?;
+            let unit = match rest.as_bytes()[digits] {
+                b's' => 1,
+                b'm' => 60,
+                b'h' => 60 * 60,
+                b'd' => 24 * 60 * 60,
+                b'w' => 7 * 24 * 60 * 60,
+                _ => return None,
+            };
+            total = total.checked_add(amount.checked_mul(unit)?)?;
+            rest = &rest[digits + 1..];
+        }
+        Some(total)
+    }
+
+    /// Seconds as a number or a duration string
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Secs {
+        Number(u64),
+        Text(String),
+    }
+
+    impl Secs {
+        fn into_secs<E: serde::de::Error>(self) -> Result<u64, E> {
+            match self {
+                Secs::Number(secs) => Ok(secs),
+                Secs::Text(text) => parse_secs(&text)
+                    .ok_or_else(|| E::custom(format!("invalid duration {:?}", text))),
+            }
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        Secs::deserialize(deserializer)?.into_secs()
+    }
+
+    pub(crate) fn deserialize_option<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        Option::<Secs>::deserialize(deserializer)?
+            .map(Secs::into_secs)
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skip_paths() {
+        let config = SessionConfig::default().with_skip_paths(["/health", "/static/*"]);
+
+        assert!(config.is_skipped_path("/health"));
+        assert!(config.is_skipped_path("/static/app.js"));
+        assert!(!config.is_skipped_path("/healthz"));
+        assert!(!config.is_skipped_path("/"));
+    }
+
+    #[test]
+    fn test_auto_secure_same_site_none() {
+        let config = SessionConfig::default().with_same_site(SameSite::None);
+        assert!(config.is_insecure_same_site_none());
+        assert!(!config.new_session_cookie().secure);
+
+        let config = config.with_auto_secure(true);
+        assert!(!config.is_insecure_same_site_none());
+        assert!(config.new_session_cookie().secure);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(SessionConfig::new("secret").validate(), Ok(()));
+        assert_eq!(
+            SessionConfig::with_secrets(Vec::<String>::new()).validate(),
+            Err(ConfigError::NoSecrets)
+        );
+        assert_eq!(
+            SessionConfig::with_secrets(["new", ""]).validate(),
+            Err(ConfigError::EmptySecret)
+        );
+        assert_eq!(
+            SessionConfig::with_secrets(["a", "b", "a"]).validate(),
+            Err(ConfigError::DuplicateSecret)
+        );
+        assert!(matches!(
+            SessionConfig::new("secret").with_cookie_name("").validate(),
+            Err(ConfigError::InvalidCookieName(_))
+        ));
+        assert!(matches!(
+            SessionConfig::new("secret")
+                .with_cookie_name("my session")
+                .validate(),
+            Err(ConfigError::InvalidCookieName(_))
+        ));
+        assert!(matches!(
+            SessionConfig::new("secret")
+                .with_same_site(SameSite::None)
+                .validate(),
+            Err(ConfigError::ConflictingAttributes(_))
+        ));
+        assert!(matches!(
+            SessionConfig::new("secret")
+                .with_cookie_name("__Host-sid")
+                .with_secure(true)
+                .with_cookie_domain("example.com")
+                .validate(),
+            Err(ConfigError::ConflictingAttributes(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let vars = |name: &str| match name {
+            "SESSION_SECRETS" => Some("new, old".to_string()),
+            "SESSION_COOKIE_NAME" => Some("sid".to_string()),
+            "SESSION_MAX_AGE" => Some("3600".to_string()),
+            "SESSION_SECURE" => Some("yes".to_string()),
+            "SESSION_SAME_SITE" => Some("Strict".to_string()),
+            _ => None,
+        };
+        let config = SessionConfig::from_vars(vars).unwrap();
+        assert_eq!(config.secrets, vec![Arc::from("new"), Arc::from("old")]);
+        assert_eq!(config.cookie_name, "sid");
+        assert_eq!(config.max_age, Some(3600));
+        assert!(config.cookie_secure);
+        assert_eq!(config.cookie_same_site, SameSite::Strict);
+
+        assert_eq!(
+            SessionConfig::from_vars(|_| None).unwrap_err(),
+            ConfigError::NoSecrets
+        );
+        let vars = |name: &str| match name {
+            "SESSION_SECRET" => Some("secret".to_string()),
+            "SESSION_MAX_AGE" => Some("an hour".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            SessionConfig::from_vars(vars).unwrap_err(),
+            ConfigError::InvalidEnvVar {
+                name: "SESSION_MAX_AGE".to_string(),
+                value: "an hour".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let config: SessionConfig = serde_json::from_value(serde_json::json!({
+            "secrets": ["new", "old"],
+            "cookie_same_site": "None",
+            "cookie_secure": true,
+            "max_age": "1d",
+            "idle_timeout": 1800,
+            "touch_after": "1h30m",
+            "rolling_threshold": { "percent": 25 },
+            "store_failure_policy": "fail_closed",
+            "log_redaction": "omit",
+        }))
+        .unwrap();
+
+        assert_eq!(config.secrets, vec![Arc::from("new"), Arc::from("old")]);
+        assert_eq!(config.cookie_name, "connect.sid");
+        assert_eq!(config.cookie_same_site, SameSite::None);
+        assert_eq!(config.max_age, Some(86400));
+        assert_eq!(config.idle_timeout, Some(1800));
+        assert_eq!(config.touch_after, Some(5400));
+        assert_eq!(
+            config.rolling_threshold,
+            Some(RenewalThreshold::Percent(25))
+        );
+        assert_eq!(config.store_failure_policy, StoreFailurePolicy::FailClosed);
+        assert_eq!(config.log_redaction, LogRedaction::Omit);
+
+        let invalid = serde_json::json!({ "max_age": "2 days" });
+        assert!(serde_json::from_value::<SessionConfig>(invalid).is_err());
+
+        // A config file without secrets doesn't fall back to the default secret
+        let config: SessionConfig =
+            serde_json::from_value(serde_json::json!({ "cookie_name": "sid" })).unwrap();
+        assert!(config.secrets.is_empty());
+        assert_eq!(config.validate(), Err(ConfigError::NoSecrets));
+    }
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(
+            SessionConfig::production("short").validate(),
+            Err(ConfigError::WeakSecret { min_len: 32 })
+        );
+
+        let config = SessionConfig::production("0123456789abcdef0123456789abcdef");
+        assert_eq!(config.validate(), Ok(()));
+        assert!(config.cookie_secure);
+        assert!(config.rolling);
+
+        // The default secret is only accepted when explicitly allowed
+        assert_eq!(SessionConfig::development().validate(), Ok(()));
+        assert_eq!(
+            SessionConfig::default().validate(),
+            Err(ConfigError::DefaultSecret)
+        );
+        assert_eq!(
+            SessionConfig::development()
+                .with_allow_insecure_default_secret(false)
+                .validate(),
+            Err(ConfigError::DefaultSecret)
+        );
+    }
+}
@@ -0,0 +1,473 @@
+//! Stateless sessions stored entirely in the cookie
+//!
+//! [`CookieSessionHandler`] serializes the whole session into the signed
+//! session cookie, like Node's cookie-session, so no server-side storage is
+//! needed. Handlers use the same [`Session`] API as with
+//! [`ExpressSessionHandler`](crate::ExpressSessionHandler):
+//!
+//! ```rust,ignore
+//! let session_handler = CookieSessionHandler::new(SessionConfig::new("secret").with_cookie_name("session"));
+//!
+//! // Encrypted, so clients can't read the values
+//! let session_handler = CookieSessionHandler::new(config).with_codec(EncryptedCodec::new(ExpressCodec));
+//! ```
+//!
+//! The cookie value is the base64url-encoded JSON of the session, signed by
+//! the codec. Browsers drop cookies over about 4 KB, so sessions that don't
+//! fit [`CookieSessionHandler::with_max_cookie_size`] are not written and a
+//! [`SessionError::TooLarge`] is reported. Sessions can't be revoked before
+//! they expire; destroying one only clears the client's cookie.
+//!
+//! In hybrid mode ([`CookieSessionHandler::with_overflow_store`]), sessions
+//! over the overflow threshold move to a [`SessionStore`] and the cookie
+//! carries only their ID; once small enough they move back. The depot holds
+//! the [`SessionStorage`] the session was loaded from, updated once it is
+//! written.
+
+use std::sync::Arc;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use salvo_core::http::cookie::{self, time::Duration as CookieDuration};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
+
+use crate::codec::{url_decode, CookieCodec, ExpressCodec, SignedCookie};
+use crate::config::{SessionConfig, Unset};
+use crate::error::SessionError;
+use crate::handler::{build_cookie, ErrorHandler};
+use crate::secret::SigningKey;
+use crate::session::{generate_session_id, Session, SessionData};
+use crate::store::SessionStore;
+
+/// Default limit of the session cookie's name and value, in bytes
+const DEFAULT_MAX_COOKIE_SIZE: usize = 4096;
+
+/// Default cookie size above which hybrid sessions move to the store
+const DEFAULT_OVERFLOW_THRESHOLD: usize = 3584;
+
+/// Prefix of cookie payloads referencing a stored session (outside the base64url alphabet)
+const STORE_REFERENCE: char = '~';
+
+/// Where a cookie session's data is kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionStorage {
+    /// Entirely in the cookie
+    Cookie,
+    /// In the overflow store, referenced by ID from the cookie
+    Store,
+}
+
+/// Session middleware keeping the whole session in a signed cookie
+#[derive(Clone)]
+pub struct CookieSessionHandler {
+    config: SessionConfig,
+    codec: Arc<dyn CookieCodec>,
+    keys: Arc<[SigningKey]>,
+    max_cookie_size: usize,
+    overflow: Option<Arc<dyn SessionStore>>,
+    overflow_threshold: usize,
+    error_handler: Option<ErrorHandler>,
+}
+
+impl CookieSessionHandler {
+    /// Create a cookie session handler
+    ///
+    /// An invalid configuration (see [`SessionConfig::validate`]) is logged
+    /// as a warning.
+    pub fn new(config: SessionConfig) -> Self {
+        if let Err(e) = config.validate() {
+            tracing::warn!("Invalid session configuration: {}", e);
+        }
+        Self {
+            keys: SigningKey::from_secrets(config.secrets.iter().cloned()),
+            config,
+            codec: Arc::new(ExpressCodec),
+            max_cookie_size: DEFAULT_MAX_COOKIE_SIZE,
+            overflow: None,
+            overflow_threshold: DEFAULT_OVERFLOW_THRESHOLD,
+            error_handler: None,
+        }
+    }
+
+    /// Use a custom cookie codec (default: [`ExpressCodec`])
+    ///
+    /// Wrap it in `EncryptedCodec` to hide the session values from clients.
+    pub fn with_codec<C: CookieCodec>(mut self, codec: C) -> Self {
+        self.codec = Arc::new(codec);
+        self
+    }
+
+    /// Set the largest session cookie written, name and value included (default: 4096)
+    pub fn with_max_cookie_size(mut self, bytes: usize) -> Self {
+        self.max_cookie_size = bytes;
+        self
+    }
+
+    /// Keep sessions too large for the cookie in `store` (hybrid mode)
+    ///
+    /// Sessions whose cookie would exceed the overflow threshold are saved
+    /// in the store, and the cookie carries their ID instead.
+    pub fn with_overflow_store<S: SessionStore>(mut self, store: S) -> Self {
+        self.overflow = Some(Arc::new(store));
+        self
+    }
+
+    /// Set the cookie size above which sessions move to the overflow store (default: 3584)
+    pub fn with_overflow_threshold(mut self, bytes: usize) -> Self {
+        self.overflow_threshold = bytes;
+        self
+    }
+
+    /// Set a callback invoked when a session can't be written
+    pub fn with_error_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&SessionError, &mut Response) + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Report an error to the log and the error handler
+    fn report_error(&self, error: &SessionError, res: &mut Response) {
+        tracing::error!("Cookie session failed: {}", error);
+        if let Some(handler) = &self.error_handler {
+            handler(error, res);
+        }
+    }
+
+    /// Decode the session carried by the request, if valid and unexpired
+    ///
+    /// Returns the session's ID too when it lives in the overflow store.
+    async fn load(
+        &self,
+        req: &Request,
+    ) -> Result<Option<(SessionData, Option<String>)>, SessionError> {
+        let Some(payload) = self.unsign_request_cookie(req) else {
+            return Ok(None);
+        };
+        let (data, sid) = match (payload.strip_prefix(STORE_REFERENCE), &self.overflow) {
+            (Some(sid), Some(store)) => match store.get(sid).await? {
+                Some(data) => (data, Some(sid.to_string())),
+                None => return Ok(None),
+            },
+            (Some(_), None) => return Ok(None),
+            (None, _) => match decode(&payload) {
+                Ok(data) => (data, None),
+                Err(e) => {
+                    tracing::warn!("Ignoring undecodable cookie session: {}", e);
+                    return Ok(None);
+                }
+            },
+        };
+
+        let expired = data.cookie.is_expired()
+            || self
+                .config
+                .absolute_max_age
+                .is_some_and(|max_age| data.exceeds_absolute_max_age(max_age))
+            || self
+                .config
+                .idle_timeout
+                .is_some_and(|timeout| data.is_idle(timeout));
+        Ok((!expired).then_some((data, sid)))
+    }
+
+    /// Verify the request's session cookie, returning its payload
+    fn unsign_request_cookie(&self, req: &Request) -> Option<String> {
+        let name = &self.config.cookie_name;
+        let value = req.cookie(name)?.value().to_string();
+        let signature = self
+            .codec
+            .signature_cookie_name(name)
+            .and_then(|sig_name| req.cookie(&sig_name).map(|c| c.value().to_string()));
+        let cookie = SignedCookie { value, signature };
+        self.codec.unsign(name, &cookie, &self.keys)
+    }
+
+    /// Create data for a new session
+    fn fresh_session_data(&self) -> SessionData {
+        let mut data = SessionData::with_optional_max_age(self.config.max_age);
+        data.cookie = self.config.new_session_cookie();
+        data
+    }
+
+    /// Write the session into the cookie, or the overflow store if too large
+    ///
+    /// `stored` is the ID of the session's current record in the overflow
+    /// store, removed when the session moves back into the cookie.
+    async fn write(
+        &self,
+        res: &mut Response,
+        sid: &str,
+        stored: Option<&str>,
+        data: &SessionData,
+    ) -> Result<SessionStorage, SessionError> {
+        let name = &self.config.cookie_name;
+        let signed = self.codec.sign(name, &encode(data)?, &self.keys[0]);
+        let size = name.len() + 1 + signed.value.len();
+
+        if let Some(store) = self
+            .overflow
+            .as_ref()
+            .filter(|_| size > self.overflow_threshold)
+        {
+            let ttl = data
+                .cookie
+                .max_age()
+                .map(|ms| ((ms + 999) / 1000).max(0) as u64);
+            store.set(sid, data, ttl).await?;
+            let reference = format!("{}{}", STORE_REFERENCE, sid);
+            let signed = self.codec.sign(name, &reference, &self.keys[0]);
+            self.set_cookies(res, signed, data);
+            return Ok(SessionStorage::Store);
+        }
+
+        if size > self.max_cookie_size {
+            return Err(SessionError::TooLarge {
+                size,
+                limit: self.max_cookie_size,
+            });
+        }
+        if let (Some(store), Some(stored)) = (&self.overflow, stored) {
+            store.destroy(stored).await?;
+        }
+        self.set_cookies(res, signed, data);
+        Ok(SessionStorage::Cookie)
+    }
+
+    /// Add the signed session cookie (and its signature cookie) to the response
+    fn set_cookies(&self, res: &mut Response, signed: SignedCookie, data: &SessionData) {
+        let name = &self.config.cookie_name;
+        if let (Some(sig_name), Some(signature)) =
+            (self.codec.signature_cookie_name(name), signed.signature)
+        {
+            res.add_cookie(build_cookie(
+                &self.config,
+                sig_name,
+                signature,
+                &data.cookie,
+            ));
+        }
+        res.add_cookie(build_cookie(
+            &self.config,
+            name.clone(),
+            signed.value,
+            &data.cookie,
+        ));
+    }
+
+    /// Remove a session's record from the overflow store, if it has one
+    async fn destroy_stored(&self, stored: Option<&str>) -> Result<(), SessionError> {
+        match (&self.overflow, stored) {
+            (Some(store), Some(sid)) => store.destroy(sid).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Clear the session cookie (and its signature cookie)
+    fn remove_cookie(&self, res: &mut Response) {
+        let name = &self.config.cookie_name;
+        let names = std::iter::once(name.clone()).chain(self.codec.signature_cookie_name(name));
+        for name in names {
+            let cookie = cookie::Cookie::build(name)
+                .path(self.config.cookie_path.clone())
+                .max_age(CookieDuration::ZERO)
+                .build();
+            res.add_cookie(cookie);
+        }
+    }
+}
+
+/// Encode session data as a cookie payload: base64url of its JSON
+fn encode(data: &SessionData) -> Result<String, SessionError> {
+    Ok(URL_SAFE_NO_PAD.encode(serde_json::to_vec(data)?))
+}
+
+/// Decode a cookie payload
+fn decode(payload: &str) -> Result<SessionData, SessionError> {
+    let json = URL_SAFE_NO_PAD
+        .decode(url_decode(payload))
+        .map_err(|e| SessionError::SerializationError(e.to_string()))?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+#[async_trait]
+impl Handler for CookieSessionHandler {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        let had_cookie = req.cookie(&self.config.cookie_name).is_some();
+        let loaded = match self.load(req).await {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                self.report_error(&e, res);
+                None
+            }
+        };
+        let (mut data, stored, is_new) = match loaded {
+            Some((data, stored)) => (data, stored, false),
+            None => (self.fresh_session_data(), None, true),
+        };
+        if self.config.idle_timeout.is_some() {
+            data.record_access();
+        }
+
+        // IDs of sessions kept in the cookie only identify them within the request
+        let sid = stored.clone().unwrap_or_else(generate_session_id);
+        let session = Session::new(sid, data, is_new);
+        depot.inject(session.clone());
+        depot.inject(if stored.is_some() {
+            SessionStorage::Store
+        } else {
+            SessionStorage::Cookie
+        });
+        req.extensions_mut().insert(session.clone());
+
+        ctrl.call_next(req, depot, res).await;
+
+        let modified = session.is_modified();
+        let unset =
+            self.config.unset == Unset::Destroy && !is_new && modified && session.is_empty();
+        if session.should_destroy() || unset {
+            if let Err(e) = self.destroy_stored(stored.as_deref()).await {
+                self.report_error(&e, res);
+            }
+            if had_cookie {
+                self.remove_cookie(res);
+            }
+            return;
+        }
+
+        // A regenerated session leaves its stored record behind
+        let (sid, stored) = if session.should_regenerate() {
+            if let Err(e) = self.destroy_stored(stored.as_deref()).await {
+                self.report_error(&e, res);
+            }
+            (generate_session_id(), None)
+        } else {
+            (session.id().to_string(), stored)
+        };
+
+        let rolling = self.config.rolling && !is_new;
+        if rolling {
+            session.touch();
+        }
+
+        let should_write = modified
+            || rolling
+            || session.should_regenerate()
+            || session.is_cookie_modified()
+            || (self.config.resave && !is_new)
+            || (self.config.idle_timeout.is_some() && !is_new)
+            || (is_new && self.config.save_uninitialized);
+        if !should_write {
+            return;
+        }
+
+        match self
+            .write(res, &sid, stored.as_deref(), &session.data())
+            .await
+        {
+            Ok(storage) => {
+                depot.inject(storage);
+            }
+            Err(e) => self.report_error(&e, res),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_core::{handler, Router, Service};
+
+    use super::*;
+    use crate::depot_ext::SessionDepotExt;
+    use crate::store::MemoryStore;
+
+    #[handler]
+    async fn increment(req: &mut Request, depot: &mut Depot) -> String {
+        let session = depot.session_mut().unwrap();
+        let count = session.get::<usize>("count").unwrap_or(0) + 1;
+        session.set("count", count);
+        match req.query::<usize>("pad") {
+            Some(0) => {
+                session.remove("padding");
+            }
+            Some(padding) => session.set("padding", "x".repeat(padding)),
+            None => {}
+        }
+        format!("{} {:?}", count, depot.obtain::<SessionStorage>().unwrap())
+    }
+
+    fn service() -> Service {
+        let handler = CookieSessionHandler::new(SessionConfig::new("secret"));
+        Service::new(Router::new().hoop(handler).get(increment))
+    }
+
+    #[tokio::test]
+    async fn test_session_lives_in_the_cookie() {
+        let service = service();
+        let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+
+        let mut res = TestClient::get("http://127.0.0.1/")
+            .add_header("cookie", &cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "2 Cookie");
+
+        // A tampered cookie starts over
+        let tampered = cookie.replacen("s%3A", "s%3Ax", 1).replacen("s:", "s:x", 1);
+        let mut res = TestClient::get("http://127.0.0.1/")
+            .add_header("cookie", tampered, true)
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "1 Cookie");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_session_is_not_written() {
+        let service = service();
+        let res = TestClient::get("http://127.0.0.1/?pad=5000")
+            .send(&service)
+            .await;
+        assert!(res.cookie("connect.sid").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_overflows_to_the_store() {
+        let store = MemoryStore::new();
+        let handler = CookieSessionHandler::new(SessionConfig::new("secret"))
+            .with_overflow_store(store.clone());
+        let service = Service::new(Router::new().hoop(handler).get(increment));
+        let send = |query: &'static str, cookie: String| {
+            TestClient::get(format!("http://127.0.0.1/{}", query))
+                .add_header("cookie", cookie, true)
+                .send(&service)
+        };
+
+        let mut res = send("?pad=5000", String::new()).await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+        assert!(cookie.len() < 200);
+        // The depot reflects where the session was loaded from
+        assert_eq!(res.take_string().await.unwrap(), "1 Cookie");
+        assert_eq!(store.length().await.unwrap(), 1);
+
+        let mut res = send("", cookie.clone()).await;
+        assert_eq!(res.take_string().await.unwrap(), "2 Store");
+
+        // Shrinking moves the session back into the cookie
+        let mut res = send("?pad=0", cookie).await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+        assert_eq!(res.take_string().await.unwrap(), "3 Store");
+        assert_eq!(store.length().await.unwrap(), 0);
+
+        let mut res = send("", cookie).await;
+        assert_eq!(res.take_string().await.unwrap(), "4 Cookie");
+    }
+}
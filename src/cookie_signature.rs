@@ -3,12 +3,22 @@
 //! This module implements cookie signing compatible with Node.js cookie-signature library.
 //! The format is: `s:` + session_id + `.` + base64(hmac_sha256(session_id, secret))
 
-use base64::{engine::general_purpose::STANDARD, Engine};
+use base64::{engine::general_purpose::STANDARD_NO_PAD, Engine};
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
+use crate::secret::SigningKey;
+
 type HmacSha256 = Hmac<Sha256>;
 
+/// Length of a base64 HMAC-SHA256 signature without padding
+const SIGNATURE_LEN: usize = 43;
+
+/// HMAC-SHA256 keyed with `secret`, for callers without a [`SigningKey`]
+fn keyed(secret: &str) -> HmacSha256 {
+    HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size")
+}
+
 /// Sign a value using the express-session compatible format.
 /// Returns: `s:` + value + `.` + base64_signature (without padding)
 ///
@@ -23,21 +33,25 @@ type HmacSha256 = Hmac<Sha256>;
 /// };
 /// ```
 pub fn sign(value: &str, secret: &str) -> String {
-    let signature = create_signature(value, secret);
-    format!("s:{}.{}", value, signature)
+    sign_with(value, keyed(secret))
 }
 
-/// Create HMAC-SHA256 signature in base64 format (no padding, to match Node.js)
-fn create_signature(value: &str, secret: &str) -> String {
-    let mut mac =
-        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+/// Sign a value with a precomputed key (see [`sign`])
+pub fn sign_with_key(value: &str, key: &SigningKey) -> String {
+    sign_with(value, key.hmac_sha256())
+}
+
+/// Sign `value` with a keyed HMAC
+fn sign_with(value: &str, mut mac: HmacSha256) -> String {
     mac.update(value.as_bytes());
-    let result = mac.finalize();
+
+    let mut signed = String::with_capacity(value.len() + SIGNATURE_LEN + 3);
+    signed.push_str("s:");
+    signed.push_str(value);
+    signed.push('.');
     // Node.js uses standard base64 and strips trailing '=' padding
-    STANDARD
-        .encode(result.into_bytes())
-        .trim_end_matches('=')
-        .to_string()
+    STANDARD_NO_PAD.encode_string(mac.finalize().into_bytes(), &mut signed);
+    signed
 }
 
 /// Unsign a value, verifying the signature.
@@ -53,41 +67,67 @@ fn create_signature(value: &str, secret: &str) -> String {
 /// };
 /// ```
 pub fn unsign(signed_value: &str, secret: &str) -> Option<String> {
-    // Check for 's:' prefix
-    if !signed_value.starts_with("s:") {
+    unsign_with(signed_value, std::iter::once(keyed(secret)))
+}
+
+/// Verify `signed_value` against each keyed HMAC in turn, without
+/// allocating until one matches
+fn unsign_with(signed_value: &str, macs: impl IntoIterator<Item = HmacSha256>) -> Option<String> {
+    // Reject malformed values before spending an HMAC on them
+    if !is_well_formed(signed_value) {
         return None;
     }
+    let dot = signed_value.len() - SIGNATURE_LEN - 1;
+    let (value, signature) = (&signed_value[2..dot], &signed_value[dot + 1..]);
+
+    // 43 base64 characters decode to the 32 signature bytes; the decoder
+    // wants room for its 33 byte estimate
+    let mut provided = [0u8; 33];
+    let len = STANDARD_NO_PAD
+        .decode_slice(signature, &mut provided)
+        .ok()?;
+
+    macs.into_iter()
+        .any(|mut mac| {
+            mac.update(value.as_bytes());
+            // Constant-time comparison to prevent timing attacks
+            mac.verify_slice(&provided[..len]).is_ok()
+        })
+        .then(|| value.to_string())
+}
 
-    let without_prefix = &signed_value[2..];
-
-    // Find the last '.' which separates value from signature
-    let dot_pos = without_prefix.rfind('.')?;
-    let value = &without_prefix[..dot_pos];
-    let provided_signature = &without_prefix[dot_pos + 1..];
-
-    // Create expected signature
-    let expected_signature = create_signature(value, secret);
-
-    // Constant-time comparison to prevent timing attacks
-    if constant_time_compare(&expected_signature, provided_signature) {
-        Some(value.to_string())
-    } else {
-        None
-    }
+/// Cheap structural check: `s:` prefix, a non-empty value, a dot and a
+/// base64 signature of the right length
+///
+/// Like cookie-signature, the signature starts after the last dot, so values
+/// may contain dots. Values with control characters, which no cookie can
+/// carry, are rejected.
+pub fn is_well_formed(signed_value: &str) -> bool {
+    let bytes = signed_value.as_bytes();
+    let Some(dot) = bytes.len().checked_sub(SIGNATURE_LEN + 1) else {
+        return false;
+    };
+    bytes.starts_with(b"s:")
+        && dot > 2
+        && bytes[dot] == b'.'
+        && !bytes[2..dot].iter().any(u8::is_ascii_control)
+        && bytes[dot + 1..]
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || *b == b'+' || *b == b'/')
 }
 
 /// Try to unsign with multiple secrets (for secret rotation)
 pub fn unsign_with_secrets(signed_value: &str, secrets: &[String]) -> Option<String> {
-    for secret in secrets {
-        if let Some(value) = unsign(signed_value, secret) {
-            return Some(value);
-        }
-    }
-    None
+    unsign_with(signed_value, secrets.iter().map(|secret| keyed(secret)))
+}
+
+/// Try to unsign with multiple precomputed keys (for secret rotation)
+pub fn unsign_with_keys(signed_value: &str, keys: &[SigningKey]) -> Option<String> {
+    unsign_with(signed_value, keys.iter().map(SigningKey::hmac_sha256))
 }
 
 /// Constant-time string comparison to prevent timing attacks
-fn constant_time_compare(a: &str, b: &str) -> bool {
+pub(crate) fn constant_time_compare(a: &str, b: &str) -> bool {
     if a.len() != b.len() {
         return false;
     }
@@ -131,6 +171,22 @@ mod tests {
         assert_eq!(unsigned, None);
     }
 
+    #[test]
+    fn test_well_formed() {
+        let signed = sign("session-id", "secret");
+        assert!(is_well_formed(&signed));
+        assert!(!is_well_formed(&signed[2..]));
+        assert!(!is_well_formed(&signed[..signed.len() - 1]));
+        assert!(is_well_formed(&sign("session.id", "secret")));
+        assert!(!is_well_formed(&sign("", "secret")));
+        assert!(!is_well_formed(&sign("session\nid", "secret")));
+        assert!(!is_well_formed(&format!(
+            "{}-",
+            &signed[..signed.len() - 1]
+        )));
+        assert!(!is_well_formed(&format!("s:{}", "a".repeat(1 << 20))));
+    }
+
     #[test]
     fn test_compatible_with_express() {
         // Test vector from express-session / cookie-signature
@@ -154,6 +210,15 @@ mod tests {
         assert_eq!(unsigned, Some(value.to_string()));
     }
 
+    #[test]
+    fn test_dotted_value() {
+        let signed = sign("tenant.session-id", "secret");
+        assert_eq!(
+            unsign(&signed, "secret"),
+            Some("tenant.session-id".to_string())
+        );
+    }
+
     #[test]
     fn test_secret_rotation() {
         let old_secret = "old-secret".to_string();
@@ -168,4 +233,17 @@ mod tests {
         let unsigned = unsign_with_secrets(&signed, &secrets);
         assert_eq!(unsigned, Some(value.to_string()));
     }
+
+    #[test]
+    fn test_precomputed_keys() {
+        let keys = SigningKey::from_secrets(["new-secret", "old-secret"]);
+        let signed = sign_with_key("session-id", &keys[1]);
+        assert_eq!(signed, sign("session-id", "old-secret"));
+
+        assert_eq!(
+            unsign_with_keys(&signed, &keys),
+            Some("session-id".to_string())
+        );
+        assert_eq!(unsign_with_keys(&signed, &keys[..1]), None);
+    }
 }
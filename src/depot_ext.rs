@@ -1,25 +1,131 @@
 //! Extension trait for Depot to easily access sessions
 
+use crate::error::SessionMissing;
 use crate::session::Session;
-use salvo_core::Depot;
+use salvo_core::http::StatusError;
+use salvo_core::{async_trait, Depot, Request, Response, Writer};
 
-const SESSION_KEY: &str = "salvo.express.session";
+/// Marker left in the depot when the session handler skips a request
+pub(crate) struct SessionSkipped;
 
 /// Extension trait for Salvo's Depot to provide easy session access
+#[async_trait]
 pub trait SessionDepotExt {
     /// Get a reference to the session
+    ///
+    /// Lazily loaded sessions (see [`SessionConfig::with_lazy_load`](crate::SessionConfig::with_lazy_load))
+    /// read as new and empty until loaded; use [`Self::load_session`] for those.
     fn session(&self) -> Option<&Session>;
 
     /// Get a mutable session (returns a clone with shared atomic state)
     fn session_mut(&mut self) -> Option<Session>;
+
+    /// Get the session, or why there is none
+    ///
+    /// Distinguishes a route without the session handler from a request
+    /// the handler skipped. The error renders as 500 Internal Server Error,
+    /// so handlers can simply use `?`:
+    ///
+    /// ```rust,ignore
+    /// #[handler]
+    /// async fn index(depot: &mut Depot) -> Result<String, SessionMissing> {
+    ///     let session = depot.session_required()?;
+    ///     Ok(format!("Views: {}", session.incr("views", 1)))
+    /// }
+    /// ```
+    fn session_required(&self) -> Result<Session, SessionMissing>;
+
+    /// Get the session with its stored data loaded
+    ///
+    /// Fetches lazily loaded sessions from the store on first use, so they
+    /// can be read and updated like any other. A failed load is logged and
+    /// leaves a fresh session (see [`Session::load_failed`]).
+    async fn load_session(&self) -> Result<Session, SessionMissing>;
 }
 
+#[async_trait]
 impl SessionDepotExt for Depot {
     fn session(&self) -> Option<&Session> {
-        self.get::<Session>(SESSION_KEY).ok()
+        self.obtain::<Session>().ok()
     }
 
     fn session_mut(&mut self) -> Option<Session> {
-        self.get::<Session>(SESSION_KEY).ok().cloned()
+        self.obtain::<Session>().ok().cloned()
+    }
+
+    fn session_required(&self) -> Result<Session, SessionMissing> {
+        if let Ok(session) = self.obtain::<Session>() {
+            return Ok(session.clone());
+        }
+        if self.obtain::<SessionSkipped>().is_ok() {
+            return Err(SessionMissing::Skipped);
+        }
+        Err(SessionMissing::NotInstalled)
+    }
+
+    async fn load_session(&self) -> Result<Session, SessionMissing> {
+        let session = self.session_required()?;
+        if let Err(e) = session.load().await {
+            tracing::error!("Failed to load session: {}", e);
+        }
+        Ok(session)
+    }
+}
+
+#[async_trait]
+impl Writer for SessionMissing {
+    async fn write(self, _req: &mut Request, _depot: &mut Depot, res: &mut Response) {
+        tracing::error!("{}", self);
+        res.render(StatusError::internal_server_error().brief(self.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::StatusCode;
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_core::{handler, Router, Service};
+
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::MemoryStore;
+
+    #[handler]
+    async fn views(depot: &mut Depot) -> Result<String, SessionMissing> {
+        Ok(depot.session_required()?.incr("views", 1).to_string())
+    }
+
+    #[tokio::test]
+    async fn test_session_required() {
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), SessionConfig::new("secret"))
+            .with_skip(|req| req.uri().path() == "/skipped");
+        let service = Service::new(
+            Router::new()
+                .push(Router::with_path("none").get(views))
+                .push(
+                    Router::new()
+                        .hoop(handler)
+                        .push(Router::with_path("{*}").get(views)),
+                ),
+        );
+
+        let mut res = TestClient::get("http://127.0.0.1/ok").send(&service).await;
+        assert_eq!(res.take_string().await.unwrap(), "1");
+
+        for (path, reason) in [
+            ("skipped", SessionMissing::Skipped),
+            ("none", SessionMissing::NotInstalled),
+        ] {
+            let mut res = TestClient::get(format!("http://127.0.0.1/{}", path))
+                .send(&service)
+                .await;
+            assert_eq!(res.status_code, Some(StatusCode::INTERNAL_SERVER_ERROR));
+            assert!(res
+                .take_string()
+                .await
+                .unwrap()
+                .contains(&reason.to_string()));
+        }
     }
 }
@@ -1,50 +1,303 @@
 //! Session error types
+//!
+//! [`SessionError::kind`] classifies errors, so applications can handle
+//! store outages, tampered cookies and malformed data differently, and
+//! [`SessionError::is_retryable`] tells transient failures apart. Errors
+//! wrapping another keep it as their [`source`](std::error::Error::source).
 
-use std::fmt;
+use salvo_core::http::StatusCode;
+use thiserror::Error;
 
 /// Errors that can occur during session operations
-#[derive(Debug)]
+#[derive(Debug, Error)]
 pub enum SessionError {
     /// Error from the session store
+    #[error("Session store error: {0}")]
     StoreError(String),
+    /// Error from a store backend, kept as the source
+    ///
+    /// Custom stores can wrap their client's errors with [`SessionError::backend`].
+    #[error("Session store error: {0}")]
+    Backend(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// I/O error of a file-backed store, sink or dump
+    #[error("{context}: {source}")]
+    Io {
+        /// What was being done, e.g. the file being written
+        context: String,
+        /// The underlying error
+        #[source]
+        source: std::io::Error,
+    },
     /// Error during serialization/deserialization
+    #[error("Serialization error: {0}")]
     SerializationError(String),
+    /// Session JSON that can't be encoded or decoded
+    #[error("Serialization error: {0}")]
+    Json(#[from] serde_json::Error),
     /// Invalid session ID format
+    #[error("Invalid session ID: {0}")]
     InvalidSessionId(String),
     /// Invalid cookie signature
+    #[error("Invalid cookie signature")]
     InvalidSignature,
     /// Session not found
+    #[error("Session not found")]
     NotFound,
+    /// Another request saved the session first (optimistic concurrency control)
+    #[error("Session was modified by a concurrent request")]
+    Conflict,
+    /// The session lock could not be acquired in time
+    #[error("Timed out waiting for the session lock")]
+    Locked,
+    /// The session store didn't answer in time
+    #[error("Timed out waiting for the session store")]
+    Timeout,
+    /// The request may only read the session (see `with_read_only_safe_methods`)
+    #[error("Session is read-only in this request")]
+    ReadOnly,
+    /// The serialized session exceeds the configured maximum size
+    #[error("Session of {size} bytes exceeds the {limit} byte limit")]
+    TooLarge {
+        /// Serialized size in bytes
+        size: usize,
+        /// Configured maximum in bytes
+        limit: usize,
+    },
     /// Redis error (when redis-store feature is enabled)
     #[cfg(feature = "redis-store")]
-    RedisError(redis::RedisError),
+    #[error("Redis error: {0}")]
+    RedisError(#[source] redis::RedisError),
 }
 
-impl fmt::Display for SessionError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+/// Broad class of a [`SessionError`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The session store failed or is unreachable
+    Store,
+    /// The session store or lock didn't answer in time
+    Timeout,
+    /// Session data couldn't be encoded or decoded
+    Serialization,
+    /// A cookie signature or session ID was rejected
+    Signature,
+    /// The session doesn't exist (anymore)
+    NotFound,
+    /// Another request changed the session concurrently
+    Conflict,
+    /// The session exceeds its size limit
+    TooLarge,
+    /// The request may only read the session
+    ReadOnly,
+}
+
+impl SessionError {
+    /// Wrap an error of a store backend, keeping it as the source
+    pub fn backend<E>(err: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        SessionError::Backend(err.into())
+    }
+
+    /// Broad class of this error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            SessionError::StoreError(_) | SessionError::Backend(_) | SessionError::Io { .. } => {
+                ErrorKind::Store
+            }
+            #[cfg(feature = "redis-store")]
+            SessionError::RedisError(_) => ErrorKind::Store,
+            SessionError::Timeout | SessionError::Locked => ErrorKind::Timeout,
+            SessionError::SerializationError(_) | SessionError::Json(_) => ErrorKind::Serialization,
+            SessionError::InvalidSessionId(_) | SessionError::InvalidSignature => {
+                ErrorKind::Signature
+            }
+            SessionError::NotFound => ErrorKind::NotFound,
+            SessionError::Conflict => ErrorKind::Conflict,
+            SessionError::TooLarge { .. } => ErrorKind::TooLarge,
+            SessionError::ReadOnly => ErrorKind::ReadOnly,
+        }
+    }
+
+    /// Whether the operation may succeed if retried
+    ///
+    /// True for timeouts, lost connections and conflicts (after reloading
+    /// the session); false for errors that would repeat, and for
+    /// [`SessionError::StoreError`] and [`SessionError::Backend`], whose
+    /// cause is unknown.
+    pub fn is_retryable(&self) -> bool {
         match self {
-            SessionError::StoreError(msg) => write!(f, "Session store error: {}", msg),
-            SessionError::SerializationError(msg) => write!(f, "Serialization error: {}", msg),
-            SessionError::InvalidSessionId(msg) => write!(f, "Invalid session ID: {}", msg),
-            SessionError::InvalidSignature => write!(f, "Invalid cookie signature"),
-            SessionError::NotFound => write!(f, "Session not found"),
+            SessionError::Timeout | SessionError::Locked | SessionError::Conflict => true,
+            SessionError::Io { source, .. } => matches!(
+                source.kind(),
+                std::io::ErrorKind::Interrupted
+                    | std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::WouldBlock
+            ),
             #[cfg(feature = "redis-store")]
-            SessionError::RedisError(e) => write!(f, "Redis error: {}", e),
+            SessionError::RedisError(e) => {
+                e.is_io_error()
+                    || e.is_connection_dropped()
+                    || e.is_connection_refusal()
+                    || e.is_timeout()
+                    || e.is_cluster_error()
+            }
+            _ => false,
+        }
+    }
+
+    /// HTTP status a request failing with this error is answered with
+    ///
+    /// Store failures and timeouts are 503 Service Unavailable, rejected
+    /// cookies 400 Bad Request and serialization failures 500 Internal
+    /// Server Error.
+    pub fn status_code(&self) -> StatusCode {
+        match self.kind() {
+            ErrorKind::Store | ErrorKind::Timeout => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorKind::Serialization | ErrorKind::ReadOnly => StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorKind::Signature => StatusCode::BAD_REQUEST,
+            ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            ErrorKind::Conflict => StatusCode::CONFLICT,
+            ErrorKind::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
         }
     }
 }
 
-impl std::error::Error for SessionError {}
+/// Renders the error with its [`status_code`](SessionError::status_code)
+///
+/// Details of server errors are logged rather than sent to the client.
+#[cfg(feature = "http-errors")]
+impl salvo_core::Scribe for SessionError {
+    fn render(self, res: &mut salvo_core::Response) {
+        let status = self.status_code();
+        let Some(error) = salvo_core::http::StatusError::from_code(status) else {
+            res.status_code(status);
+            return;
+        };
+        if status.is_server_error() {
+            tracing::error!("{}", self);
+            res.render(error);
+        } else {
+            res.render(error.brief(self.to_string()));
+        }
+    }
+}
+
+/// Why [`SessionDepotExt::session_required`](crate::SessionDepotExt::session_required) found no session
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+pub enum SessionMissing {
+    /// No session handler runs for this route
+    #[error("No session: the session handler is not installed on this route")]
+    NotInstalled,
+    /// The session handler runs for this route but skipped this request
+    /// (see `with_skip_paths` and `with_skip`)
+    #[error("No session: the session handler skipped this request")]
+    Skipped,
+}
+
+/// Problems found by [`SessionConfig::validate`](crate::SessionConfig::validate)
+#[derive(Clone, Debug, PartialEq, Error)]
+pub enum ConfigError {
+    /// No secret is configured
+    #[error("No session secret configured; pass one to SessionConfig::new or set `secrets`")]
+    NoSecrets,
+    /// A secret is an empty string
+    #[error("Session secrets must not be empty")]
+    EmptySecret,
+    /// The same secret is listed more than once
+    #[error("Session secrets contain duplicates; list each secret once")]
+    DuplicateSecret,
+    /// The well-known default secret is used without being explicitly allowed
+    #[error(
+        "The default \"keyboard cat\" secret is insecure; configure a random secret \
+         (or use SessionConfig::development() locally)"
+    )]
+    DefaultSecret,
+    /// A secret is shorter than the minimum length required by strict validation
+    #[error("Session secrets must be at least {min_len} bytes long; generate a random secret")]
+    WeakSecret {
+        /// Minimum accepted length in bytes
+        min_len: usize,
+    },
+    /// A cookie name is empty or contains characters not allowed in cookie names
+    #[error("Invalid cookie name {0:?}; use a non-empty name without spaces or separators")]
+    InvalidCookieName(String),
+    /// Cookie attributes that browsers reject in combination
+    #[error("Conflicting cookie attributes: {0}")]
+    ConflictingAttributes(String),
+    /// An environment variable holds a value that can't be parsed
+    #[error("Invalid value {value:?} for environment variable {name}")]
+    InvalidEnvVar {
+        /// Name of the variable
+        name: String,
+        /// Value that failed to parse
+        value: String,
+    },
+}
 
 #[cfg(feature = "redis-store")]
 impl From<redis::RedisError> for SessionError {
     fn from(err: redis::RedisError) -> Self {
-        SessionError::RedisError(err)
+        if err.is_timeout() {
+            SessionError::Timeout
+        } else {
+            SessionError::RedisError(err)
+        }
     }
 }
 
-impl From<serde_json::Error> for SessionError {
-    fn from(err: serde_json::Error) -> Self {
-        SessionError::SerializationError(err.to_string())
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use super::*;
+
+    #[test]
+    fn test_classification() {
+        let json = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let error = SessionError::from(json);
+        assert_eq!(error.kind(), ErrorKind::Serialization);
+        assert!(error.source().is_some());
+        assert!(error.to_string().starts_with("Serialization error: "));
+
+        let io = std::io::Error::from(std::io::ErrorKind::TimedOut);
+        let error = SessionError::Io {
+            context: "Failed to write sessions.json".to_string(),
+            source: io,
+        };
+        assert_eq!(error.kind(), ErrorKind::Store);
+        assert!(error.is_retryable());
+        assert!(error.source().is_some());
+
+        let error = SessionError::backend("connection reset");
+        assert_eq!(error.to_string(), "Session store error: connection reset");
+        assert!(!error.is_retryable());
+
+        assert_eq!(SessionError::Locked.kind(), ErrorKind::Timeout);
+        assert_eq!(
+            SessionError::InvalidSignature.status_code(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            SessionError::Timeout.status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[cfg(feature = "http-errors")]
+    #[tokio::test]
+    async fn test_errors_render_status() {
+        use salvo_core::test::TestClient;
+        use salvo_core::{handler, Router, Service};
+
+        #[handler]
+        async fn conflict() -> Result<&'static str, SessionError> {
+            Err(SessionError::Conflict)
+        }
+
+        let service = Service::new(Router::new().get(conflict));
+        let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        assert_eq!(res.status_code, Some(StatusCode::CONFLICT));
     }
 }
@@ -0,0 +1,129 @@
+//! In-process session events
+//!
+//! Every handler publishes a [`SessionEvent`] on a `tokio::sync::broadcast`
+//! channel as it creates, saves, regenerates, destroys or finds expired
+//! sessions. Subscribe to react elsewhere in the process, e.g. to track
+//! websocket presence or invalidate a cache:
+//!
+//! ```rust,ignore
+//! let session_handler = ExpressSessionHandler::new(store, config);
+//! let mut events = session_handler.subscribe();
+//! tokio::spawn(async move {
+//!     while let Ok(event) = events.recv().await {
+//!         if let SessionEvent::Destroyed { sid } = event {
+//!             presence.disconnect(&sid);
+//!         }
+//!     }
+//! });
+//! ```
+//!
+//! Events are only built while someone is subscribed. A subscriber that
+//! falls more than the channel capacity behind misses events and gets
+//! `RecvError::Lagged`.
+
+/// Default number of events buffered for slow subscribers
+pub(crate) const DEFAULT_EVENT_CAPACITY: usize = 256;
+
+/// Something that happened to a session during a request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SessionEvent {
+    /// A new session was saved for the first time (after its `Saved` event)
+    Created {
+        /// Session ID
+        sid: String,
+    },
+    /// A session was written to the store
+    Saved {
+        /// Session ID
+        sid: String,
+    },
+    /// A stored session was replaced by one with a new ID (after its `Saved` event)
+    Regenerated {
+        /// Previous session ID, already destroyed
+        old_sid: String,
+        /// New session ID
+        new_sid: String,
+    },
+    /// A session was removed from the store, explicitly or when regenerated
+    Destroyed {
+        /// Session ID
+        sid: String,
+    },
+    /// A request presented a stored session past its expiry, absolute
+    /// lifetime or idle timeout
+    Expired {
+        /// Session ID
+        sid: String,
+    },
+}
+
+impl SessionEvent {
+    /// ID of the session the event is about (the new ID when regenerated)
+    pub fn sid(&self) -> &str {
+        match self {
+            SessionEvent::Created { sid }
+            | SessionEvent::Saved { sid }
+            | SessionEvent::Destroyed { sid }
+            | SessionEvent::Expired { sid } => sid,
+            SessionEvent::Regenerated { new_sid, .. } => new_sid,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::test::TestClient;
+    use salvo_core::{handler, Depot, Router, Service};
+
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::depot_ext::SessionDepotExt;
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::MemoryStore;
+
+    #[handler]
+    async fn login(depot: &mut Depot) {
+        let session = depot.session_mut().unwrap();
+        session.set("user", 1);
+        if session.get::<bool>("seen").is_some() {
+            session.regenerate_keep_data();
+        }
+        session.set("seen", true);
+    }
+
+    #[tokio::test]
+    async fn test_events() {
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), SessionConfig::new("secret"));
+        let mut events = handler.subscribe();
+        let service = Service::new(Router::new().hoop(handler).get(login));
+
+        let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        let cookie = res.cookie("connect.sid").unwrap().to_string();
+        let saved = events.try_recv().unwrap();
+        let sid = saved.sid().to_string();
+        assert_eq!(saved, SessionEvent::Saved { sid: sid.clone() });
+        assert_eq!(
+            events.try_recv().unwrap(),
+            SessionEvent::Created { sid: sid.clone() }
+        );
+
+        TestClient::get("http://127.0.0.1/")
+            .add_header("cookie", cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(
+            events.try_recv().unwrap(),
+            SessionEvent::Destroyed { sid: sid.clone() }
+        );
+        let new_sid = events.try_recv().unwrap().sid().to_string();
+        assert_ne!(new_sid, sid);
+        assert_eq!(
+            events.try_recv().unwrap(),
+            SessionEvent::Regenerated {
+                old_sid: sid,
+                new_sid
+            }
+        );
+        assert!(events.try_recv().is_err());
+    }
+}
@@ -0,0 +1,86 @@
+//! Handler argument extraction
+//!
+//! The session handler also stores the session in the request's
+//! extensions, so handlers can take it as an argument instead of going
+//! through the depot:
+//!
+//! ```rust,ignore
+//! #[handler]
+//! async fn index(session: Session) -> String {
+//!     let views = session.incr("views", 1);
+//!     format!("Views: {}", views)
+//! }
+//! ```
+
+use salvo_core::extract::Metadata;
+use salvo_core::http::StatusError;
+use salvo_core::{Extractible, Request};
+
+use crate::session::Session;
+
+impl<'ex> Extractible<'ex> for Session {
+    fn metadata() -> &'static Metadata {
+        static METADATA: Metadata = Metadata::new("Session");
+        &METADATA
+    }
+
+    /// Take the session installed by the session handler, or fail with 500
+    ///
+    /// Lazily loaded sessions are loaded first; a failed load is logged and
+    /// leaves a fresh session.
+    #[allow(refining_impl_trait)]
+    async fn extract(req: &'ex mut Request) -> Result<Self, StatusError> {
+        let session = req.extensions().get::<Session>().cloned().ok_or_else(|| {
+            tracing::error!("Session extracted on a route without the session handler");
+            StatusError::internal_server_error().brief("Session middleware is not installed")
+        })?;
+        if let Err(e) = session.load().await {
+            tracing::error!("Failed to load session: {}", e);
+        }
+        Ok(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::StatusCode;
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_core::{handler, Router, Service, Writer};
+
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::MemoryStore;
+
+    #[handler]
+    async fn views(session: Session) -> String {
+        session.incr("views", 1).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_session_argument() {
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), SessionConfig::new("secret"));
+        let service = Service::new(
+            Router::new()
+                .push(Router::with_path("with").hoop(handler).get(views))
+                .push(Router::with_path("without").get(views)),
+        );
+
+        let mut res = TestClient::get("http://127.0.0.1/with")
+            .send(&service)
+            .await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+        assert_eq!(res.take_string().await.unwrap(), "1");
+
+        let mut res = TestClient::get("http://127.0.0.1/with")
+            .add_header("cookie", &cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "2");
+
+        let res = TestClient::get("http://127.0.0.1/without")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+}
@@ -0,0 +1,166 @@
+//! Client fingerprint binding
+//!
+//! [`Fingerprint`] records a fingerprint of the client when a session is
+//! created and compares it on every load, so a cookie replayed from another
+//! browser stands out. The default fingerprint is a hash of the
+//! `User-Agent` header; any function of the request can be used instead:
+//!
+//! ```rust,ignore
+//! let session_handler = ExpressSessionHandler::new(store, config).with_fingerprint(
+//!     Fingerprint::new()
+//!         .with_function(|req| {
+//!             let ua = req.header::<String>("user-agent")?;
+//!             let lang = req.header::<String>("accept-language").unwrap_or_default();
+//!             Some(fingerprint::hash(&format!("{}|{}", ua, lang)))
+//!         })
+//!         .with_on_mismatch(FingerprintAction::Destroy),
+//! );
+//! ```
+//!
+//! The fingerprint is stored as `clientFingerprint` next to the session's
+//! cookie. Sessions without one (e.g. created by Node.js) are bound to the
+//! client they are next saved from. Requests without a fingerprint are not
+//! checked.
+
+use std::sync::Arc;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use salvo_core::Request;
+use sha2::{Digest, Sha256};
+
+/// Function computing a client fingerprint from a request
+pub type FingerprintFn = Arc<dyn Fn(&Request) -> Option<String> + Send + Sync>;
+
+/// What happens when a session is loaded by a client with another fingerprint
+#[derive(Clone, Debug, PartialEq)]
+pub enum FingerprintAction {
+    /// Use the session anyway
+    Ignore,
+    /// Log a warning and use the session
+    Log,
+    /// Destroy the stored session and continue with a new one
+    Destroy,
+}
+
+/// Binding of sessions to a fingerprint of the client
+#[derive(Clone)]
+pub struct Fingerprint {
+    function: FingerprintFn,
+    on_mismatch: FingerprintAction,
+}
+
+impl Default for Fingerprint {
+    fn default() -> Self {
+        Self {
+            function: Arc::new(user_agent),
+            on_mismatch: FingerprintAction::Log,
+        }
+    }
+}
+
+impl Fingerprint {
+    /// Fingerprint clients by their `User-Agent` header, logging mismatches
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the function computing the fingerprint (default: [`user_agent`])
+    ///
+    /// Returning None leaves the request unchecked.
+    pub fn with_function<F>(mut self, function: F) -> Self
+    where
+        F: Fn(&Request) -> Option<String> + Send + Sync + 'static,
+    {
+        self.function = Arc::new(function);
+        self
+    }
+
+    /// Set what happens on a fingerprint mismatch (default: Log)
+    pub fn with_on_mismatch(mut self, action: FingerprintAction) -> Self {
+        self.on_mismatch = action;
+        self
+    }
+
+    /// What happens on a fingerprint mismatch
+    pub fn on_mismatch(&self) -> &FingerprintAction {
+        &self.on_mismatch
+    }
+
+    /// Fingerprint of the client sending `req`
+    pub fn compute(&self, req: &Request) -> Option<String> {
+        (self.function)(req)
+    }
+}
+
+impl std::fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Fingerprint")
+            .field("on_mismatch", &self.on_mismatch)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Hash a value into a compact fingerprint (base64url SHA-256)
+pub fn hash(value: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(value.as_bytes()))
+}
+
+/// The default fingerprint: a hash of the `User-Agent` header
+pub fn user_agent(req: &Request) -> Option<String> {
+    req.header::<String>("user-agent").map(|ua| hash(&ua))
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_core::{handler, Depot, Router, Service};
+
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::depot_ext::SessionDepotExt;
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::{MemoryStore, SessionStore};
+
+    #[handler]
+    async fn visit(depot: &mut Depot) -> String {
+        let session = depot.session_mut().unwrap();
+        let visits = session.get::<u64>("visits").unwrap_or(0) + 1;
+        session.set("visits", visits);
+        visits.to_string()
+    }
+
+    async fn request(service: &Service, ua: &str, cookie: &str) -> String {
+        TestClient::get("http://127.0.0.1/")
+            .add_header("user-agent", ua, true)
+            .add_header("cookie", cookie, true)
+            .send(service)
+            .await
+            .take_string()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fingerprint_mismatch() {
+        let store = MemoryStore::new();
+        let handler = ExpressSessionHandler::new(store.clone(), SessionConfig::new("secret"))
+            .with_fingerprint(Fingerprint::new().with_on_mismatch(FingerprintAction::Destroy));
+        let service = Service::new(Router::new().hoop(handler).get(visit));
+
+        let res = TestClient::get("http://127.0.0.1/")
+            .add_header("user-agent", "Firefox", true)
+            .send(&service)
+            .await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+        let sid = store.ids().await.unwrap().pop().unwrap();
+        let data = store.get(&sid).await.unwrap().unwrap();
+        assert_eq!(data.fingerprint, Some(hash("Firefox")));
+
+        assert_eq!(request(&service, "Firefox", &cookie).await, "2");
+        // Replayed from another browser: destroyed
+        assert_eq!(request(&service, "curl", &cookie).await, "1");
+        assert!(store.get(&sid).await.unwrap().is_none());
+        assert_eq!(request(&service, "Firefox", &cookie).await, "1");
+    }
+}
@@ -0,0 +1,84 @@
+//! Flash messages (connect-flash compatible)
+//!
+//! Messages are stored in the session under `flash` as an object mapping
+//! each kind to an array of messages, the layout used by Node.js
+//! `connect-flash`, so a redirect issued by one stack can be rendered by the
+//! other. Reading messages removes them. With lazy loading, take the
+//! session from the extractor or `depot.load_session()` so the stored
+//! messages are seen.
+//!
+//! ```rust,ignore
+//! session.flash("info", "Profile updated");
+//! // ...after the redirect
+//! let info = session.take_flash_kind("info");
+//! ```
+
+use std::collections::HashMap;
+
+use crate::session::Session;
+
+/// Session key holding the flash messages (same as connect-flash)
+pub const FLASH_KEY: &str = "flash";
+
+/// Flash messages grouped by kind
+pub type FlashMessages = HashMap<String, Vec<String>>;
+
+impl Session {
+    /// Queue a flash message of the given kind, like connect-flash's `req.flash(type, msg)`
+    pub fn flash<K: Into<String>, M: Into<String>>(&self, kind: K, message: M) {
+        let mut messages: FlashMessages = self.get(FLASH_KEY).unwrap_or_default();
+        messages
+            .entry(kind.into())
+            .or_default()
+            .push(message.into());
+        self.set(FLASH_KEY, messages);
+    }
+
+    /// Take all flash messages, like connect-flash's `req.flash()`
+    pub fn take_flash(&self) -> FlashMessages {
+        let messages: FlashMessages = self.get(FLASH_KEY).unwrap_or_default();
+        if !messages.is_empty() {
+            self.set(FLASH_KEY, FlashMessages::new());
+        }
+        messages
+    }
+
+    /// Take the flash messages of one kind, like connect-flash's `req.flash(type)`
+    pub fn take_flash_kind(&self, kind: &str) -> Vec<String> {
+        let mut messages: FlashMessages = self.get(FLASH_KEY).unwrap_or_default();
+        match messages.remove(kind) {
+            Some(taken) => {
+                self.set(FLASH_KEY, messages);
+                taken
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Peek at the flash messages without removing them
+    pub fn flash_messages(&self) -> FlashMessages {
+        self.get(FLASH_KEY).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionData;
+
+    #[test]
+    fn test_connect_flash_layout() {
+        let session = Session::new("sid".to_string(), SessionData::default(), false);
+        session.flash("info", "saved");
+        session.flash("info", "again");
+        session.flash("error", "failed");
+
+        let data = serde_json::to_value(session.data()).unwrap();
+        assert_eq!(data["flash"]["info"], serde_json::json!(["saved", "again"]));
+
+        assert_eq!(session.take_flash_kind("info"), vec!["saved", "again"]);
+        assert!(session.take_flash_kind("info").is_empty());
+        assert_eq!(session.take_flash()["error"], vec!["failed"]);
+        assert!(session.take_flash().is_empty());
+    }
+}
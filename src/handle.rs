@@ -0,0 +1,272 @@
+//! Session handles outliving the request
+//!
+//! # Upgraded connections
+//!
+//! The handler persists the session after the response is produced, which
+//! for a WebSocket upgrade is the `101 Switching Protocols` response: the
+//! socket lives on long after that save. A [`SessionHandle`] taken before
+//! the upgrade keeps working for the socket's lifetime, and changes made
+//! through it are persisted with [`SessionHandle::commit`].
+//!
+//! ```rust,ignore
+//! #[handler]
+//! async fn chat(req: &mut Request, depot: &mut Depot, res: &mut Response) -> Result<(), StatusError> {
+//!     let session = depot.session().unwrap().handle().await.map_err(|_| StatusError::internal_server_error())?;
+//!     WebSocketUpgrade::new()
+//!         .upgrade(req, res, |mut ws| async move {
+//!             while let Some(Ok(msg)) = ws.recv().await {
+//!                 session.incr("messages", 1);
+//!                 if let Err(e) = session.commit().await {
+//!                     tracing::error!("Failed to save session: {}", e);
+//!                 }
+//!             }
+//!         })
+//!         .await
+//! }
+//! ```
+//!
+//! # Background tasks
+//!
+//! A [`DetachedSession`] is an independent copy of the session for spawned
+//! tasks (sending emails, processing queues), committed to a store later:
+//!
+//! ```rust,ignore
+//! let mut detached = depot.session().unwrap().detach();
+//! let store = store.clone();
+//! tokio::spawn(async move {
+//!     send_welcome_email().await;
+//!     detached.set("welcomeEmailSent", true);
+//!     detached.commit(&store).await
+//! });
+//! ```
+
+use std::ops::{Deref, DerefMut};
+
+use chrono::Utc;
+
+use crate::error::SessionError;
+use crate::session::{Session, SessionData};
+use crate::store::SessionStore;
+
+/// Attempts of [`DetachedSession::commit`] to write before reporting a conflict
+const COMMIT_ATTEMPTS: usize = 3;
+
+/// `Send + 'static` handle on the request's session
+///
+/// Shares its state with the request's session, so changes made before the
+/// response are saved by the handler as usual. Dereferences to [`Session`]
+/// for reading and writing; [`Session::reload`] picks up changes made by
+/// other requests in the meantime.
+#[derive(Clone)]
+pub struct SessionHandle {
+    session: Session,
+}
+
+impl Session {
+    /// Get a handle that outlives the request, e.g. for a WebSocket upgrade
+    ///
+    /// Loads deferred session data first, so the handle never blocks.
+    pub async fn handle(&self) -> Result<SessionHandle, SessionError> {
+        self.load().await?;
+        Ok(SessionHandle {
+            session: self.clone(),
+        })
+    }
+}
+
+impl SessionHandle {
+    /// Persist changes made since the last save, returning whether anything was written
+    ///
+    /// Fails with [`SessionError::NotFound`] if the request destroyed or
+    /// regenerated the session, as the handle's ID no longer refers to it.
+    pub async fn commit(&self) -> Result<bool, SessionError> {
+        if self.session.should_destroy() || self.session.should_regenerate() {
+            return Err(SessionError::NotFound);
+        }
+        if !self.session.is_modified() {
+            return Ok(false);
+        }
+        self.session.save().await?;
+        Ok(true)
+    }
+}
+
+impl Deref for SessionHandle {
+    type Target = Session;
+
+    fn deref(&self) -> &Session {
+        &self.session
+    }
+}
+
+/// Owned copy of a session, committed to the store explicitly
+///
+/// The copy is taken when detaching: it doesn't see later changes of the
+/// request's session, and the request doesn't see the copy's changes.
+/// Dereferences to [`SessionData`] for reading and writing.
+///
+/// [`Self::commit`] re-reads the stored session and writes only the keys
+/// changed on the copy, leaving the rest as other requests saved it. A
+/// request-scoped save that overlaps the commit can still overwrite those
+/// keys: with the default [`ConflictStrategy::LastWriteWins`](crate::ConflictStrategy::LastWriteWins)
+/// and without `with_patch_updates`, a request that loaded the session
+/// before the commit rewrites the whole session after it. Use a versioned
+/// conflict strategy where that matters.
+#[derive(Clone, Debug)]
+pub struct DetachedSession {
+    id: String,
+    data: SessionData,
+    original: SessionData,
+}
+
+impl Session {
+    /// Take an owned copy of the session for a background task
+    pub fn detach(&self) -> DetachedSession {
+        let data = self.data();
+        DetachedSession {
+            id: self.id().to_string(),
+            original: data.clone(),
+            data,
+        }
+    }
+}
+
+impl DetachedSession {
+    /// Get the session ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Keys set, changed or removed since detaching or the last commit
+    pub fn dirty_keys(&self) -> Vec<String> {
+        self.data.changed_keys(&self.original)
+    }
+
+    /// Write the changed keys to the store, returning whether anything was written
+    ///
+    /// The stored TTL follows the cookie expiry. Fails with
+    /// [`SessionError::NotFound`] if the session is not in the store (never
+    /// saved, destroyed or expired), and with [`SessionError::Conflict`] if
+    /// concurrent versioned saves keep winning.
+    pub async fn commit<S: SessionStore + ?Sized>(
+        &mut self,
+        store: &S,
+    ) -> Result<bool, SessionError> {
+        let changed = self.dirty_keys();
+        if changed.is_empty() {
+            return Ok(false);
+        }
+
+        for _ in 0..COMMIT_ATTEMPTS {
+            let Some(mut current) = store.get(&self.id).await? else {
+                return Err(SessionError::NotFound);
+            };
+            let expected = current.version;
+            current.apply_keys(&self.data, &changed);
+            current.prune_expired_keys();
+            current.version = expected.map(|v| v + 1);
+
+            let ttl = current
+                .cookie
+                .expires
+                .map(|expires| (expires - Utc::now()).num_seconds().max(1) as u64);
+            if store
+                .compare_and_set(&self.id, &current, expected, ttl)
+                .await?
+            {
+                self.original = current.clone();
+                self.data = current;
+                return Ok(true);
+            }
+        }
+
+        Err(SessionError::Conflict)
+    }
+}
+
+impl Deref for DetachedSession {
+    type Target = SessionData;
+
+    fn deref(&self) -> &SessionData {
+        &self.data
+    }
+}
+
+impl DerefMut for DetachedSession {
+    fn deref_mut(&mut self) -> &mut SessionData {
+        &mut self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+
+    use crate::error::SessionError;
+    use crate::session::{Session, SessionData, SessionSaver};
+    use crate::store::{MemoryStore, SessionStore};
+
+    #[tokio::test]
+    async fn test_commit_from_spawned_task() {
+        let session = Session::new("sid".to_string(), SessionData::default(), false);
+        let saved = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&saved);
+        let saver: SessionSaver = Arc::new(move |sid, data, _| {
+            log.lock().push(sid);
+            Box::pin(async move { Ok(data) })
+        });
+        session.set_saver(saver);
+
+        let handle = session.handle().await.unwrap();
+        tokio::spawn(async move {
+            assert!(!handle.commit().await.unwrap());
+            handle.set("messages", 1);
+            assert!(handle.commit().await.unwrap());
+            assert!(!handle.commit().await.unwrap());
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(*saved.lock(), vec!["sid"]);
+        assert_eq!(session.get::<i32>("messages"), Some(1));
+
+        session.regenerate();
+        let handle = session.handle().await.unwrap();
+        assert!(matches!(handle.commit().await, Err(SessionError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_detached_commit_keeps_other_keys() {
+        let store = MemoryStore::new();
+        let mut data = SessionData::default();
+        data.set("user", "alice");
+        store.set("sid", &data, None).await.unwrap();
+
+        let session = Session::new("sid".to_string(), data.clone(), false);
+        let mut detached = session.detach();
+        assert!(!detached.commit(&store).await.unwrap());
+
+        // The request saves its own change in the meantime
+        data.set("cart", 2);
+        store.set("sid", &data, None).await.unwrap();
+
+        detached.set("emailSent", true);
+        session.set("ignored", 1);
+        assert!(detached.commit(&store).await.unwrap());
+        assert!(!detached.commit(&store).await.unwrap());
+
+        let stored = store.get("sid").await.unwrap().unwrap();
+        assert_eq!(stored.get::<i32>("cart"), Some(2));
+        assert_eq!(stored.get::<bool>("emailSent"), Some(true));
+        assert!(!stored.contains("ignored"));
+
+        store.destroy("sid").await.unwrap();
+        detached.remove("user");
+        assert!(matches!(
+            detached.commit(&store).await,
+            Err(SessionError::NotFound)
+        ));
+    }
+}
@@ -1,18 +1,85 @@
 //! Express-session compatible middleware handler for Salvo
 
+use parking_lot::Mutex;
 use salvo_core::http::cookie::{
-    self, time::Duration as CookieDuration, SameSite as CookieSameSite,
+    self,
+    time::{Duration as CookieDuration, OffsetDateTime},
+    SameSite as CookieSameSite,
 };
+use salvo_core::http::header::HeaderName;
+use salvo_core::http::Method;
 use salvo_core::prelude::*;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::IpAddr;
 use std::sync::Arc;
-use uuid::Uuid;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tracing::Instrument;
 
-use crate::config::{SameSite, SessionConfig};
-use crate::cookie_signature::{sign, unsign_with_secrets};
-use crate::session::{Session, SessionData};
+use crate::audit::{AuditRecord, AuditSink};
+use crate::auth::user_key;
+use crate::binding::{client_ip, BindingAction};
+use crate::brute_force::SignatureGuard;
+use crate::catcher::IssuedCookies;
+use crate::codec::{url_decode, CookieCodec, ExpressCodec, SignedCookie};
+use crate::compat::AsyncSessionCompat;
+use crate::config::{
+    ConflictStrategy, LogRedaction, OversizePolicy, RenewalThreshold, SameSite, SessionConfig,
+    StoreFailurePolicy, Unset,
+};
+use crate::depot_ext::SessionSkipped;
+use crate::error::{ConfigError, SessionError};
+use crate::events::{SessionEvent, DEFAULT_EVENT_CAPACITY};
+use crate::fingerprint::{Fingerprint, FingerprintAction};
+use crate::hooks::SessionHooks;
+use crate::legacy::LegacyFormat;
+use crate::lock::{LockGuard, SessionLocker};
+#[cfg(feature = "metrics")]
+use crate::metrics::{Event, SessionMetrics, StoreMetrics};
+use crate::remember::RememberMe;
+use crate::secret::{SecretCache, SecretProvider, SigningKey};
+use crate::session::{
+    generate_session_id, Session, SessionCookie, SessionData, SessionLoader, SessionReloader,
+    SessionSaver,
+};
 use crate::store::SessionStore;
+use crate::validator::{SessionValidator, Validation};
+
+/// Number of tracked touch times above which stale entries are pruned
+const TOUCH_PRUNE_THRESHOLD: usize = 1024;
+
+/// Compare-and-set attempts before a merged save reports a conflict
+const MERGE_ATTEMPTS: usize = 3;
+
+/// Predicate deciding whether a request bypasses the session middleware
+pub type SkipPredicate = Arc<dyn Fn(&Request) -> bool + Send + Sync>;
+
+/// Predicate deciding whether a response status leaves the session unpersisted
+pub type StatusPredicate = Arc<dyn Fn(StatusCode) -> bool + Send + Sync>;
+
+/// Callback invoked with store errors and the response being built
+pub type ErrorHandler = Arc<dyn Fn(&SessionError, &mut Response) + Send + Sync>;
+
+/// Callback shrinking a session over the maximum size, given its serialized size
+pub type OversizeHandler = Arc<dyn Fn(&mut SessionData, usize) + Send + Sync>;
 
-const SESSION_KEY: &str = "salvo.express.session";
+/// What a request tells about its client, for session binding
+#[derive(Clone, Default)]
+struct ClientIdentity {
+    ip: Option<IpAddr>,
+    fingerprint: Option<String>,
+}
+
+/// Session cookie of a request, verified once per request
+#[derive(Clone, Debug)]
+struct VerifiedCookie {
+    /// Name of the accepted cookie, the primary or a legacy one
+    name: String,
+    sid: String,
+    /// Index of the secret the signature verified with (0 = primary)
+    secret_index: usize,
+}
 
 /// Express-session compatible middleware for Salvo
 ///
@@ -22,231 +89,3091 @@ const SESSION_KEY: &str = "salvo.express.session";
 pub struct ExpressSessionHandler<S: SessionStore> {
     store: Arc<S>,
     config: SessionConfig,
+    codec: Arc<dyn CookieCodec>,
+    keys: Arc<[SigningKey]>,
+    secret_cache: Option<Arc<SecretCache>>,
+    locker: Option<Arc<dyn SessionLocker>>,
+    remember: Option<Arc<RememberMe>>,
+    hooks: Option<Arc<dyn SessionHooks>>,
+    events: broadcast::Sender<SessionEvent>,
+    audit: Option<Arc<dyn AuditSink>>,
+    compat: Option<Arc<AsyncSessionCompat>>,
+    legacy: Option<Arc<LegacyFormat>>,
+    fingerprint: Option<Arc<Fingerprint>>,
+    signature_guard: Option<Arc<SignatureGuard>>,
+    validator: Option<Arc<dyn SessionValidator>>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<StoreMetrics>>,
+    error_handler: Option<ErrorHandler>,
+    oversize_handler: Option<OversizeHandler>,
+    skip: Option<SkipPredicate>,
+    skip_persistence: Option<StatusPredicate>,
+    last_touched: Arc<Mutex<HashMap<String, Instant>>>,
 }
 
 impl<S: SessionStore> ExpressSessionHandler<S> {
     /// Create a new session handler
+    ///
+    /// An invalid configuration (see [`SessionConfig::validate`]) is logged
+    /// as a warning; use [`Self::try_new`] to refuse it instead.
     pub fn new(store: S, config: SessionConfig) -> Self {
+        if let Err(e) = config.validate() {
+            tracing::warn!("Invalid session configuration: {}", e);
+        }
+        Self::build(store, config)
+    }
+
+    /// Create a new session handler, validating the configuration first
+    pub fn try_new(store: S, config: SessionConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
+        Ok(Self::build(store, config))
+    }
+
+    /// Create a handler without validating the configuration
+    fn build(store: S, config: SessionConfig) -> Self {
         Self {
             store: Arc::new(store),
+            keys: SigningKey::from_secrets(config.secrets.iter().cloned()),
             config,
+            codec: Arc::new(ExpressCodec),
+            secret_cache: None,
+            locker: None,
+            remember: None,
+            hooks: None,
+            events: broadcast::Sender::new(DEFAULT_EVENT_CAPACITY),
+            audit: None,
+            compat: None,
+            legacy: None,
+            fingerprint: None,
+            signature_guard: None,
+            validator: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            error_handler: None,
+            oversize_handler: None,
+            skip: None,
+            skip_persistence: None,
+            last_touched: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    /// Generate a new session ID
-    fn generate_session_id(&self) -> String {
-        // Use UUID v4 for session IDs, similar to uid-safe in Node.js
-        Uuid::new_v4().to_string()
+    /// Ping the store, so an unreachable or misconfigured store fails at
+    /// startup instead of on the first request
+    ///
+    /// ```rust,ignore
+    /// let session_handler = ExpressSessionHandler::new(store, config)
+    ///     .verify_store()
+    ///     .await?;
+    /// ```
+    pub async fn verify_store(self) -> Result<Self, SessionError> {
+        self.store.ping().await?;
+        Ok(self)
     }
 
-    /// Get session ID from cookie
-    fn get_session_id_from_cookie(&self, req: &Request) -> Option<String> {
-        // Get the cookie value
-        let cookie_value = req.cookie(&self.config.cookie_name)?;
-        let signed_value = cookie_value.value();
+    /// Flush the store before the server exits, so recently modified
+    /// sessions aren't lost
+    ///
+    /// Await it after the server stopped accepting requests, e.g. once
+    /// graceful shutdown completed. Sessions are saved when each request
+    /// finishes, so only the store may still hold unwritten data.
+    pub async fn shutdown(&self) -> Result<(), SessionError> {
+        self.store.flush().await
+    }
 
-        // URL decode the value (cookies are URL encoded)
-        let decoded = match urlencoding::decode(signed_value) {
-            Ok(d) => d.to_string(),
-            Err(_) => signed_value.to_string(),
-        };
+    /// Create a handler for another part of the router tree that shares this
+    /// handler's store, codec and callbacks but uses a different configuration
+    ///
+    /// Scoped handlers must be mounted on sibling routers, not nested below
+    /// another session handler (a nested handler defers to the outer one).
+    ///
+    /// ```rust,ignore
+    /// let session = ExpressSessionHandler::new(store, config.clone());
+    /// let admin_session = session.scoped(
+    ///     config
+    ///         .with_cookie_path("/admin")
+    ///         .with_max_age(900)
+    ///         .with_same_site(SameSite::Strict),
+    /// );
+    ///
+    /// let router = Router::new()
+    ///     .push(Router::with_path("admin").hoop(admin_session).get(admin))
+    ///     .push(Router::new().hoop(session).get(index));
+    /// ```
+    ///
+    /// An invalid configuration is logged as a warning, as in [`Self::new`].
+    pub fn scoped(&self, config: SessionConfig) -> Self {
+        if let Err(e) = config.validate() {
+            tracing::warn!("Invalid session configuration: {}", e);
+        }
+        Self {
+            keys: SigningKey::from_secrets(config.secrets.iter().cloned()),
+            config,
+            ..self.clone()
+        }
+    }
 
-        // Unsign the cookie value
-        unsign_with_secrets(&decoded, &self.config.secrets)
+    /// Use a custom cookie codec (default: [`ExpressCodec`])
+    pub fn with_codec<C: CookieCodec>(mut self, codec: C) -> Self {
+        self.codec = Arc::new(codec);
+        self
     }
 
-    /// Set session cookie on response
-    fn set_session_cookie(&self, res: &mut Response, session_id: &str) {
-        let signed = sign(session_id, &self.config.secrets[0]);
+    /// Source signing secrets from a provider instead of the static config
+    ///
+    /// Secrets are fetched on the first request and re-fetched whenever the
+    /// provider reports a change, so keys rotate without a restart. The
+    /// configured secrets are used until the first fetch succeeds.
+    pub fn with_secret_provider<P: SecretProvider>(mut self, provider: P) -> Self {
+        self.secret_cache = Some(Arc::new(SecretCache::new(Arc::new(provider))));
+        self
+    }
 
-        // Build cookie with owned strings to avoid lifetime issues
-        let cookie_name = self.config.cookie_name.clone();
-        let cookie_path = self.config.cookie_path.clone();
-        let cookie_domain = self.config.cookie_domain.clone();
+    /// Serialize concurrent requests for the same session with a lock
+    ///
+    /// Each request carrying a session ID waits for the session's lock
+    /// before it is loaded and releases it after it is saved, PHP-style, so
+    /// read-modify-write sequences never interleave. Requests that can't get
+    /// the lock in time are answered with 503 Service Unavailable.
+    ///
+    /// ```rust,ignore
+    /// let session_handler = ExpressSessionHandler::new(store, config)
+    ///     .with_locker(MemoryLocker::new().with_wait(Duration::from_secs(5)));
+    /// ```
+    pub fn with_locker<L: SessionLocker>(mut self, locker: L) -> Self {
+        self.locker = Some(Arc::new(locker));
+        self
+    }
+
+    /// Re-establish logins from remember-me tokens once the session expired
+    ///
+    /// Tokens are issued with [`Session::remember_me`] and revoked with
+    /// [`Session::forget_me`] (or by logging out).
+    ///
+    /// ```rust,ignore
+    /// let session_handler = ExpressSessionHandler::new(store, config)
+    ///     .with_remember_me(RememberMe::new(MemoryStore::new()).with_max_age(14 * 86400));
+    /// ```
+    pub fn with_remember_me(mut self, remember: RememberMe) -> Self {
+        self.remember = Some(Arc::new(remember));
+        self
+    }
+
+    /// Run application callbacks when sessions are created, loaded, saved or destroyed
+    pub fn with_hooks<H: SessionHooks>(mut self, hooks: H) -> Self {
+        self.hooks = Some(Arc::new(hooks));
+        self
+    }
+
+    /// Record who changed which session keys, and when, in `sink`
+    ///
+    /// Only saves that change keys are recorded.
+    pub fn with_audit<A: AuditSink>(mut self, sink: A) -> Self {
+        self.audit = Some(Arc::new(sink));
+        self
+    }
+
+    /// Convert `salvo_session` (async-session) sessions on first access
+    ///
+    /// Requests with an old session cookie but no express-session one start
+    /// a new session holding the old values, and the old cookie is expired.
+    /// See [`crate::compat`].
+    pub fn with_async_session_compat(mut self, compat: AsyncSessionCompat) -> Self {
+        self.compat = Some(Arc::new(compat));
+        self
+    }
+
+    /// Also accept sessions in a legacy cookie and store format
+    ///
+    /// Requests without a usable session cookie but with a valid legacy one
+    /// start a new session holding the legacy values; only the
+    /// express-session format is written. See [`crate::legacy`].
+    pub fn with_legacy_format(mut self, legacy: LegacyFormat) -> Self {
+        self.legacy = Some(Arc::new(legacy));
+        self
+    }
+
+    /// Bind sessions to a fingerprint of the client, e.g. its `User-Agent`
+    ///
+    /// See [`crate::fingerprint`].
+    pub fn with_fingerprint(mut self, fingerprint: Fingerprint) -> Self {
+        self.fingerprint = Some(Arc::new(fingerprint));
+        self
+    }
+
+    /// Count invalid cookie signatures per client IP, see [`crate::brute_force`]
+    pub fn with_signature_guard(mut self, guard: SignatureGuard) -> Self {
+        self.signature_guard = Some(Arc::new(guard));
+        self
+    }
+
+    /// Let `validator` accept, reject or regenerate every loaded session
+    ///
+    /// See [`crate::validator`]. Sessions are loaded eagerly while a
+    /// validator is set, even with `lazy_load`.
+    pub fn with_validator<V: SessionValidator>(mut self, validator: V) -> Self {
+        self.validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Buffer up to `capacity` events for slow subscribers (default: 256)
+    ///
+    /// Replaces the event channel: call it before [`Self::subscribe`].
+    pub fn with_event_capacity(mut self, capacity: usize) -> Self {
+        self.events = broadcast::Sender::new(capacity);
+        self
+    }
+
+    /// Receive the [`SessionEvent`]s of this handler and its clones
+    pub fn subscribe(&self) -> broadcast::Receiver<SessionEvent> {
+        self.events.subscribe()
+    }
 
-        let mut cookie_builder = cookie::Cookie::build((cookie_name, signed))
-            .path(cookie_path)
-            .http_only(self.config.cookie_http_only)
-            .secure(self.config.cookie_secure);
+    /// Record session counts and store latency in `metrics`, labeled by store type
+    ///
+    /// The label is [`SessionStore::name`]; type-erased stores report the
+    /// store they wrap.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: &SessionMetrics) -> Self {
+        self.metrics = Some(metrics.store(self.store.name()));
+        self
+    }
 
-        if let Some(domain) = cookie_domain {
-            cookie_builder = cookie_builder.domain(domain);
+    /// Whether metrics are attached
+    fn has_metrics(&self) -> bool {
+        #[cfg(feature = "metrics")]
+        {
+            self.metrics.is_some()
         }
+        #[cfg(not(feature = "metrics"))]
+        {
+            false
+        }
+    }
 
-        // Set max age (if configured, otherwise session cookie)
-        if let Some(max_age) = self.config.max_age {
-            cookie_builder =
-                cookie_builder.max_age(CookieDuration::seconds(max_age as i64));
+    /// Count a session lifecycle event in the metrics
+    #[cfg(feature = "metrics")]
+    fn count(&self, event: Event) {
+        if let Some(metrics) = &self.metrics {
+            metrics.count(event);
         }
+    }
 
-        // Set SameSite
-        cookie_builder = match self.config.cookie_same_site {
-            SameSite::Strict => cookie_builder.same_site(CookieSameSite::Strict),
-            SameSite::Lax => cookie_builder.same_site(CookieSameSite::Lax),
-            SameSite::None => cookie_builder.same_site(CookieSameSite::None),
-        };
+    /// Run a store call in a tracing span, recording its latency in the metrics
+    ///
+    /// The span (`session.store.<operation>` for OpenTelemetry) is a child
+    /// of the current request span and carries a hash of the session ID,
+    /// never the ID itself.
+    async fn store_call<T>(
+        &self,
+        operation: &'static str,
+        sid: &str,
+        call: impl Future<Output = Result<T, SessionError>>,
+    ) -> Result<T, SessionError> {
+        let span = tracing::info_span!(
+            "session_store",
+            otel.name = %format!("session.store.{}", operation),
+            otel.status_code = tracing::field::Empty,
+            session.operation = operation,
+            session.id_hash = tracing::field::Empty,
+            error = tracing::field::Empty,
+        );
+        if let Some(id) = self.log_id(sid) {
+            span.record("session.id_hash", tracing::field::display(id));
+        }
 
-        res.add_cookie(cookie_builder.build());
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let result = call.instrument(span.clone()).await;
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.observe(operation, start.elapsed());
+        }
+
+        if let Err(e) = &result {
+            span.record("otel.status_code", "ERROR");
+            span.record("error", tracing::field::display(e));
+        }
+        result
     }
 
-    /// Remove session cookie
-    fn remove_session_cookie(&self, res: &mut Response) {
-        let cookie_name = self.config.cookie_name.clone();
-        let cookie_path = self.config.cookie_path.clone();
+    /// Session identifier for logs and spans, per the redaction policy
+    fn log_id(&self, sid: &str) -> Option<String> {
+        match self.config.log_redaction {
+            LogRedaction::Hash => Some(hash_session_id(sid)),
+            LogRedaction::Omit => None,
+        }
+    }
 
-        let cookie = cookie::Cookie::build(cookie_name)
-            .path(cookie_path)
-            .max_age(CookieDuration::ZERO)
-            .build();
+    /// Attach the session to the request span, so every log entry of the
+    /// request can be correlated with it
+    fn record_session_id(&self, sid: &str) {
+        if let Some(id) = self.log_id(sid) {
+            tracing::Span::current().record("session.id_hash", tracing::field::display(id));
+        }
+    }
 
-        res.add_cookie(cookie);
+    /// Keys of the current signing secrets, primary first
+    fn keys(&self) -> Arc<[SigningKey]> {
+        self.secret_cache
+            .as_ref()
+            .and_then(|cache| cache.current())
+            .unwrap_or_else(|| Arc::clone(&self.keys))
     }
 
-    /// Calculate TTL for session storage
-    fn get_session_ttl(&self, session_data: &SessionData) -> Option<u64> {
-        // Use cookie expiration if available
-        if let Some(expires) = session_data.cookie.expires {
-            let now = chrono::Utc::now();
-            let diff = expires - now;
-            let secs = diff.num_seconds();
-            if secs > 0 {
-                return Some(secs as u64);
+    /// Set a callback invoked when a store operation fails
+    ///
+    /// Called for failures to load, save, touch or destroy a session (after
+    /// the error is logged). The callback receives the error and the
+    /// response, so it can record metrics, raise alerts or turn persistent
+    /// failures into e.g. a 503.
+    pub fn with_error_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&SessionError, &mut Response) + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Set a callback invoked when a session exceeds [`SessionConfig::with_max_size`]
+    ///
+    /// Runs before each write of an oversized session and may shrink it,
+    /// e.g. by dropping cached values; the [`OversizePolicy`](crate::OversizePolicy)
+    /// applies if it is still too large afterwards.
+    pub fn with_oversize_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&mut SessionData, usize) + Send + Sync + 'static,
+    {
+        self.oversize_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Set a predicate for requests that bypass the middleware entirely
+    ///
+    /// Complements [`SessionConfig::with_skip_paths`] for rules that depend
+    /// on more than the path.
+    pub fn with_skip<F>(mut self, skip: F) -> Self
+    where
+        F: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        self.skip = Some(Arc::new(skip));
+        self
+    }
+
+    /// Leave sessions unsaved, untouched and without Set-Cookie after
+    /// responses whose status matches (default: never)
+    ///
+    /// Keeps failed requests from creating or extending sessions:
+    ///
+    /// ```rust,ignore
+    /// let session_handler = ExpressSessionHandler::new(store, config)
+    ///     .with_skip_persistence(|status| {
+    ///         status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+    ///     });
+    /// ```
+    ///
+    /// Destroying a session still takes effect. Explicit
+    /// [`Session::save`] calls and early saves happen before the status is
+    /// known and are unaffected.
+    pub fn with_skip_persistence<F>(mut self, skip: F) -> Self
+    where
+        F: Fn(StatusCode) -> bool + Send + Sync + 'static,
+    {
+        self.skip_persistence = Some(Arc::new(skip));
+        self
+    }
+
+    /// Check whether the response status leaves the session unpersisted
+    fn skips_persistence(&self, res: &Response) -> bool {
+        self.skip_persistence
+            .as_ref()
+            .is_some_and(|skip| skip(res.status_code.unwrap_or(StatusCode::OK)))
+    }
+
+    /// Check whether the request bypasses session handling
+    fn is_skipped(&self, req: &Request) -> bool {
+        self.config.is_skipped_path(req.uri().path())
+            || self.skip.as_ref().is_some_and(|skip| skip(req))
+    }
+
+    /// Report a store error to the configured error handler
+    fn report_error(&self, error: &SessionError, res: &mut Response) {
+        if let Some(handler) = &self.error_handler {
+            handler(error, res);
+        }
+    }
+
+    /// Generate a new session ID
+    fn generate_session_id(&self) -> String {
+        generate_session_id()
+    }
+
+    /// Create data for a new session
+    fn fresh_session_data(&self) -> SessionData {
+        let mut data = SessionData::with_optional_max_age(self.config.max_age);
+        data.cookie = self.config.new_session_cookie();
+        if self.config.idle_timeout.is_some() {
+            data.record_access();
+        }
+        data
+    }
+
+    /// Create a session whose store lookup is deferred until first access
+    fn deferred_session(&self, sid: String, client: ClientIdentity, read_only: bool) -> Session {
+        let handler = self.clone();
+        let lookup_sid = sid.clone();
+        let mut fresh = self.fresh_session_data();
+        self.bind_client(&mut fresh, &client);
+        let loader: SessionLoader = Box::new(move || {
+            Box::pin(async move {
+                let mut data = match handler.load_by_id(&lookup_sid, read_only).await? {
+                    Some(data) => match handler.verify_client(&lookup_sid, &data, &client).await {
+                        Ok(()) => Some(data),
+                        Err(_) => None,
+                    },
+                    None => None,
+                };
+                if let Some(data) = data.as_mut() {
+                    if handler.config.idle_timeout.is_some() {
+                        data.record_access();
+                    }
+                    handler.bind_client(data, &client);
+                }
+                Ok(data)
+            })
+        });
+        Session::new_deferred(sid, loader, fresh)
+    }
+
+    /// The client's IP and fingerprint, as far as session binding uses them
+    fn client_identity(&self, req: &Request) -> ClientIdentity {
+        ClientIdentity {
+            ip: self
+                .config
+                .ip_binding
+                .as_ref()
+                .and_then(|binding| binding.client_ip(req)),
+            fingerprint: self.fingerprint.as_ref().and_then(|f| f.compute(req)),
+        }
+    }
+
+    /// Check a loaded session against the client it is bound to
+    ///
+    /// Returns what to do with a session that must not be resumed; sessions
+    /// failing the fingerprint check with [`FingerprintAction::Destroy`] are
+    /// destroyed here.
+    async fn verify_client(
+        &self,
+        sid: &str,
+        data: &SessionData,
+        client: &ClientIdentity,
+    ) -> Result<(), BindingAction> {
+        if let (Some(binding), Some(bound), Some(ip)) =
+            (&self.config.ip_binding, data.client_ip, client.ip)
+        {
+            if !binding.matches(bound, ip) {
+                tracing::warn!(%bound, %ip, "Session used from outside its bound network");
+                return Err(binding.on_mismatch.clone());
+            }
+        }
+
+        let (Some(fingerprint), Some(bound), Some(current)) =
+            (&self.fingerprint, &data.fingerprint, &client.fingerprint)
+        else {
+            return Ok(());
+        };
+        if bound == current {
+            return Ok(());
+        }
+        match fingerprint.on_mismatch() {
+            FingerprintAction::Ignore => Ok(()),
+            FingerprintAction::Log => {
+                tracing::warn!("Session used by a client with another fingerprint");
+                Ok(())
+            }
+            FingerprintAction::Destroy => {
+                tracing::warn!("Destroying session used by a client with another fingerprint");
+                if let Err(e) = self.destroy_session(sid, data).await {
+                    tracing::error!("Failed to destroy session: {}", e);
+                }
+                Err(BindingAction::Regenerate)
             }
         }
-        // Fall back to config max age (None = no TTL for session cookies)
-        self.config.max_age
     }
-}
 
-impl<S: SessionStore> Clone for ExpressSessionHandler<S> {
-    fn clone(&self) -> Self {
-        Self {
-            store: Arc::clone(&self.store),
-            config: self.config.clone(),
+    /// Bind a session to the client where it isn't bound yet
+    fn bind_client(&self, data: &mut SessionData, client: &ClientIdentity) {
+        if self.config.ip_binding.is_some() && data.client_ip.is_none() && client.ip.is_some() {
+            data.client_ip = client.ip;
+            data.mark_changed();
+        }
+        if self.fingerprint.is_some() && data.fingerprint.is_none() && client.fingerprint.is_some()
+        {
+            data.fingerprint = client.fingerprint.clone();
+            data.mark_changed();
         }
     }
-}
 
-#[async_trait]
-impl<S: SessionStore> Handler for ExpressSessionHandler<S> {
-    async fn handle(
+    /// Attach the store access behind [`Session::save`] and [`Session::reload`]
+    fn attach_store(&self, session: &Session, read_only: bool) {
+        let handler = Arc::new(self.clone());
+
+        let saver_handler = Arc::clone(&handler);
+        let saver: SessionSaver = Arc::new(move |sid, data, changed| {
+            let handler = Arc::clone(&saver_handler);
+            Box::pin(async move { handler.persist(&sid, data, &changed, false).await })
+        });
+
+        let reloader: SessionReloader = Arc::new(move |sid| {
+            let handler = Arc::clone(&handler);
+            Box::pin(async move { handler.load_by_id(&sid, read_only).await })
+        });
+
+        session.set_saver(saver);
+        session.set_reloader(reloader);
+        if let Some(limit) = self.config.max_size {
+            session.set_max_size(limit);
+        }
+    }
+
+    /// Load the existing session referenced by the request
+    ///
+    /// Returns None when the request carries no valid session ID, or the
+    /// session is missing, expired, past its absolute lifetime or idle.
+    async fn load_session(
         &self,
-        req: &mut Request,
-        depot: &mut Depot,
+        req: &Request,
+        cookie: Option<&VerifiedCookie>,
+        read_only: bool,
+    ) -> Result<Option<(String, SessionData)>, SessionError> {
+        let Some(sid) = self.get_session_id(req, cookie) else {
+            return Ok(None);
+        };
+        Ok(self
+            .load_by_id(&sid, read_only)
+            .await?
+            .map(|data| (sid, data)))
+    }
+
+    /// Load a session by ID, returning None if missing or no longer valid
+    ///
+    /// Read-only requests never touch the session as it loads.
+    async fn load_by_id(
+        &self,
+        sid: &str,
+        read_only: bool,
+    ) -> Result<Option<SessionData>, SessionError> {
+        let touch = !read_only && self.touches_on_load() && self.touch_due(sid);
+        let loaded = if touch {
+            let loaded = self.store.get_and_touch(sid, self.config.max_age);
+            self.store_call("get_and_touch", sid, loaded).await?
+        } else {
+            self.store_call("get", sid, self.store.get(sid)).await?
+        };
+        let Some(mut data) = loaded else {
+            return Ok(None);
+        };
+        if touch {
+            self.record_touch(sid);
+            #[cfg(feature = "metrics")]
+            self.count(Event::Touched);
+        }
+
+        // Check if session is expired or past its absolute lifetime
+        if data.cookie.is_expired() || self.exceeds_absolute_max_age(&data) || self.is_idle(&data) {
+            self.emit(|| SessionEvent::Expired {
+                sid: sid.to_string(),
+            });
+            return Ok(None);
+        }
+
+        data.prune_expired_keys();
+
+        if let Some(hooks) = &self.hooks {
+            if !hooks.on_load(sid, &data).await {
+                return Ok(None);
+            }
+        }
+        #[cfg(feature = "metrics")]
+        self.count(Event::Loaded);
+        Ok(Some(data))
+    }
+
+    /// Load the values of a session in a legacy format, expiring its cookie
+    ///
+    /// Tries [`LegacyFormat`] first, then [`AsyncSessionCompat`]. Store
+    /// errors are logged and leave the legacy cookie in place for a retry.
+    async fn migrate_legacy(
+        &self,
+        req: &Request,
         res: &mut Response,
-        ctrl: &mut FlowCtrl,
-    ) {
-        // Try to get session ID from cookie
-        let (session_id, is_new, existing_data) = match self.get_session_id_from_cookie(req) {
-            Some(sid) => {
-                // Try to load existing session
-                match self.store.get(&sid).await {
-                    Ok(Some(data)) => {
-                        // Check if session is expired
-                        if data.cookie.is_expired() {
-                            // Session expired, create new one
-                            let new_id = self.generate_session_id();
-                            let new_data = SessionData::with_optional_max_age(self.config.max_age);
-                            (new_id, true, new_data)
-                        } else {
-                            (sid, false, data)
-                        }
-                    }
-                    Ok(None) => {
-                        // Session not found, create new one
-                        let new_id = self.generate_session_id();
-                        let new_data = SessionData::with_optional_max_age(self.config.max_age);
-                        (new_id, true, new_data)
+    ) -> Option<Vec<(String, serde_json::Value)>> {
+        if let Some(legacy) = self.legacy.as_ref().filter(|l| l.has_cookie(req)) {
+            match legacy.migrate(req, &self.keys()).await {
+                Ok(values) => {
+                    // A legacy cookie of the same name is overwritten instead
+                    if legacy.cookie_name() != self.config.cookie_name {
+                        legacy.expire_cookie(res);
                     }
-                    Err(e) => {
-                        tracing::error!("Failed to load session: {}", e);
-                        let new_id = self.generate_session_id();
-                        let new_data = SessionData::with_optional_max_age(self.config.max_age);
-                        (new_id, true, new_data)
+                    if values.is_some() {
+                        return values;
                     }
                 }
+                Err(e) => tracing::error!("Failed to convert legacy session: {}", e),
             }
-            None => {
-                // No cookie, create new session
-                let new_id = self.generate_session_id();
-                let new_data = SessionData::with_optional_max_age(self.config.max_age);
-                (new_id, true, new_data)
+        }
+
+        if let Some(compat) = self.compat.as_ref().filter(|c| c.has_cookie(req)) {
+            match compat.migrate(req).await {
+                Ok(values) => {
+                    compat.expire_cookie(res);
+                    return values;
+                }
+                Err(e) => tracing::error!("Failed to convert salvo_session session: {}", e),
             }
+        }
+        None
+    }
+
+    /// Check whether the session is past the configured absolute lifetime
+    fn exceeds_absolute_max_age(&self, data: &SessionData) -> bool {
+        self.config
+            .absolute_max_age
+            .is_some_and(|max_age| data.exceeds_absolute_max_age(max_age))
+    }
+
+    /// Check whether the session has been idle longer than the configured timeout
+    fn is_idle(&self, data: &SessionData) -> bool {
+        self.config
+            .idle_timeout
+            .is_some_and(|timeout| data.is_idle(timeout))
+    }
+
+    /// Whether a cookie value or token is short enough to be worth verifying
+    fn within_length(&self, value: &str) -> bool {
+        let within = value.len() <= self.config.max_cookie_length;
+        if !within {
+            tracing::debug!(len = value.len(), "Ignoring oversized session token");
+        }
+        within
+    }
+
+    /// Get the cookie named `name` sent by the client
+    fn get_request_cookie(&self, req: &Request, name: &str) -> Option<SignedCookie> {
+        // Get the cookie value, plus the signature cookie if the codec uses one
+        let value = req.cookie(name)?.value();
+        if !self.within_length(value) {
+            return None;
+        }
+        let signature = self
+            .codec
+            .signature_cookie_name(name)
+            .and_then(|sig_name| req.cookie(&sig_name).map(|c| c.value().to_string()))
+            .filter(|signature| self.within_length(signature));
+
+        Some(SignedCookie {
+            value: value.to_string(),
+            signature,
+        })
+    }
+
+    /// Verify the first accepted cookie with a valid signature
+    ///
+    /// Secrets are tried in order, so the result also tells whether the
+    /// cookie was signed with a rotated one.
+    fn unsign_request_cookie(&self, req: &Request) -> Option<VerifiedCookie> {
+        if !self.config.transport.uses_cookie() {
+            return None;
+        }
+        let keys = self.keys();
+        std::iter::once(&self.config.cookie_name)
+            .chain(&self.config.legacy_cookie_names)
+            .find_map(|name| {
+                let cookie = self.get_request_cookie(req, name)?;
+                keys.iter().enumerate().find_map(|(index, key)| {
+                    let sid = self
+                        .codec
+                        .unsign(name, &cookie, std::slice::from_ref(key))?;
+                    Some(VerifiedCookie {
+                        name: name.clone(),
+                        sid,
+                        secret_index: index,
+                    })
+                })
+            })
+    }
+
+    /// Get session ID from the configured request header
+    fn get_session_id_from_header(&self, req: &Request) -> Option<String> {
+        let header = req
+            .header::<String>(&self.config.header_name)
+            .filter(|header| self.within_length(header))?;
+        let token = match &self.config.header_scheme {
+            Some(scheme) => {
+                let (found, token) = header.trim().split_once(' ')?;
+                if !found.eq_ignore_ascii_case(scheme) {
+                    return None;
+                }
+                token.trim().to_string()
+            }
+            None => header.trim().to_string(),
         };
 
-        // Create session wrapper
-        let session = Session::new(session_id.clone(), existing_data, is_new);
+        let cookie = self.codec_token_to_cookie(token);
+        self.codec
+            .unsign(&self.config.cookie_name, &cookie, &self.keys())
+    }
 
-        // Store session in depot
-        depot.insert(SESSION_KEY, session.clone());
+    /// Get session ID from the query parameter of a WebSocket upgrade request
+    fn get_session_id_from_query(&self, req: &Request) -> Option<String> {
+        let param = self.config.websocket_query_param.as_deref()?;
+        let is_upgrade = req
+            .header::<String>("upgrade")
+            .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+        if !is_upgrade {
+            return None;
+        }
 
-        // Continue with the request
-        ctrl.call_next(req, depot, res).await;
+        let token = req
+            .query::<String>(param)
+            .filter(|token| self.within_length(token))?;
+        let cookie = self.codec_token_to_cookie(token);
+        self.codec
+            .unsign(&self.config.cookie_name, &cookie, &self.keys())
+    }
 
-        // After request processing, handle session persistence
+    /// Get session ID from the verified cookie, or else the request header
+    /// or query, using the configured transport
+    fn get_session_id(&self, req: &Request, cookie: Option<&VerifiedCookie>) -> Option<String> {
+        cookie
+            .map(|cookie| cookie.sid.clone())
+            .or_else(|| {
+                if self.config.transport.uses_header() {
+                    self.get_session_id_from_header(req)
+                } else {
+                    None
+                }
+            })
+            .or_else(|| self.get_session_id_from_query(req))
+    }
 
-        // Check if session should be destroyed
-        if session.should_destroy() {
-            if let Err(e) = self.store.destroy(&session_id).await {
-                tracing::error!("Failed to destroy session: {}", e);
+    /// Split a header token into the codec's cookie value and signature
+    ///
+    /// Codecs with a separate signature cookie carry it as `<value>.<signature>`.
+    fn codec_token_to_cookie(&self, token: String) -> SignedCookie {
+        if self
+            .codec
+            .signature_cookie_name(&self.config.cookie_name)
+            .is_some()
+        {
+            if let Some((value, signature)) = token.rsplit_once('.') {
+                return SignedCookie::with_signature(value, signature);
             }
-            self.remove_session_cookie(res);
-            return;
         }
+        SignedCookie::new(token)
+    }
 
-        // Check if session should be regenerated
-        let final_session_id = if session.should_regenerate() {
-            // Destroy old session
-            if let Err(e) = self.store.destroy(&session_id).await {
-                tracing::error!("Failed to destroy old session during regeneration: {}", e);
+    /// Build a cookie carrying the session's own cookie attributes
+    fn build_cookie(
+        &self,
+        name: String,
+        value: String,
+        session_cookie: &SessionCookie,
+    ) -> cookie::Cookie<'static> {
+        build_cookie(&self.config, name, value, session_cookie)
+    }
+
+    /// Set the session token on the response using the configured transport
+    fn set_session_token(
+        &self,
+        res: &mut Response,
+        session_id: &str,
+        session_data: &SessionData,
+        unchanged: Option<&SignedCookie>,
+    ) {
+        let signed = self.codec.sign_session(
+            &self.config.cookie_name,
+            session_id,
+            &self.keys()[0],
+            session_data,
+        );
+
+        if self.config.response_header {
+            let token = match &signed.signature {
+                Some(signature) => format!("{}.{}", signed.value, signature),
+                None => signed.value.clone(),
+            };
+            let token = match &self.config.header_scheme {
+                Some(scheme) => format!("{} {}", scheme, token),
+                None => token,
+            };
+            match HeaderName::from_bytes(self.config.header_name.as_bytes()) {
+                Ok(name) => {
+                    if let Err(e) = res.add_header(name, token, true) {
+                        tracing::error!("Failed to set session header: {}", e);
+                    }
+                }
+                Err(e) => tracing::error!("Invalid session header name: {}", e),
             }
-            // Generate new ID
-            self.generate_session_id()
-        } else {
-            session_id
-        };
+        }
+
+        // The client already holds exactly this cookie; skip a redundant Set-Cookie
+        let redundant = unchanged.is_some_and(|incoming| {
+            url_decode(&incoming.value) == signed.value
+                && incoming.signature.as_deref().map(url_decode) == signed.signature
+        });
+
+        if self.config.transport.uses_cookie() && !redundant {
+            self.set_session_cookie(res, signed, &session_data.cookie);
+        }
+    }
+
+    /// Set session cookie on response
+    fn set_session_cookie(
+        &self,
+        res: &mut Response,
+        signed: SignedCookie,
+        session_cookie: &SessionCookie,
+    ) {
+        let cookie_name = self.config.cookie_name.clone();
 
-        let session_data = session.data();
-        let ttl = self.get_session_ttl(&session_data);
+        if let (Some(sig_name), Some(signature)) = (
+            self.codec.signature_cookie_name(&cookie_name),
+            signed.signature,
+        ) {
+            res.add_cookie(self.build_cookie(sig_name, signature, session_cookie));
+        }
+        res.add_cookie(self.build_cookie(cookie_name, signed.value, session_cookie));
+    }
 
-        // Determine if we need to save
-        let should_save = session.is_modified()
+    /// Persist the session and set its cookie before downstream handlers run
+    ///
+    /// Covers the writes due regardless of what handlers do: new sessions
+    /// (with `save_uninitialized`), `resave` and idle timeout refreshes.
+    /// Returns whether the session was saved. Deferred, lazily created and
+    /// regenerated sessions are left for after the response.
+    async fn save_early(
+        &self,
+        session: &Session,
+        res: &mut Response,
+        request_cookie: Option<&SignedCookie>,
+        reissue: bool,
+    ) -> bool {
+        if !session.is_loaded() || !session.has_id() || session.should_regenerate() {
+            return false;
+        }
+        let is_new = session.is_new();
+        let due = session.is_modified()
             || self.config.resave
             || (is_new && self.config.save_uninitialized)
-            || session.should_regenerate();
-
-        // Determine if we should set cookie
-        let should_set_cookie =
-            is_new || session.should_regenerate() || (self.config.rolling && session.is_modified());
+            || (!is_new && self.config.idle_timeout.is_some());
+        if !due {
+            return false;
+        }
+        if let Err(e) = session.save().await {
+            tracing::error!("Failed to save session early: {}", e);
+            self.report_error(&e, res);
+            return false;
+        }
 
-        if should_save {
-            // Save session to store
-            if let Err(e) = self.store.set(&final_session_id, &session_data, ttl).await {
-                tracing::error!("Failed to save session: {}", e);
-            }
-        } else if !is_new && !session.is_modified() {
-            // Touch session to reset TTL
-            if let Err(e) = self
-                .store
-                .touch(&final_session_id, &session_data, ttl)
-                .await
-            {
-                tracing::error!("Failed to touch session: {}", e);
+        let sid = session.id().to_string();
+        if is_new {
+            #[cfg(feature = "metrics")]
+            self.count(Event::Created);
+            if let Some(hooks) = &self.hooks {
+                hooks.on_create(&sid, &session.data()).await;
             }
+            self.emit(|| SessionEvent::Created { sid: sid.clone() });
+        }
+        if is_new || reissue {
+            let unchanged = request_cookie.filter(|_| !self.config.rolling);
+            session.with_data(|data| self.set_session_token(res, &sid, data, unchanged));
         }
+        true
+    }
 
-        if should_set_cookie {
-            self.set_session_cookie(res, &final_session_id);
+    /// Session, signature, legacy and remember-me cookies set on the response
+    fn issued_cookies(&self, res: &Response) -> Vec<cookie::Cookie<'static>> {
+        let mut names: Vec<String> = Vec::new();
+        for name in
+            std::iter::once(&self.config.cookie_name).chain(&self.config.legacy_cookie_names)
+        {
+            names.push(name.clone());
+            names.extend(self.codec.signature_cookie_name(name));
         }
+        if let Some(remember) = &self.remember {
+            names.push(remember.cookie_name().to_string());
+        }
+        res.cookies()
+            .delta()
+            .filter(|cookie| names.iter().any(|name| name == cookie.name()))
+            .cloned()
+            .collect()
     }
-}
 
-/// Get session from depot
-pub fn get_session(depot: &Depot) -> Option<&Session> {
-    depot.get::<Session>(SESSION_KEY).ok()
-}
+    /// Remove session cookie
+    fn remove_session_cookie(&self, res: &mut Response) {
+        self.remove_cookie(res, &self.config.cookie_name);
+    }
 
-/// Get mutable session from depot (returns clone with shared state)
-pub fn get_session_mut(depot: &mut Depot) -> Option<Session> {
-    depot.get::<Session>(SESSION_KEY).ok().cloned()
+    /// Remove a session cookie (and its signature cookie) by name
+    fn remove_cookie(&self, res: &mut Response, cookie_name: &str) {
+        let mut names = vec![cookie_name.to_string()];
+        names.extend(self.codec.signature_cookie_name(cookie_name));
+
+        for name in names {
+            let cookie = cookie::Cookie::build(name)
+                .path(self.config.cookie_path.clone())
+                .max_age(CookieDuration::ZERO)
+                .build();
+
+            res.add_cookie(cookie);
+        }
+    }
+
+    /// Check whether the session lifetime is due for renewal
+    ///
+    /// Always true when no rolling threshold is configured or the session
+    /// cookie has no expiry.
+    fn needs_renewal(&self, cookie: &SessionCookie) -> bool {
+        let (Some(threshold), Some(remaining)) = (&self.config.rolling_threshold, cookie.max_age())
+        else {
+            return true;
+        };
+
+        match threshold {
+            RenewalThreshold::Seconds(secs) => remaining < (*secs as i64) * 1000,
+            RenewalThreshold::Percent(percent) => match cookie.original_max_age {
+                Some(original) => remaining * 100 < original * (*percent as i64),
+                None => true,
+            },
+        }
+    }
+
+    /// Whether sessions are touched as they load rather than after the request
+    ///
+    /// Only where the TTL doesn't depend on the loaded session, see
+    /// [`SessionConfig::with_touch_on_load`].
+    fn touches_on_load(&self) -> bool {
+        self.config.touch_on_load
+            && !self.config.resave
+            && self.config.idle_timeout.is_none()
+            && self.config.absolute_max_age.is_none()
+            && self.config.rolling_threshold.is_none()
+    }
+
+    /// Check whether an unmodified session is due for a store touch (`touch_after`)
+    fn touch_due(&self, session_id: &str) -> bool {
+        let Some(touch_after) = self.config.touch_after else {
+            return true;
+        };
+
+        let last_touched = self.last_touched.lock();
+        match last_touched.get(session_id) {
+            Some(at) => at.elapsed() >= Duration::from_secs(touch_after),
+            None => true,
+        }
+    }
+
+    /// Write the session with a new version, resolving conflicts per the config
+    ///
+    /// `changed` lists the keys changed during the request, written alone
+    /// when `patch` is set and merged onto the stored copy on conflict.
+    /// Returns the data as written.
+    async fn persist(
+        &self,
+        sid: &str,
+        mut data: SessionData,
+        changed: &[String],
+        patch: bool,
+    ) -> Result<SessionData, SessionError> {
+        data.prune_expired_keys();
+        let mut expected = data.version;
+
+        if self.config.conflict_strategy == ConflictStrategy::LastWriteWins {
+            // Keep an existing version current without adding one to plain sessions
+            if let Some(version) = expected {
+                data.version = Some(version + 1);
+                data.mark_changed();
+            }
+            let ttl = self.get_session_ttl(&data);
+            // Truncated keys aren't in `changed`, so rewrite the whole session
+            if self.enforce_max_size(&mut data)? {
+                self.store_call("set", sid, self.store.set(sid, &data, ttl))
+                    .await?;
+            } else if patch {
+                self.store_call("patch", sid, self.store.patch(sid, &data, changed, ttl))
+                    .await?;
+            } else {
+                self.store_call("set", sid, self.store.set(sid, &data, ttl))
+                    .await?;
+            }
+            self.saved(sid, &data, changed).await;
+            return Ok(data);
+        }
+
+        for _ in 0..MERGE_ATTEMPTS {
+            data.version = Some(expected.unwrap_or(0) + 1);
+            data.mark_changed();
+            let ttl = self.get_session_ttl(&data);
+            self.enforce_max_size(&mut data)?;
+            let written = self.store.compare_and_set(sid, &data, expected, ttl);
+            if self.store_call("compare_and_set", sid, written).await? {
+                self.saved(sid, &data, changed).await;
+                return Ok(data);
+            }
+            if self.config.conflict_strategy == ConflictStrategy::Error {
+                break;
+            }
+
+            // A concurrently destroyed session (e.g. logout) stays destroyed
+            let Some(mut current) = self.store_call("get", sid, self.store.get(sid)).await? else {
+                break;
+            };
+            expected = current.version;
+            current.apply_changes(&data, changed);
+            data = current;
+        }
+
+        Err(SessionError::Conflict)
+    }
+
+    /// Remove a session from the store
+    async fn destroy_session(&self, sid: &str, data: &SessionData) -> Result<(), SessionError> {
+        self.store_call("destroy", sid, self.store.destroy(sid))
+            .await?;
+        #[cfg(feature = "metrics")]
+        self.count(Event::Destroyed);
+        if let Some(hooks) = &self.hooks {
+            hooks.on_destroy(sid, data).await;
+        }
+        self.emit(|| SessionEvent::Destroyed {
+            sid: sid.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Publish an event, if anyone is subscribed
+    fn emit(&self, event: impl FnOnce() -> SessionEvent) {
+        if self.events.receiver_count() > 0 {
+            let _ = self.events.send(event());
+        }
+    }
+
+    /// Bookkeeping after a successful write
+    async fn saved(&self, sid: &str, data: &SessionData, changed: &[String]) {
+        self.record_touch(sid);
+        #[cfg(feature = "metrics")]
+        self.count(Event::Saved);
+        if let Some(hooks) = &self.hooks {
+            hooks.on_save(sid, data).await;
+        }
+        self.emit(|| SessionEvent::Saved {
+            sid: sid.to_string(),
+        });
+        if let (Some(audit), false) = (&self.audit, changed.is_empty()) {
+            let record = AuditRecord {
+                at: chrono::Utc::now(),
+                session: hash_session_id(sid),
+                user: user_key(data),
+                changed: {
+                    let mut changed = changed.to_vec();
+                    changed.sort();
+                    changed
+                },
+            };
+            if let Err(e) = audit.record(&record).await {
+                tracing::error!("Failed to record session audit entry: {}", e);
+            }
+        }
+    }
+
+    /// Apply the size limit before a write, returning whether values were removed
+    fn enforce_max_size(&self, data: &mut SessionData) -> Result<bool, SessionError> {
+        let Some(limit) = self.config.max_size else {
+            return Ok(false);
+        };
+        let size = data.serialized_size();
+        if size <= limit {
+            return Ok(false);
+        }
+
+        if let Some(handler) = &self.oversize_handler {
+            data.mark_changed();
+            handler(data, size);
+        }
+        let size = data.serialized_size();
+        if size <= limit {
+            return Ok(false);
+        }
+        if self.config.oversize_policy == OversizePolicy::Reject {
+            return Err(SessionError::TooLarge { size, limit });
+        }
+
+        let removed = data.truncate_to(limit);
+        tracing::warn!(
+            "Session of {} bytes exceeds the {} byte limit; dropped {:?}",
+            size,
+            limit,
+            removed
+        );
+        let size = data.serialized_size();
+        if size > limit {
+            return Err(SessionError::TooLarge { size, limit });
+        }
+        Ok(true)
+    }
+
+    /// Record that the session's store TTL was just refreshed
+    fn record_touch(&self, session_id: &str) {
+        let Some(touch_after) = self.config.touch_after else {
+            return;
+        };
+
+        let mut last_touched = self.last_touched.lock();
+        if last_touched.len() >= TOUCH_PRUNE_THRESHOLD {
+            let window = Duration::from_secs(touch_after);
+            last_touched.retain(|_, at| at.elapsed() < window);
+        }
+        last_touched.insert(session_id.to_string(), Instant::now());
+    }
+
+    /// Calculate TTL for session storage
+    fn get_session_ttl(&self, session_data: &SessionData) -> Option<u64> {
+        let ttl = self.get_cookie_ttl(session_data);
+
+        // Abandoned sessions expire from the store after the idle timeout
+        let ttl = match (ttl, self.config.idle_timeout) {
+            (Some(ttl), Some(idle)) => Some(ttl.min(idle)),
+            (None, idle) => idle,
+            (ttl, None) => ttl,
+        };
+
+        // Never keep the session beyond its absolute lifetime
+        let remaining = match (self.config.absolute_max_age, session_data.created_at) {
+            (Some(max_age), Some(created)) => {
+                let deadline = created + chrono::Duration::seconds(max_age as i64);
+                Some((deadline - chrono::Utc::now()).num_seconds().max(0) as u64)
+            }
+            _ => None,
+        };
+
+        match (ttl, remaining) {
+            (Some(ttl), Some(remaining)) => Some(ttl.min(remaining)),
+            (None, remaining) => remaining,
+            (ttl, None) => ttl,
+        }
+    }
+
+    /// Calculate TTL from the session cookie
+    fn get_cookie_ttl(&self, session_data: &SessionData) -> Option<u64> {
+        // Use cookie expiration if available
+        if let Some(expires) = session_data.cookie.expires {
+            let now = chrono::Utc::now();
+            let diff = expires - now;
+            let secs = diff.num_seconds();
+            if secs > 0 {
+                return Some(secs as u64);
+            }
+        }
+        // Fall back to config max age (None = no TTL for session cookies)
+        self.config.max_age
+    }
+
+    /// Load the session, run the rest of the chain and persist the result
+    ///
+    /// `cookie` is the request's session cookie, verified by the caller.
+    async fn process(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+        cookie: Option<VerifiedCookie>,
+    ) {
+        // Whether the store failed and the session must not be persisted
+        let mut transient = false;
+
+        // A session cookie whose signature doesn't verify
+        let bad_signature = (self.has_metrics() || self.signature_guard.is_some())
+            && self.config.transport.uses_cookie()
+            && req.cookie(&self.config.cookie_name).is_some()
+            && cookie.is_none();
+
+        #[cfg(feature = "metrics")]
+        if bad_signature {
+            self.count(Event::SignatureFailure);
+        }
+
+        // Count signature failures per client, rejecting brute forcing
+        if let Some(guard) = self.signature_guard.as_ref().filter(|_| bad_signature) {
+            if let Some(ip) = client_ip(req, guard.trust_proxy()) {
+                if guard.record(ip).await {
+                    res.status_code(StatusCode::BAD_REQUEST);
+                    ctrl.skip_rest();
+                    return;
+                }
+            }
+        }
+
+        let client = self.client_identity(req);
+
+        // Safe requests only read the session, if so configured
+        let read_only = self.config.read_only_safe_methods
+            && matches!(*req.method(), Method::GET | Method::HEAD);
+
+        // Defer the store lookup until the session is first accessed
+        // Validators need the request and rejecting IP bindings must abort
+        // it, so both rule out deferring
+        let rejects = matches!(
+            &self.config.ip_binding,
+            Some(binding) if binding.on_mismatch == BindingAction::Reject
+        );
+        let deferred = if self.config.lazy_load && self.validator.is_none() && !rejects {
+            self.get_session_id(req, cookie.as_ref())
+                .map(|sid| self.deferred_session(sid, client.clone(), read_only))
+        } else {
+            None
+        };
+
+        let session = match deferred {
+            Some(session) => session,
+            None => {
+                // Try to load the existing session referenced by the request
+                let loaded = match self.load_session(req, cookie.as_ref(), read_only).await {
+                    Ok(loaded) => loaded,
+                    Err(e) => {
+                        tracing::error!("Failed to load session: {}", e);
+                        self.report_error(&e, res);
+                        match self.config.store_failure_policy {
+                            StoreFailurePolicy::FailOpen => {}
+                            StoreFailurePolicy::FailClosed => transient = true,
+                            StoreFailurePolicy::Abort => {
+                                res.status_code(StatusCode::SERVICE_UNAVAILABLE);
+                                ctrl.skip_rest();
+                                return;
+                            }
+                        }
+                        None
+                    }
+                };
+
+                // Sessions used by another client than they are bound to are not resumed
+                let loaded = match loaded {
+                    Some((sid, data)) => match self.verify_client(&sid, &data, &client).await {
+                        Ok(()) => Some((sid, data)),
+                        Err(BindingAction::Regenerate) => None,
+                        Err(BindingAction::Reject) => {
+                            res.status_code(StatusCode::FORBIDDEN);
+                            ctrl.skip_rest();
+                            return;
+                        }
+                    },
+                    None => None,
+                };
+
+                // Let the application accept, reject or regenerate the session
+                let mut regenerate = false;
+                let loaded = match (loaded, &self.validator) {
+                    (Some((sid, data)), Some(validator)) => {
+                        match validator.validate(req, &sid, &data).await {
+                            Validation::Accept => Some((sid, data)),
+                            Validation::Reject => None,
+                            Validation::Regenerate => {
+                                regenerate = true;
+                                Some((sid, data))
+                            }
+                        }
+                    }
+                    (loaded, _) => loaded,
+                };
+
+                // Carry over a legacy session the request still references
+                let migrated = if loaded.is_none() {
+                    self.migrate_legacy(req, res).await
+                } else {
+                    None
+                };
+
+                // Fall back to a new session when there is no usable one
+                let (session_id, mut session_data) = match loaded {
+                    Some((sid, data)) => (Some(sid), data),
+                    None => (None, self.fresh_session_data()),
+                };
+
+                // Record the access for idle timeout tracking
+                if self.config.idle_timeout.is_some() {
+                    session_data.record_access();
+                }
+                self.bind_client(&mut session_data, &client);
+
+                // Create session wrapper; lazy sessions only get an ID when needed
+                let session = match session_id {
+                    Some(sid) => Session::new(sid, session_data, false),
+                    None if self.config.lazy => Session::new_lazy(session_data),
+                    None => Session::new(self.generate_session_id(), session_data, true),
+                };
+                for (key, value) in migrated.into_iter().flatten() {
+                    session.set(&key, value);
+                }
+                if regenerate {
+                    session.regenerate_keep_data();
+                }
+                session
+            }
+        };
+
+        // Let handlers save or reload the session mid-request
+        self.attach_store(&session, read_only);
+        if read_only {
+            session.set_read_only();
+        }
+
+        if session.has_id() {
+            self.record_session_id(session.id());
+        }
+
+        // Store session in depot, and in the request for handler extraction
+        depot.inject(session.clone());
+        req.extensions_mut().insert(session.clone());
+
+        // Log the user in again from a remember-me token
+        if let Some(remember) = &self.remember {
+            remember.restore(req, &session).await;
+        }
+
+        // Keep the incoming cookie to detect redundant Set-Cookie headers
+        let request_cookie = self.get_request_cookie(req, &self.config.cookie_name);
+
+        // Sessions found under a legacy cookie name are moved to the primary name
+        let legacy_cookie = cookie
+            .as_ref()
+            .map(|cookie| cookie.name.clone())
+            .filter(|name| *name != self.config.cookie_name);
+
+        // Cookies signed with an old secret are re-issued with the primary one
+        let resign = self.config.resign_rotated
+            && cookie
+                .as_ref()
+                .is_some_and(|cookie| cookie.secret_index != 0);
+
+        // Persist the session before long-lived handlers run
+        let early_saved = self.config.early_save
+            && !transient
+            && !read_only
+            && self
+                .save_early(
+                    &session,
+                    res,
+                    request_cookie.as_ref(),
+                    legacy_cookie.is_some() || resign,
+                )
+                .await;
+
+        // Continue with the request
+        ctrl.call_next(req, depot, res).await;
+
+        // After request processing, handle session persistence
+
+        // Deferred sessions that were never accessed need no persistence;
+        // changes made without loading apply on top of the stored data
+        if !session.is_loaded() {
+            let touched = session.is_modified()
+                || session.is_cookie_modified()
+                || session.should_destroy()
+                || session.should_regenerate();
+            if !touched {
+                return;
+            }
+            // Failures are reported below
+            let _ = session.load().await;
+        }
+
+        // Deferred load failures can't abort anymore; never persist instead
+        if session.load_failed() {
+            let e = SessionError::StoreError("Deferred session load failed".to_string());
+            self.report_error(&e, res);
+            transient |= self.config.store_failure_policy != StoreFailurePolicy::FailOpen;
+        }
+
+        // Sessions created after a store failure are never persisted (fail-closed)
+        if transient {
+            return;
+        }
+
+        let is_new = session.is_new();
+        let modified = session.is_modified();
+
+        // Lazily created sessions that were never written leave no trace
+        if self.config.lazy
+            && is_new
+            && !modified
+            && !session.is_saved()
+            && !self.config.save_uninitialized
+        {
+            return;
+        }
+
+        // A lazy session only gets its ID now
+        let assigned = !session.has_id();
+        let session_id = session.id().to_string();
+        if assigned {
+            self.record_session_id(&session_id);
+        }
+
+        // Check if session should be destroyed, explicitly or by being emptied
+        let unset = self.config.unset == Unset::Destroy
+            && !is_new
+            && !session.should_regenerate()
+            && modified
+            && session.is_empty();
+        if session.should_destroy() || unset {
+            if let Err(e) = self.destroy_session(&session_id, &session.data()).await {
+                tracing::error!("Failed to destroy session: {}", e);
+                self.report_error(&e, res);
+            }
+            if self.config.transport.uses_cookie() {
+                self.remove_session_cookie(res);
+                if let Some(legacy) = &legacy_cookie {
+                    self.remove_cookie(res, legacy);
+                }
+            }
+            return;
+        }
+
+        // Failed requests neither create nor extend sessions, if so configured
+        if self.skips_persistence(res) {
+            return;
+        }
+
+        if read_only {
+            if modified || session.should_regenerate() || session.is_cookie_modified() {
+                tracing::warn!(
+                    method = %req.method(),
+                    "Dropping session changes made by a read-only request"
+                );
+            }
+            return;
+        }
+
+        // A changed privilege key moves an existing session to a new ID
+        if !is_new
+            && !session.should_regenerate()
+            && session.privilege_changed(&self.config.privilege_keys)
+        {
+            session.regenerate_keep_data();
+        }
+
+        // Check if session should be regenerated
+        let stored = !is_new || session.is_saved();
+        let final_session_id = if session.should_regenerate() {
+            // Destroy old session, unless it was never stored
+            if stored {
+                if let Err(e) = self.destroy_session(&session_id, &session.data()).await {
+                    tracing::error!("Failed to destroy old session during regeneration: {}", e);
+                    self.report_error(&e, res);
+                }
+            }
+            // Generate new ID
+            let sid = self.generate_session_id();
+            self.record_session_id(&sid);
+            sid
+        } else {
+            session_id
+        };
+
+        // Check rolling renewal threshold; renewing resets the cookie expiry
+        let renew = self.needs_renewal(&session.cookie());
+        let roll = self.config.rolling && modified && renew;
+        if roll && !is_new {
+            session.touch();
+        }
+
+        // Sessions created by Node.js don't record a creation time; their
+        // absolute lifetime starts with the first request seen here
+        let backfilled = !is_new
+            && self.config.absolute_max_age.is_some()
+            && session.read().created_at.is_none();
+        if backfilled {
+            let mut data = session.write();
+            data.created_at = Some(chrono::Utc::now());
+            data.mark_changed();
+        }
+
+        // Determine if we need to save; an early save already covered unmodified sessions
+        let should_save = modified
+            || backfilled
+            || session.should_regenerate()
+            || (!early_saved
+                && (self.config.resave
+                    || (is_new && self.config.save_uninitialized)
+                    || (!is_new && self.config.idle_timeout.is_some())));
+
+        // Determine if we should set cookie
+        // A session found under a legacy name is re-issued under the primary name
+        let should_set_cookie = is_new
+            || session.should_regenerate()
+            || roll
+            || session.is_cookie_modified()
+            || legacy_cookie.is_some()
+            || resign
+            || (modified && self.codec.reissue_on_change(&session.dirty_keys()));
+
+        // Without rolling or attribute changes, only a changed value warrants a Set-Cookie
+        let unchanged = if self.config.rolling || session.is_cookie_modified() {
+            None
+        } else {
+            request_cookie.as_ref()
+        };
+
+        if should_save {
+            // Save session to store; the store call outlives the session lock,
+            // so this is the one copy of the data a save makes
+            let mut session_data = session.data();
+            // A regenerated session starts a new record
+            if session.should_regenerate() {
+                session_data.version = None;
+                session_data.mark_changed();
+            }
+            // Existing sessions can be updated in place, key by key
+            let patch = self.config.patch_updates && !is_new && !session.should_regenerate();
+            let changed = session.dirty_keys();
+            match self
+                .persist(&final_session_id, session_data, &changed, patch)
+                .await
+            {
+                Ok(saved) => {
+                    if (is_new && !early_saved) || session.should_regenerate() {
+                        #[cfg(feature = "metrics")]
+                        self.count(Event::Created);
+                        if let Some(hooks) = &self.hooks {
+                            hooks.on_create(&final_session_id, &saved).await;
+                        }
+                        self.emit(|| {
+                            if session.should_regenerate() && stored {
+                                SessionEvent::Regenerated {
+                                    old_sid: session.id().to_string(),
+                                    new_sid: final_session_id.clone(),
+                                }
+                            } else {
+                                SessionEvent::Created {
+                                    sid: final_session_id.clone(),
+                                }
+                            }
+                        });
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Failed to save session: {}", e);
+                    self.report_error(&e, res);
+                }
+            }
+        } else if !is_new
+            && !modified
+            && !early_saved
+            && renew
+            && !self.touches_on_load()
+            && self.touch_due(&final_session_id)
+        {
+            // Touch session to reset TTL
+            let session_data = session.data();
+            let ttl = self.get_session_ttl(&session_data);
+            let touched = self.store.touch(&final_session_id, &session_data, ttl);
+            match self.store_call("touch", &final_session_id, touched).await {
+                Ok(()) => {
+                    self.record_touch(&final_session_id);
+                    #[cfg(feature = "metrics")]
+                    self.count(Event::Touched);
+                }
+                Err(e) => {
+                    tracing::error!("Failed to touch session: {}", e);
+                    self.report_error(&e, res);
+                }
+            }
+        }
+
+        if should_set_cookie {
+            session
+                .with_data(|data| self.set_session_token(res, &final_session_id, data, unchanged));
+        }
+
+        if let Some(legacy) = legacy_cookie.filter(|_| self.config.expire_legacy_cookies) {
+            if self.config.transport.uses_cookie() {
+                self.remove_cookie(res, &legacy);
+            }
+        }
+    }
+}
+
+impl ExpressSessionHandler<Box<dyn SessionStore>> {
+    /// Create a session handler over a type-erased store
+    ///
+    /// Lets applications pick the backend at runtime without carrying the
+    /// store type through their signatures:
+    ///
+    /// ```rust,ignore
+    /// let store: Box<dyn SessionStore> = match backend {
+    ///     "redis" => Box::new(RedisStore::from_url(&url).await?),
+    ///     _ => Box::new(MemoryStore::new()),
+    /// };
+    /// let handler = ExpressSessionHandler::new_boxed(store, config);
+    /// ```
+    ///
+    /// An invalid configuration is logged as a warning, as in [`Self::new`].
+    pub fn new_boxed(store: Box<dyn SessionStore>, config: SessionConfig) -> Self {
+        Self::new(store, config)
+    }
+}
+
+impl<S: SessionStore> Clone for ExpressSessionHandler<S> {
+    fn clone(&self) -> Self {
+        Self {
+            store: Arc::clone(&self.store),
+            config: self.config.clone(),
+            codec: Arc::clone(&self.codec),
+            keys: Arc::clone(&self.keys),
+            secret_cache: self.secret_cache.clone(),
+            locker: self.locker.clone(),
+            remember: self.remember.clone(),
+            hooks: self.hooks.clone(),
+            events: self.events.clone(),
+            audit: self.audit.clone(),
+            compat: self.compat.clone(),
+            legacy: self.legacy.clone(),
+            fingerprint: self.fingerprint.clone(),
+            signature_guard: self.signature_guard.clone(),
+            validator: self.validator.clone(),
+            #[cfg(feature = "metrics")]
+            metrics: self.metrics.clone(),
+            error_handler: self.error_handler.clone(),
+            oversize_handler: self.oversize_handler.clone(),
+            skip: self.skip.clone(),
+            skip_persistence: self.skip_persistence.clone(),
+            last_touched: Arc::clone(&self.last_touched),
+        }
+    }
+}
+
+/// Build a cookie carrying the session's own cookie attributes
+///
+/// Like express-session, attributes come from the session's cookie data
+/// (which handlers may change), falling back to the configuration.
+pub(crate) fn build_cookie(
+    config: &SessionConfig,
+    name: String,
+    value: String,
+    session_cookie: &SessionCookie,
+) -> cookie::Cookie<'static> {
+    // Build cookie with owned strings to avoid lifetime issues
+    let cookie_path = session_cookie.path.clone();
+    let cookie_domain = session_cookie.domain.clone();
+
+    let same_site = session_cookie
+        .same_site
+        .as_deref()
+        .and_then(SameSite::parse)
+        .unwrap_or_else(|| config.cookie_same_site.clone());
+
+    // Browsers drop SameSite=None cookies without Secure
+    let secure = session_cookie.secure || (config.auto_secure && same_site == SameSite::None);
+
+    let mut cookie_builder = cookie::Cookie::build((name, value))
+        .path(cookie_path)
+        .http_only(session_cookie.http_only)
+        .secure(secure);
+
+    if let Some(domain) = cookie_domain {
+        cookie_builder = cookie_builder.domain(domain);
+    }
+
+    // Set max age from the session expiry (none = session cookie)
+    if let Some(remaining_ms) = session_cookie.max_age() {
+        let secs = ((remaining_ms + 999) / 1000).max(0);
+        cookie_builder = cookie_builder.max_age(CookieDuration::seconds(secs));
+    }
+
+    // Set Expires from the session expiry, like express-session
+    if config.cookie_expires {
+        if let Some(expires) = session_cookie.expires {
+            match OffsetDateTime::from_unix_timestamp(expires.timestamp()) {
+                Ok(expires) => cookie_builder = cookie_builder.expires(expires),
+                Err(e) => tracing::warn!("Invalid session cookie expiry: {}", e),
+            }
+        }
+    }
+
+    // Set SameSite
+    cookie_builder = match same_site {
+        SameSite::Strict => cookie_builder.same_site(CookieSameSite::Strict),
+        SameSite::Lax => cookie_builder.same_site(CookieSameSite::Lax),
+        SameSite::None => cookie_builder.same_site(CookieSameSite::None),
+    };
+
+    cookie_builder.build()
+}
+
+#[async_trait]
+impl<S: SessionStore> Handler for ExpressSessionHandler<S> {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        // Log entries of the request, downstream handlers included, carry the redacted session ID
+        let span = tracing::info_span!("session", session.id_hash = tracing::field::Empty);
+        self.handle_request(req, depot, res, ctrl)
+            .instrument(span)
+            .await
+    }
+}
+
+impl<S: SessionStore> ExpressSessionHandler<S> {
+    /// Body of [`Handler::handle`], run in the request's session span
+    async fn handle_request(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        // Excluded requests never touch cookies or the store
+        if self.is_skipped(req) {
+            depot.inject(SessionSkipped);
+            ctrl.call_next(req, depot, res).await;
+            return;
+        }
+
+        if let Some(cache) = &self.secret_cache {
+            cache.ensure_loaded().await;
+        }
+
+        // A session handler further up the router tree already owns this request
+        if depot.obtain::<Session>().is_ok() {
+            tracing::warn!(
+                "Nested session handler ignored; mount scoped handlers on sibling routers"
+            );
+            ctrl.call_next(req, depot, res).await;
+            return;
+        }
+
+        // Verify the session cookie once for the whole request
+        let cookie = self.unsign_request_cookie(req);
+
+        // Serialize concurrent requests for the same session
+        let sid = match self.locker {
+            Some(_) => self.get_session_id(req, cookie.as_ref()),
+            None => None,
+        };
+        let lock = match (&self.locker, sid) {
+            (Some(locker), Some(sid)) => match LockGuard::acquire(locker, sid).await {
+                Ok(guard) => Some(guard),
+                Err(e) => {
+                    tracing::error!("Failed to lock session: {}", e);
+                    self.report_error(&e, res);
+                    res.status_code(StatusCode::SERVICE_UNAVAILABLE);
+                    ctrl.skip_rest();
+                    return;
+                }
+            },
+            _ => None,
+        };
+
+        self.process(req, depot, res, ctrl, cookie).await;
+
+        if let Some(remember) = &self.remember {
+            if let Ok(session) = depot.obtain::<Session>() {
+                remember.finish(req, session, res).await;
+            }
+        }
+
+        // Leave the cookies set above for a catcher rendering the error page
+        let failed = res
+            .status_code
+            .is_some_and(|status| status.is_client_error() || status.is_server_error());
+        if self.config.catcher_cookies && failed {
+            let cookies = self.issued_cookies(res);
+            if !cookies.is_empty() {
+                depot.inject(IssuedCookies(cookies));
+            }
+        }
+
+        if let Some(guard) = lock {
+            if let Err(e) = guard.release().await {
+                tracing::error!("Failed to unlock session: {}", e);
+                self.report_error(&e, res);
+            }
+        }
+    }
+}
+
+/// Short, non-reversible fingerprint of a session ID for traces
+///
+/// Lets traces of one session be correlated without exposing an ID that
+/// could be replayed.
+pub(crate) fn hash_session_id(sid: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(sid.as_bytes())[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Get session from depot
+pub fn get_session(depot: &Depot) -> Option<&Session> {
+    depot.obtain::<Session>().ok()
+}
+
+/// Get mutable session from depot (returns clone with shared state)
+pub fn get_session_mut(depot: &mut Depot) -> Option<Session> {
+    depot.obtain::<Session>().ok().cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+    use crate::config::SessionTransport;
+    use crate::cookie_signature;
+    use crate::depot_ext::SessionDepotExt;
+    use crate::store::MemoryStore;
+
+    /// Log output captured by a test subscriber
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[handler]
+    async fn log_something(depot: &mut Depot) {
+        depot.obtain::<Session>().unwrap().set("seen", true);
+        tracing::info!("handled");
+    }
+
+    async fn log_request(redaction: LogRedaction) -> (String, String) {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let config = SessionConfig::new("secret").with_log_redaction(redaction);
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), config);
+        let service = Service::new(Router::new().hoop(handler).get(log_something));
+        let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        let sid = res.cookie("connect.sid").unwrap().value().to_string();
+        let sid = url_decode(&sid)[2..].split('.').next().unwrap().to_string();
+
+        let logs = String::from_utf8(captured.0.lock().clone()).unwrap();
+        (sid, logs)
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_logs_carry_hashed_session_id() {
+        let (sid, logs) = log_request(LogRedaction::Hash).await;
+        assert!(logs.contains(&format!("session.id_hash={}", hash_session_id(&sid))));
+        assert!(logs.contains("handled"));
+        assert!(!logs.contains(&sid));
+
+        let (sid, logs) = log_request(LogRedaction::Omit).await;
+        assert!(logs.contains("handled"));
+        assert!(!logs.contains("session.id_hash"));
+        assert!(!logs.contains(&sid));
+    }
+
+    /// Store whose backend can't be reached
+    struct Unreachable;
+
+    #[async_trait]
+    impl SessionStore for Unreachable {
+        async fn get(&self, _sid: &str) -> Result<Option<SessionData>, SessionError> {
+            Err(SessionError::StoreError("connection refused".to_string()))
+        }
+
+        async fn set(
+            &self,
+            _sid: &str,
+            _session: &SessionData,
+            _ttl_secs: Option<u64>,
+        ) -> Result<(), SessionError> {
+            Err(SessionError::StoreError("connection refused".to_string()))
+        }
+
+        async fn destroy(&self, _sid: &str) -> Result<(), SessionError> {
+            Err(SessionError::StoreError("connection refused".to_string()))
+        }
+
+        async fn touch(
+            &self,
+            _sid: &str,
+            _session: &SessionData,
+            _ttl_secs: Option<u64>,
+        ) -> Result<(), SessionError> {
+            Err(SessionError::StoreError("connection refused".to_string()))
+        }
+
+        async fn ping(&self) -> Result<(), SessionError> {
+            Err(SessionError::StoreError("connection refused".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_store() {
+        let config = SessionConfig::new("secret");
+        assert!(
+            ExpressSessionHandler::new(MemoryStore::new(), config.clone())
+                .verify_store()
+                .await
+                .is_ok()
+        );
+        assert!(ExpressSessionHandler::new(Unreachable, config)
+            .verify_store()
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_cookies_are_ignored() {
+        let store = MemoryStore::new();
+        let config = SessionConfig::new("secret").with_max_cookie_length(64);
+        let handler = ExpressSessionHandler::new(store.clone(), config);
+        let sid = generate_session_id();
+        let mut data = SessionData::new(3600);
+        data.set("user", "alice");
+        store.set(&sid, &data, Some(3600)).await.unwrap();
+
+        let signed = cookie_signature::sign(&sid, "secret");
+        let req = |cookie: String| {
+            let mut req = Request::new();
+            req.cookies_mut()
+                .add(cookie::Cookie::new("connect.sid", cookie));
+            req
+        };
+        let req = req(signed);
+        assert!(handler.unsign_request_cookie(&req).is_none());
+        assert_eq!(handler.get_session_id(&req, None), None);
+        let handler = handler.scoped(SessionConfig::new("secret"));
+        let cookie = handler.unsign_request_cookie(&req);
+        assert_eq!(handler.get_session_id(&req, cookie.as_ref()), Some(sid));
+    }
+
+    #[handler]
+    async fn set_query(req: &mut Request, depot: &mut Depot) {
+        let session = depot.obtain::<Session>().unwrap();
+        for (key, value) in req.queries().iter() {
+            session.set(key, value);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_privilege_change_cycles_session_id() {
+        let store = MemoryStore::new();
+        let config = SessionConfig::new("secret").with_privilege_keys(["userId"]);
+        let handler = ExpressSessionHandler::new(store.clone(), config);
+        let service = Service::new(Router::new().hoop(handler).get(set_query));
+
+        let res = TestClient::get("http://127.0.0.1/?theme=dark")
+            .send(&service)
+            .await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+
+        // Other keys keep the ID
+        let res = TestClient::get("http://127.0.0.1/?theme=light")
+            .add_header("cookie", &cookie, true)
+            .send(&service)
+            .await;
+        if let Some(set) = res.cookie("connect.sid") {
+            assert_eq!(set.stripped().to_string(), cookie);
+        }
+
+        let res = TestClient::get("http://127.0.0.1/?userId=42")
+            .add_header("cookie", &cookie, true)
+            .send(&service)
+            .await;
+        let cycled = res.cookie("connect.sid").unwrap().stripped().to_string();
+        assert_ne!(cycled, cookie);
+        assert_eq!(store.length().await.unwrap(), 1);
+        let sid = store.ids().await.unwrap().pop().unwrap();
+        let data = store.get(&sid).await.unwrap().unwrap();
+        assert_eq!(data.get::<String>("theme").as_deref(), Some("light"));
+        assert_eq!(data.get::<String>("userId").as_deref(), Some("42"));
+    }
+
+    /// Memory store recording the store calls made
+    #[derive(Clone, Default)]
+    struct Recording {
+        inner: MemoryStore,
+        calls: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    #[async_trait]
+    impl SessionStore for Recording {
+        async fn get(&self, sid: &str) -> Result<Option<SessionData>, SessionError> {
+            self.calls.lock().push("get");
+            self.inner.get(sid).await
+        }
+
+        async fn set(
+            &self,
+            sid: &str,
+            session: &SessionData,
+            ttl_secs: Option<u64>,
+        ) -> Result<(), SessionError> {
+            self.calls.lock().push("set");
+            self.inner.set(sid, session, ttl_secs).await
+        }
+
+        async fn destroy(&self, sid: &str) -> Result<(), SessionError> {
+            self.inner.destroy(sid).await
+        }
+
+        async fn touch(
+            &self,
+            sid: &str,
+            session: &SessionData,
+            ttl_secs: Option<u64>,
+        ) -> Result<(), SessionError> {
+            self.calls.lock().push("touch");
+            self.inner.touch(sid, session, ttl_secs).await
+        }
+
+        async fn get_and_touch(
+            &self,
+            sid: &str,
+            ttl_secs: Option<u64>,
+        ) -> Result<Option<SessionData>, SessionError> {
+            self.calls.lock().push("get_and_touch");
+            self.inner.get_and_touch(sid, ttl_secs).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_touch_on_load() {
+        for (touch_on_load, expected) in
+            [(false, vec!["get", "touch"]), (true, vec!["get_and_touch"])]
+        {
+            let store = Recording::default();
+            let config = SessionConfig::new("secret").with_touch_on_load(touch_on_load);
+            let handler = ExpressSessionHandler::new(store.clone(), config);
+            let service = Service::new(Router::new().hoop(handler).get(set_query));
+
+            let res = TestClient::get("http://127.0.0.1/?theme=dark")
+                .send(&service)
+                .await;
+            let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+            store.calls.lock().clear();
+
+            TestClient::get("http://127.0.0.1/")
+                .add_header("cookie", &cookie, true)
+                .send(&service)
+                .await;
+            assert_eq!(*store.calls.lock(), expected);
+        }
+    }
+
+    /// Reports whether the session was already stored, then changes it
+    #[handler]
+    async fn stream_start(depot: &mut Depot) -> String {
+        let session = depot.session().unwrap();
+        let stored = session.reload().await.is_ok();
+        session.set("streaming", true);
+        stored.to_string()
+    }
+
+    #[tokio::test]
+    async fn test_early_save() {
+        for early_save in [false, true] {
+            let store = Recording::default();
+            let config = SessionConfig::new("secret")
+                .with_save_uninitialized(true)
+                .with_early_save(early_save);
+            let handler = ExpressSessionHandler::new(store.clone(), config);
+            let service = Service::new(Router::new().hoop(handler).get(stream_start));
+
+            let mut res = TestClient::get("http://127.0.0.1/").send(&service).await;
+            assert!(res.cookie("connect.sid").is_some());
+            assert_eq!(res.take_string().await.unwrap(), early_save.to_string());
+
+            // The handler's changes are saved after it returns
+            let sid = store.inner.ids().await.unwrap().pop().unwrap();
+            let data = store.inner.get(&sid).await.unwrap().unwrap();
+            assert_eq!(data.get::<bool>("streaming"), Some(true));
+        }
+    }
+
+    /// Changes the session and answers with the status in the `status` query
+    #[handler]
+    async fn fail_with(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+        depot.session().unwrap().incr("attempts", 1);
+        let status = req.query::<u16>("status").unwrap_or(200);
+        res.status_code(StatusCode::from_u16(status).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_skip_persistence() {
+        let store = Recording::default();
+        let handler = ExpressSessionHandler::new(store.clone(), SessionConfig::new("secret"))
+            .with_skip_persistence(|status| status.is_server_error());
+        let service = Service::new(Router::new().hoop(handler).get(fail_with));
+
+        let res = TestClient::get("http://127.0.0.1/?status=503")
+            .send(&service)
+            .await;
+        assert!(res.cookie("connect.sid").is_none());
+        assert_eq!(store.inner.length().await.unwrap(), 0);
+
+        let res = TestClient::get("http://127.0.0.1/?status=404")
+            .send(&service)
+            .await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+        store.calls.lock().clear();
+
+        // Existing sessions are neither saved nor touched
+        TestClient::get("http://127.0.0.1/?status=500")
+            .add_header("cookie", &cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(*store.calls.lock(), vec!["get"]);
+    }
+
+    /// Counts visits, reporting whether a checked write was allowed
+    #[handler]
+    async fn try_write(depot: &mut Depot) -> String {
+        let session = depot.session().unwrap();
+        session.incr("visits", 1);
+        session.try_set("seen", true).is_ok().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_read_only_safe_methods() {
+        // Reads neither write nor touch, however the session is loaded
+        for (touch_on_load, lazy_load) in [(false, false), (true, false), (true, true)] {
+            let store = Recording::default();
+            let config = SessionConfig::new("secret")
+                .with_read_only_safe_methods(true)
+                .with_touch_on_load(touch_on_load)
+                .with_lazy_load(lazy_load);
+            let handler = ExpressSessionHandler::new(store.clone(), config);
+            let service = Service::new(Router::new().hoop(handler).get(try_write).post(try_write));
+
+            let mut res = TestClient::post("http://127.0.0.1/").send(&service).await;
+            let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+            assert_eq!(res.take_string().await.unwrap(), "true");
+            store.calls.lock().clear();
+
+            let mut res = TestClient::get("http://127.0.0.1/")
+                .add_header("cookie", &cookie, true)
+                .send(&service)
+                .await;
+            assert_eq!(res.cookies().delta().count(), 0);
+            assert_eq!(res.take_string().await.unwrap(), "false");
+            assert_eq!(*store.calls.lock(), vec!["get"]);
+
+            let sid = store.inner.ids().await.unwrap().pop().unwrap();
+            let data = store.inner.get(&sid).await.unwrap().unwrap();
+            assert_eq!(data.get::<u64>("visits"), Some(1));
+        }
+    }
+
+    /// Loads the session, then counts visits
+    #[handler]
+    async fn load_visit(depot: &mut Depot) -> String {
+        let session = depot.session().unwrap();
+        session.load().await.unwrap();
+        session.incr("visits", 1).to_string()
+    }
+
+    /// Writes to the session without loading it
+    #[handler]
+    async fn blind_write(depot: &mut Depot) {
+        depot.session().unwrap().set("blind", true);
+    }
+
+    #[handler]
+    async fn untouched() -> &'static str {
+        "ok"
+    }
+
+    #[tokio::test]
+    async fn test_lazy_load() {
+        let store = Recording::default();
+        let config = SessionConfig::new("secret").with_lazy_load(true);
+        let handler = ExpressSessionHandler::new(store.clone(), config);
+        let service = Service::new(
+            Router::new()
+                .hoop(handler)
+                .push(Router::with_path("visit").get(load_visit))
+                .push(Router::with_path("blind").get(blind_write))
+                .push(Router::with_path("static").get(untouched)),
+        );
+        let request = |path: &str, cookie: &str| {
+            TestClient::get(format!("http://127.0.0.1/{}", path))
+                .add_header("cookie", cookie, true)
+                .send(&service)
+        };
+
+        let res = TestClient::get("http://127.0.0.1/visit")
+            .send(&service)
+            .await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+        let sid = store.inner.ids().await.unwrap().pop().unwrap();
+
+        // Routes that never use the session never touch the store
+        store.calls.lock().clear();
+        request("static", &cookie).await;
+        assert!(store.calls.lock().is_empty());
+
+        let mut res = request("visit", &cookie).await;
+        assert_eq!(res.take_string().await.unwrap(), "2");
+
+        // Writes without loading are applied on top of the stored data
+        request("blind", &cookie).await;
+        let data = store.inner.get(&sid).await.unwrap().unwrap();
+        assert_eq!(data.get::<u64>("visits"), Some(2));
+        assert_eq!(data.get::<bool>("blind"), Some(true));
+
+        // An ID missing from the store is never adopted
+        store.inner.destroy(&sid).await.unwrap();
+        let mut res = request("visit", &cookie).await;
+        let renewed = res.cookie("connect.sid").unwrap().stripped().to_string();
+        assert_ne!(renewed, cookie);
+        assert_eq!(res.take_string().await.unwrap(), "1");
+        assert!(store.inner.get(&sid).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_created_at_backfill() {
+        for (absolute_max_age, persisted) in [(None, false), (Some(3600), true)] {
+            let store = MemoryStore::new();
+            let config = SessionConfig::new("secret").with_absolute_max_age(absolute_max_age);
+            let handler = ExpressSessionHandler::new(store.clone(), config);
+            let service = Service::new(Router::new().hoop(handler).get(untouched));
+
+            // Written by Node.js, without a creation time
+            let sid = generate_session_id();
+            let mut data = SessionData::new(3600);
+            data.created_at = None;
+            store.set(&sid, &data, Some(3600)).await.unwrap();
+
+            let signed = cookie_signature::sign(&sid, "secret");
+            let cookie = format!("connect.sid={}", urlencoding::encode(&signed));
+            TestClient::get("http://127.0.0.1/")
+                .add_header("cookie", cookie, true)
+                .send(&service)
+                .await;
+            let data = store.get(&sid).await.unwrap().unwrap();
+            assert_eq!(data.created_at.is_some(), persisted);
+        }
+    }
+
+    #[handler]
+    async fn hang() {
+        std::future::pending::<()>().await;
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_releases_lock() {
+        let locker = crate::lock::MemoryLocker::new().with_wait(Duration::from_millis(100));
+        let config = SessionConfig::new("secret").with_save_uninitialized(true);
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), config).with_locker(locker);
+        let service = Arc::new(Service::new(
+            Router::new()
+                .hoop(handler)
+                .get(untouched)
+                .push(Router::with_path("hang").get(hang)),
+        ));
+
+        let res = TestClient::get("http://127.0.0.1/").send(&*service).await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+
+        // The client goes away while the handler holds the lock
+        let request = TestClient::get("http://127.0.0.1/hang").add_header("cookie", &cookie, true);
+        let hung = tokio::spawn({
+            let service = Arc::clone(&service);
+            async move { request.send(&*service).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        hung.abort();
+        assert!(hung.await.unwrap_err().is_cancelled());
+
+        let res = TestClient::get("http://127.0.0.1/")
+            .add_header("cookie", &cookie, true)
+            .send(&*service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+    }
+
+    #[tokio::test]
+    async fn test_header_transport() {
+        let config = SessionConfig::new("secret")
+            .with_transport(SessionTransport::Header)
+            .with_header_name("Authorization")
+            .with_header_scheme("Session")
+            .with_response_header(true);
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), config);
+        let service = Service::new(Router::new().hoop(handler).get(load_visit));
+
+        let mut res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        let token = res.headers().get("authorization").unwrap().clone();
+        assert!(token.to_str().unwrap().starts_with("Session "));
+        assert!(res.headers().get("set-cookie").is_none());
+        assert_eq!(res.take_string().await.unwrap(), "1");
+
+        let mut res = TestClient::get("http://127.0.0.1/")
+            .add_header("authorization", token, true)
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "2");
+    }
+
+    #[tokio::test]
+    async fn test_websocket_query_param() {
+        let config = SessionConfig::new("secret").with_websocket_query_param("sid");
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), config);
+        let service = Service::new(Router::new().hoop(handler).get(load_visit));
+
+        let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        let token = res.cookie("connect.sid").unwrap().value().to_string();
+        let url = format!(
+            "http://127.0.0.1/?sid={}",
+            urlencoding::encode(&url_decode(&token))
+        );
+
+        let mut res = TestClient::get(&url)
+            .add_header("upgrade", "websocket", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "2");
+
+        // Plain requests ignore the parameter
+        let mut res = TestClient::get(&url).send(&service).await;
+        assert_eq!(res.take_string().await.unwrap(), "1");
+    }
+
+    #[tokio::test]
+    async fn test_rolling_threshold() {
+        let store = Recording::default();
+        let config = SessionConfig::new("secret")
+            .with_max_age(3600)
+            .with_rolling(true)
+            .with_rolling_threshold(RenewalThreshold::Percent(50));
+        let handler = ExpressSessionHandler::new(store.clone(), config);
+        let service = Service::new(
+            Router::new()
+                .hoop(handler)
+                .get(untouched)
+                .push(Router::with_path("write").get(set_query)),
+        );
+
+        for (remaining, renewed) in [(3000, false), (1000, true)] {
+            for path in ["write?theme=dark", ""] {
+                // A session with `remaining` seconds of its hour left
+                let sid = generate_session_id();
+                let mut data = SessionData::new(3600);
+                data.cookie.expires =
+                    Some(chrono::Utc::now() + chrono::Duration::seconds(remaining));
+                store.inner.set(&sid, &data, Some(3600)).await.unwrap();
+                store.calls.lock().clear();
+
+                let signed = cookie_signature::sign(&sid, "secret");
+                let cookie = format!("connect.sid={}", urlencoding::encode(&signed));
+                let res = TestClient::get(format!("http://127.0.0.1/{}", path))
+                    .add_header("cookie", cookie, true)
+                    .send(&service)
+                    .await;
+                if path.is_empty() {
+                    assert_eq!(store.calls.lock().contains(&"touch"), renewed);
+                } else {
+                    assert_eq!(res.cookies().delta().count() == 1, renewed);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_store_failure_policy() {
+        let signed = cookie_signature::sign(&generate_session_id(), "secret");
+        let cookie = format!("connect.sid={}", urlencoding::encode(&signed));
+        for (policy, status) in [
+            (StoreFailurePolicy::FailOpen, StatusCode::OK),
+            (StoreFailurePolicy::FailClosed, StatusCode::OK),
+            (StoreFailurePolicy::Abort, StatusCode::SERVICE_UNAVAILABLE),
+        ] {
+            let config = SessionConfig::new("secret").with_store_failure_policy(policy.clone());
+            let handler = ExpressSessionHandler::new(Unreachable, config);
+            let service = Service::new(Router::new().hoop(handler).get(try_write));
+
+            let mut res = TestClient::get("http://127.0.0.1/")
+                .add_header("cookie", &cookie, true)
+                .send(&service)
+                .await;
+            assert_eq!(res.status_code, Some(status));
+
+            // Only a fail-open session replaces the client's cookie
+            let fresh = policy == StoreFailurePolicy::FailOpen;
+            assert_eq!(res.cookies().delta().count() == 1, fresh);
+            if status == StatusCode::OK {
+                assert_eq!(res.take_string().await.unwrap(), "true");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_touch_after() {
+        for (touch_after, expected) in [
+            (None, vec!["get", "touch", "get", "touch"]),
+            (Some(60), vec!["get", "get"]),
+        ] {
+            let store = Recording::default();
+            let config = SessionConfig::new("secret")
+                .with_save_uninitialized(true)
+                .with_touch_after(touch_after);
+            let handler = ExpressSessionHandler::new(store.clone(), config);
+            let service = Service::new(Router::new().hoop(handler).get(untouched));
+
+            let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+            let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+            store.calls.lock().clear();
+
+            // Saving the new session starts the window
+            for _ in 0..2 {
+                TestClient::get("http://127.0.0.1/")
+                    .add_header("cookie", &cookie, true)
+                    .send(&service)
+                    .await;
+            }
+            assert_eq!(*store.calls.lock(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scoped_config() {
+        let store = MemoryStore::new();
+        let session = ExpressSessionHandler::new(store.clone(), SessionConfig::new("secret"));
+        let admin_session = session.scoped(
+            SessionConfig::new("secret")
+                .with_cookie_name("admin.sid")
+                .with_cookie_path("/admin"),
+        );
+        let service = Service::new(
+            Router::new()
+                .push(
+                    Router::with_path("admin")
+                        .hoop(admin_session)
+                        .get(load_visit),
+                )
+                .push(Router::new().hoop(session).get(load_visit)),
+        );
+
+        let res = TestClient::get("http://127.0.0.1/admin")
+            .send(&service)
+            .await;
+        let admin = res.cookie("admin.sid").unwrap();
+        assert_eq!(admin.path(), Some("/admin"));
+        assert!(res.cookie("connect.sid").is_none());
+        let admin = admin.stripped().to_string();
+
+        // Each scope keeps its own session in the shared store
+        let mut res = TestClient::get("http://127.0.0.1/")
+            .add_header("cookie", &admin, true)
+            .send(&service)
+            .await;
+        assert!(res.cookie("connect.sid").is_some());
+        assert_eq!(res.take_string().await.unwrap(), "1");
+        assert_eq!(store.length().await.unwrap(), 2);
+
+        let mut res = TestClient::get("http://127.0.0.1/admin")
+            .add_header("cookie", &admin, true)
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "2");
+    }
+
+    /// Reports whether the session has an ID yet
+    #[handler]
+    async fn has_id(depot: &mut Depot) -> String {
+        depot.session().unwrap().has_id().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_lazy_creation() {
+        for lazy in [false, true] {
+            let store = MemoryStore::new();
+            let config = SessionConfig::new("secret").with_lazy(lazy);
+            let handler = ExpressSessionHandler::new(store.clone(), config);
+            let service = Service::new(
+                Router::new()
+                    .hoop(handler)
+                    .get(has_id)
+                    .push(Router::with_path("write").get(try_write)),
+            );
+
+            // No ID is generated or issued until the session is written
+            let mut res = TestClient::get("http://127.0.0.1/").send(&service).await;
+            assert_eq!(res.take_string().await.unwrap(), (!lazy).to_string());
+            assert_eq!(res.cookie("connect.sid").is_some(), !lazy);
+            assert_eq!(store.length().await.unwrap(), 0);
+
+            let res = TestClient::get("http://127.0.0.1/write")
+                .send(&service)
+                .await;
+            assert!(res.cookie("connect.sid").is_some());
+            assert_eq!(store.length().await.unwrap(), 1);
+        }
+    }
+
+    /// Keeps the session cookie for a week, like a "remember me" checkbox
+    #[handler]
+    async fn remember(depot: &mut Depot) {
+        let session = depot.session().unwrap();
+        session.set_cookie_max_age_secs(7 * 24 * 3600);
+        session.set_cookie_same_site(SameSite::Strict);
+    }
+
+    #[tokio::test]
+    async fn test_cookie_from_session() {
+        let config = SessionConfig::new("secret")
+            .with_max_age(3600)
+            .with_rolling(true);
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), config);
+        let service = Service::new(
+            Router::new()
+                .hoop(handler)
+                .get(set_query)
+                .push(Router::with_path("remember").get(remember)),
+        );
+        let week = CookieDuration::seconds(7 * 24 * 3600);
+
+        let res = TestClient::get("http://127.0.0.1/remember")
+            .send(&service)
+            .await;
+        let set = res.cookie("connect.sid").unwrap();
+        assert_eq!(set.max_age(), Some(week));
+        assert_eq!(set.same_site(), Some(CookieSameSite::Strict));
+        let cookie = set.stripped().to_string();
+
+        // Later cookies keep the session's attributes over the config's
+        let res = TestClient::get("http://127.0.0.1/?theme=dark")
+            .add_header("cookie", &cookie, true)
+            .send(&service)
+            .await;
+        let set = res.cookies().delta().next().unwrap();
+        assert!(set.max_age().unwrap() > CookieDuration::seconds(3600));
+        assert_eq!(set.same_site(), Some(CookieSameSite::Strict));
+    }
+
+    #[tokio::test]
+    async fn test_expires_attribute() {
+        for expires in [true, false] {
+            let config = SessionConfig::new("secret")
+                .with_max_age(3600)
+                .with_expires_attribute(expires);
+            let handler = ExpressSessionHandler::new(MemoryStore::new(), config);
+            let service = Service::new(Router::new().hoop(handler).get(try_write));
+
+            let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+            let set = res.headers().get("set-cookie").unwrap().to_str().unwrap();
+            assert!(set.contains("Max-Age=3600"));
+            assert_eq!(set.contains("Expires="), expires);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redundant_set_cookie_skipped() {
+        let config = SessionConfig::new("secret").with_max_age(3600);
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), config);
+        let service = Service::new(
+            Router::new()
+                .hoop(handler)
+                .get(set_query)
+                .push(Router::with_path("remember").get(remember)),
+        );
+
+        let res = TestClient::get("http://127.0.0.1/?theme=dark")
+            .send(&service)
+            .await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+
+        // The client already holds this cookie
+        let res = TestClient::get("http://127.0.0.1/?theme=light")
+            .add_header("cookie", &cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(res.cookies().delta().count(), 0);
+
+        // Changed attributes are re-issued under the same value
+        let res = TestClient::get("http://127.0.0.1/remember")
+            .add_header("cookie", &cookie, true)
+            .send(&service)
+            .await;
+        let set = res.cookies().delta().next().unwrap();
+        assert_eq!(set.stripped().to_string(), cookie);
+        assert_eq!(set.same_site(), Some(CookieSameSite::Strict));
+    }
+
+    #[tokio::test]
+    async fn test_legacy_cookie_names() {
+        for expire in [true, false] {
+            let store = MemoryStore::new();
+            let config = SessionConfig::new("secret")
+                .with_legacy_cookie_names(["sid"])
+                .with_expire_legacy_cookies(expire);
+            let handler = ExpressSessionHandler::new(store.clone(), config);
+            let service = Service::new(Router::new().hoop(handler).get(load_visit));
+
+            // Issued before the cookie was renamed
+            let sid = generate_session_id();
+            let mut data = SessionData::new(3600);
+            data.set("visits", 1);
+            store.set(&sid, &data, Some(3600)).await.unwrap();
+            let signed = cookie_signature::sign(&sid, "secret");
+            let cookie = format!("sid={}", urlencoding::encode(&signed));
+
+            let mut res = TestClient::get("http://127.0.0.1/")
+                .add_header("cookie", cookie, true)
+                .send(&service)
+                .await;
+            let reissued = res
+                .cookies()
+                .get("connect.sid")
+                .unwrap()
+                .value()
+                .to_string();
+            assert_eq!(url_decode(&reissued), signed);
+            let expired = res.cookies().delta().filter(|c| c.name() == "sid").count();
+            assert_eq!(expired == 1, expire);
+            assert_eq!(res.take_string().await.unwrap(), "2");
+        }
+    }
+
+    #[handler]
+    async fn clear(depot: &mut Depot) {
+        depot.session().unwrap().clear();
+    }
+
+    #[tokio::test]
+    async fn test_unset_destroy() {
+        for (unset, kept) in [(Unset::Keep, true), (Unset::Destroy, false)] {
+            let store = MemoryStore::new();
+            let config = SessionConfig::new("secret").with_unset(unset);
+            let handler = ExpressSessionHandler::new(store.clone(), config);
+            let service = Service::new(
+                Router::new()
+                    .hoop(handler)
+                    .get(try_write)
+                    .push(Router::with_path("clear").get(clear)),
+            );
+
+            let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+            let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+
+            let res = TestClient::get("http://127.0.0.1/clear")
+                .add_header("cookie", &cookie, true)
+                .send(&service)
+                .await;
+            assert_eq!(store.length().await.unwrap() == 1, kept);
+            let removed = res.cookies().delta().any(|c| c.value().is_empty());
+            assert_eq!(removed, !kept);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resign_rotated() {
+        for resign in [true, false] {
+            let store = MemoryStore::new();
+            let config = SessionConfig::with_secrets(["new", "old"]).with_resign_rotated(resign);
+            let handler = ExpressSessionHandler::new(store.clone(), config);
+            let service = Service::new(Router::new().hoop(handler).get(untouched));
+
+            // Signed before the secret was rotated
+            let sid = generate_session_id();
+            store
+                .set(&sid, &SessionData::new(3600), Some(3600))
+                .await
+                .unwrap();
+            let signed = cookie_signature::sign(&sid, "old");
+            let cookie = format!("connect.sid={}", urlencoding::encode(&signed));
+
+            let res = TestClient::get("http://127.0.0.1/")
+                .add_header("cookie", cookie, true)
+                .send(&service)
+                .await;
+            let resigned = res.cookies().delta().next().map(|c| url_decode(c.value()));
+            assert_eq!(resigned.is_some(), resign);
+            if let Some(resigned) = resigned {
+                assert_eq!(resigned, cookie_signature::sign(&sid, "new"));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_boxed_store() {
+        let memory = MemoryStore::new();
+        let store: Box<dyn SessionStore> = Box::new(memory.clone());
+        let handler = ExpressSessionHandler::new_boxed(store, SessionConfig::new("secret"));
+        let service = Service::new(Router::new().hoop(handler).get(load_visit));
+
+        let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+        assert_eq!(memory.length().await.unwrap(), 1);
+
+        let mut res = TestClient::get("http://127.0.0.1/")
+            .add_header("cookie", &cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "2");
+    }
+
+    #[tokio::test]
+    async fn test_error_handler_on_save() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let handler = ExpressSessionHandler::new(Unreachable, SessionConfig::new("secret"))
+            .with_error_handler({
+                let errors = Arc::clone(&errors);
+                move |e, res| {
+                    errors.lock().push(e.to_string());
+                    res.status_code(StatusCode::SERVICE_UNAVAILABLE);
+                }
+            });
+        let service = Service::new(Router::new().hoop(handler).get(try_write));
+
+        // Nothing is loaded for a new session; only its save fails
+        let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        assert_eq!(res.status_code, Some(StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(errors.lock().len(), 1);
+        assert!(errors.lock()[0].contains("connection refused"));
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_store_call_spans() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), SessionConfig::new("secret"));
+        let service = Service::new(Router::new().hoop(handler).get(try_write));
+        let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        let sid = res.cookie("connect.sid").unwrap().value().to_string();
+        let sid = url_decode(&sid)[2..].split('.').next().unwrap().to_string();
+
+        // Each store call closes a span naming the operation and the hashed ID
+        let logs = String::from_utf8(captured.0.lock().clone()).unwrap();
+        let span = logs
+            .lines()
+            .find(|line| line.contains("session_store"))
+            .unwrap();
+        assert!(span.contains("session.operation=\"set\""));
+        assert!(span.contains(&format!("session.id_hash={}", hash_session_id(&sid))));
+        assert!(!logs.contains(&sid));
+    }
+
+    #[tokio::test]
+    async fn test_keys_per_secret() {
+        let store = MemoryStore::new();
+        let config =
+            SessionConfig::with_secrets(["one", "two", "three"]).with_resign_rotated(false);
+        let handler = ExpressSessionHandler::new(store.clone(), config);
+        let service = Service::new(Router::new().hoop(handler).get(load_visit));
+
+        let sid = generate_session_id();
+        store
+            .set(&sid, &SessionData::new(3600), Some(3600))
+            .await
+            .unwrap();
+
+        // Repeated requests reuse the handler's keys without mixing up secrets
+        for (round, secret) in ["three", "one", "two", "one"].into_iter().enumerate() {
+            let signed = cookie_signature::sign(&sid, secret);
+            let cookie = format!("connect.sid={}", urlencoding::encode(&signed));
+            let mut res = TestClient::get("http://127.0.0.1/")
+                .add_header("cookie", cookie, true)
+                .send(&service)
+                .await;
+            assert_eq!(res.take_string().await.unwrap(), (round + 1).to_string());
+        }
+
+        // Other secrets still don't verify
+        let signed = cookie_signature::sign(&sid, "four");
+        let cookie = format!("connect.sid={}", urlencoding::encode(&signed));
+        let mut res = TestClient::get("http://127.0.0.1/")
+            .add_header("cookie", cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "1");
+    }
+
+    /// Counts visits through the loading depot accessor
+    #[handler]
+    async fn depot_visit(depot: &mut Depot) -> String {
+        let session = depot.load_session().await.unwrap();
+        session.incr("visits", 1).to_string()
+    }
+
+    /// Counts visits through the extractor
+    #[handler]
+    async fn extracted_visit(session: Session) -> String {
+        session.incr("visits", 1).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_lazy_load_counter() {
+        for path in ["depot", "extracted"] {
+            let config = SessionConfig::new("secret").with_lazy_load(true);
+            let handler = ExpressSessionHandler::new(MemoryStore::new(), config);
+            let service = Service::new(
+                Router::new()
+                    .hoop(handler)
+                    .push(Router::with_path("depot").get(depot_visit))
+                    .push(Router::with_path("extracted").get(extracted_visit)),
+            );
+
+            let mut cookie = String::new();
+            for visits in 1..=3 {
+                let mut res = TestClient::get(format!("http://127.0.0.1/{}", path))
+                    .add_header("cookie", &cookie, true)
+                    .send(&service)
+                    .await;
+                if let Some(set) = res.cookie("connect.sid") {
+                    cookie = set.stripped().to_string();
+                }
+                assert_eq!(res.take_string().await.unwrap(), visits.to_string());
+            }
+        }
+    }
+
+    /// Removes the key in the `key` query without loading the session first
+    #[handler]
+    async fn blind_remove(req: &mut Request, depot: &mut Depot) {
+        let session = depot.session().unwrap();
+        match req.query::<String>("key") {
+            Some(key) => {
+                session.remove(&key);
+            }
+            None => session.clear(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lazy_load_removals() {
+        let store = MemoryStore::new();
+        let config = SessionConfig::new("secret").with_lazy_load(true);
+        let handler = ExpressSessionHandler::new(store.clone(), config);
+        let service = Service::new(
+            Router::new()
+                .hoop(handler)
+                .get(set_query)
+                .push(Router::with_path("remove").get(blind_remove)),
+        );
+
+        let res = TestClient::get("http://127.0.0.1/?theme=dark&lang=en")
+            .send(&service)
+            .await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+        let sid = store.ids().await.unwrap().pop().unwrap();
+
+        TestClient::get("http://127.0.0.1/remove?key=theme")
+            .add_header("cookie", &cookie, true)
+            .send(&service)
+            .await;
+        let data = store.get(&sid).await.unwrap().unwrap();
+        assert!(!data.contains("theme"));
+        assert_eq!(data.get::<String>("lang").as_deref(), Some("en"));
+
+        TestClient::get("http://127.0.0.1/remove")
+            .add_header("cookie", &cookie, true)
+            .send(&service)
+            .await;
+        assert!(store.get(&sid).await.unwrap().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let config = SessionConfig::with_secrets(["a", "a"]);
+        assert_eq!(
+            ExpressSessionHandler::try_new(MemoryStore::new(), config.clone()).err(),
+            Some(ConfigError::DuplicateSecret)
+        );
+        // Only logged when constructed infallibly
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), config);
+        assert_eq!(handler.config.secrets.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() {
+        let store = MemoryStore::new();
+        let config = SessionConfig::new("secret").with_idle_timeout(60);
+        let handler = ExpressSessionHandler::new(store.clone(), config);
+        let service = Service::new(Router::new().hoop(handler).get(load_visit));
+
+        for (idle_secs, expected) in [(10, "6"), (120, "1")] {
+            let sid = generate_session_id();
+            let mut data = SessionData::new(3600);
+            data.set("visits", 5);
+            data.last_access = Some(chrono::Utc::now() - chrono::Duration::seconds(idle_secs));
+            store.set(&sid, &data, Some(3600)).await.unwrap();
+
+            let signed = cookie_signature::sign(&sid, "secret");
+            let cookie = format!("connect.sid={}", urlencoding::encode(&signed));
+            let mut res = TestClient::get("http://127.0.0.1/")
+                .add_header("cookie", cookie, true)
+                .send(&service)
+                .await;
+            assert_eq!(res.take_string().await.unwrap(), expected);
+
+            let issued = res.cookie("connect.sid").map(|c| c.value().to_string());
+            let stored = store.get(&sid).await.unwrap().unwrap();
+            if idle_secs < 60 {
+                // The active session survives and its access is recorded
+                assert!(stored.last_access.unwrap() > data.last_access.unwrap());
+                assert_eq!(stored.get::<i64>("visits"), Some(6));
+            } else {
+                // The idle session is replaced by a new one
+                let issued = issued.unwrap();
+                let new_sid =
+                    cookie_signature::unsign(&urlencoding::decode(&issued).unwrap(), "secret")
+                        .unwrap();
+                assert_ne!(new_sid, sid);
+                assert_eq!(stored.get::<i64>("visits"), Some(5));
+            }
+        }
+    }
+
+    /// Saves mid-handler, then reports what the store holds at that point
+    struct SaveMidway {
+        store: Recording,
+    }
+
+    #[async_trait]
+    impl Handler for SaveMidway {
+        async fn handle(
+            &self,
+            _req: &mut Request,
+            depot: &mut Depot,
+            res: &mut Response,
+            _ctrl: &mut FlowCtrl,
+        ) {
+            let session = depot.session().unwrap();
+            session.incr("visits", 1);
+            session.save().await.unwrap();
+
+            let stored = self.store.inner.get(session.id()).await.unwrap().unwrap();
+            let sets = self
+                .store
+                .calls
+                .lock()
+                .iter()
+                .filter(|c| **c == "set")
+                .count();
+            res.render(format!("{}/{}", stored.get::<i64>("visits").unwrap(), sets));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_explicit_save() {
+        let store = Recording::default();
+        let handler = ExpressSessionHandler::new(store.clone(), SessionConfig::new("secret"));
+        let service = Service::new(Router::new().hoop(handler).goal(SaveMidway {
+            store: store.clone(),
+        }));
+
+        // The save reaches the store before the response, and isn't repeated after it
+        let mut res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        assert_eq!(res.take_string().await.unwrap(), "1/1");
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+        let sets = |store: &Recording| store.calls.lock().iter().filter(|c| **c == "set").count();
+        assert_eq!(sets(&store), 1);
+
+        let mut res = TestClient::get("http://127.0.0.1/")
+            .add_header("cookie", &cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "2/2");
+        assert_eq!(sets(&store), 2);
+    }
+
+    /// Changes the session in memory and in the store behind it, then reloads
+    struct ReloadMidway {
+        store: MemoryStore,
+    }
+
+    #[async_trait]
+    impl Handler for ReloadMidway {
+        async fn handle(
+            &self,
+            _req: &mut Request,
+            depot: &mut Depot,
+            res: &mut Response,
+            _ctrl: &mut FlowCtrl,
+        ) {
+            let session = depot.session().unwrap();
+            session.set("theme", "light");
+            session.set("draft", true);
+            assert!(session.is_modified());
+
+            let mut stored = self.store.get(session.id()).await.unwrap().unwrap();
+            stored.set("theme", "dark");
+            self.store
+                .set(session.id(), &stored, Some(3600))
+                .await
+                .unwrap();
+
+            session.reload().await.unwrap();
+            res.render(format!(
+                "{}/{}/{}",
+                session.get::<String>("theme").unwrap(),
+                session.contains("draft"),
+                session.is_modified()
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let store = MemoryStore::new();
+        let handler = ExpressSessionHandler::new(store.clone(), SessionConfig::new("secret"));
+        let service = Service::new(Router::new().hoop(handler).goal(ReloadMidway {
+            store: store.clone(),
+        }));
+
+        let sid = generate_session_id();
+        let mut data = SessionData::new(3600);
+        data.set("theme", "system");
+        store.set(&sid, &data, Some(3600)).await.unwrap();
+
+        let signed = cookie_signature::sign(&sid, "secret");
+        let mut res = TestClient::get("http://127.0.0.1/")
+            .add_header(
+                "cookie",
+                format!("connect.sid={}", urlencoding::encode(&signed)),
+                true,
+            )
+            .send(&service)
+            .await;
+
+        // In-memory changes are replaced by the store's copy and no longer dirty
+        assert_eq!(res.take_string().await.unwrap(), "dark/false/false");
+        let stored = store.get(&sid).await.unwrap().unwrap();
+        assert_eq!(stored.get::<String>("theme").as_deref(), Some("dark"));
+        assert!(!stored.contains("draft"));
+    }
 }
@@ -0,0 +1,146 @@
+//! Session lifecycle hooks
+//!
+//! Implement [`SessionHooks`] to observe sessions as the handler creates,
+//! loads, saves and destroys them, e.g. to mirror them into an audit
+//! system or warm a cache. [`SessionHooks::on_load`] can also reject a
+//! stored session to enforce a custom policy.
+//!
+//! ```rust,ignore
+//! struct Audit;
+//!
+//! #[async_trait]
+//! impl SessionHooks for Audit {
+//!     async fn on_create(&self, sid: &str, data: &SessionData) {
+//!         tracing::info!(user = ?data.get::<u64>("userId"), "session created");
+//!     }
+//!
+//!     async fn on_load(&self, _sid: &str, data: &SessionData) -> bool {
+//!         // Sessions from before the password reset are no longer valid
+//!         data.created_at > Some(password_reset_at())
+//!     }
+//! }
+//!
+//! let session_handler = ExpressSessionHandler::new(store, config).with_hooks(Audit);
+//! ```
+
+use async_trait::async_trait;
+
+use crate::session::SessionData;
+
+/// Callbacks run by the handler around store operations
+///
+/// All methods default to doing nothing. Hooks run inline with the
+/// request, so slow work should be spawned off.
+#[async_trait]
+pub trait SessionHooks: Send + Sync + 'static {
+    /// A new (or regenerated) session was saved for the first time
+    async fn on_create(&self, sid: &str, data: &SessionData) {
+        let _ = (sid, data);
+    }
+
+    /// A valid session was loaded from the store
+    ///
+    /// Returning false discards it: the request continues with a new
+    /// session, as if the stored one had expired.
+    async fn on_load(&self, sid: &str, data: &SessionData) -> bool {
+        let _ = (sid, data);
+        true
+    }
+
+    /// A session was written to the store
+    async fn on_save(&self, sid: &str, data: &SessionData) {
+        let _ = (sid, data);
+    }
+
+    /// A session was destroyed, explicitly or when regenerated
+    ///
+    /// `data` is the session as the request left it.
+    async fn on_destroy(&self, sid: &str, data: &SessionData) {
+        let _ = (sid, data);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_core::{handler, Depot, Router, Service};
+
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::depot_ext::SessionDepotExt;
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::MemoryStore;
+
+    #[derive(Clone, Default)]
+    struct Recorder {
+        events: Arc<Mutex<Vec<String>>>,
+        reject: bool,
+    }
+
+    #[async_trait]
+    impl SessionHooks for Recorder {
+        async fn on_create(&self, _sid: &str, _data: &SessionData) {
+            self.events.lock().push("create".to_string());
+        }
+
+        async fn on_load(&self, _sid: &str, _data: &SessionData) -> bool {
+            self.events.lock().push("load".to_string());
+            !self.reject
+        }
+
+        async fn on_save(&self, _sid: &str, data: &SessionData) {
+            let count = data.get::<i32>("count").unwrap_or(0);
+            self.events.lock().push(format!("save {}", count));
+        }
+    }
+
+    #[handler]
+    async fn increment(depot: &mut Depot) -> String {
+        let session = depot.session_mut().unwrap();
+        let count = session.get::<i32>("count").unwrap_or(0) + 1;
+        session.set("count", count);
+        count.to_string()
+    }
+
+    async fn two_requests(hooks: Recorder) -> String {
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), SessionConfig::new("secret"))
+            .with_hooks(hooks);
+        let service = Service::new(Router::new().hoop(handler).get(increment));
+
+        let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        let cookie = res.cookie("connect.sid").unwrap().to_string();
+        TestClient::get("http://127.0.0.1/")
+            .add_header("cookie", cookie, true)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_hooks_follow_the_session() {
+        let hooks = Recorder::default();
+        assert_eq!(two_requests(hooks.clone()).await, "2");
+        assert_eq!(
+            *hooks.events.lock(),
+            vec!["save 1", "create", "load", "save 2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_on_load_can_reject() {
+        let hooks = Recorder {
+            reject: true,
+            ..Default::default()
+        };
+        assert_eq!(two_requests(hooks.clone()).await, "1");
+        assert_eq!(
+            *hooks.events.lock(),
+            vec!["save 1", "create", "load", "save 1", "create"]
+        );
+    }
+}
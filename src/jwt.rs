@@ -0,0 +1,215 @@
+//! JWT session cookies
+//!
+//! With [`JwtCodec`], the session cookie is a compact HS256-signed JWT
+//! carrying the session ID and selected session values as claims, so sibling
+//! services sharing the secret can authenticate requests without a store
+//! round-trip. The full, mutable session still lives in the store.
+//!
+//! ```rust,ignore
+//! let session_handler = ExpressSessionHandler::new(store, config)
+//!     .with_codec(JwtCodec::new().with_claims(["passport", "role"]).with_issuer("auth.example.com"));
+//!
+//! // In a sibling service
+//! let keys = SigningKey::from_secrets(secrets);
+//! let claims = JwtCodec::new().with_issuer("auth.example.com").verify(token, &keys)?;
+//! let user = &claims["passport"]["user"];
+//! ```
+//!
+//! Claims: `sid`, `iat`, `exp` (from the session expiry, if any), `iss` (if
+//! configured) and each selected session key that is set. The cookie is
+//! re-issued whenever a selected key changes. Claims are signed, not
+//! encrypted: don't select secrets.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::Utc;
+use hmac::Mac;
+use serde_json::{json, Map, Value};
+
+use crate::codec::{CookieCodec, SignedCookie};
+use crate::secret::SigningKey;
+use crate::session::SessionData;
+
+/// Compact JWT header for HS256: `{"alg":"HS256","typ":"JWT"}`
+const HEADER: &str = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9";
+
+/// Codec issuing HS256 JWTs holding the session ID and selected claims
+#[derive(Clone, Debug, Default)]
+pub struct JwtCodec {
+    claims: Vec<String>,
+    issuer: Option<String>,
+}
+
+impl JwtCodec {
+    /// Create a codec embedding only the session ID
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Embed these session keys as claims
+    pub fn with_claims<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.claims = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the `iss` claim, which verification then requires
+    pub fn with_issuer<S: Into<String>>(mut self, issuer: S) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    /// Verify a token against any of the keys, returning its claims
+    ///
+    /// Fails for bad signatures, expired tokens and a mismatched issuer.
+    pub fn verify(&self, token: &str, keys: &[SigningKey]) -> Option<Map<String, Value>> {
+        let (signed, signature) = token.rsplit_once('.')?;
+        let (header, payload) = signed.split_once('.')?;
+        if header != HEADER {
+            return None;
+        }
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        let valid = keys.iter().any(|key| {
+            let mut mac = key.hmac_sha256();
+            mac.update(signed.as_bytes());
+            mac.verify_slice(&signature).is_ok()
+        });
+        if !valid {
+            return None;
+        }
+
+        let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+        let Value::Object(claims) = serde_json::from_slice(&payload).ok()? else {
+            return None;
+        };
+        let expired = claims
+            .get("exp")
+            .and_then(Value::as_i64)
+            .is_some_and(|exp| exp <= Utc::now().timestamp());
+        if expired || claims.get("iss").and_then(Value::as_str) != self.issuer.as_deref() {
+            return None;
+        }
+        Some(claims)
+    }
+
+    /// Sign a set of claims into a compact JWT
+    fn encode(&self, mut claims: Map<String, Value>, key: &SigningKey) -> String {
+        claims.insert("iat".to_string(), json!(Utc::now().timestamp()));
+        if let Some(issuer) = &self.issuer {
+            claims.insert("iss".to_string(), json!(issuer));
+        }
+        let payload = URL_SAFE_NO_PAD.encode(Value::Object(claims).to_string());
+        let signed = format!("{}.{}", HEADER, payload);
+
+        let mut mac = key.hmac_sha256();
+        mac.update(signed.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        format!("{}.{}", signed, signature)
+    }
+}
+
+impl CookieCodec for JwtCodec {
+    fn sign(&self, _name: &str, value: &str, key: &SigningKey) -> SignedCookie {
+        let mut claims = Map::new();
+        claims.insert("sid".to_string(), json!(value));
+        SignedCookie::new(self.encode(claims, key))
+    }
+
+    fn sign_session(
+        &self,
+        _name: &str,
+        value: &str,
+        key: &SigningKey,
+        session: &SessionData,
+    ) -> SignedCookie {
+        let mut claims = Map::new();
+        claims.insert("sid".to_string(), json!(value));
+        if let Some(expires) = session.cookie.expires {
+            claims.insert("exp".to_string(), json!(expires.timestamp()));
+        }
+        for key in &self.claims {
+            if let Some(value) = session.data.get(key) {
+                claims.insert(key.clone(), value.clone());
+            }
+        }
+        SignedCookie::new(self.encode(claims, key))
+    }
+
+    fn unsign(&self, _name: &str, cookie: &SignedCookie, keys: &[SigningKey]) -> Option<String> {
+        let claims = self.verify(&cookie.value, keys)?;
+        claims.get("sid")?.as_str().map(String::from)
+    }
+
+    fn reissue_on_change(&self, changed: &[String]) -> bool {
+        changed.iter().any(|key| self.claims.contains(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::test::TestClient;
+    use salvo_core::{handler, Depot, Router, Service};
+
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::depot_ext::SessionDepotExt;
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::{MemoryStore, SessionStore};
+
+    #[test]
+    fn test_header() {
+        assert_eq!(
+            URL_SAFE_NO_PAD.decode(HEADER).unwrap(),
+            br#"{"alg":"HS256","typ":"JWT"}"#
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let codec = JwtCodec::new().with_issuer("auth");
+        let keys = SigningKey::from_secrets(["secret"]);
+        let token = codec.sign("connect.sid", "abc", &keys[0]).value;
+        let claims = codec.verify(&token, &keys).unwrap();
+        assert_eq!(claims["sid"], "abc");
+        assert_eq!(claims["iss"], "auth");
+
+        assert!(codec
+            .verify(&token, &SigningKey::from_secrets(["other"]))
+            .is_none());
+        assert!(JwtCodec::new().verify(&token, &keys).is_none());
+        let mut expired = Map::new();
+        expired.insert("exp".to_string(), json!(Utc::now().timestamp() - 1));
+        assert!(codec
+            .verify(&codec.encode(expired, &keys[0]), &keys)
+            .is_none());
+    }
+
+    #[handler]
+    async fn login(depot: &mut Depot) {
+        depot.session_mut().unwrap().set("role", "admin");
+    }
+
+    #[tokio::test]
+    async fn test_claims_follow_the_session() {
+        let codec = JwtCodec::new().with_claims(["role"]);
+        let store = MemoryStore::new();
+        let config = SessionConfig::new("secret").with_max_age(3600);
+        let handler = ExpressSessionHandler::new(store.clone(), config).with_codec(codec.clone());
+        let service = Service::new(Router::new().hoop(handler).get(login));
+
+        let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        let token = res.cookie("connect.sid").unwrap().value().to_string();
+        let keys = SigningKey::from_secrets(["secret"]);
+        let claims = codec.verify(&token, &keys).unwrap();
+        assert_eq!(claims["role"], "admin");
+        assert!(claims["exp"].is_i64());
+
+        let sid = codec
+            .unsign("connect.sid", &SignedCookie::new(token), &keys)
+            .unwrap();
+        assert!(store.get(&sid).await.unwrap().is_some());
+    }
+}
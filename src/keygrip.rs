@@ -0,0 +1,113 @@
+//! Keygrip compatible cookie signature (Node.js cookie-session)
+//!
+//! This module implements the signing scheme used by the Node.js `cookies` and
+//! `keygrip` libraries, which is what `cookie-session` relies on. Unlike
+//! express-session, the cookie value is left untouched and the signature is
+//! sent in a separate cookie named `<name>.sig`:
+//!
+//! - `<name>=<value>`
+//! - `<name>.sig=` + base64url(hmac_sha1(`<name>=<value>`, key)) (without padding)
+//!
+//! Keys are [`SigningKey`]s, keyed once per secret.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::Mac;
+
+use crate::secret::SigningKey;
+
+/// Suffix appended to the cookie name for the signature cookie
+pub const SIGNATURE_SUFFIX: &str = ".sig";
+
+/// Sign data with a single key, returning the base64url digest.
+///
+/// This matches Node.js keygrip:
+/// ```javascript
+/// function sign(data, key) {
+///   return crypto
+///     .createHmac('sha1', key)
+///     .update(data).digest('base64')
+///     .replace(/\/|\+|=/g, function(x) {
+///       return ({ "/": "_", "+": "-", "=": "" })[x]
+///     })
+/// }
+/// ```
+pub fn sign(data: &str, key: &SigningKey) -> String {
+    let mut mac = key.hmac_sha1();
+    mac.update(data.as_bytes());
+    URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+}
+
+/// Find the index of the key that produced `digest` for `data`.
+///
+/// Returns None if no key matches. An index greater than zero means the
+/// signature was produced by a rotated key and should be re-issued.
+pub fn index(data: &str, digest: &str, keys: &[SigningKey]) -> Option<usize> {
+    keys.iter()
+        .position(|key| constant_time_compare(&sign(data, key), digest))
+}
+
+/// Verify that `digest` was produced for `data` by any of the keys
+pub fn verify(data: &str, digest: &str, keys: &[SigningKey]) -> bool {
+    index(data, digest, keys).is_some()
+}
+
+/// Name of the signature cookie for the given cookie name
+pub fn signature_cookie_name(name: &str) -> String {
+    format!("{}{}", name, SIGNATURE_SUFFIX)
+}
+
+/// Sign a cookie the way the Node.js `cookies` library does (`name=value`)
+pub fn sign_cookie(name: &str, value: &str, key: &SigningKey) -> String {
+    sign(&format!("{}={}", name, value), key)
+}
+
+/// Verify a cookie value against its `.sig` cookie using any of the keys
+pub fn verify_cookie(name: &str, value: &str, digest: &str, keys: &[SigningKey]) -> bool {
+    verify(&format!("{}={}", name, value), digest, keys)
+}
+
+/// Constant-time string comparison to prevent timing attacks
+fn constant_time_compare(a: &str, b: &str) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut result = 0u8;
+    for (x, y) in a.bytes().zip(b.bytes()) {
+        result |= x ^ y;
+    }
+    result == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compatible_with_keygrip() {
+        // Verified with Node.js:
+        // const Keygrip = require('keygrip');
+        // new Keygrip(['secret']).sign('session=my session id');
+        let digest = sign_cookie("session", "my session id", &SigningKey::new("secret"));
+        assert_eq!(digest, "RPOjJ8EFa1Rv9Sv-B1dO-8yRaOM");
+    }
+
+    #[test]
+    fn test_verify_cookie() {
+        let keys = SigningKey::from_secrets(["secret"]);
+        let digest = sign_cookie("session", "abc", &keys[0]);
+
+        assert!(verify_cookie("session", "abc", &digest, &keys));
+        assert!(!verify_cookie("session", "abd", &digest, &keys));
+        assert!(!verify_cookie("other", "abc", &digest, &keys));
+    }
+
+    #[test]
+    fn test_key_rotation_index() {
+        let keys = SigningKey::from_secrets(["new", "old"]);
+        let digest = sign("data", &keys[1]);
+
+        assert_eq!(index("data", &digest, &keys), Some(1));
+        assert_eq!(index("data", &digest, &keys[..1]), None);
+    }
+}
@@ -0,0 +1,198 @@
+//! Dual-read migration from a legacy session format
+//!
+//! During a switch from another session system, [`LegacyFormat`] lets the
+//! handler accept the old cookies and records while writing only the
+//! express-session format. A request without a usable express-session
+//! cookie but with a valid legacy one gets a new session holding the legacy
+//! session's values, so live traffic moves over without logging anyone out:
+//!
+//! ```rust,ignore
+//! let legacy = LegacyFormat::new("sid", old_store)
+//!     .with_codec(KeygripCodec)
+//!     .with_secrets(["old-secret"]);
+//! let session_handler = ExpressSessionHandler::new(store, config).with_legacy_format(legacy);
+//! ```
+//!
+//! Legacy records are read through a [`SessionStore`]; records in other
+//! formats can be adapted with [`RecordStoreAdapter`](crate::store::RecordStoreAdapter),
+//! [`AsyncSessionStoreAdapter`](crate::store::AsyncSessionStoreAdapter) or a
+//! store of your own. They are left in place, so the old system keeps
+//! working during the rollout.
+
+use std::sync::Arc;
+
+use salvo_core::http::cookie::{self, time::Duration as CookieDuration};
+use salvo_core::{Request, Response};
+use serde_json::Value;
+
+use crate::codec::{CookieCodec, ExpressCodec, SignedCookie};
+use crate::error::SessionError;
+use crate::secret::SigningKey;
+use crate::store::SessionStore;
+
+/// Cookie and store of a legacy session format
+pub struct LegacyFormat {
+    cookie_name: String,
+    cookie_path: String,
+    codec: Arc<dyn CookieCodec>,
+    keys: Vec<SigningKey>,
+    store: Arc<dyn SessionStore>,
+}
+
+impl LegacyFormat {
+    /// Accept cookies named `cookie_name` referencing sessions in `store`
+    ///
+    /// Cookies are verified with [`ExpressCodec`] and the handler's secrets
+    /// unless configured otherwise.
+    pub fn new<N: Into<String>, S: SessionStore>(cookie_name: N, store: S) -> Self {
+        Self {
+            cookie_name: cookie_name.into(),
+            cookie_path: "/".to_string(),
+            codec: Arc::new(ExpressCodec),
+            keys: Vec::new(),
+            store: Arc::new(store),
+        }
+    }
+
+    /// Set the codec of legacy cookies (default: [`ExpressCodec`])
+    pub fn with_codec<C: CookieCodec>(mut self, codec: C) -> Self {
+        self.codec = Arc::new(codec);
+        self
+    }
+
+    /// Set the secrets legacy cookies are signed with (default: the handler's)
+    pub fn with_secrets<I, S>(mut self, secrets: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.keys = secrets
+            .into_iter()
+            .map(|secret| SigningKey::new(secret.into()))
+            .collect();
+        self
+    }
+
+    /// Set the legacy cookie path, used to expire it (default: "/")
+    pub fn with_cookie_path<S: Into<String>>(mut self, path: S) -> Self {
+        self.cookie_path = path.into();
+        self
+    }
+
+    /// Name of the legacy cookie
+    pub(crate) fn cookie_name(&self) -> &str {
+        &self.cookie_name
+    }
+
+    /// Load the values of the request's legacy session
+    ///
+    /// Returns None for a missing, tampered or expired session. `keys` are
+    /// used unless the format has its own secrets.
+    pub(crate) async fn migrate(
+        &self,
+        req: &Request,
+        keys: &[SigningKey],
+    ) -> Result<Option<Vec<(String, Value)>>, SessionError> {
+        let Some(value) = req.cookie(&self.cookie_name) else {
+            return Ok(None);
+        };
+        let signature = self
+            .codec
+            .signature_cookie_name(&self.cookie_name)
+            .and_then(|name| req.cookie(&name).map(|c| c.value().to_string()));
+        let cookie = SignedCookie {
+            value: value.value().to_string(),
+            signature,
+        };
+
+        let keys = if self.keys.is_empty() {
+            keys
+        } else {
+            &self.keys
+        };
+        let Some(sid) = self.codec.unsign(&self.cookie_name, &cookie, keys) else {
+            return Ok(None);
+        };
+        let Some(data) = self.store.get(&sid).await? else {
+            return Ok(None);
+        };
+        if data.cookie.is_expired() {
+            return Ok(None);
+        }
+        Ok(Some(data.data.into_iter().collect()))
+    }
+
+    /// Whether the request carries a legacy cookie
+    pub(crate) fn has_cookie(&self, req: &Request) -> bool {
+        req.cookie(&self.cookie_name).is_some()
+    }
+
+    /// Tell the client to drop the legacy cookie
+    pub(crate) fn expire_cookie(&self, res: &mut Response) {
+        let names = std::iter::once(self.cookie_name.clone())
+            .chain(self.codec.signature_cookie_name(&self.cookie_name));
+        for name in names {
+            let cookie = cookie::Cookie::build(name)
+                .path(self.cookie_path.clone())
+                .max_age(CookieDuration::ZERO)
+                .build();
+            res.add_cookie(cookie);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_core::{handler, Depot, Router, Service};
+
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::cookie_signature;
+    use crate::depot_ext::SessionDepotExt;
+    use crate::handler::ExpressSessionHandler;
+    use crate::session::SessionData;
+    use crate::store::MemoryStore;
+
+    #[handler]
+    async fn whoami(depot: &mut Depot) -> String {
+        let session = depot.session().unwrap();
+        session.get::<String>("user").unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn test_legacy_session_is_migrated() {
+        let old_store = MemoryStore::with_prefix("old:");
+        let mut data = SessionData::new(3600);
+        data.set("user", "alice");
+        old_store.set("old-sid", &data, Some(3600)).await.unwrap();
+
+        let store = MemoryStore::new();
+        let handler = ExpressSessionHandler::new(store.clone(), SessionConfig::new("secret"))
+            .with_legacy_format(LegacyFormat::new("sid", old_store.clone()).with_secrets(["old"]));
+        let service = Service::new(Router::new().hoop(handler).get(whoami));
+
+        let cookie = format!("sid={}", cookie_signature::sign("old-sid", "old"));
+        let mut res = TestClient::get("http://127.0.0.1/")
+            .add_header("cookie", cookie, true)
+            .send(&service)
+            .await;
+        assert!(res.cookie("connect.sid").is_some());
+        assert_eq!(
+            res.cookie("sid").unwrap().max_age(),
+            Some(CookieDuration::ZERO)
+        );
+        assert_eq!(res.take_string().await.unwrap(), "alice");
+        assert_eq!(store.length().await.unwrap(), 1);
+        // The legacy record stays for the old system
+        assert!(old_store.get("old-sid").await.unwrap().is_some());
+
+        // Signed with a secret the legacy format doesn't accept
+        let cookie = format!("sid={}", cookie_signature::sign("old-sid", "secret"));
+        let mut res = TestClient::get("http://127.0.0.1/")
+            .add_header("cookie", cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "");
+    }
+}
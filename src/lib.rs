@@ -9,8 +9,15 @@
 //! ## Features
 //!
 //! - **Express-session compatible cookie format**: Uses the same `s:` prefix and HMAC-SHA256 signature
+//! - **Cookie-session compatible signing**: Optional Keygrip (`<name>.sig`) cookie format
 //! - **Connect-redis compatible storage**: Sessions stored in Redis with the same format as connect-redis
+//! - **Pluggable cookie codecs**: Implement `CookieCodec` for custom signing formats
 //! - **Pluggable storage backends**: Supports Redis, Memory, or custom stores
+//! - **Hot-reloadable secrets**: Rotate signing secrets live via a `SecretProvider`
+//! - **CSRF protection**: csurf compatible tokens stored in the session
+//! - **Flash messages**: connect-flash compatible one-time messages
+//! - **Authentication helpers**: Passport compatible login state and a `RequireLogin` guard
+//! - **Request locking**: Optionally serialize concurrent requests per session
 //! - **Full session lifecycle**: Create, read, update, delete, touch, and regenerate sessions
 //!
 //! ## Quick Start
@@ -46,21 +53,100 @@
 //! }
 //! ```
 
+#[cfg(feature = "admin")]
+pub mod admin;
+pub mod analytics;
+pub mod audit;
+pub mod auth;
+pub mod binding;
+pub mod brute_force;
+pub mod catcher;
+pub mod codec;
+pub mod compat;
 pub mod config;
+pub mod cookie_session;
 pub mod cookie_signature;
 pub mod error;
+pub mod events;
+pub mod extract;
+pub mod fingerprint;
+pub mod flash;
+pub mod handle;
 pub mod handler;
+pub mod hooks;
+pub mod jwt;
+pub mod keygrip;
+pub mod legacy;
+pub mod lock;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod namespace;
+#[cfg(feature = "oapi")]
+pub mod oapi;
+pub mod reaper;
+pub mod remember;
+pub mod require;
+pub mod secret;
 pub mod session;
+pub mod session_csrf;
 pub mod store;
+pub mod typed;
+pub mod validator;
 
-pub use config::SessionConfig;
-pub use error::SessionError;
-pub use handler::ExpressSessionHandler;
+pub use analytics::{AgeBucket, SessionAnalytics, SessionStats};
+pub use audit::{AuditRecord, AuditSink, FileAuditSink, StoreAuditSink, TracingAuditSink};
+pub use auth::RequireLogin;
+pub use binding::{BindingAction, IpBinding};
+pub use brute_force::{FailureTracker, MemoryFailureTracker, SignatureGuard};
+pub use catcher::RestoreSessionCookies;
+pub use codec::{CookieCodec, ExpressCodec, KeygripCodec, SignedCookie};
+pub use compat::{AsyncSessionCompat, AsyncSessionSource};
+pub use config::{
+    ConflictStrategy, LogRedaction, OversizePolicy, RenewalThreshold, SessionConfig,
+    SessionTransport, StoreFailurePolicy, Unset,
+};
+pub use cookie_session::{CookieSessionHandler, SessionStorage};
+
+#[cfg(feature = "encryption")]
+pub use codec::EncryptedCodec;
+pub use error::{ConfigError, ErrorKind, SessionError, SessionMissing};
+pub use events::SessionEvent;
+pub use fingerprint::{Fingerprint, FingerprintAction};
+pub use flash::FlashMessages;
+pub use handle::{DetachedSession, SessionHandle};
+pub use handler::{
+    ErrorHandler, ExpressSessionHandler, OversizeHandler, SkipPredicate, StatusPredicate,
+};
+pub use hooks::SessionHooks;
+pub use jwt::JwtCodec;
+pub use legacy::LegacyFormat;
+pub use lock::{MemoryLocker, SessionLocker};
+#[cfg(feature = "metrics")]
+pub use metrics::SessionMetrics;
+pub use namespace::SessionNamespace;
+#[cfg(feature = "oapi")]
+pub use oapi::{SessionOpenApiExt, SessionRouterExt};
+pub use reaper::SessionReaper;
+pub use remember::RememberMe;
+pub use require::RequireSession;
+pub use secret::{FileSecretProvider, SecretProvider, SigningKey};
 pub use session::{Session, SessionData};
-pub use store::{MemoryStore, SessionStore};
+pub use session_csrf::CsrfProtection;
+pub use store::{
+    MemoryStore, RecordStore, RecordStoreAdapter, SessionRecord, SessionStore, SignedStore,
+    StoreStatus,
+};
+pub use typed::TypedSession;
+pub use validator::{SessionValidator, Validation};
 
 #[cfg(feature = "redis-store")]
-pub use store::RedisStore;
+pub use compat::RedisAsyncSessionSource;
+#[cfg(feature = "redis-store")]
+pub use lock::RedisLocker;
+#[cfg(feature = "async-session-store")]
+pub use store::AsyncSessionStoreAdapter;
+#[cfg(feature = "redis-store")]
+pub use store::{RedisConnectOptions, RedisStore};
 
 /// Extension trait for Depot to easily access session
 pub mod depot_ext;
@@ -0,0 +1,303 @@
+//! Per-session request locking
+//!
+//! express-session lets concurrent requests for the same session run in
+//! parallel, so their writes can overwrite each other. A [`SessionLocker`]
+//! makes the handler serialize them instead, like PHP's session locking:
+//! each request waits for the session's lock before loading it and releases
+//! it after saving.
+//!
+//! [`MemoryLocker`] locks within one process; [`RedisLocker`] (with the
+//! `redis-store` feature) locks across instances with `SET NX PX`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use uuid::Uuid;
+
+use crate::error::SessionError;
+
+/// Default lifetime of a lock, after which it is released even if its holder never did
+const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+/// Default time a request waits for a lock
+const DEFAULT_WAIT: Duration = Duration::from_secs(10);
+
+/// Delay between attempts to take a held lock
+const RETRY_INTERVAL: Duration = Duration::from_millis(25);
+
+/// Exclusive per-session locks
+#[async_trait]
+pub trait SessionLocker: Send + Sync + 'static {
+    /// Wait for the lock of the session, returning a token to release it with
+    ///
+    /// Fails with [`SessionError::Locked`] if the lock isn't acquired in time.
+    async fn lock(&self, sid: &str) -> Result<String, SessionError>;
+
+    /// Release a lock acquired with [`Self::lock`]
+    ///
+    /// Does nothing if the lock expired and was taken by another request.
+    async fn unlock(&self, sid: &str, token: &str) -> Result<(), SessionError>;
+}
+
+/// Lock held by a request
+///
+/// Requests cancelled mid-handler, e.g. by a client disconnecting, never
+/// reach [`Self::release`]; dropping the guard then releases the lock on a
+/// spawned task rather than leaving it held until it expires.
+pub(crate) struct LockGuard {
+    locker: Arc<dyn SessionLocker>,
+    sid: String,
+    token: Option<String>,
+}
+
+impl LockGuard {
+    /// Wait for the lock of the session
+    pub(crate) async fn acquire(
+        locker: &Arc<dyn SessionLocker>,
+        sid: String,
+    ) -> Result<Self, SessionError> {
+        let token = locker.lock(&sid).await?;
+        Ok(Self {
+            locker: Arc::clone(locker),
+            sid,
+            token: Some(token),
+        })
+    }
+
+    /// Release the lock
+    pub(crate) async fn release(mut self) -> Result<(), SessionError> {
+        match self.token.take() {
+            Some(token) => self.locker.unlock(&self.sid, &token).await,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        let Some(token) = self.token.take() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let locker = Arc::clone(&self.locker);
+        let sid = std::mem::take(&mut self.sid);
+        runtime.spawn(async move {
+            if let Err(e) = locker.unlock(&sid, &token).await {
+                tracing::error!("Failed to unlock session: {}", e);
+            }
+        });
+    }
+}
+
+/// In-process session locks
+///
+/// Only serializes requests handled by the same process; use
+/// [`RedisLocker`] when running several instances.
+pub struct MemoryLocker {
+    held: Mutex<HashMap<String, (String, Instant)>>,
+    ttl: Duration,
+    wait: Duration,
+}
+
+impl MemoryLocker {
+    /// Create a locker with a 30 second lock lifetime and a 10 second wait
+    pub fn new() -> Self {
+        Self {
+            held: Mutex::new(HashMap::new()),
+            ttl: DEFAULT_TTL,
+            wait: DEFAULT_WAIT,
+        }
+    }
+
+    /// Set how long a lock is held at most (default: 30 seconds)
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set how long a request waits for a lock (default: 10 seconds)
+    pub fn with_wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    /// Take the lock if it is free or expired
+    fn try_lock(&self, sid: &str) -> Option<String> {
+        let mut held = self.held.lock();
+        let now = Instant::now();
+        if held.get(sid).is_some_and(|(_, expires)| *expires > now) {
+            return None;
+        }
+
+        let token = Uuid::new_v4().to_string();
+        held.insert(sid.to_string(), (token.clone(), now + self.ttl));
+        Some(token)
+    }
+}
+
+impl Default for MemoryLocker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl SessionLocker for MemoryLocker {
+    async fn lock(&self, sid: &str) -> Result<String, SessionError> {
+        let deadline = Instant::now() + self.wait;
+        loop {
+            if let Some(token) = self.try_lock(sid) {
+                return Ok(token);
+            }
+            if Instant::now() >= deadline {
+                return Err(SessionError::Locked);
+            }
+            tokio::time::sleep(RETRY_INTERVAL).await;
+        }
+    }
+
+    async fn unlock(&self, sid: &str, token: &str) -> Result<(), SessionError> {
+        let mut held = self.held.lock();
+        if held.get(sid).is_some_and(|(owner, _)| owner == token) {
+            held.remove(sid);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "redis-store")]
+pub use redis_locker::RedisLocker;
+
+#[cfg(feature = "redis-store")]
+mod redis_locker {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use async_trait::async_trait;
+    use redis::aio::ConnectionManager;
+    use uuid::Uuid;
+
+    use super::{SessionLocker, DEFAULT_TTL, DEFAULT_WAIT, RETRY_INTERVAL};
+    use crate::error::SessionError;
+
+    /// Delete the lock only if it still holds our token
+    const UNLOCK_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+    /// Redis session locks, shared by every instance using the same Redis
+    ///
+    /// Locks are `SET <prefix><sid> <token> NX PX <ttl>` keys, released with
+    /// a script that only deletes the key while it holds the caller's token.
+    pub struct RedisLocker {
+        conn: Arc<ConnectionManager>,
+        prefix: String,
+        ttl: Duration,
+        wait: Duration,
+    }
+
+    impl RedisLocker {
+        /// Create a locker using the connection (key prefix: "sess-lock:")
+        pub fn from_connection_manager(conn: ConnectionManager) -> Self {
+            Self {
+                conn: Arc::new(conn),
+                prefix: "sess-lock:".to_string(),
+                ttl: DEFAULT_TTL,
+                wait: DEFAULT_WAIT,
+            }
+        }
+
+        /// Set the key prefix (default: "sess-lock:")
+        ///
+        /// Keep it distinct from the store's prefix so lock keys aren't
+        /// mistaken for sessions.
+        pub fn with_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+            self.prefix = prefix.into();
+            self
+        }
+
+        /// Set how long a lock is held at most (default: 30 seconds)
+        pub fn with_ttl(mut self, ttl: Duration) -> Self {
+            self.ttl = ttl;
+            self
+        }
+
+        /// Set how long a request waits for a lock (default: 10 seconds)
+        pub fn with_wait(mut self, wait: Duration) -> Self {
+            self.wait = wait;
+            self
+        }
+    }
+
+    #[async_trait]
+    impl SessionLocker for RedisLocker {
+        async fn lock(&self, sid: &str) -> Result<String, SessionError> {
+            let key = format!("{}{}", self.prefix, sid);
+            let token = Uuid::new_v4().to_string();
+            let mut conn = (*self.conn).clone();
+
+            let deadline = Instant::now() + self.wait;
+            loop {
+                let acquired: Option<String> = redis::cmd("SET")
+                    .arg(&key)
+                    .arg(&token)
+                    .arg("NX")
+                    .arg("PX")
+                    .arg(self.ttl.as_millis() as u64)
+                    .query_async(&mut conn)
+                    .await?;
+                if acquired.is_some() {
+                    return Ok(token);
+                }
+                if Instant::now() >= deadline {
+                    return Err(SessionError::Locked);
+                }
+                tokio::time::sleep(RETRY_INTERVAL).await;
+            }
+        }
+
+        async fn unlock(&self, sid: &str, token: &str) -> Result<(), SessionError> {
+            let key = format!("{}{}", self.prefix, sid);
+            let mut conn = (*self.conn).clone();
+
+            let _: i32 = redis::Script::new(UNLOCK_SCRIPT)
+                .key(&key)
+                .arg(token)
+                .invoke_async(&mut conn)
+                .await?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_locker() {
+        let locker = MemoryLocker::new().with_wait(Duration::from_millis(50));
+
+        let token = locker.lock("sid").await.unwrap();
+        assert!(matches!(
+            locker.lock("sid").await,
+            Err(SessionError::Locked)
+        ));
+        assert!(locker.lock("other").await.is_ok());
+
+        // A stale token doesn't release someone else's lock
+        locker.unlock("sid", "stale").await.unwrap();
+        assert!(locker.lock("sid").await.is_err());
+
+        locker.unlock("sid", &token).await.unwrap();
+        assert!(locker.lock("sid").await.is_ok());
+    }
+}
@@ -0,0 +1,247 @@
+//! Prometheus metrics (requires the `metrics` feature)
+//!
+//! [`SessionMetrics`] collects counters and store latency histograms from
+//! every handler it is attached to, labeled by store type, and serves them
+//! in the Prometheus text format:
+//!
+//! ```rust,ignore
+//! let metrics = SessionMetrics::new();
+//! let session_handler = ExpressSessionHandler::new(store, config).with_metrics(&metrics);
+//!
+//! let router = Router::new()
+//!     .push(Router::with_path("metrics").get(metrics.clone()))
+//!     .push(Router::new().hoop(session_handler).get(index));
+//! ```
+//!
+//! Exported series:
+//!
+//! - `session_created_total`, `session_loaded_total`, `session_saved_total`,
+//!   `session_touched_total`, `session_destroyed_total`
+//! - `session_signature_failures_total`: session cookies with a bad signature
+//! - `session_store_duration_seconds`: store call latency, by `operation`
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use salvo_core::http::header::CONTENT_TYPE;
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
+
+/// Upper bounds of the store latency histogram buckets, in seconds
+const LATENCY_BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+/// Session lifecycle events counted by [`SessionMetrics`]
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Event {
+    Created,
+    Loaded,
+    Saved,
+    Touched,
+    Destroyed,
+    SignatureFailure,
+}
+
+impl Event {
+    const ALL: [Event; 6] = [
+        Event::Created,
+        Event::Loaded,
+        Event::Saved,
+        Event::Touched,
+        Event::Destroyed,
+        Event::SignatureFailure,
+    ];
+
+    /// Name of the counter
+    fn metric(self) -> &'static str {
+        match self {
+            Event::Created => "session_created_total",
+            Event::Loaded => "session_loaded_total",
+            Event::Saved => "session_saved_total",
+            Event::Touched => "session_touched_total",
+            Event::Destroyed => "session_destroyed_total",
+            Event::SignatureFailure => "session_signature_failures_total",
+        }
+    }
+
+    /// Help text of the counter
+    fn help(self) -> &'static str {
+        match self {
+            Event::Created => "Sessions created",
+            Event::Loaded => "Sessions loaded from the store",
+            Event::Saved => "Sessions saved to the store",
+            Event::Touched => "Sessions whose expiry was refreshed",
+            Event::Destroyed => "Sessions destroyed",
+            Event::SignatureFailure => "Session cookies rejected for a bad signature",
+        }
+    }
+}
+
+/// Latency histogram of one store operation
+#[derive(Default)]
+struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            if secs <= bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Metrics of the handlers using one store type
+#[derive(Default)]
+pub(crate) struct StoreMetrics {
+    events: [AtomicU64; Event::ALL.len()],
+    latency: Mutex<BTreeMap<&'static str, Arc<Histogram>>>,
+}
+
+impl StoreMetrics {
+    /// Count a lifecycle event
+    pub(crate) fn count(&self, event: Event) {
+        self.events[event as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the latency of a store operation
+    pub(crate) fn observe(&self, operation: &'static str, elapsed: Duration) {
+        let histogram = Arc::clone(self.latency.lock().entry(operation).or_default());
+        histogram.observe(elapsed);
+    }
+}
+
+/// Session metrics registry, shared by the handlers it is attached to
+#[derive(Clone, Default)]
+pub struct SessionMetrics {
+    stores: Arc<Mutex<BTreeMap<String, Arc<StoreMetrics>>>>,
+}
+
+impl SessionMetrics {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Metrics for a store type, created on first use
+    pub(crate) fn store(&self, store: &str) -> Arc<StoreMetrics> {
+        Arc::clone(self.stores.lock().entry(store.to_string()).or_default())
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let stores: Vec<(String, Arc<StoreMetrics>)> = self
+            .stores
+            .lock()
+            .iter()
+            .map(|(name, metrics)| (name.clone(), Arc::clone(metrics)))
+            .collect();
+        let mut out = String::new();
+
+        for event in Event::ALL {
+            let _ = writeln!(out, "# HELP {} {}", event.metric(), event.help());
+            let _ = writeln!(out, "# TYPE {} counter", event.metric());
+            for (store, metrics) in &stores {
+                let value = metrics.events[event as usize].load(Ordering::Relaxed);
+                let _ = writeln!(out, "{}{{store=\"{}\"}} {}", event.metric(), store, value);
+            }
+        }
+
+        let name = "session_store_duration_seconds";
+        let _ = writeln!(out, "# HELP {} Latency of session store calls", name);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (store, metrics) in &stores {
+            let latency: Vec<(&str, Arc<Histogram>)> = metrics
+                .latency
+                .lock()
+                .iter()
+                .map(|(op, histogram)| (*op, Arc::clone(histogram)))
+                .collect();
+            for (op, histogram) in latency {
+                let labels = format!("store=\"{}\",operation=\"{}\"", store, op);
+                for (bucket, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+                    let _ = writeln!(
+                        out,
+                        "{}_bucket{{{},le=\"{}\"}} {}",
+                        name,
+                        labels,
+                        bound,
+                        bucket.load(Ordering::Relaxed)
+                    );
+                }
+                let count = histogram.count.load(Ordering::Relaxed);
+                let sum = histogram.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+                let _ = writeln!(out, "{}_bucket{{{},le=\"+Inf\"}} {}", name, labels, count);
+                let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, sum);
+                let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, count);
+            }
+        }
+        out
+    }
+}
+
+/// Serves [`SessionMetrics::render`] as a Prometheus scrape endpoint
+#[async_trait]
+impl Handler for SessionMetrics {
+    async fn handle(
+        &self,
+        _req: &mut Request,
+        _depot: &mut Depot,
+        res: &mut Response,
+        _ctrl: &mut FlowCtrl,
+    ) {
+        let _ = res.add_header(CONTENT_TYPE, "text/plain; version=0.0.4", true);
+        let _ = res.write_body(self.render());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::{MemoryStore, SessionStore};
+
+    #[test]
+    fn test_render() {
+        let metrics = SessionMetrics::new();
+        let store = metrics.store(MemoryStore::new().name());
+        store.count(Event::Saved);
+        store.count(Event::Saved);
+        store.observe("get", Duration::from_millis(3));
+
+        let text = metrics.render();
+        assert!(text.contains("session_saved_total{store=\"MemoryStore\"} 2"));
+        assert!(text.contains("session_created_total{store=\"MemoryStore\"} 0"));
+        assert!(text.contains(
+            "session_store_duration_seconds_bucket{store=\"MemoryStore\",operation=\"get\",le=\"0.0025\"} 0"
+        ));
+        assert!(text.contains(
+            "session_store_duration_seconds_bucket{store=\"MemoryStore\",operation=\"get\",le=\"0.005\"} 1"
+        ));
+        assert!(text.contains(
+            "session_store_duration_seconds_count{store=\"MemoryStore\",operation=\"get\"} 1"
+        ));
+    }
+
+    #[test]
+    fn test_type_erased_store_label() {
+        let metrics = SessionMetrics::new();
+        let store: Box<dyn SessionStore> = Box::new(MemoryStore::new());
+        let _handler = ExpressSessionHandler::new_boxed(store, SessionConfig::new("secret"))
+            .with_metrics(&metrics);
+        let text = metrics.render();
+        assert!(text.contains("session_saved_total{store=\"MemoryStore\"} 0"));
+        assert!(!text.contains("Box"));
+    }
+}
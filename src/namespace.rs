@@ -0,0 +1,141 @@
+//! Namespaced views over the session data
+//!
+//! Features sharing a session (cart, auth, wizard state) can each work in
+//! their own namespace so their key names never collide. A namespace is
+//! stored as a nested object under its name, which Node.js code sees as
+//! e.g. `req.session.cart.items`.
+//!
+//! ```rust,ignore
+//! let cart = session.namespace("cart");
+//! cart.set("items", vec!["book"]);
+//! let items: Vec<String> = cart.get("items").unwrap_or_default();
+//!
+//! // Drop all of the wizard's state at once
+//! session.namespace("wizard").clear();
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::session::Session;
+
+/// View of the session scoped to the keys of one namespace
+pub struct SessionNamespace<'a> {
+    session: &'a Session,
+    name: String,
+}
+
+impl Session {
+    /// Get a view whose keys are stored nested under `name`
+    pub fn namespace<N: Into<String>>(&self, name: N) -> SessionNamespace<'_> {
+        SessionNamespace {
+            session: self,
+            name: name.into(),
+        }
+    }
+}
+
+impl SessionNamespace<'_> {
+    /// Name of the namespace (the session key holding it)
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get a value from the namespace
+    pub fn get<T: for<'de> Deserialize<'de>>(&self, key: &str) -> Option<T> {
+        let data = self.session.read();
+        let value = data.data.get(&self.name)?.as_object()?.get(key)?;
+        serde_json::from_value(value.clone()).ok()
+    }
+
+    /// Set a value in the namespace
+    ///
+    /// A non-object value already stored under the namespace's name is
+    /// replaced.
+    pub fn set<T: Serialize>(&self, key: &str, value: T) {
+        let Ok(value) = serde_json::to_value(value) else {
+            return;
+        };
+
+        let mut data = self.session.write();
+        let entry = data
+            .data
+            .entry(self.name.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !entry.is_object() {
+            *entry = Value::Object(Map::new());
+        }
+        if let Value::Object(map) = entry {
+            map.insert(key.to_string(), value);
+        }
+        drop(data);
+
+        self.session.mark_modified();
+    }
+
+    /// Remove a value from the namespace
+    pub fn remove(&self, key: &str) -> Option<Value> {
+        let removed = self
+            .session
+            .write()
+            .data
+            .get_mut(&self.name)?
+            .as_object_mut()?
+            .remove(key);
+        if removed.is_some() {
+            self.session.mark_modified();
+        }
+        removed
+    }
+
+    /// Check if a key exists in the namespace
+    pub fn contains(&self, key: &str) -> bool {
+        self.session
+            .read()
+            .data
+            .get(&self.name)
+            .and_then(Value::as_object)
+            .is_some_and(|map| map.contains_key(key))
+    }
+
+    /// Keys of the namespace
+    pub fn keys(&self) -> Vec<String> {
+        self.session
+            .read()
+            .data
+            .get(&self.name)
+            .and_then(Value::as_object)
+            .map(|map| map.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Remove the whole namespace from the session
+    pub fn clear(&self) {
+        if self.session.write().data.remove(&self.name).is_some() {
+            self.session.mark_modified();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::session::{Session, SessionData};
+
+    #[test]
+    fn test_namespaces_are_isolated() {
+        let session = Session::new("sid".to_string(), SessionData::default(), false);
+        let cart = session.namespace("cart");
+        let wizard = session.namespace("wizard");
+
+        cart.set("step", 1);
+        wizard.set("step", 3);
+        assert_eq!(cart.get::<i32>("step"), Some(1));
+        assert_eq!(wizard.get::<i32>("step"), Some(3));
+        assert_eq!(session.get_path::<i32>("wizard.step"), Some(3));
+
+        wizard.clear();
+        assert!(!wizard.contains("step"));
+        assert_eq!(cart.keys(), vec!["step"]);
+        assert_eq!(cart.remove("step"), Some(serde_json::json!(1)));
+    }
+}
@@ -0,0 +1,144 @@
+//! OpenAPI documentation of the session token (requires the `oapi` feature)
+//!
+//! ```rust,ignore
+//! use salvo_express_session::{SessionOpenApiExt, SessionRouterExt};
+//!
+//! let router = Router::new()
+//!     .hoop(session_handler)
+//!     .push(Router::with_path("api").require_session(RequireSession::new()).get(api));
+//! let doc = OpenApi::new("api", "1.0.0")
+//!     .session_security_scheme(&config)
+//!     .merge_router(&router);
+//! ```
+
+use salvo_core::Router;
+use salvo_oapi::security::{ApiKey, ApiKeyValue, SecurityRequirement, SecurityScheme};
+use salvo_oapi::{OpenApi, RouterExt};
+
+use crate::config::{SessionConfig, SessionTransport};
+use crate::require::RequireSession;
+
+/// Name under which the session security scheme is registered
+pub const SECURITY_SCHEME: &str = "session";
+
+/// `apiKey` security scheme carrying the session token of `config`
+///
+/// The key is read from the cookie named `cookie_name`, or from the
+/// `header_name` header when the token is only sent in a header.
+pub fn security_scheme(config: &SessionConfig) -> SecurityScheme {
+    let key = match config.transport {
+        SessionTransport::Header => ApiKey::Header(ApiKeyValue::new(&config.header_name)),
+        SessionTransport::Cookie | SessionTransport::CookieAndHeader => {
+            ApiKey::Cookie(ApiKeyValue::new(&config.cookie_name))
+        }
+    };
+    SecurityScheme::ApiKey(key)
+}
+
+/// Security requirement referencing [`SECURITY_SCHEME`]
+pub fn security_requirement() -> SecurityRequirement {
+    SecurityRequirement::new(SECURITY_SCHEME, Vec::<String>::new())
+}
+
+/// Registers the session security scheme in an OpenAPI document
+pub trait SessionOpenApiExt {
+    /// Add the session token of `config` as the [`SECURITY_SCHEME`] scheme
+    #[must_use]
+    fn session_security_scheme(self, config: &SessionConfig) -> Self;
+}
+
+impl SessionOpenApiExt for OpenApi {
+    fn session_security_scheme(self, config: &SessionConfig) -> Self {
+        self.add_security_scheme(SECURITY_SCHEME, security_scheme(config))
+    }
+}
+
+/// Guards routers with [`RequireSession`] and advertises it in OpenAPI
+pub trait SessionRouterExt {
+    /// Mount `guard` and require the [`SECURITY_SCHEME`] scheme on every
+    /// endpoint of this router and its descendants
+    #[must_use]
+    fn require_session(self, guard: RequireSession) -> Self;
+}
+
+impl SessionRouterExt for Router {
+    fn require_session(self, guard: RequireSession) -> Self {
+        self.hoop(guard).oapi_security(security_requirement())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use salvo_core::http::StatusCode;
+    use salvo_core::test::TestClient;
+    use salvo_core::Service;
+    use salvo_oapi::endpoint;
+
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::MemoryStore;
+
+    #[endpoint]
+    async fn private() -> &'static str {
+        "private"
+    }
+
+    #[endpoint]
+    async fn public() -> &'static str {
+        "public"
+    }
+
+    fn router(config: SessionConfig) -> Router {
+        let handler = ExpressSessionHandler::new(MemoryStore::new(), config);
+        Router::new()
+            .hoop(handler)
+            .push(
+                Router::with_path("private")
+                    .require_session(RequireSession::new())
+                    .get(private),
+            )
+            .push(Router::with_path("public").get(public))
+    }
+
+    #[tokio::test]
+    async fn test_security_scheme() {
+        let config = SessionConfig::new("secret").with_cookie_name("sid");
+        let doc = OpenApi::new("test", "1.0.0")
+            .session_security_scheme(&config)
+            .merge_router(&router(config));
+        let json: serde_json::Value = serde_json::from_str(&doc.to_json().unwrap()).unwrap();
+
+        assert_eq!(
+            json["components"]["securitySchemes"][SECURITY_SCHEME],
+            serde_json::json!({"type": "apiKey", "in": "cookie", "name": "sid"})
+        );
+        assert_eq!(
+            json["paths"]["/private"]["get"]["security"],
+            serde_json::json!([{SECURITY_SCHEME: []}])
+        );
+        assert!(json["paths"]["/public"]["get"]["security"].is_null());
+    }
+
+    #[test]
+    fn test_header_scheme() {
+        let config = SessionConfig::new("secret").with_transport(SessionTransport::Header);
+        let scheme = serde_json::to_value(security_scheme(&config)).unwrap();
+        assert_eq!(scheme["in"], "header");
+        assert_eq!(scheme["name"], config.header_name.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_guard_mounted() {
+        let service = Service::new(router(SessionConfig::new("secret")));
+
+        let res = TestClient::get("http://127.0.0.1/private")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+
+        let res = TestClient::get("http://127.0.0.1/public")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+    }
+}
@@ -0,0 +1,121 @@
+//! Background removal of expired sessions
+//!
+//! Stores backed by files, SQL tables or embedded databases can't expire
+//! keys on their own. [`SessionReaper`] periodically removes expired sessions
+//! through [`SessionStore::prune_expired`], in batches, so the backing
+//! storage stays bounded:
+//!
+//! ```rust,ignore
+//! let reaper = SessionReaper::new(store.clone())
+//!     .with_interval(Duration::from_secs(300))
+//!     .with_batch_size(500)
+//!     .start();
+//! ```
+//!
+//! Stores with native expiry (Redis) don't need a reaper.
+
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::error::SessionError;
+use crate::store::SessionStore;
+
+/// Periodic pruner of expired sessions
+pub struct SessionReaper {
+    store: Arc<dyn SessionStore>,
+    interval: Duration,
+    batch_size: usize,
+}
+
+impl SessionReaper {
+    /// Create a reaper over `store`, running every 10 minutes in batches of 1000
+    pub fn new<S: SessionStore>(store: S) -> Self {
+        Self {
+            store: Arc::new(store),
+            interval: Duration::from_secs(10 * 60),
+            batch_size: 1000,
+        }
+    }
+
+    /// Set how often expired sessions are removed (default: 10 minutes)
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set how many sessions are removed per store call (default: 1000)
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Prune in a background task every interval
+    ///
+    /// The task stops once the returned handle and all its clones are dropped.
+    pub fn start(self) -> Arc<Self> {
+        let reaper = Arc::new(self);
+        tokio::spawn(Self::run(Arc::downgrade(&reaper), reaper.interval));
+        reaper
+    }
+
+    /// Pruning loop of [`Self::start`]
+    async fn run(reaper: Weak<Self>, interval: Duration) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let Some(reaper) = reaper.upgrade() else {
+                return;
+            };
+            match reaper.prune_now().await {
+                Ok(0) => {}
+                Ok(pruned) => tracing::debug!("Pruned {} expired sessions", pruned),
+                Err(e) => tracing::error!("Failed to prune expired sessions: {}", e),
+            }
+        }
+    }
+
+    /// Remove all sessions expired by now, batch by batch
+    ///
+    /// Returns the number removed.
+    pub async fn prune_now(&self) -> Result<usize, SessionError> {
+        let before = Utc::now();
+        let mut total = 0;
+        loop {
+            let pruned = self.store.prune_expired(before, self.batch_size).await?;
+            total += pruned;
+            if pruned < self.batch_size {
+                return Ok(total);
+            }
+            // Let requests use the store between batches
+            tokio::task::yield_now().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionData;
+    use crate::store::MemoryStore;
+
+    #[tokio::test]
+    async fn test_prune_now() {
+        let store = MemoryStore::new();
+        for sid in ["a", "b", "c"] {
+            let mut data = SessionData::new(3600);
+            data.cookie.expires = Some(Utc::now() - chrono::Duration::minutes(1));
+            store.set(sid, &data, None).await.unwrap();
+        }
+        store
+            .set("live", &SessionData::new(3600), None)
+            .await
+            .unwrap();
+
+        let reaper = SessionReaper::new(store.clone()).with_batch_size(2);
+        assert_eq!(reaper.prune_now().await.unwrap(), 3);
+        assert_eq!(reaper.prune_now().await.unwrap(), 0);
+        assert_eq!(store.ids().await.unwrap(), vec!["live"]);
+    }
+}
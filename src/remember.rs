@@ -0,0 +1,262 @@
+//! Remember-me persistent logins
+//!
+//! A second, long-lived cookie re-establishes the login once the session
+//! itself expired. It holds a `selector:validator` token: the selector
+//! names the token record in a [`SessionStore`], which keeps only a hash of
+//! the validator. The token is rotated every time it is used, and a known
+//! selector with a wrong validator (a stolen, already used token) revokes
+//! it.
+//!
+//! ```rust,ignore
+//! let session_handler = ExpressSessionHandler::new(store, config)
+//!     .with_remember_me(RememberMe::new(MemoryStore::new()).with_secure(true));
+//!
+//! // On login, if the user ticked "remember me"
+//! session.login(user.id, claims)?;
+//! session.remember_me();
+//! ```
+
+use std::sync::Arc;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use salvo_core::http::cookie::{self, time::Duration as CookieDuration, SameSite};
+use salvo_core::{Request, Response};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::cookie_signature::constant_time_compare;
+use crate::error::SessionError;
+use crate::session::{Session, SessionData};
+use crate::store::SessionStore;
+
+/// Default lifetime of a remember-me token: 30 days
+const DEFAULT_MAX_AGE: u64 = 30 * 24 * 60 * 60;
+
+impl Session {
+    /// Keep the user logged in across session expiry with a remember-me token
+    ///
+    /// The token is issued after the response, if a user is logged in by
+    /// then. Needs [`ExpressSessionHandler::with_remember_me`](crate::ExpressSessionHandler::with_remember_me).
+    pub fn remember_me(&self) {
+        self.set_remember(true);
+    }
+
+    /// Revoke the request's remember-me token and remove its cookie
+    ///
+    /// Called by [`Self::logout`]; destroying the session also revokes it.
+    pub fn forget_me(&self) {
+        self.set_remember(false);
+    }
+}
+
+/// Remember-me token settings
+pub struct RememberMe {
+    store: Arc<dyn SessionStore>,
+    cookie_name: String,
+    cookie_path: String,
+    max_age: u64,
+    secure: bool,
+}
+
+impl RememberMe {
+    /// Name of the remember-me cookie
+    pub(crate) fn cookie_name(&self) -> &str {
+        &self.cookie_name
+    }
+
+    /// Keep remember-me tokens in `store`
+    ///
+    /// Use a store (or key prefix) of its own, so tokens aren't listed
+    /// among the sessions.
+    pub fn new<S: SessionStore>(store: S) -> Self {
+        Self {
+            store: Arc::new(store),
+            cookie_name: "remember_me".to_string(),
+            cookie_path: "/".to_string(),
+            max_age: DEFAULT_MAX_AGE,
+            secure: false,
+        }
+    }
+
+    /// Set the cookie name (default: "remember_me")
+    pub fn with_cookie_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.cookie_name = name.into();
+        self
+    }
+
+    /// Set the cookie path (default: "/")
+    pub fn with_cookie_path<S: Into<String>>(mut self, path: S) -> Self {
+        self.cookie_path = path.into();
+        self
+    }
+
+    /// Set the token lifetime in seconds (default: 30 days)
+    pub fn with_max_age(mut self, seconds: u64) -> Self {
+        self.max_age = seconds;
+        self
+    }
+
+    /// Set the cookie's Secure flag (default: false)
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Get the selector and validator of the request's token
+    fn request_token(&self, req: &Request) -> Option<(String, String)> {
+        let value = req.cookie(&self.cookie_name)?.value().to_string();
+        let (selector, validator) = value.split_once(':')?;
+        if selector.is_empty() || validator.is_empty() {
+            return None;
+        }
+        Some((selector.to_string(), validator.to_string()))
+    }
+
+    /// Log the session in again from the request's token, if it has none
+    ///
+    /// A used token is always rotated, and an invalid one removed.
+    pub(crate) async fn restore(&self, req: &Request, session: &Session) {
+        let Some((selector, validator)) = self.request_token(req) else {
+            return;
+        };
+        if let Err(e) = session.load().await {
+            tracing::error!("Failed to load session: {}", e);
+            return;
+        }
+        if session.is_authenticated() {
+            return;
+        }
+
+        let record = match self.store.get(&selector).await {
+            Ok(record) => record,
+            Err(e) => {
+                tracing::error!("Failed to load remember-me token: {}", e);
+                return;
+            }
+        };
+        let Some(record) = record else {
+            session.forget_me();
+            return;
+        };
+
+        let expected = record.get::<String>("validator").unwrap_or_default();
+        if !constant_time_compare(&hash_validator(&validator), &expected) {
+            tracing::warn!("Remember-me token presented with a wrong validator; revoking it");
+            session.forget_me();
+            return;
+        }
+
+        let user = record.get::<Value>("user").unwrap_or(Value::Null);
+        let claims = record.get::<Value>("claims").unwrap_or(Value::Null);
+        if let Err(e) = session.login(user, claims) {
+            tracing::error!("Failed to restore remembered login: {}", e);
+            return;
+        }
+        session.remember_me();
+    }
+
+    /// Issue or revoke the token as requested during the request
+    pub(crate) async fn finish(&self, req: &Request, session: &Session, res: &mut Response) {
+        let issue = match session.remember_request() {
+            _ if session.should_destroy() => false,
+            Some(remember) => remember && session.is_authenticated(),
+            None => return,
+        };
+
+        let current = self.request_token(req);
+        if let Some((selector, _)) = &current {
+            if let Err(e) = self.store.destroy(selector).await {
+                tracing::error!("Failed to revoke remember-me token: {}", e);
+            }
+        }
+
+        if issue {
+            if let Err(e) = self.issue(session, res).await {
+                tracing::error!("Failed to issue remember-me token: {}", e);
+            }
+        } else if current.is_some() {
+            let cookie = cookie::Cookie::build(self.cookie_name.clone())
+                .path(self.cookie_path.clone())
+                .max_age(CookieDuration::ZERO)
+                .build();
+            res.add_cookie(cookie);
+        }
+    }
+
+    /// Store a new token for the logged-in user and set its cookie
+    async fn issue(&self, session: &Session, res: &mut Response) -> Result<(), SessionError> {
+        let selector = Uuid::new_v4().simple().to_string();
+        let validator = Uuid::new_v4().simple().to_string();
+
+        let mut record = SessionData::new(self.max_age);
+        record.set("validator", hash_validator(&validator));
+        record.set(
+            "user",
+            session
+                .get_path::<Value>("passport.user")
+                .unwrap_or(Value::Null),
+        );
+        if let Some(claims) = session.get_path::<Value>("passport.claims") {
+            record.set("claims", claims);
+        }
+        self.store
+            .set(&selector, &record, Some(self.max_age))
+            .await?;
+
+        let cookie = cookie::Cookie::build((
+            self.cookie_name.clone(),
+            format!("{}:{}", selector, validator),
+        ))
+        .path(self.cookie_path.clone())
+        .http_only(true)
+        .secure(self.secure)
+        .same_site(SameSite::Lax)
+        .max_age(CookieDuration::seconds(self.max_age as i64))
+        .build();
+        res.add_cookie(cookie);
+        Ok(())
+    }
+}
+
+/// Hash a validator for storage, so a leaked store can't be replayed
+fn hash_validator(validator: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(validator.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::cookie::Cookie;
+    use salvo_core::Request;
+
+    use super::*;
+    use crate::store::MemoryStore;
+
+    fn request(token: &str) -> Request {
+        let mut req = Request::new();
+        req.cookies_mut()
+            .add(Cookie::new("remember_me", token.to_string()));
+        req
+    }
+
+    #[tokio::test]
+    async fn test_restore_checks_the_validator() {
+        let store = MemoryStore::new();
+        let mut record = SessionData::new(60);
+        record.set("validator", hash_validator("secret"));
+        record.set("user", 7);
+        store.set("sel", &record, None).await.unwrap();
+        let remember = RememberMe::new(store);
+
+        let session = Session::new("sid".to_string(), SessionData::default(), true);
+        remember.restore(&request("sel:wrong"), &session).await;
+        assert!(!session.is_authenticated());
+        assert_eq!(session.remember_request(), Some(false));
+
+        let session = Session::new("sid".to_string(), SessionData::default(), true);
+        remember.restore(&request("sel:secret"), &session).await;
+        assert_eq!(session.user_id::<u64>(), Some(7));
+        assert_eq!(session.remember_request(), Some(true));
+    }
+}
@@ -0,0 +1,80 @@
+//! Guard for routes that need an established session
+//!
+//! ```rust,ignore
+//! let router = Router::new()
+//!     .hoop(session_handler)
+//!     .push(Router::with_path("api").hoop(RequireSession::new()).get(api));
+//! ```
+
+use salvo_core::http::StatusCode;
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
+
+use crate::depot_ext::SessionDepotExt;
+
+/// Middleware rejecting requests that don't carry an existing session
+///
+/// Requests without a valid session cookie (or token), or whose session
+/// expired or is no longer in the store, are answered with 401
+/// Unauthorized. Must be mounted after the session handler.
+///
+/// With the `oapi` feature, mount it with
+/// [`SessionRouterExt::require_session`](crate::oapi::SessionRouterExt) so
+/// the guarded endpoints advertise the session security scheme.
+#[derive(Clone, Debug)]
+pub struct RequireSession {
+    status: StatusCode,
+}
+
+impl RequireSession {
+    /// Create a guard answering with 401 Unauthorized
+    pub fn new() -> Self {
+        Self {
+            status: StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    /// Set the status code for requests without a session (default: 401)
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+impl Default for RequireSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Handler for RequireSession {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        let session = match depot.session_required() {
+            Ok(session) => session,
+            Err(e) => {
+                tracing::error!("RequireSession: {}", e);
+                res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                ctrl.skip_rest();
+                return;
+            }
+        };
+
+        // Lazily loaded sessions only know whether they exist once loaded
+        if let Err(e) = session.load().await {
+            tracing::error!("Failed to load session: {}", e);
+        }
+        if session.is_new() {
+            res.status_code(self.status);
+            ctrl.skip_rest();
+            return;
+        }
+
+        ctrl.call_next(req, depot, res).await;
+    }
+}
@@ -0,0 +1,231 @@
+//! Hot-reloadable signing secrets
+//!
+//! By default the handler signs and verifies cookies with the static
+//! [`SessionConfig::secrets`](crate::SessionConfig::secrets). A
+//! [`SecretProvider`] sources them from elsewhere (a secrets manager, a
+//! mounted file) instead and notifies the handler when they change, so keys
+//! can be rotated without restarting the server.
+//!
+//! Either way, each secret is turned into a [`SigningKey`] once, when the
+//! handler is built or the secrets are fetched, rather than per request.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use parking_lot::RwLock;
+use sha1::Sha1;
+use sha2::Sha256;
+
+use crate::error::SessionError;
+
+/// A signing secret with its HMAC keys computed up front
+///
+/// Keying an HMAC hashes the padded secret, which costs as much as signing
+/// a session ID, so codecs clone these keyed instances instead.
+#[derive(Clone)]
+pub struct SigningKey {
+    secret: Arc<str>,
+    sha256: Hmac<Sha256>,
+    sha1: Hmac<Sha1>,
+}
+
+impl SigningKey {
+    /// Key the HMACs used by the built-in codecs with `secret`
+    pub fn new<S: Into<Arc<str>>>(secret: S) -> Self {
+        let secret = secret.into();
+        Self {
+            sha256: Hmac::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size"),
+            sha1: Hmac::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size"),
+            secret,
+        }
+    }
+
+    /// Key each of `secrets`, keeping their order
+    pub fn from_secrets<I, S>(secrets: I) -> Arc<[SigningKey]>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<Arc<str>>,
+    {
+        secrets.into_iter().map(Self::new).collect()
+    }
+
+    /// The secret this key was built from
+    pub fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    /// HMAC-SHA256 keyed with the secret (express-session, JWT)
+    pub fn hmac_sha256(&self) -> Hmac<Sha256> {
+        self.sha256.clone()
+    }
+
+    /// HMAC-SHA1 keyed with the secret (Keygrip)
+    pub fn hmac_sha1(&self) -> Hmac<Sha1> {
+        self.sha1.clone()
+    }
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningKey").finish_non_exhaustive()
+    }
+}
+
+/// Source of signing secrets
+///
+/// ```rust,ignore
+/// struct VaultSecrets { client: VaultClient }
+///
+/// #[async_trait]
+/// impl SecretProvider for VaultSecrets {
+///     async fn secrets(&self) -> Result<Vec<String>, SessionError> {
+///         self.client.read("session/keys").await.map_err(SessionError::backend)
+///     }
+///
+///     async fn changed(&self) {
+///         tokio::time::sleep(Duration::from_secs(300)).await;
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait SecretProvider: Send + Sync + 'static {
+    /// Fetch the current secrets, primary (signing) secret first
+    async fn secrets(&self) -> Result<Vec<String>, SessionError>;
+
+    /// Resolve when the secrets may have changed and should be fetched again
+    ///
+    /// The default never resolves, so secrets are fetched once.
+    async fn changed(&self) {
+        std::future::pending::<()>().await
+    }
+}
+
+/// Provider reading secrets from a file, one per line, primary first
+///
+/// Blank lines and lines starting with `#` are ignored. The file is re-read
+/// every poll interval (default: 60 seconds).
+#[derive(Clone, Debug)]
+pub struct FileSecretProvider {
+    path: PathBuf,
+    poll_interval: Duration,
+}
+
+impl FileSecretProvider {
+    /// Read secrets from the file at `path`
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            poll_interval: Duration::from_secs(60),
+        }
+    }
+
+    /// Set how often the file is re-read (default: 60 seconds)
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+}
+
+#[async_trait]
+impl SecretProvider for FileSecretProvider {
+    async fn secrets(&self) -> Result<Vec<String>, SessionError> {
+        let contents = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|source| SessionError::Io {
+                context: format!("Failed to read {}", self.path.display()),
+                source,
+            })?;
+
+        Ok(contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect())
+    }
+
+    async fn changed(&self) {
+        tokio::time::sleep(self.poll_interval).await;
+    }
+}
+
+/// Keys of the latest secrets fetched from a provider, shared by handler clones
+pub(crate) struct SecretCache {
+    provider: Arc<dyn SecretProvider>,
+    current: RwLock<Option<Arc<[SigningKey]>>>,
+    watching: AtomicBool,
+}
+
+impl SecretCache {
+    pub(crate) fn new(provider: Arc<dyn SecretProvider>) -> Self {
+        Self {
+            provider,
+            current: RwLock::new(None),
+            watching: AtomicBool::new(false),
+        }
+    }
+
+    /// Keys of the most recently fetched secrets, if any fetch succeeded
+    pub(crate) fn current(&self) -> Option<Arc<[SigningKey]>> {
+        self.current.read().clone()
+    }
+
+    /// Fetch the secrets on first use and start watching for changes
+    pub(crate) async fn ensure_loaded(self: &Arc<Self>) {
+        if self.current.read().is_none() {
+            self.refresh().await;
+        }
+
+        if !self.watching.swap(true, Ordering::SeqCst) {
+            let provider = Arc::clone(&self.provider);
+            let cache = Arc::downgrade(self);
+            tokio::spawn(watch(provider, cache));
+        }
+    }
+
+    /// Fetch the secrets, keeping the previous ones on failure
+    async fn refresh(&self) {
+        match self.provider.secrets().await {
+            Ok(secrets) if secrets.is_empty() || secrets.iter().any(String::is_empty) => {
+                tracing::error!(
+                    "Secret provider returned no usable secrets; keeping previous ones"
+                );
+            }
+            Ok(secrets) => *self.current.write() = Some(SigningKey::from_secrets(secrets)),
+            Err(e) => tracing::error!("Failed to fetch session secrets: {}", e),
+        }
+    }
+}
+
+/// Refresh the cache whenever the provider reports a change, until the
+/// handlers sharing it are dropped
+async fn watch(provider: Arc<dyn SecretProvider>, cache: Weak<SecretCache>) {
+    loop {
+        provider.changed().await;
+        match cache.upgrade() {
+            Some(cache) => cache.refresh().await,
+            None => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_provider() {
+        let path = std::env::temp_dir().join(format!("session-secrets-{}", std::process::id()));
+        std::fs::write(&path, "# primary first\nnew-secret\n\n  old-secret  \n").unwrap();
+
+        let secrets = FileSecretProvider::new(&path).secrets().await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(secrets, vec!["new-secret", "old-secret"]);
+    }
+}
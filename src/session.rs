@@ -1,12 +1,73 @@
 //! Session data structure compatible with express-session
 
 use chrono::{DateTime, Utc};
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use uuid::Uuid;
+
+use crate::config::SameSite;
+use crate::error::SessionError;
+
+/// Future resolving to the stored session data, if any
+pub type LoadFuture =
+    Pin<Box<dyn Future<Output = Result<Option<SessionData>, SessionError>> + Send>>;
+
+/// Deferred store lookup for lazily loaded sessions
+pub type SessionLoader = Box<dyn FnOnce() -> LoadFuture + Send>;
+
+/// Future resolving to the session data as written to the store
+pub type SaveFuture = Pin<Box<dyn Future<Output = Result<SessionData, SessionError>> + Send>>;
+
+/// Writes session data and its changed keys to the store on behalf of the handler
+pub type SessionSaver = Arc<dyn Fn(String, SessionData, Vec<String>) -> SaveFuture + Send + Sync>;
+
+/// Re-reads session data from the store on behalf of the handler
+pub type SessionReloader = Arc<dyn Fn(String) -> LoadFuture + Send + Sync>;
+
+/// A store load that has not happened yet
+struct PendingLoad {
+    loader: SessionLoader,
+}
+
+/// Removals made to a deferred session before it was loaded
+#[derive(Default)]
+struct BlindRemovals {
+    /// Whether all data was cleared
+    cleared: bool,
+    /// Keys removed, after the clear if any
+    keys: Vec<String>,
+}
+
+impl BlindRemovals {
+    fn is_empty(&self) -> bool {
+        !self.cleared && self.keys.is_empty()
+    }
+
+    /// Apply the removals to the loaded data
+    fn apply(&self, data: &mut SessionData) {
+        if self.cleared {
+            data.clear();
+        }
+        for key in &self.keys {
+            data.remove(key);
+        }
+    }
+}
+
+/// Generate a new session ID
+///
+/// Uses UUID v4, similar to uid-safe in Node.js.
+pub fn generate_session_id() -> String {
+    Uuid::new_v4().to_string()
+}
 
 /// Cookie data structure compatible with express-session
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -148,23 +209,72 @@ impl SessionCookie {
 }
 
 /// Session data structure compatible with express-session/connect-redis
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Sessions parsed with [`SessionData::from_json`] keep their JSON and are
+/// saved from it while unchanged. The methods here account for their
+/// changes; code assigning the public fields directly must call
+/// [`SessionData::mark_changed`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SessionData {
     /// Cookie information
     pub cookie: SessionCookie,
 
+    /// Session creation time, used to enforce an absolute lifetime cap.
+    /// Sessions created by Node.js lack this field until first saved here.
+    #[serde(rename = "createdAt", default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+
+    /// Last time the session was accessed, used to enforce an idle timeout
+    #[serde(
+        rename = "lastAccess",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_access: Option<DateTime<Utc>>,
+
+    /// Revision counter incremented on every save, for optimistic concurrency control
+    #[serde(
+        rename = "sessionVersion",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub version: Option<u64>,
+
+    /// Client IP the session is bound to, see [`IpBinding`](crate::binding::IpBinding)
+    #[serde(rename = "clientIp", default, skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<IpAddr>,
+
+    /// Client fingerprint the session is bound to, see [`Fingerprint`](crate::fingerprint::Fingerprint)
+    #[serde(
+        rename = "clientFingerprint",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub fingerprint: Option<String>,
+
+    /// Integrity MAC of the stored record, see [`SignedStore`](crate::store::SignedStore)
+    #[serde(
+        rename = "sessionMac",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub mac: Option<String>,
+
+    /// Expiry of keys set with a TTL, checked on read and pruned on load and save
+    #[serde(
+        rename = "keyExpires",
+        default,
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub key_expires: HashMap<String, DateTime<Utc>>,
+
     /// Additional session data (flattened at same level as cookie)
     #[serde(flatten)]
     pub data: HashMap<String, Value>,
-}
 
-impl Default for SessionData {
-    fn default() -> Self {
-        Self {
-            cookie: SessionCookie::default(),
-            data: HashMap::new(),
-        }
-    }
+    /// JSON the session was parsed from, until it changes
+    #[serde(skip)]
+    pub(crate) raw: Option<Arc<str>>,
 }
 
 impl SessionData {
@@ -172,7 +282,15 @@ impl SessionData {
     pub fn new(max_age_secs: u64) -> Self {
         Self {
             cookie: SessionCookie::new(max_age_secs),
+            created_at: Some(Utc::now()),
+            last_access: None,
+            version: None,
+            client_ip: None,
+            fingerprint: None,
+            mac: None,
+            key_expires: HashMap::new(),
             data: HashMap::new(),
+            raw: None,
         }
     }
 
@@ -182,7 +300,15 @@ impl SessionData {
     pub fn new_session_cookie() -> Self {
         Self {
             cookie: SessionCookie::new_session_cookie(),
+            created_at: Some(Utc::now()),
+            last_access: None,
+            version: None,
+            client_ip: None,
+            fingerprint: None,
+            mac: None,
+            key_expires: HashMap::new(),
             data: HashMap::new(),
+            raw: None,
         }
     }
 
@@ -192,12 +318,158 @@ impl SessionData {
     pub fn with_optional_max_age(max_age_secs: Option<u64>) -> Self {
         Self {
             cookie: SessionCookie::with_optional_max_age(max_age_secs),
+            created_at: Some(Utc::now()),
+            last_access: None,
+            version: None,
+            client_ip: None,
+            fingerprint: None,
+            mac: None,
+            key_expires: HashMap::new(),
             data: HashMap::new(),
+            raw: None,
+        }
+    }
+
+    /// Check if the session is older than `max_age_secs` since creation
+    ///
+    /// Sessions without a recorded creation time are never considered past the cap.
+    pub fn exceeds_absolute_max_age(&self, max_age_secs: u64) -> bool {
+        match self.created_at {
+            Some(created) => created + chrono::Duration::seconds(max_age_secs as i64) <= Utc::now(),
+            None => false,
         }
     }
 
+    /// Check if the session has not been accessed for `idle_secs`
+    ///
+    /// Sessions without a recorded access time are never considered idle.
+    pub fn is_idle(&self, idle_secs: u64) -> bool {
+        match self.last_access {
+            Some(last) => last + chrono::Duration::seconds(idle_secs as i64) <= Utc::now(),
+            None => false,
+        }
+    }
+
+    /// Record an access to the session at the current time
+    pub fn record_access(&mut self) {
+        self.last_access = Some(Utc::now());
+        self.raw = None;
+    }
+
+    /// Parse a stored session, keeping `json` to save it from while unchanged
+    pub fn from_json(json: String) -> Result<Self, SessionError> {
+        let mut session: Self = serde_json::from_str(&json)?;
+        session.raw = Some(json.into());
+        Ok(session)
+    }
+
+    /// Serialize the session for storage
+    ///
+    /// An unchanged session parsed with [`Self::from_json`] returns its
+    /// original JSON without serializing, so records written by Node.js are
+    /// saved back byte for byte.
+    pub fn to_json(&self) -> Result<Cow<'_, str>, SessionError> {
+        match &self.raw {
+            Some(raw) => Ok(Cow::Borrowed(raw)),
+            None => Ok(Cow::Owned(serde_json::to_string(self)?)),
+        }
+    }
+
+    /// Forget the JSON the session was parsed from, after changing its fields directly
+    pub fn mark_changed(&mut self) {
+        self.raw = None;
+    }
+
+    /// Copy the listed keys and the metadata (cookie, timestamps, version) from `source`
+    ///
+    /// Keys missing from `source` are removed. Used to apply one request's
+    /// changes on top of another copy of the session.
+    pub fn apply_changes(&mut self, source: &SessionData, changed: &[String]) {
+        self.apply_keys(source, changed);
+        self.cookie = source.cookie.clone();
+        self.created_at = source.created_at;
+        self.last_access = source.last_access;
+        self.version = source.version;
+        self.client_ip = source.client_ip;
+        self.raw = None;
+        self.fingerprint = source.fingerprint.clone();
+    }
+
+    /// Copy only the listed keys (and their expiries) from `source`
+    pub(crate) fn apply_keys(&mut self, source: &SessionData, changed: &[String]) {
+        self.raw = None;
+        for key in changed {
+            match source.data.get(key) {
+                Some(value) => {
+                    self.data.insert(key.clone(), value.clone());
+                }
+                None => {
+                    self.data.remove(key);
+                }
+            }
+        }
+        for key in changed {
+            match source.key_expires.get(key) {
+                Some(expires) => {
+                    self.key_expires.insert(key.clone(), *expires);
+                }
+                None => {
+                    self.key_expires.remove(key);
+                }
+            }
+        }
+    }
+
+    /// Keys set, changed or removed compared to `original`
+    pub(crate) fn changed_keys(&self, original: &SessionData) -> Vec<String> {
+        let changed = self
+            .data
+            .iter()
+            .filter(|(key, value)| {
+                original.data.get(*key) != Some(*value)
+                    || original.key_expires.get(*key) != self.key_expires.get(*key)
+            })
+            .map(|(key, _)| key.clone());
+        let removed = original
+            .data
+            .keys()
+            .filter(|key| !self.data.contains_key(*key))
+            .cloned();
+        changed.chain(removed).collect()
+    }
+
+    /// Size of the session serialized as JSON, in bytes
+    pub fn serialized_size(&self) -> usize {
+        self.to_json().map_or(0, |json| json.len())
+    }
+
+    /// Remove the largest values until the serialized session fits in `limit` bytes
+    ///
+    /// Returns the removed keys. The cookie and metadata are never removed,
+    /// so the result can still exceed the limit.
+    pub fn truncate_to(&mut self, limit: usize) -> Vec<String> {
+        let mut keys: Vec<String> = self.data.keys().cloned().collect();
+        keys.sort_by_cached_key(|key| {
+            std::cmp::Reverse(serde_json::to_vec(&self.data[key]).map_or(0, |v| v.len()))
+        });
+
+        let mut removed = Vec::new();
+        for key in keys {
+            if self.serialized_size() <= limit {
+                break;
+            }
+            self.data.remove(&key);
+            self.raw = None;
+            removed.push(key);
+        }
+        removed
+    }
+
     /// Get a value from session data
     pub fn get<T: for<'de> Deserialize<'de>>(&self, key: &str) -> Option<T> {
+        if self.is_key_expired(key) {
+            return None;
+        }
         self.data
             .get(key)
             .and_then(|v| serde_json::from_value(v.clone()).ok())
@@ -207,22 +479,64 @@ impl SessionData {
     pub fn set<T: Serialize>(&mut self, key: &str, value: T) {
         if let Ok(v) = serde_json::to_value(value) {
             self.data.insert(key.to_string(), v);
+            self.key_expires.remove(key);
+            self.raw = None;
         }
     }
 
+    /// Set a value that expires after `ttl`, independently of the session
+    pub fn set_with_ttl<T: Serialize>(&mut self, key: &str, value: T, ttl: std::time::Duration) {
+        if let Ok(v) = serde_json::to_value(value) {
+            let ttl = chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+            let expires = Utc::now()
+                .checked_add_signed(ttl)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC);
+            self.data.insert(key.to_string(), v);
+            self.key_expires.insert(key.to_string(), expires);
+            self.raw = None;
+        }
+    }
+
+    /// Check if a key set with a TTL has expired
+    pub fn is_key_expired(&self, key: &str) -> bool {
+        self.key_expires
+            .get(key)
+            .is_some_and(|expires| *expires <= Utc::now())
+    }
+
+    /// Remove keys whose TTL has expired
+    pub fn prune_expired_keys(&mut self) {
+        let now = Utc::now();
+        let data = &mut self.data;
+        let raw = &mut self.raw;
+        self.key_expires.retain(|key, expires| {
+            let live = *expires > now;
+            if !live {
+                data.remove(key);
+                *raw = None;
+            }
+            live
+        });
+    }
+
     /// Remove a value from session data
     pub fn remove(&mut self, key: &str) -> Option<Value> {
-        self.data.remove(key)
+        let expired = self.is_key_expired(key);
+        self.key_expires.remove(key);
+        self.raw = None;
+        self.data.remove(key).filter(|_| !expired)
     }
 
     /// Check if a key exists
     pub fn contains(&self, key: &str) -> bool {
-        self.data.contains_key(key)
+        self.data.contains_key(key) && !self.is_key_expired(key)
     }
 
     /// Clear all session data (except cookie)
     pub fn clear(&mut self) {
         self.data.clear();
+        self.key_expires.clear();
+        self.raw = None;
     }
 
     /// Check if session data is empty (no user data)
@@ -233,51 +547,346 @@ impl SessionData {
 
 /// Session wrapper that tracks modifications
 pub struct Session {
-    /// Session ID
-    id: String,
+    /// Session ID, generated on first use for lazily created sessions
+    id: Arc<OnceLock<String>>,
 
     /// Session data
     data: Arc<RwLock<SessionData>>,
 
-    /// Whether the session has been modified
+    /// Whether the session has been written to
     modified: Arc<AtomicBool>,
 
+    /// Data as last loaded or saved, to tell real changes from no-op writes
+    original: Arc<RwLock<SessionData>>,
+
+    /// Whether the cookie attributes have been changed by a handler
+    cookie_modified: Arc<AtomicBool>,
+
     /// Whether this is a new session
-    is_new: bool,
+    is_new: Arc<AtomicBool>,
+
+    /// Deferred store load, for lazily loaded sessions not yet accessed;
+    /// held while loading so concurrent loads wait for the data
+    pending: Arc<tokio::sync::Mutex<Option<PendingLoad>>>,
+
+    /// Whether the session data is loaded (always true unless deferred)
+    loaded: Arc<AtomicBool>,
+
+    /// Whether a deferred store load failed
+    load_failed: Arc<AtomicBool>,
+
+    /// Removals made before the deferred load, applied to the loaded data
+    blind_removals: Arc<Mutex<BlindRemovals>>,
+
+    /// Store writer for explicit saves, attached by the handler
+    saver: Arc<OnceLock<SessionSaver>>,
+
+    /// Store reader for explicit reloads, attached by the handler
+    reloader: Arc<OnceLock<SessionReloader>>,
+
+    /// Whether the session was explicitly saved during the request
+    saved: Arc<AtomicBool>,
+
+    /// Maximum serialized size enforced by [`Self::try_set`], set by the handler
+    max_size: Arc<OnceLock<usize>>,
+
+    /// Whether the request may only read the session
+    read_only: Arc<AtomicBool>,
 
     /// Whether the session should be destroyed
     destroy: Arc<AtomicBool>,
 
     /// Whether the session should be regenerated
     regenerate: Arc<AtomicBool>,
+
+    /// Pending remember-me change: issue (true) or revoke (false) the token
+    remember: Arc<Mutex<Option<bool>>>,
+
+    /// Keys whose change regenerates the session ID, besides the configured ones
+    privilege_keys: Arc<Mutex<Vec<String>>>,
 }
 
 impl Session {
     /// Create a new session with the given ID and data
     pub fn new(id: String, data: SessionData, is_new: bool) -> Self {
+        Self::build(OnceLock::from(id), data, is_new, None)
+    }
+
+    /// Create a new session whose ID is only generated when first needed
+    ///
+    /// Used by lazy session creation: until a handler reads the ID or the
+    /// session is persisted, no ID exists.
+    pub fn new_lazy(data: SessionData) -> Self {
+        Self::build(OnceLock::new(), data, true, None)
+    }
+
+    /// Create a session whose data is only fetched from the store by [`Self::load`]
+    ///
+    /// Until then the accessors see `fresh`, and changes made to it are
+    /// applied on top of the stored data once loaded. If the loader finds no
+    /// session, `fresh` is kept and the session gets a new ID when saved, so
+    /// an unknown ID is never adopted.
+    pub fn new_deferred(sid: String, loader: SessionLoader, fresh: SessionData) -> Self {
+        let pending = PendingLoad { loader };
+        Self::build(OnceLock::from(sid), fresh, false, Some(pending))
+    }
+
+    fn build(
+        id: OnceLock<String>,
+        data: SessionData,
+        is_new: bool,
+        pending: Option<PendingLoad>,
+    ) -> Self {
         Self {
-            id,
+            id: Arc::new(id),
+            original: Arc::new(RwLock::new(data.clone())),
             data: Arc::new(RwLock::new(data)),
             modified: Arc::new(AtomicBool::new(false)),
-            is_new,
+            cookie_modified: Arc::new(AtomicBool::new(false)),
+            is_new: Arc::new(AtomicBool::new(is_new)),
+            loaded: Arc::new(AtomicBool::new(pending.is_none())),
+            pending: Arc::new(tokio::sync::Mutex::new(pending)),
+            load_failed: Arc::new(AtomicBool::new(false)),
+            blind_removals: Arc::new(Mutex::new(BlindRemovals::default())),
+            saver: Arc::new(OnceLock::new()),
+            reloader: Arc::new(OnceLock::new()),
+            saved: Arc::new(AtomicBool::new(false)),
+            max_size: Arc::new(OnceLock::new()),
+            read_only: Arc::new(AtomicBool::new(false)),
             destroy: Arc::new(AtomicBool::new(false)),
             regenerate: Arc::new(AtomicBool::new(false)),
+            remember: Arc::new(Mutex::new(None)),
+            privilege_keys: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Fetch deferred session data from the store, if not loaded yet
+    ///
+    /// Handlers of lazily loaded sessions must await this before reading
+    /// the session; the synchronous accessors never touch the store. The
+    /// [`Session`] extractor, [`SessionDepotExt::load_session`](crate::SessionDepotExt::load_session)
+    /// and the built-in hoops do it for you.
+    pub async fn load(&self) -> Result<(), SessionError> {
+        let mut pending = self.pending.lock().await;
+        let Some(load) = pending.take() else {
+            return Ok(());
+        };
+
+        let loaded = (load.loader)().await;
+        let mut removals = self.blind_removals.lock();
+        let removed = std::mem::take(&mut *removals);
+        let result = match loaded {
+            Ok(Some(data)) => {
+                self.install(data, &removed);
+                Ok(())
+            }
+            Ok(None) => {
+                self.orphan();
+                Ok(())
+            }
+            Err(e) => {
+                self.orphan();
+                self.load_failed.store(true, Ordering::SeqCst);
+                Err(e)
+            }
+        };
+        self.loaded.store(true, Ordering::SeqCst);
+        result
+    }
+
+    /// Replace the placeholder with loaded data, keeping changes made to it
+    fn install(&self, loaded: SessionData, removals: &BlindRemovals) {
+        let mut data = self.data.write();
+        let changed = data.changed_keys(&self.original.read());
+        let mut merged = loaded.clone();
+        removals.apply(&mut merged);
+        merged.apply_keys(&data, &changed);
+        if self.cookie_modified.load(Ordering::SeqCst) {
+            merged.cookie = data.cookie.clone();
+        }
+        *self.original.write() = loaded;
+        *data = merged;
+    }
+
+    /// Give up the requested ID after it was found missing or unloadable
+    fn orphan(&self) {
+        self.is_new.store(true, Ordering::SeqCst);
+        self.regenerate.store(true, Ordering::SeqCst);
+    }
+
+    /// Check if the session data has been loaded (always true unless deferred)
+    pub fn is_loaded(&self) -> bool {
+        self.loaded.load(Ordering::SeqCst)
+    }
+
+    /// Check if a deferred store load failed
+    pub fn load_failed(&self) -> bool {
+        self.load_failed.load(Ordering::SeqCst)
+    }
+
+    /// Record a removal made before the deferred load; `None` clears all data
+    fn remove_before_load(&self, key: Option<&str>) {
+        let mut removals = self.blind_removals.lock();
+        if self.is_loaded() {
+            return;
+        }
+        match key {
+            Some(key) => removals.keys.push(key.to_string()),
+            None => {
+                removals.cleared = true;
+                removals.keys.clear();
+            }
         }
+        self.modified.store(true, Ordering::SeqCst);
+    }
+
+    /// Read access to the session data (the placeholder until deferred data is loaded)
+    pub(crate) fn read(&self) -> RwLockReadGuard<'_, SessionData> {
+        self.data.read()
+    }
+
+    /// Write access to the session data (the placeholder until deferred data is loaded)
+    pub(crate) fn write(&self) -> RwLockWriteGuard<'_, SessionData> {
+        let mut data = self.data.write();
+        data.raw = None;
+        data
     }
 
     /// Get the session ID
     pub fn id(&self) -> &str {
-        &self.id
+        self.id.get_or_init(generate_session_id)
+    }
+
+    /// Check if the session ID has been generated yet
+    pub fn has_id(&self) -> bool {
+        self.id.get().is_some()
     }
 
     /// Check if this is a new session
+    ///
+    /// Lazily loaded sessions only know once [`Self::load`] has run.
     pub fn is_new(&self) -> bool {
-        self.is_new
+        self.is_new.load(Ordering::SeqCst)
     }
 
     /// Check if the session has been modified
+    ///
+    /// Like express-session's hash comparison, writes that leave the user
+    /// data as it was loaded (e.g. setting a key to its current value) don't
+    /// count, so they cause no store write or rolling cookie. Cookie changes
+    /// and regeneration always count.
     pub fn is_modified(&self) -> bool {
-        self.modified.load(Ordering::SeqCst)
+        if !self.modified.load(Ordering::SeqCst) {
+            return false;
+        }
+        if self.is_cookie_modified() || self.should_regenerate() {
+            return true;
+        }
+        if !self.blind_removals.lock().is_empty() {
+            return true;
+        }
+        let data = self.data.read();
+        let original = self.original.read();
+        data.data != original.data || data.key_expires != original.key_expires
+    }
+
+    /// Keys set, changed or removed since the data was loaded or last saved
+    ///
+    /// Removed keys are included; they are absent from [`Self::data`].
+    pub fn dirty_keys(&self) -> Vec<String> {
+        self.read().changed_keys(&self.original.read())
+    }
+
+    /// Flag the session data as changed so it gets saved
+    pub(crate) fn mark_modified(&self) {
+        self.modified.store(true, Ordering::SeqCst);
+    }
+
+    /// Attach the store writer used by [`Self::save`]
+    pub(crate) fn set_saver(&self, saver: SessionSaver) {
+        let _ = self.saver.set(saver);
+    }
+
+    /// Persist the session to the store now, like express-session's `req.session.save()`
+    ///
+    /// Useful before doing something irreversible (e.g. emailing a token
+    /// stored in the session). Changes made after saving are still persisted
+    /// after the response; an unchanged session isn't written twice. If
+    /// concurrent changes were merged in by [`ConflictStrategy::RetryMerge`](crate::ConflictStrategy::RetryMerge),
+    /// the session reflects them afterwards.
+    ///
+    /// Fails with [`SessionError::ReadOnly`] in read-only requests.
+    pub async fn save(&self) -> Result<(), SessionError> {
+        if self.is_read_only() {
+            return Err(SessionError::ReadOnly);
+        }
+        let saver = self.saver.get().cloned().ok_or_else(|| {
+            SessionError::StoreError("Session is not attached to a store".to_string())
+        })?;
+        self.load().await?;
+
+        let saved = saver(self.id().to_string(), self.data(), self.dirty_keys()).await?;
+        *self.original.write() = saved.clone();
+        *self.data.write() = saved;
+        self.modified.store(false, Ordering::SeqCst);
+        self.saved.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Attach the store reader used by [`Self::reload`]
+    pub(crate) fn set_reloader(&self, reloader: SessionReloader) {
+        let _ = self.reloader.set(reloader);
+    }
+
+    /// Replace the session data with the current copy from the store, like
+    /// express-session's `req.session.reload()`
+    ///
+    /// Lets long-running handlers observe changes made by concurrent
+    /// requests. Unsaved changes are discarded. Fails with
+    /// [`SessionError::NotFound`] if the session is no longer in the store.
+    pub async fn reload(&self) -> Result<(), SessionError> {
+        let reloader = self.reloader.get().cloned().ok_or_else(|| {
+            SessionError::StoreError("Session is not attached to a store".to_string())
+        })?;
+        self.load().await?;
+
+        let data = reloader(self.id().to_string())
+            .await?
+            .ok_or(SessionError::NotFound)?;
+        *self.original.write() = data.clone();
+        *self.data.write() = data;
+        self.modified.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Check if the request may only read the session
+    ///
+    /// True for GET and HEAD requests with
+    /// [`SessionConfig::with_read_only_safe_methods`](crate::SessionConfig::with_read_only_safe_methods).
+    /// Changes made by such requests are dropped with a warning.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::SeqCst)
+    }
+
+    /// Restrict the request to reading the session
+    pub(crate) fn set_read_only(&self) {
+        self.read_only.store(true, Ordering::SeqCst);
+    }
+
+    /// Check if the session was explicitly saved during this request
+    pub fn is_saved(&self) -> bool {
+        self.saved.load(Ordering::SeqCst)
+    }
+
+    /// Check if the cookie attributes have been changed by a handler
+    pub fn is_cookie_modified(&self) -> bool {
+        self.cookie_modified.load(Ordering::SeqCst)
+    }
+
+    /// Mark the session and its cookie as modified
+    fn mark_cookie_modified(&self) {
+        self.modified.store(true, Ordering::SeqCst);
+        self.cookie_modified.store(true, Ordering::SeqCst);
     }
 
     /// Check if the session should be destroyed
@@ -290,34 +899,265 @@ impl Session {
         self.regenerate.load(Ordering::SeqCst)
     }
 
+    /// Request issuing (true) or revoking (false) the remember-me token
+    pub(crate) fn set_remember(&self, remember: bool) {
+        *self.remember.lock() = Some(remember);
+    }
+
+    /// Pending remember-me change, if any
+    pub(crate) fn remember_request(&self) -> Option<bool> {
+        *self.remember.lock()
+    }
+
+    /// Regenerate the session ID if any of these keys change during the request
+    ///
+    /// Adds to the keys configured with
+    /// [`SessionConfig::with_privilege_keys`](crate::SessionConfig::with_privilege_keys).
+    /// The data moves to the new ID as with [`Self::regenerate_keep_data`].
+    pub fn cycle_on_privilege_change<I, S>(&self, keys: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.privilege_keys
+            .lock()
+            .extend(keys.into_iter().map(Into::into));
+    }
+
+    /// Whether a privilege key, configured or registered on the session, changed
+    pub(crate) fn privilege_changed(&self, configured: &[String]) -> bool {
+        let registered = self.privilege_keys.lock();
+        if configured.is_empty() && registered.is_empty() {
+            return false;
+        }
+        self.dirty_keys()
+            .iter()
+            .any(|key| configured.contains(key) || registered.contains(key))
+    }
+
     /// Get a value from the session
     pub fn get<T: for<'de> Deserialize<'de>>(&self, key: &str) -> Option<T> {
-        self.data.read().get(key)
+        self.read().get(key)
     }
 
     /// Set a value in the session
     pub fn set<T: Serialize>(&self, key: &str, value: T) {
-        self.data.write().set(key, value);
+        self.write().set(key, value);
+        self.modified.store(true, Ordering::SeqCst);
+    }
+
+    /// Set a value, failing if the session would exceed its maximum size
+    ///
+    /// Returns [`SessionError::TooLarge`], leaving the session unchanged, if
+    /// [`SessionConfig::with_max_size`](crate::SessionConfig::with_max_size)
+    /// is set and the serialized session would be larger than that, and
+    /// [`SessionError::ReadOnly`] in read-only requests.
+    pub fn try_set<T: Serialize>(&self, key: &str, value: T) -> Result<(), SessionError> {
+        if self.is_read_only() {
+            return Err(SessionError::ReadOnly);
+        }
+        let value = serde_json::to_value(value)?;
+        let mut data = self.write();
+        let previous = data.data.insert(key.to_string(), value);
+
+        if let Some(&limit) = self.max_size.get() {
+            let size = data.serialized_size();
+            if size > limit {
+                match previous {
+                    Some(previous) => data.data.insert(key.to_string(), previous),
+                    None => data.data.remove(key),
+                };
+                return Err(SessionError::TooLarge { size, limit });
+            }
+        }
+
+        data.key_expires.remove(key);
+        drop(data);
+        self.modified.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Set the maximum size enforced by [`Self::try_set`]
+    pub(crate) fn set_max_size(&self, limit: usize) {
+        let _ = self.max_size.set(limit);
+    }
+
+    /// Set a value that expires after `ttl`, e.g. a short-lived verification code
+    ///
+    /// Once expired the key reads as missing; it is removed from the stored
+    /// session on the next load or save. Setting the key again without a TTL
+    /// makes it permanent.
+    pub fn set_with_ttl<T: Serialize>(&self, key: &str, value: T, ttl: std::time::Duration) {
+        self.write().set_with_ttl(key, value, ttl);
+        self.modified.store(true, Ordering::SeqCst);
+    }
+
+    /// Set several values at once, under a single lock acquisition
+    pub fn set_many<I, K, V>(&self, entries: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: Serialize,
+    {
+        let mut data = self.write();
+        let mut changed = false;
+        for (key, value) in entries {
+            data.set(key.as_ref(), value);
+            changed = true;
+        }
+        if changed {
+            self.modified.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Shallow-merge the fields of a JSON object into the session
+    ///
+    /// Top-level keys of `value` overwrite existing ones, dropping any TTL
+    /// they were set with. Fails if `value` is not an object.
+    pub fn merge(&self, value: Value) -> Result<(), SessionError> {
+        let Value::Object(fields) = value else {
+            return Err(SessionError::SerializationError(
+                "Only JSON objects can be merged into a session".to_string(),
+            ));
+        };
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        let mut data = self.write();
+        for key in fields.keys() {
+            data.key_expires.remove(key);
+        }
+        data.data.extend(fields);
+        drop(data);
+        self.modified.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Get a value, inserting the computed default if it is missing
+    ///
+    /// The check and insert happen under the session lock. A stored value
+    /// that doesn't deserialize as `T` is replaced by the default.
+    pub fn get_or_insert_with<T, F>(&self, key: &str, default: F) -> T
+    where
+        T: Serialize + for<'de> Deserialize<'de>,
+        F: FnOnce() -> T,
+    {
+        let mut data = self.write();
+        if let Some(value) = data.get(key) {
+            return value;
+        }
+
+        let value = default();
+        data.set(key, &value);
+        self.modified.store(true, Ordering::SeqCst);
+        value
+    }
+
+    /// Get a value at a dotted path inside nested data, e.g. `"user.profile.name"`
+    ///
+    /// Segments index into objects by key and into arrays by position.
+    pub fn get_path<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Option<T> {
+        let data = self.read();
+        let mut segments = path.split('.');
+        let mut current = data.data.get(segments.next()?)?;
+        for segment in segments {
+            current = match current {
+                Value::Object(map) => map.get(segment)?,
+                Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+        serde_json::from_value(current.clone()).ok()
+    }
+
+    /// Set a value at a dotted path inside nested data, e.g. `"user.profile.name"`
+    ///
+    /// Missing or non-object intermediate values are replaced by objects;
+    /// existing arrays are indexed by position. Like [`Self::set`], this
+    /// drops any TTL of the top-level key. Returns false, leaving the
+    /// session unchanged, if an array index is out of bounds or the value
+    /// can't be serialized.
+    pub fn set_path<T: Serialize>(&self, path: &str, value: T) -> bool {
+        let Ok(value) = serde_json::to_value(value) else {
+            return false;
+        };
+
+        let mut data = self.write();
+        let mut segments = path.split('.');
+        let Some(first) = segments.next() else {
+            return false;
+        };
+        let mut current = data.data.entry(first.to_string()).or_insert(Value::Null);
+        for segment in segments {
+            if let Value::Array(items) = current {
+                match segment.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+                    Some(item) => current = item,
+                    None => return false,
+                }
+                continue;
+            }
+            if !current.is_object() {
+                *current = Value::Object(serde_json::Map::new());
+            }
+            let Value::Object(map) = current else {
+                unreachable!("value was just made an object");
+            };
+            current = map.entry(segment).or_insert(Value::Null);
+        }
+        *current = value;
+        data.key_expires.remove(first);
+        drop(data);
+
         self.modified.store(true, Ordering::SeqCst);
+        true
+    }
+
+    /// Add `delta` to a numeric value and return the result
+    ///
+    /// The update happens under the session lock. A missing or non-integer
+    /// value counts as zero.
+    pub fn incr(&self, key: &str, delta: i64) -> i64 {
+        let mut data = self.write();
+        let value = data.get::<i64>(key).unwrap_or(0).saturating_add(delta);
+        data.set(key, value);
+        self.modified.store(true, Ordering::SeqCst);
+        value
+    }
+
+    /// Subtract `delta` from a numeric value and return the result
+    pub fn decr(&self, key: &str, delta: i64) -> i64 {
+        self.incr(key, delta.saturating_neg())
     }
 
     /// Remove a value from the session
     pub fn remove(&self, key: &str) -> Option<Value> {
-        let result = self.data.write().remove(key);
+        let result = self.write().remove(key);
+        self.remove_before_load(Some(key));
         if result.is_some() {
             self.modified.store(true, Ordering::SeqCst);
         }
         result
     }
 
+    /// Remove a value and return it deserialized, e.g. for one-shot tokens
+    ///
+    /// The session is only marked modified if the key existed. A value that
+    /// doesn't deserialize as `T` is still removed.
+    pub fn take<T: for<'de> Deserialize<'de>>(&self, key: &str) -> Option<T> {
+        let value = self.remove(key)?;
+        serde_json::from_value(value).ok()
+    }
+
     /// Check if a key exists in the session
     pub fn contains(&self, key: &str) -> bool {
-        self.data.read().contains(key)
+        self.read().contains(key)
     }
 
     /// Clear all session data
     pub fn clear(&self) {
-        self.data.write().clear();
+        self.write().clear();
+        self.remove_before_load(None);
         self.modified.store(true, Ordering::SeqCst);
     }
 
@@ -326,15 +1166,43 @@ impl Session {
         self.destroy.store(true, Ordering::SeqCst);
     }
 
-    /// Mark the session for regeneration (new ID)
+    /// Mark the session for regeneration (new ID), keeping its data
+    ///
+    /// Same as [`Self::regenerate_keep_data`]. Note that express-session's
+    /// `req.session.regenerate()` starts over with an empty session; use
+    /// [`Self::regenerate_clear`] for that.
     pub fn regenerate(&self) {
+        self.regenerate_keep_data();
+    }
+
+    /// Move the session's data to a new ID, e.g. to prevent fixation on login
+    ///
+    /// The old session is destroyed after the request.
+    pub fn regenerate_keep_data(&self) {
         self.regenerate.store(true, Ordering::SeqCst);
         self.modified.store(true, Ordering::SeqCst);
     }
 
+    /// Replace the session with a fresh, empty one under a new ID, like
+    /// express-session's `req.session.regenerate()`
+    ///
+    /// The data is cleared immediately, so values set afterwards belong to
+    /// the new session; its absolute lifetime and cookie expiry start over.
+    /// The old session is destroyed after the request.
+    pub fn regenerate_clear(&self) {
+        {
+            let mut data = self.write();
+            data.clear();
+            data.created_at = Some(Utc::now());
+            data.cookie.touch();
+        }
+        self.remove_before_load(None);
+        self.regenerate_keep_data();
+    }
+
     /// Touch the session - update cookie expiration
     pub fn touch(&self) {
-        self.data.write().cookie.touch();
+        self.write().cookie.touch();
     }
 
     /// Set the cookie expiration time directly
@@ -350,8 +1218,8 @@ impl Session {
     /// session.set_cookie_expires(Some(expires));
     /// ```
     pub fn set_cookie_expires(&self, expires: Option<DateTime<Utc>>) {
-        self.data.write().cookie.set_expires(expires);
-        self.modified.store(true, Ordering::SeqCst);
+        self.write().cookie.set_expires(expires);
+        self.mark_cookie_modified();
     }
 
     /// Set the cookie max age in milliseconds
@@ -364,8 +1232,8 @@ impl Session {
     /// session.set_cookie_max_age(Some(60 * 60 * 1000));
     /// ```
     pub fn set_cookie_max_age(&self, max_age_ms: Option<i64>) {
-        self.data.write().cookie.set_max_age(max_age_ms);
-        self.modified.store(true, Ordering::SeqCst);
+        self.write().cookie.set_max_age(max_age_ms);
+        self.mark_cookie_modified();
     }
 
     /// Set the cookie max age in seconds (convenience method)
@@ -376,40 +1244,118 @@ impl Session {
     /// session.set_cookie_max_age_secs(3600);
     /// ```
     pub fn set_cookie_max_age_secs(&self, max_age_secs: u64) {
-        self.data.write().cookie.set_max_age_secs(max_age_secs);
-        self.modified.store(true, Ordering::SeqCst);
+        self.write().cookie.set_max_age_secs(max_age_secs);
+        self.mark_cookie_modified();
+    }
+
+    /// Set the cookie's Secure flag, like `req.session.cookie.secure = true`
+    pub fn set_cookie_secure(&self, secure: bool) {
+        self.write().cookie.secure = secure;
+        self.mark_cookie_modified();
+    }
+
+    /// Set the cookie's HttpOnly flag, like `req.session.cookie.httpOnly = false`
+    pub fn set_cookie_http_only(&self, http_only: bool) {
+        self.write().cookie.http_only = http_only;
+        self.mark_cookie_modified();
+    }
+
+    /// Set the cookie's SameSite attribute, like `req.session.cookie.sameSite = 'strict'`
+    pub fn set_cookie_same_site(&self, same_site: SameSite) {
+        self.write().cookie.same_site = Some(same_site.as_str().to_string());
+        self.mark_cookie_modified();
+    }
+
+    /// Set the cookie's Domain attribute, like `req.session.cookie.domain = '.example.com'`
+    ///
+    /// Browsers treat cookies with a different domain or path as distinct,
+    /// so the cookie previously issued under the old one is left in place.
+    pub fn set_cookie_domain(&self, domain: Option<String>) {
+        self.write().cookie.domain = domain;
+        self.mark_cookie_modified();
+    }
+
+    /// Set the cookie's Path attribute, like `req.session.cookie.path = '/app'`
+    ///
+    /// See [`Self::set_cookie_domain`] about the previously issued cookie.
+    /// Not to be confused with [`Self::set_path`], which sets nested data.
+    pub fn set_cookie_path<P: Into<String>>(&self, path: P) {
+        self.write().cookie.path = path.into();
+        self.mark_cookie_modified();
     }
 
     /// Get a copy of the session data
+    ///
+    /// Copies every value; prefer [`Self::with_data`] to inspect large sessions.
     pub fn data(&self) -> SessionData {
-        self.data.read().clone()
+        self.read().clone()
+    }
+
+    /// Run `f` with a borrow of the session data, without copying it
+    ///
+    /// The session is locked while `f` runs, so `f` must not access the
+    /// session itself.
+    pub fn with_data<R, F: FnOnce(&SessionData) -> R>(&self, f: F) -> R {
+        f(&self.read())
     }
 
     /// Get the session cookie
     pub fn cookie(&self) -> SessionCookie {
-        self.data.read().cookie.clone()
+        self.read().cookie.clone()
     }
 
     /// Check if the session is expired
     pub fn is_expired(&self) -> bool {
-        self.data.read().cookie.is_expired()
+        self.read().cookie.is_expired()
     }
 
     /// Check if the session is empty (no user data)
     pub fn is_empty(&self) -> bool {
-        self.data.read().is_empty()
+        self.read().is_empty()
+    }
+
+    /// Number of user data keys (excluding `cookie`)
+    pub fn len(&self) -> usize {
+        self.read().data.len()
+    }
+
+    /// Snapshot of the user data keys (excluding `cookie`)
+    pub fn keys(&self) -> Vec<String> {
+        self.read().data.keys().cloned().collect()
+    }
+
+    /// Snapshot of the user data entries (excluding `cookie`)
+    pub fn entries(&self) -> Vec<(String, Value)> {
+        self.read()
+            .data
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
     }
 }
 
 impl Clone for Session {
     fn clone(&self) -> Self {
         Self {
-            id: self.id.clone(),
+            id: Arc::clone(&self.id),
             data: Arc::clone(&self.data),
             modified: Arc::clone(&self.modified),
-            is_new: self.is_new,
+            original: Arc::clone(&self.original),
+            cookie_modified: Arc::clone(&self.cookie_modified),
+            is_new: Arc::clone(&self.is_new),
+            pending: Arc::clone(&self.pending),
+            loaded: Arc::clone(&self.loaded),
+            load_failed: Arc::clone(&self.load_failed),
+            blind_removals: Arc::clone(&self.blind_removals),
+            saver: Arc::clone(&self.saver),
+            reloader: Arc::clone(&self.reloader),
+            saved: Arc::clone(&self.saved),
+            max_size: Arc::clone(&self.max_size),
+            read_only: Arc::clone(&self.read_only),
             destroy: Arc::clone(&self.destroy),
             regenerate: Arc::clone(&self.regenerate),
+            remember: Arc::clone(&self.remember),
+            privilege_keys: Arc::clone(&self.privilege_keys),
         }
     }
 }
@@ -417,10 +1363,305 @@ impl Clone for Session {
 impl std::fmt::Debug for Session {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Session")
-            .field("id", &self.id)
+            .field("id", &self.id.get())
             .field("data", &*self.data.read())
             .field("modified", &self.modified.load(Ordering::SeqCst))
-            .field("is_new", &self.is_new)
+            .field("is_new", &self.is_new.load(Ordering::SeqCst))
+            .field("loaded", &self.is_loaded())
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        Session::new("sid".to_string(), SessionData::default(), false)
+    }
+
+    #[test]
+    fn test_get_or_insert_with() {
+        let session = session();
+        assert_eq!(session.get_or_insert_with("views", || 1), 1);
+        assert!(session.is_modified());
+        assert_eq!(session.get_or_insert_with("views", || 5), 1);
+    }
+
+    #[test]
+    fn test_take() {
+        let session = session();
+        assert_eq!(session.take::<String>("state"), None);
+        assert!(!session.is_modified());
+
+        session.set("state", "xyz");
+        assert_eq!(session.take::<String>("state").as_deref(), Some("xyz"));
+        assert!(!session.contains("state"));
+    }
+
+    #[test]
+    fn test_incr_decr() {
+        let session = session();
+        assert_eq!(session.incr("views", 1), 1);
+        assert_eq!(session.incr("views", 2), 3);
+        assert_eq!(session.decr("views", 5), -2);
+        assert_eq!(session.get::<i64>("views"), Some(-2));
+    }
+
+    #[test]
+    fn test_no_op_writes_are_not_modifications() {
+        let mut data = SessionData::default();
+        data.set("user", "alice");
+        let session = Session::new("sid".to_string(), data, false);
+
+        session.set("user", "alice");
+        assert!(!session.is_modified());
+
+        session.set("user", "bob");
+        assert!(session.is_modified());
+        session.set("user", "alice");
+        assert!(!session.is_modified());
+
+        session.set_cookie_max_age_secs(60);
+        assert!(session.is_modified());
+    }
+
+    #[test]
+    fn test_dirty_keys() {
+        let mut data = SessionData::default();
+        data.set("user", "alice");
+        data.set("theme", "dark");
+        let session = Session::new("sid".to_string(), data, false);
+
+        session.set("user", "alice");
+        assert!(session.dirty_keys().is_empty());
+
+        session.set("views", 1);
+        session.remove("theme");
+        let mut dirty = session.dirty_keys();
+        dirty.sort();
+        assert_eq!(dirty, vec!["theme", "views"]);
+    }
+
+    #[test]
+    fn test_apply_changes() {
+        let mut current = SessionData::default();
+        current.set("cart", vec![1]);
+        current.set("theme", "dark");
+        current.version = Some(3);
+
+        let mut ours = SessionData::default();
+        ours.set("user", "alice");
+        ours.version = Some(2);
+
+        current.apply_changes(&ours, &["user".to_string(), "theme".to_string()]);
+        assert_eq!(current.get::<String>("user").as_deref(), Some("alice"));
+        assert_eq!(current.get::<Vec<i32>>("cart"), Some(vec![1]));
+        assert!(!current.contains("theme"));
+        assert_eq!(current.version, Some(2));
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let session = session();
+        session.set_max_size(SessionData::default().serialized_size() + 20);
+
+        assert!(session.try_set("user", "alice").is_ok());
+        let err = session.try_set("user", "x".repeat(100)).unwrap_err();
+        assert!(matches!(err, SessionError::TooLarge { .. }));
+        assert_eq!(session.get::<String>("user").as_deref(), Some("alice"));
+
+        let mut data = SessionData::default();
+        data.set("small", 1);
+        data.set("large", "x".repeat(100));
+        let limit = data.serialized_size() - 50;
+        assert_eq!(data.truncate_to(limit), vec!["large"]);
+        assert!(data.contains("small"));
+    }
+
+    #[test]
+    fn test_key_ttl() {
+        let session = session();
+        session.set_with_ttl("code", "123456", std::time::Duration::from_secs(60));
+        session.set_with_ttl("stale", "x", std::time::Duration::ZERO);
+        assert_eq!(session.get::<String>("code").as_deref(), Some("123456"));
+        assert!(!session.contains("stale"));
+
+        let mut data = session.data();
+        data.prune_expired_keys();
+        assert!(data.data.contains_key("code"));
+        assert!(!data.data.contains_key("stale"));
+
+        // Setting without a TTL makes the key permanent
+        session.set("code", "654321");
+        assert!(!session.data().key_expires.contains_key("code"));
+
+        // So do merging over it and writing inside it
+        session.set_with_ttl("merged", "x", std::time::Duration::ZERO);
+        session.merge(serde_json::json!({ "merged": "y" })).unwrap();
+        assert_eq!(session.get::<String>("merged").as_deref(), Some("y"));
+
+        session.set_with_ttl("nested", serde_json::json!({}), std::time::Duration::ZERO);
+        assert!(session.set_path("nested.value", 1));
+        assert_eq!(session.get_path::<i64>("nested.value"), Some(1));
+        assert!(session.contains("nested"));
+
+        session.set_with_ttl("tried", "x", std::time::Duration::ZERO);
+        session.try_set("tried", "y").unwrap();
+        assert_eq!(session.get::<String>("tried").as_deref(), Some("y"));
+        let data = session.data();
+        assert!(["merged", "nested", "tried"]
+            .iter()
+            .all(|key| !data.key_expires.contains_key(*key)));
+    }
+
+    #[test]
+    fn test_regenerate_variants() {
+        let kept = session();
+        kept.set("user", "alice");
+        kept.regenerate_keep_data();
+        assert!(kept.should_regenerate());
+        assert_eq!(kept.get::<String>("user").as_deref(), Some("alice"));
+
+        let cleared = session();
+        cleared.set("user", "alice");
+        cleared.regenerate_clear();
+        assert!(cleared.should_regenerate());
+        assert!(cleared.is_empty());
+    }
+
+    #[test]
+    fn test_cookie_mutation() {
+        let session = session();
+        session.set_cookie_secure(true);
+        session.set_cookie_same_site(SameSite::Strict);
+        session.set_cookie_domain(Some("example.com".to_string()));
+        session.set_cookie_path("/app");
+
+        assert!(session.is_cookie_modified());
+        let cookie = session.cookie();
+        assert!(cookie.secure);
+        assert_eq!(cookie.same_site.as_deref(), Some("strict"));
+        assert_eq!(cookie.domain.as_deref(), Some("example.com"));
+        assert_eq!(cookie.path, "/app");
+    }
+
+    #[test]
+    fn test_introspection() {
+        let session = session();
+        session.set("user", "alice");
+        session.set("views", 3);
+
+        assert_eq!(session.len(), 2);
+        let mut keys = session.keys();
+        keys.sort();
+        assert_eq!(keys, vec!["user", "views"]);
+        assert!(session
+            .entries()
+            .contains(&("views".to_string(), serde_json::json!(3))));
+    }
+
+    #[test]
+    fn test_nested_paths() {
+        let session = session();
+        session.set("user", serde_json::json!({ "roles": ["admin"] }));
+
+        assert!(session.set_path("user.profile.name", "alice"));
+        assert!(session.set_path("user.roles.0", "owner"));
+        assert!(!session.set_path("user.roles.5", "none"));
+
+        assert_eq!(
+            session.get_path::<String>("user.profile.name").as_deref(),
+            Some("alice")
+        );
+        assert_eq!(
+            session.get_path::<String>("user.roles.0").as_deref(),
+            Some("owner")
+        );
+        assert_eq!(session.get_path::<String>("user.missing"), None);
+    }
+
+    #[test]
+    fn test_bulk_updates() {
+        let session = session();
+        session.set_many([("user", "alice"), ("role", "admin")]);
+        assert_eq!(session.len(), 2);
+
+        session
+            .merge(serde_json::json!({ "role": "owner", "views": 1 }))
+            .unwrap();
+        assert_eq!(session.get::<String>("role").as_deref(), Some("owner"));
+        assert_eq!(session.get::<i32>("views"), Some(1));
+        assert!(session.merge(serde_json::json!([1, 2])).is_err());
+    }
+
+    #[test]
+    fn test_raw_json_reuse() {
+        // Written by Node.js: key order and spacing differ from serde_json's
+        let json = r#"{"user": "alice", "cookie": {"originalMaxAge": null, "path": "/"}}"#;
+        let mut data = SessionData::from_json(json.to_string()).unwrap();
+        assert_eq!(data.to_json().unwrap(), json);
+        assert_eq!(data.serialized_size(), json.len());
+
+        data.prune_expired_keys();
+        assert_eq!(data.to_json().unwrap(), json);
+
+        data.set("user", "bob");
+        let saved: Value = serde_json::from_str(&data.to_json().unwrap()).unwrap();
+        assert_eq!(saved["user"], "bob");
+
+        // Changes through the session wrapper count too
+        let session = Session::new(
+            "sid".to_string(),
+            SessionData::from_json(json.to_string()).unwrap(),
+            false,
+        );
+        assert!(session.with_data(|data| data.raw.is_some()));
+        session.set("theme", "dark");
+        assert!(session.with_data(|data| data.raw.is_none()));
+    }
+
+    #[test]
+    fn test_with_data() {
+        let session = session();
+        session.set("user", "alice");
+        let user = session.with_data(|data| data.get::<String>("user"));
+        assert_eq!(user.as_deref(), Some("alice"));
+        assert_eq!(
+            session.with_data(SessionData::serialized_size),
+            session.data().serialized_size()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deferred_load() {
+        let loader: SessionLoader = Box::new(|| {
+            Box::pin(async {
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                let mut stored = SessionData::new(3600);
+                stored.set("visits", 1);
+                stored.set("theme", "dark");
+                Ok(Some(stored))
+            })
+        });
+        let session = Session::new_deferred("sid".to_string(), loader, SessionData::new(3600));
+        let other = session.clone();
+
+        // Reads see the placeholder, writes are kept through the load
+        assert_eq!(session.get::<u64>("visits"), None);
+        session.set("theme", "light");
+        assert!(!session.is_loaded());
+
+        // A concurrent load waits for the data instead of seeing the placeholder
+        let (first, second) = tokio::join!(session.load(), async {
+            other.load().await.unwrap();
+            other.get::<u64>("visits")
+        });
+        first.unwrap();
+        assert_eq!(second, Some(1));
+        assert_eq!(session.get::<String>("theme").as_deref(), Some("light"));
+        assert_eq!(session.dirty_keys(), vec!["theme".to_string()]);
+        assert_eq!(session.id(), "sid");
+        assert!(!session.is_new());
+    }
+}
@@ -0,0 +1,275 @@
+//! CSRF protection built on the session (csurf compatible)
+//!
+//! Tokens follow the Node.js `csrf` package used by `csurf`: a per-session
+//! secret is stored in the session under `csrfSecret`, and each token is
+//! `<salt>-<base64url(sha1(<salt>-<secret>))>`. Node.js and Rust services
+//! sharing sessions therefore accept each other's tokens.
+//!
+//! ```rust,ignore
+//! #[handler]
+//! async fn form(depot: &mut Depot) -> String {
+//!     let token = depot.session().unwrap().csrf_token();
+//!     format!(r#"<input type="hidden" name="_csrf" value="{}">"#, token)
+//! }
+//!
+//! let router = Router::new()
+//!     .hoop(session_handler)
+//!     .hoop(CsrfProtection::new())
+//!     .get(form)
+//!     .post(submit);
+//! ```
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use salvo_core::http::{Method, StatusCode};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
+use sha1::{Digest, Sha1};
+
+use crate::depot_ext::SessionDepotExt;
+use crate::session::Session;
+
+/// Session key holding the CSRF secret (same as csurf)
+pub const SECRET_KEY: &str = "csrfSecret";
+
+/// Form field and query parameter carrying the token (same as csurf)
+pub const FIELD_NAME: &str = "_csrf";
+
+/// Headers checked for the token, in order (same as csurf)
+pub const HEADER_NAMES: [&str; 4] = ["csrf-token", "xsrf-token", "x-csrf-token", "x-xsrf-token"];
+
+/// Length of the random salt prefixed to each token
+const SALT_LENGTH: usize = 8;
+
+/// Length of the per-session secret in bytes
+const SECRET_LENGTH: usize = 18;
+
+const SALT_CHARS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Generate a new CSRF secret (base64url of 18 random bytes)
+pub fn create_secret() -> String {
+    let mut bytes = [0u8; SECRET_LENGTH];
+    random_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Create a token for the secret with a random salt
+pub fn create_token(secret: &str) -> String {
+    tokenize(secret, &random_salt())
+}
+
+/// Verify that the token was created for the secret
+pub fn verify_token(secret: &str, token: &str) -> bool {
+    let Some((salt, _)) = token.split_once('-') else {
+        return false;
+    };
+    constant_time_eq(token.as_bytes(), tokenize(secret, salt).as_bytes())
+}
+
+/// Build the token for a secret and salt: `<salt>-<hash>`
+fn tokenize(secret: &str, salt: &str) -> String {
+    let digest = Sha1::digest(format!("{}-{}", salt, secret).as_bytes());
+    format!("{}-{}", salt, URL_SAFE_NO_PAD.encode(digest))
+}
+
+/// Fill `buf` from the operating system's random number generator
+fn random_bytes(buf: &mut [u8]) {
+    getrandom::fill(buf).expect("the operating system's random number generator failed");
+}
+
+/// Random alphanumeric salt
+///
+/// Bytes at or above the largest multiple of the alphabet size are
+/// rejected, so every character is equally likely.
+fn random_salt() -> String {
+    let limit = 256 / SALT_CHARS.len() * SALT_CHARS.len();
+    let mut salt = String::with_capacity(SALT_LENGTH);
+    let mut bytes = [0u8; SALT_LENGTH];
+    while salt.len() < SALT_LENGTH {
+        random_bytes(&mut bytes);
+        let accepted = bytes.iter().map(|b| *b as usize).filter(|b| *b < limit);
+        for b in accepted.take(SALT_LENGTH - salt.len()) {
+            salt.push(SALT_CHARS[b % SALT_CHARS.len()] as char);
+        }
+    }
+    salt
+}
+
+/// Constant-time comparison to prevent timing attacks
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+impl Session {
+    /// Get a CSRF token for this session, like csurf's `req.csrfToken()`
+    ///
+    /// Creates and stores the session's CSRF secret on first use. Every call
+    /// returns a fresh token; all of them stay valid for the session.
+    pub fn csrf_token(&self) -> String {
+        let secret = match self.get::<String>(SECRET_KEY) {
+            Some(secret) => secret,
+            None => {
+                let secret = create_secret();
+                self.set(SECRET_KEY, &secret);
+                secret
+            }
+        };
+        create_token(&secret)
+    }
+
+    /// Verify a CSRF token against this session's secret
+    pub fn verify_csrf_token(&self, token: &str) -> bool {
+        self.get::<String>(SECRET_KEY)
+            .is_some_and(|secret| verify_token(&secret, token))
+    }
+}
+
+/// Middleware rejecting unsafe requests without a valid CSRF token
+///
+/// Requests with methods other than GET, HEAD and OPTIONS must carry a token
+/// from [`Session::csrf_token`] in one of the [`HEADER_NAMES`] headers, the
+/// `_csrf` form field or the `_csrf` query parameter; otherwise they are
+/// answered with 403 Forbidden. Must be mounted after the session handler.
+#[derive(Clone, Debug)]
+pub struct CsrfProtection {
+    ignore_methods: Vec<Method>,
+}
+
+impl CsrfProtection {
+    /// Create CSRF protection ignoring GET, HEAD and OPTIONS requests
+    pub fn new() -> Self {
+        Self {
+            ignore_methods: vec![Method::GET, Method::HEAD, Method::OPTIONS],
+        }
+    }
+
+    /// Set the methods that don't require a token (default: GET, HEAD, OPTIONS)
+    pub fn with_ignore_methods<I: IntoIterator<Item = Method>>(mut self, methods: I) -> Self {
+        self.ignore_methods = methods.into_iter().collect();
+        self
+    }
+
+    /// Find the token sent with the request
+    async fn request_token(req: &mut Request) -> Option<String> {
+        if let Some(token) = HEADER_NAMES
+            .iter()
+            .find_map(|name| req.header::<String>(*name))
+        {
+            return Some(token);
+        }
+        if let Some(token) = req.query::<String>(FIELD_NAME) {
+            return Some(token);
+        }
+        req.form::<String>(FIELD_NAME).await
+    }
+}
+
+impl Default for CsrfProtection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Handler for CsrfProtection {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        if self.ignore_methods.contains(req.method()) {
+            ctrl.call_next(req, depot, res).await;
+            return;
+        }
+
+        let Some(session) = depot.session_mut() else {
+            tracing::error!("CsrfProtection requires the session handler to run first");
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            ctrl.skip_rest();
+            return;
+        };
+        if let Err(e) = session.load().await {
+            tracing::error!("Failed to load session: {}", e);
+        }
+
+        let valid = Self::request_token(req)
+            .await
+            .is_some_and(|token| session.verify_csrf_token(&token));
+        if !valid {
+            res.status_code(StatusCode::FORBIDDEN);
+            ctrl.skip_rest();
+            return;
+        }
+
+        ctrl.call_next(req, depot, res).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_core::{handler, Router, Service, Writer};
+
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::MemoryStore;
+
+    #[test]
+    fn test_compatible_with_csrf_package() {
+        // Verified with Node.js:
+        // new (require('csrf'))()._tokenize('keyboard cat secret', 'saltsalt')
+        assert_eq!(
+            tokenize("keyboard cat secret", "saltsalt"),
+            "saltsalt-Yv6Nskz3dPSVEI-9WHyAw3en_rw"
+        );
+    }
+
+    #[test]
+    fn test_create_and_verify() {
+        let secret = create_secret();
+        assert_eq!(secret.len(), 24);
+
+        let token = create_token(&secret);
+        let (salt, _) = token.split_once('-').unwrap();
+        assert_eq!(salt.len(), SALT_LENGTH);
+        assert!(salt.bytes().all(|b| b.is_ascii_alphanumeric()));
+        assert_ne!(salt, random_salt());
+        assert!(verify_token(&secret, &token));
+        assert!(!verify_token(&create_secret(), &token));
+        assert!(!verify_token(&secret, "invalid"));
+    }
+
+    #[handler]
+    async fn form(session: Session) -> String {
+        session.csrf_token()
+    }
+
+    #[handler]
+    async fn submit() -> &'static str {
+        "accepted"
+    }
+
+    #[tokio::test]
+    async fn test_lazily_loaded_session() {
+        let config = SessionConfig::new("secret").with_lazy_load(true);
+        let service = Service::new(
+            Router::new()
+                .hoop(ExpressSessionHandler::new(MemoryStore::new(), config))
+                .hoop(CsrfProtection::new())
+                .get(form)
+                .post(submit),
+        );
+
+        let mut res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+        let token = res.take_string().await.unwrap();
+
+        let mut res = TestClient::post("http://127.0.0.1/")
+            .add_header("cookie", &cookie, true)
+            .add_header(HEADER_NAMES[0], &token, true)
+            .send(&service)
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "accepted");
+    }
+}
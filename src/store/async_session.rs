@@ -0,0 +1,191 @@
+//! Bridge to async-session stores (requires the `async-session-store` feature)
+//!
+//! [`AsyncSessionStoreAdapter`] runs on top of any `async_session::SessionStore`
+//! (async-sqlx-session, async-mongodb-session, ...), so the same tables and
+//! collections can back this crate:
+//!
+//! ```rust,ignore
+//! let store = AsyncSessionStoreAdapter::new(async_sqlx_session::PostgresSessionStore::new(url).await?);
+//! ```
+//!
+//! async-session keeps each session as a JSON document `{"id", "expiry",
+//! "data"}` whose values are JSON-encoded strings, under an ID derived from
+//! the cookie value. The adapter uses the base64 of the express-session ID
+//! as that cookie value. The express-session cookie is kept as a `cookie`
+//! value; sessions without one get one expiring with the document.
+
+use ::async_session::{Session, SessionStore as AsyncStore};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{Duration, Utc};
+use serde_json::{Map, Value};
+
+use super::SessionStore;
+use crate::compat::AsyncSession;
+use crate::error::SessionError;
+use crate::session::{SessionCookie, SessionData};
+
+/// [`SessionStore`] over an `async_session::SessionStore`
+pub struct AsyncSessionStoreAdapter<T: AsyncStore> {
+    inner: T,
+}
+
+impl<T: AsyncStore> AsyncSessionStoreAdapter<T> {
+    /// Store sessions in `inner`
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    /// Get a reference to the wrapped store
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+/// async-session cookie value standing for a session ID
+fn cookie_value(sid: &str) -> String {
+    STANDARD.encode(sid)
+}
+
+/// async-session ID of the document holding a session
+fn document_id(sid: &str) -> String {
+    Session::id_from_cookie_value(&cookie_value(sid)).expect("cookie value is base64")
+}
+
+/// Convert between the async-session `Session` and its serialized form
+fn convert<A: serde::Serialize, B: serde::de::DeserializeOwned>(
+    session: &A,
+) -> Result<B, SessionError> {
+    Ok(serde_json::from_value(serde_json::to_value(session)?)?)
+}
+
+/// Encode a session in the async-session format
+fn encode(
+    sid: &str,
+    session: &SessionData,
+    ttl_secs: Option<u64>,
+) -> Result<AsyncSession, SessionError> {
+    let Value::Object(map) = serde_json::to_value(session)? else {
+        unreachable!("SessionData serializes to a map");
+    };
+    let expiry = match ttl_secs {
+        Some(ttl) => Some(Utc::now() + Duration::seconds(ttl as i64)),
+        None => session.cookie.expires,
+    };
+    Ok(AsyncSession {
+        id: document_id(sid),
+        expiry,
+        data: map
+            .into_iter()
+            .map(|(key, value)| (key, value.to_string()))
+            .collect(),
+    })
+}
+
+/// Decode a session from the async-session format
+fn decode(session: AsyncSession) -> Result<SessionData, SessionError> {
+    let expiry = session.expiry;
+    let mut map: Map<String, Value> = session.into_values().collect();
+    if !map.contains_key("cookie") {
+        let cookie = SessionCookie {
+            expires: expiry,
+            ..SessionCookie::default()
+        };
+        map.insert("cookie".to_string(), serde_json::to_value(cookie)?);
+    }
+    Ok(serde_json::from_value(Value::Object(map))?)
+}
+
+#[async_trait]
+impl<T: AsyncStore> SessionStore for AsyncSessionStoreAdapter<T> {
+    async fn get(&self, sid: &str) -> Result<Option<SessionData>, SessionError> {
+        let loaded = self.inner.load_session(cookie_value(sid)).await;
+        let Some(session) = loaded.map_err(SessionError::backend)? else {
+            return Ok(None);
+        };
+        let session: AsyncSession = convert(&session)?;
+        // Not every async-session store checks the expiry on load
+        if session.is_expired() {
+            return Ok(None);
+        }
+        decode(session).map(Some)
+    }
+
+    async fn set(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        ttl_secs: Option<u64>,
+    ) -> Result<(), SessionError> {
+        let session: Session = convert(&encode(sid, session, ttl_secs)?)?;
+        self.inner
+            .store_session(session)
+            .await
+            .map(drop)
+            .map_err(SessionError::backend)
+    }
+
+    async fn destroy(&self, sid: &str) -> Result<(), SessionError> {
+        let session: Session = convert(&AsyncSession {
+            id: document_id(sid),
+            expiry: None,
+            data: Default::default(),
+        })?;
+        self.inner
+            .destroy_session(session)
+            .await
+            .map_err(SessionError::backend)
+    }
+
+    async fn touch(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        ttl_secs: Option<u64>,
+    ) -> Result<(), SessionError> {
+        // The expiry is part of the document
+        self.set(sid, session, ttl_secs).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::async_session::MemoryStore;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_adapter() {
+        let store = AsyncSessionStoreAdapter::new(MemoryStore::new());
+        let mut data = SessionData::new(3600);
+        data.set("user_id", 42);
+        store.set("abc", &data, Some(3600)).await.unwrap();
+
+        // Stored in the async-session format
+        let stored = store.inner().load_session(cookie_value("abc")).await;
+        let stored: Value = serde_json::to_value(stored.unwrap().unwrap()).unwrap();
+        assert_eq!(stored["id"], document_id("abc"));
+        assert_eq!(stored["data"]["user_id"], "42");
+        assert!(stored["expiry"].is_string());
+
+        let loaded = store.get("abc").await.unwrap().unwrap();
+        assert_eq!(loaded.get::<u64>("user_id"), Some(42));
+        assert!(store.get("other").await.unwrap().is_none());
+
+        // Written by an async-session app
+        let session: Session = serde_json::from_str(&format!(
+            r#"{{"id":"{}","expiry":null,"data":{{"name":"\"alice\""}}}}"#,
+            document_id("old")
+        ))
+        .unwrap();
+        store.inner().store_session(session).await.unwrap();
+        let loaded = store.get("old").await.unwrap().unwrap();
+        assert_eq!(loaded.get::<String>("name"), Some("alice".to_string()));
+        assert!(loaded.cookie.expires.is_none());
+
+        store.destroy("abc").await.unwrap();
+        assert!(store.get("abc").await.unwrap().is_none());
+        assert_eq!(store.inner().count().await, 1);
+    }
+}
@@ -2,10 +2,15 @@
 //!
 //! This is primarily for development and testing.
 //! For production, use RedisStore or another persistent store.
+//! With [`MemoryStore::with_snapshot`], sessions survive restarts of a
+//! single instance: they are loaded on startup and written back by
+//! [`SessionStore::flush`].
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use parking_lot::RwLock;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -27,6 +32,7 @@ struct StoredSession {
 pub struct MemoryStore {
     sessions: Arc<RwLock<HashMap<String, StoredSession>>>,
     prefix: String,
+    snapshot: Option<PathBuf>,
 }
 
 impl MemoryStore {
@@ -35,6 +41,7 @@ impl MemoryStore {
         Self {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             prefix: "sess:".to_string(),
+            snapshot: None,
         }
     }
 
@@ -43,6 +50,47 @@ impl MemoryStore {
         Self {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             prefix: prefix.into(),
+            snapshot: None,
+        }
+    }
+
+    /// Load sessions from a JSON snapshot file, and write them back to it on
+    /// [`SessionStore::flush`]
+    ///
+    /// A missing file starts an empty store; an unreadable one is logged
+    /// and ignored. Expired sessions are not restored.
+    pub fn with_snapshot<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        let path = path.into();
+        match std::fs::read(&path) {
+            Ok(bytes) => match serde_json::from_slice::<HashMap<String, SessionData>>(&bytes) {
+                Ok(snapshot) => self.restore(snapshot),
+                Err(e) => tracing::warn!(
+                    "Ignoring invalid session snapshot {}: {}",
+                    path.display(),
+                    e
+                ),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("Failed to read session snapshot {}: {}", path.display(), e),
+        }
+        self.snapshot = Some(path);
+        self
+    }
+
+    /// Insert the unexpired sessions of a snapshot, keyed by storage key
+    fn restore(&self, snapshot: HashMap<String, SessionData>) {
+        let now = chrono::Utc::now();
+        let mut sessions = self.sessions.write();
+        for (key, data) in snapshot {
+            // The cookie expiry stands in for the TTL, which isn't saved
+            let expires_at = match data.cookie.expires {
+                Some(expires) => match (expires - now).to_std() {
+                    Ok(remaining) => Some(Instant::now() + remaining),
+                    Err(_) => continue,
+                },
+                None => None,
+            };
+            sessions.insert(key, StoredSession { data, expires_at });
         }
     }
 
@@ -73,6 +121,7 @@ impl Clone for MemoryStore {
         Self {
             sessions: Arc::clone(&self.sessions),
             prefix: self.prefix.clone(),
+            snapshot: self.snapshot.clone(),
         }
     }
 }
@@ -114,6 +163,53 @@ impl SessionStore for MemoryStore {
         Ok(())
     }
 
+    async fn patch(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        changed: &[String],
+        ttl_secs: Option<u64>,
+    ) -> Result<(), SessionError> {
+        let key = self.make_key(sid);
+        let expires_at = ttl_secs.map(|secs| Instant::now() + Duration::from_secs(secs));
+        let mut sessions = self.sessions.write();
+
+        let Some(stored) = sessions.get_mut(&key) else {
+            let data = session.clone();
+            sessions.insert(key, StoredSession { data, expires_at });
+            return Ok(());
+        };
+
+        stored.data.apply_changes(session, changed);
+        stored.expires_at = expires_at;
+        Ok(())
+    }
+
+    async fn compare_and_set(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        expected: Option<u64>,
+        ttl_secs: Option<u64>,
+    ) -> Result<bool, SessionError> {
+        let key = self.make_key(sid);
+        let mut sessions = self.sessions.write();
+
+        let now = Instant::now();
+        let current = sessions
+            .get(&key)
+            .filter(|stored| stored.expires_at.is_none_or(|exp| exp > now))
+            .and_then(|stored| stored.data.version);
+        if current != expected {
+            return Ok(false);
+        }
+
+        let expires_at = ttl_secs.map(|secs| now + Duration::from_secs(secs));
+        let data = session.clone();
+        sessions.insert(key, StoredSession { data, expires_at });
+        Ok(true)
+    }
+
     async fn destroy(&self, sid: &str) -> Result<(), SessionError> {
         let key = self.make_key(sid);
         self.sessions.write().remove(&key);
@@ -161,6 +257,58 @@ impl SessionStore for MemoryStore {
         let sessions = self.sessions.read();
         Ok(sessions.values().map(|s| s.data.clone()).collect())
     }
+
+    async fn prune_expired(
+        &self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, SessionError> {
+        let mut sessions = self.sessions.write();
+        let now = Instant::now();
+        let expired: Vec<String> = sessions
+            .iter()
+            .filter(|(_, stored)| {
+                stored.expires_at.is_some_and(|exp| exp <= now)
+                    || stored.data.cookie.expires.is_some_and(|exp| exp < before)
+            })
+            .map(|(key, _)| key.clone())
+            .take(limit)
+            .collect();
+        for key in &expired {
+            sessions.remove(key);
+        }
+        Ok(expired.len())
+    }
+
+    async fn ping(&self) -> Result<(), SessionError> {
+        // Nothing to connect to
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), SessionError> {
+        let Some(path) = &self.snapshot else {
+            return Ok(());
+        };
+        self.cleanup_expired();
+        let snapshot: HashMap<String, SessionData> = self
+            .sessions
+            .read()
+            .iter()
+            .map(|(key, stored)| (key.clone(), stored.data.clone()))
+            .collect();
+        let json = serde_json::to_vec(&snapshot)?;
+
+        // Replace the file atomically, so a crash mid-write keeps the old snapshot
+        let tmp = path.with_extension("tmp");
+        let written = async {
+            tokio::fs::write(&tmp, json).await?;
+            tokio::fs::rename(&tmp, path).await
+        };
+        written.await.map_err(|source| SessionError::Io {
+            context: format!("Failed to write {}", path.display()),
+            source,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -190,6 +338,88 @@ mod tests {
         assert!(retrieved.is_none());
     }
 
+    #[tokio::test]
+    async fn test_memory_store_patch() {
+        let store = MemoryStore::new();
+
+        let mut data = SessionData::new(3600);
+        data.set("user", "alice");
+        data.set("cart", vec![1, 2]);
+        store.set("test-id", &data, Some(3600)).await.unwrap();
+
+        // Only the listed keys are written; others keep the stored value
+        let mut update = SessionData::new(3600);
+        update.set("user", "bob");
+        update.set("cart", Vec::<i32>::new());
+        let changed = vec!["user".to_string(), "theme".to_string()];
+        store
+            .patch("test-id", &update, &changed, Some(3600))
+            .await
+            .unwrap();
+
+        let stored = store.get("test-id").await.unwrap().unwrap();
+        assert_eq!(stored.get::<String>("user"), Some("bob".to_string()));
+        assert_eq!(stored.get::<Vec<i32>>("cart"), Some(vec![1, 2]));
+        assert!(!stored.contains("theme"));
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_compare_and_set() {
+        let store = MemoryStore::new();
+
+        let mut data = SessionData::new(3600);
+        data.version = Some(1);
+        assert!(store
+            .compare_and_set("test-id", &data, None, Some(3600))
+            .await
+            .unwrap());
+        assert!(!store
+            .compare_and_set("test-id", &data, None, Some(3600))
+            .await
+            .unwrap());
+
+        data.version = Some(2);
+        assert!(store
+            .compare_and_set("test-id", &data, Some(1), Some(3600))
+            .await
+            .unwrap());
+        assert!(!store
+            .compare_and_set("test-id", &data, Some(1), Some(3600))
+            .await
+            .unwrap());
+        assert_eq!(
+            store.get("test-id").await.unwrap().unwrap().version,
+            Some(2)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_batches() {
+        let store = MemoryStore::new();
+        let mut data = SessionData::new(3600);
+        data.set("user", "alice");
+        store
+            .set_many(&[
+                ("a".to_string(), data.clone(), Some(3600)),
+                ("b".to_string(), data, Some(0)),
+            ])
+            .await
+            .unwrap();
+
+        let sids = ["a", "b", "c"].map(String::from);
+        let sessions = store.get_many(&sids).await.unwrap();
+        assert_eq!(sessions.len(), 3);
+        assert_eq!(
+            sessions[0]
+                .as_ref()
+                .unwrap()
+                .get::<String>("user")
+                .as_deref(),
+            Some("alice")
+        );
+        assert!(sessions[1].is_none() && sessions[2].is_none());
+    }
+
     #[tokio::test]
     async fn test_memory_store_expiry() {
         let store = MemoryStore::new();
@@ -200,4 +430,44 @@ mod tests {
         let retrieved = store.get("test-id").await.unwrap();
         assert!(retrieved.is_none());
     }
+
+    #[tokio::test]
+    async fn test_memory_store_sessions_for_user() {
+        let store = MemoryStore::new();
+
+        let mut data = SessionData::new(3600);
+        data.set("passport", serde_json::json!({ "user": 42 }));
+        store.set("laptop", &data, Some(3600)).await.unwrap();
+        store.set("phone", &data, Some(3600)).await.unwrap();
+        data.set("passport", serde_json::json!({ "user": "bob" }));
+        store.set("other", &data, Some(3600)).await.unwrap();
+
+        let mut sids = store.sessions_for_user("42").await.unwrap();
+        sids.sort();
+        assert_eq!(sids, vec!["laptop", "phone"]);
+
+        assert_eq!(store.destroy_all_for_user("42").await.unwrap(), 2);
+        assert!(store.sessions_for_user("42").await.unwrap().is_empty());
+        assert_eq!(store.sessions_for_user("bob").await.unwrap(), vec!["other"]);
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_snapshot() {
+        let path = std::env::temp_dir().join(format!("sessions-{}.json", uuid::Uuid::new_v4()));
+        let store = MemoryStore::new().with_snapshot(&path);
+
+        let mut data = SessionData::new(3600);
+        data.set("user", "alice");
+        store.set("kept", &data, Some(3600)).await.unwrap();
+        let mut expired = SessionData::new(3600);
+        expired.cookie.expires = Some(chrono::Utc::now() - chrono::Duration::minutes(1));
+        store.set("expired", &expired, None).await.unwrap();
+        store.flush().await.unwrap();
+
+        let restored = MemoryStore::new().with_snapshot(&path);
+        std::fs::remove_file(&path).unwrap();
+        let kept = restored.get("kept").await.unwrap().unwrap();
+        assert_eq!(kept.get::<String>("user"), Some("alice".to_string()));
+        assert!(restored.get("expired").await.unwrap().is_none());
+    }
 }
@@ -1,13 +1,26 @@
 //! Session store implementations
 
 mod memory;
+mod record;
+mod signed;
 mod traits;
+mod transfer;
 
 pub use memory::MemoryStore;
-pub use traits::SessionStore;
+pub use record::{RecordStore, RecordStoreAdapter, SessionRecord};
+pub use signed::SignedStore;
+pub(crate) use traits::BATCH_SIZE;
+pub use traits::{SessionStore, StoreStatus};
+pub use transfer::{dump, migrate, restore};
+
+#[cfg(feature = "async-session-store")]
+mod async_session;
+
+#[cfg(feature = "async-session-store")]
+pub use self::async_session::AsyncSessionStoreAdapter;
 
 #[cfg(feature = "redis-store")]
 mod redis_store;
 
 #[cfg(feature = "redis-store")]
-pub use redis_store::RedisStore;
+pub use redis_store::{RedisConnectOptions, RedisStore};
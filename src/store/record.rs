@@ -0,0 +1,276 @@
+//! Bridge to record-based stores such as tower-sessions backends
+//!
+//! tower-sessions stores (sqlx, mongodb, dynamodb, ...) persist a session as
+//! a record of an `i128` ID, a map of values and an expiry date, through
+//! `save`, `load` and `delete`. [`RecordStore`] describes that interface, and
+//! [`RecordStoreAdapter`] turns any implementation into a [`SessionStore`].
+//! With the `tower-sessions` feature, every `tower_sessions::SessionStore` is
+//! a [`RecordStore`]:
+//!
+//! ```rust,ignore
+//! let store = RecordStoreAdapter::new(tower_sessions_sqlx_store::PostgresStore::new(pool));
+//! ```
+//!
+//! Session IDs are mapped to record IDs by hashing, so the adapter can't
+//! list sessions ([`SessionStore::ids`] is unsupported).
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use super::SessionStore;
+use crate::error::SessionError;
+use crate::session::SessionData;
+
+/// Session as persisted by a record store
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionRecord {
+    /// Record ID derived from the session ID
+    pub id: i128,
+    /// Serialized session, including the `cookie` entry
+    pub data: HashMap<String, Value>,
+    /// When the record expires
+    pub expiry_date: DateTime<Utc>,
+}
+
+/// Store persisting [`SessionRecord`]s, modeled on `tower_sessions::SessionStore`
+#[async_trait]
+pub trait RecordStore: Send + Sync + 'static {
+    /// Insert or replace a record
+    async fn save(&self, record: &SessionRecord) -> Result<(), SessionError>;
+
+    /// Get a record by ID
+    async fn load(&self, id: i128) -> Result<Option<SessionRecord>, SessionError>;
+
+    /// Delete a record
+    async fn delete(&self, id: i128) -> Result<(), SessionError>;
+}
+
+/// [`SessionStore`] over a [`RecordStore`]
+pub struct RecordStoreAdapter<T: RecordStore> {
+    inner: T,
+    default_ttl: u64,
+}
+
+impl<T: RecordStore> RecordStoreAdapter<T> {
+    /// Store sessions in `inner`
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            default_ttl: 86400,
+        }
+    }
+
+    /// Set the TTL of sessions without a cookie expiry (default: 86400 = 1 day)
+    ///
+    /// Record stores need an expiry date for every record.
+    pub fn with_default_ttl(mut self, ttl: u64) -> Self {
+        self.default_ttl = ttl;
+        self
+    }
+
+    /// Get a reference to the wrapped store
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    /// Build the record saved for a session
+    fn record(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        ttl_secs: Option<u64>,
+    ) -> Result<SessionRecord, SessionError> {
+        let data = match serde_json::to_value(session)? {
+            Value::Object(map) => map.into_iter().collect(),
+            _ => unreachable!("SessionData serializes to a map"),
+        };
+        let expiry_date = match (ttl_secs, session.cookie.expires) {
+            (Some(ttl), _) => Utc::now() + Duration::seconds(ttl as i64),
+            (None, Some(expires)) => expires,
+            (None, None) => Utc::now() + Duration::seconds(self.default_ttl as i64),
+        };
+        Ok(SessionRecord {
+            id: record_id(sid),
+            data,
+            expiry_date,
+        })
+    }
+}
+
+/// Record ID of a session: the first 16 bytes of the SHA-256 of its ID
+fn record_id(sid: &str) -> i128 {
+    let digest = Sha256::digest(sid.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    i128::from_be_bytes(bytes)
+}
+
+#[async_trait]
+impl<T: RecordStore> SessionStore for RecordStoreAdapter<T> {
+    async fn get(&self, sid: &str) -> Result<Option<SessionData>, SessionError> {
+        let Some(record) = self.inner.load(record_id(sid)).await? else {
+            return Ok(None);
+        };
+        // Not every backend deletes expired records on load
+        if record.expiry_date <= Utc::now() {
+            return Ok(None);
+        }
+        let data = Value::Object(record.data.into_iter().collect());
+        Ok(Some(serde_json::from_value(data)?))
+    }
+
+    async fn set(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        ttl_secs: Option<u64>,
+    ) -> Result<(), SessionError> {
+        self.inner.save(&self.record(sid, session, ttl_secs)?).await
+    }
+
+    async fn destroy(&self, sid: &str) -> Result<(), SessionError> {
+        self.inner.delete(record_id(sid)).await
+    }
+
+    async fn touch(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        ttl_secs: Option<u64>,
+    ) -> Result<(), SessionError> {
+        // Record stores have no separate expiry update
+        self.set(sid, session, ttl_secs).await
+    }
+}
+
+#[cfg(feature = "tower-sessions")]
+mod tower {
+    use tower_sessions::cookie::time::OffsetDateTime;
+    use tower_sessions::session::{Id, Record};
+    use tower_sessions::SessionStore as TowerStore;
+
+    use super::*;
+
+    impl From<&SessionRecord> for Record {
+        fn from(record: &SessionRecord) -> Self {
+            Record {
+                id: Id(record.id),
+                data: record.data.clone(),
+                expiry_date: OffsetDateTime::from_unix_timestamp(record.expiry_date.timestamp())
+                    .unwrap_or(OffsetDateTime::UNIX_EPOCH),
+            }
+        }
+    }
+
+    impl From<Record> for SessionRecord {
+        fn from(record: Record) -> Self {
+            SessionRecord {
+                id: record.id.0,
+                data: record.data,
+                expiry_date: DateTime::from_timestamp(record.expiry_date.unix_timestamp(), 0)
+                    .unwrap_or(DateTime::UNIX_EPOCH),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl<T: TowerStore> RecordStore for T {
+        async fn save(&self, record: &SessionRecord) -> Result<(), SessionError> {
+            TowerStore::save(self, &record.into())
+                .await
+                .map_err(SessionError::backend)
+        }
+
+        async fn load(&self, id: i128) -> Result<Option<SessionRecord>, SessionError> {
+            let record = TowerStore::load(self, &Id(id))
+                .await
+                .map_err(SessionError::backend)?;
+            Ok(record.map(SessionRecord::from))
+        }
+
+        async fn delete(&self, id: i128) -> Result<(), SessionError> {
+            TowerStore::delete(self, &Id(id))
+                .await
+                .map_err(SessionError::backend)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parking_lot::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Records(Mutex<HashMap<i128, SessionRecord>>);
+
+    #[async_trait]
+    impl RecordStore for Records {
+        async fn save(&self, record: &SessionRecord) -> Result<(), SessionError> {
+            self.0.lock().insert(record.id, record.clone());
+            Ok(())
+        }
+
+        async fn load(&self, id: i128) -> Result<Option<SessionRecord>, SessionError> {
+            Ok(self.0.lock().get(&id).cloned())
+        }
+
+        async fn delete(&self, id: i128) -> Result<(), SessionError> {
+            self.0.lock().remove(&id);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_adapter() {
+        let store = RecordStoreAdapter::new(Records::default());
+        let mut data = SessionData::new(3600);
+        data.set("user", "alice");
+        store.set("abc", &data, Some(3600)).await.unwrap();
+
+        let record = store.inner().0.lock().values().next().cloned().unwrap();
+        assert_eq!(record.id, record_id("abc"));
+        assert_eq!(record.data["user"], "alice");
+        assert!(record.data.contains_key("cookie"));
+
+        let loaded = store.get("abc").await.unwrap().unwrap();
+        assert_eq!(loaded.get::<String>("user"), Some("alice".to_string()));
+        assert!(store.get("other").await.unwrap().is_none());
+
+        store.set("abc", &data, Some(0)).await.unwrap();
+        assert!(store.get("abc").await.unwrap().is_none());
+
+        store.destroy("abc").await.unwrap();
+        assert!(store.inner().0.lock().is_empty());
+    }
+
+    #[cfg(feature = "tower-sessions")]
+    #[tokio::test]
+    async fn test_tower_sessions_store() {
+        use tower_sessions::session::Id;
+        use tower_sessions::SessionStore as TowerStore;
+
+        let store = RecordStoreAdapter::new(tower_sessions::MemoryStore::default());
+        let mut data = SessionData::new(3600);
+        data.set("user", "alice");
+        store.set("abc", &data, Some(3600)).await.unwrap();
+
+        let record = TowerStore::load(store.inner(), &Id(record_id("abc")))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.data["user"], "alice");
+
+        let loaded = store.get("abc").await.unwrap().unwrap();
+        assert_eq!(loaded.get::<String>("user"), Some("alice".to_string()));
+        assert!(store.get("other").await.unwrap().is_none());
+
+        store.destroy("abc").await.unwrap();
+        assert!(store.get("abc").await.unwrap().is_none());
+    }
+}
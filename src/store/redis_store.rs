@@ -6,14 +6,193 @@
 //! - TTL: Based on session cookie expiration
 
 use async_trait::async_trait;
-use redis::aio::ConnectionManager;
-use redis::AsyncCommands;
-use std::sync::Arc;
+use chrono::Utc;
+use redis::aio::{ConnectionLike, ConnectionManager, ConnectionManagerConfig};
+use redis::{
+    AsyncCommands, Cmd, Expiry, Pipeline, RedisFuture, RedisResult, Script, SetExpiry, SetOptions,
+    Value,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::watch;
 
-use super::SessionStore;
+use super::traits::scan_sessions_for_user;
+use super::{SessionStore, StoreStatus};
+use crate::auth::user_key;
 use crate::error::SessionError;
 use crate::session::SessionData;
 
+/// Atomically write a session if its stored `sessionVersion` matches.
+/// ARGV: expected version ("" for none), JSON, TTL in seconds ("" for no expiry),
+/// the user index key ("" for none), the session ID, the delete command and
+/// the expiry as a Unix time in milliseconds ("" to expire after the TTL).
+const COMPARE_AND_SET_SCRIPT: &str = r#"
+local stored = redis.call('GET', KEYS[1])
+local version = nil
+if stored then
+    local ok, decoded = pcall(cjson.decode, stored)
+    if ok and type(decoded) == 'table' and type(decoded['sessionVersion']) == 'number' then
+        version = decoded['sessionVersion']
+    end
+end
+if ARGV[1] == '' then
+    if version ~= nil then return 0 end
+elseif version ~= tonumber(ARGV[1]) then
+    return 0
+end
+if ARGV[3] == '' then
+    redis.call('SET', KEYS[1], ARGV[2])
+elseif tonumber(ARGV[3]) > 0 and ARGV[7] ~= '' then
+    redis.call('SET', KEYS[1], ARGV[2], 'PXAT', ARGV[7])
+elseif tonumber(ARGV[3]) > 0 then
+    redis.call('SET', KEYS[1], ARGV[2], 'EX', ARGV[3])
+else
+    redis.call(ARGV[6], KEYS[1])
+end
+if ARGV[4] ~= '' then
+    if ARGV[3] == '' then
+        redis.call('SADD', ARGV[4], ARGV[5])
+    elseif tonumber(ARGV[3]) > 0 then
+        redis.call('SADD', ARGV[4], ARGV[5])
+        redis.call('EXPIRE', ARGV[4], ARGV[3])
+    else
+        redis.call('SREM', ARGV[4], ARGV[5])
+    end
+end
+return 1
+"#;
+
+/// Get a session and refresh its TTL, for servers without GETEX.
+/// ARGV: TTL in seconds.
+const GET_AND_TOUCH_SCRIPT: &str = r#"
+local stored = redis.call('GET', KEYS[1])
+if stored then
+    redis.call('EXPIRE', KEYS[1], ARGV[1])
+end
+return stored
+"#;
+
+/// Delete a session and remove it from its user's index set.
+/// ARGV: user index prefix, session ID, delete command. Users are found like `user_key`
+/// does for string and integer IDs; others are left for lookups to prune.
+const DESTROY_SCRIPT: &str = r#"
+local stored = redis.call('GET', KEYS[1])
+redis.call(ARGV[3], KEYS[1])
+if not stored then
+    return 0
+end
+local ok, decoded = pcall(cjson.decode, stored)
+if not ok or type(decoded) ~= 'table' or type(decoded['passport']) ~= 'table' then
+    return 1
+end
+local user = decoded['passport']['user']
+if type(user) == 'number' and user == math.floor(user) then
+    user = string.format('%d', user)
+end
+if type(user) == 'string' then
+    redis.call('SREM', ARGV[1] .. user, ARGV[2])
+end
+return 1
+"#;
+
+/// The store's Lua scripts, hashed once
+struct Scripts {
+    compare_and_set: Script,
+    get_and_touch: Script,
+    destroy: Script,
+}
+
+fn scripts() -> &'static Scripts {
+    static SCRIPTS: OnceLock<Scripts> = OnceLock::new();
+    SCRIPTS.get_or_init(|| Scripts {
+        compare_and_set: Script::new(COMPARE_AND_SET_SCRIPT),
+        get_and_touch: Script::new(GET_AND_TOUCH_SCRIPT),
+        destroy: Script::new(DESTROY_SCRIPT),
+    })
+}
+
+/// Timeouts and reconnection policy of a [`RedisStore`] connection
+///
+/// The defaults are those of the redis crate: no timeouts, and 6
+/// reconnection attempts 100ms × 2ⁿ apart.
+#[derive(Clone, Debug)]
+pub struct RedisConnectOptions {
+    response_timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    retries: usize,
+    backoff: Duration,
+    max_backoff: Option<Duration>,
+}
+
+impl Default for RedisConnectOptions {
+    fn default() -> Self {
+        Self {
+            response_timeout: None,
+            connect_timeout: None,
+            retries: 6,
+            backoff: Duration::from_millis(100),
+            max_backoff: None,
+        }
+    }
+}
+
+impl RedisConnectOptions {
+    /// Options with the redis crate's defaults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail commands not answered within `timeout` with [`SessionError::Timeout`]
+    pub fn with_response_timeout(mut self, timeout: Duration) -> Self {
+        self.response_timeout = Some(timeout);
+        self
+    }
+
+    /// Give up connection attempts that take longer than `timeout`
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set how often to retry connecting before failing (default: 6)
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Set the base delay between retries, doubled with each attempt (default: 100ms)
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Cap the delay between retries (default: None - no cap)
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = Some(max_backoff);
+        self
+    }
+
+    /// The equivalent connection manager configuration
+    fn manager_config(&self) -> ConnectionManagerConfig {
+        let millis = |duration: Duration| duration.as_millis().try_into().unwrap_or(u64::MAX);
+        let mut config = ConnectionManagerConfig::new()
+            .set_number_of_retries(self.retries)
+            .set_exponent_base(2)
+            .set_factor(millis(self.backoff).max(1));
+        if let Some(timeout) = self.response_timeout {
+            config = config.set_response_timeout(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            config = config.set_connection_timeout(timeout);
+        }
+        if let Some(max_backoff) = self.max_backoff {
+            config = config.set_max_delay(millis(max_backoff));
+        }
+        config
+    }
+}
+
 /// Redis session store compatible with connect-redis
 ///
 /// This store uses the same format as the Node.js connect-redis package,
@@ -29,8 +208,16 @@ use crate::session::SessionData;
 /// ```
 pub struct RedisStore {
     conn: Arc<ConnectionManager>,
+    status: Arc<watch::Sender<StoreStatus>>,
     prefix: String,
     default_ttl: u64,
+    disable_ttl: bool,
+    disable_touch: bool,
+    user_index: Option<String>,
+    unlink: bool,
+    expire_at: bool,
+    /// Set when scripts are enabled; whether they were loaded
+    scripts: Option<Arc<AtomicBool>>,
 }
 
 impl RedisStore {
@@ -42,8 +229,15 @@ impl RedisStore {
         let conn = ConnectionManager::new(client).await?;
         Ok(Self {
             conn: Arc::new(conn),
+            status: Arc::new(watch::Sender::new(StoreStatus::Connected)),
             prefix: "sess:".to_string(),
             default_ttl: 86400,
+            disable_ttl: false,
+            disable_touch: false,
+            user_index: None,
+            unlink: false,
+            expire_at: false,
+            scripts: None,
         })
     }
 
@@ -55,13 +249,48 @@ impl RedisStore {
         Self::new(client).await
     }
 
+    /// Create a new Redis store with connection timeouts and a reconnection policy
+    ///
+    /// ```rust,ignore
+    /// let options = RedisConnectOptions::new()
+    ///     .with_response_timeout(Duration::from_millis(500))
+    ///     .with_connect_timeout(Duration::from_secs(2))
+    ///     .with_max_backoff(Duration::from_secs(5));
+    /// let store = RedisStore::new_with_options(client, options).await?;
+    /// ```
+    pub async fn new_with_options(
+        client: redis::Client,
+        options: RedisConnectOptions,
+    ) -> Result<Self, SessionError> {
+        let conn = ConnectionManager::new_with_config(client, options.manager_config()).await?;
+        Ok(Self::from_connection_manager(conn))
+    }
+
+    /// Create a new Redis store from a connection string, with connection options
+    pub async fn from_url_with_options(
+        url: &str,
+        options: RedisConnectOptions,
+    ) -> Result<Self, SessionError> {
+        let client = redis::Client::open(url).map_err(|e| {
+            SessionError::StoreError(format!("Failed to create Redis client: {}", e))
+        })?;
+        Self::new_with_options(client, options).await
+    }
+
     /// Create a new Redis store with custom prefix
     pub async fn with_prefix(client: redis::Client, prefix: &str) -> Result<Self, SessionError> {
         let conn = ConnectionManager::new(client).await?;
         Ok(Self {
             conn: Arc::new(conn),
+            status: Arc::new(watch::Sender::new(StoreStatus::Connected)),
             prefix: prefix.to_string(),
             default_ttl: 86400,
+            disable_ttl: false,
+            disable_touch: false,
+            user_index: None,
+            unlink: false,
+            expire_at: false,
+            scripts: None,
         })
     }
 
@@ -69,11 +298,23 @@ impl RedisStore {
     pub fn from_connection_manager(conn: ConnectionManager) -> Self {
         Self {
             conn: Arc::new(conn),
+            status: Arc::new(watch::Sender::new(StoreStatus::Connected)),
             prefix: "sess:".to_string(),
             default_ttl: 86400,
+            disable_ttl: false,
+            disable_touch: false,
+            user_index: None,
+            unlink: false,
+            expire_at: false,
+            scripts: None,
         }
     }
 
+    /// Create a [`RedisLocker`](crate::RedisLocker) sharing this store's connection
+    pub fn locker(&self) -> crate::lock::RedisLocker {
+        crate::lock::RedisLocker::from_connection_manager((*self.conn).clone())
+    }
+
     /// Set the key prefix (default: "sess:")
     pub fn set_prefix(&mut self, prefix: &str) {
         self.prefix = prefix.to_string();
@@ -96,23 +337,227 @@ impl RedisStore {
         self
     }
 
+    /// Save sessions without an expiry, like connect-redis' `disableTTL`
+    ///
+    /// Keys persist until destroyed, for deployments that expire them
+    /// externally. Touches become no-ops.
+    pub fn with_disable_ttl(mut self, disable: bool) -> Self {
+        self.disable_ttl = disable;
+        self
+    }
+
+    /// Make touches no-ops, like connect-redis' `disableTouch`
+    ///
+    /// Sessions then expire a TTL after their last save, not their last access.
+    pub fn with_disable_touch(mut self, disable: bool) -> Self {
+        self.disable_touch = disable;
+        self
+    }
+
+    /// Maintain a set of session IDs per logged-in user under `prefix + uid`
+    ///
+    /// Makes [`SessionStore::sessions_for_user`] and
+    /// [`SessionStore::destroy_all_for_user`] cheap instead of loading every
+    /// session. The prefix (e.g. "user_sessions:") must not start with the
+    /// session prefix. Only sessions saved by this store are indexed.
+    pub fn with_user_index(mut self, prefix: &str) -> Self {
+        self.user_index = Some(prefix.to_string());
+        self
+    }
+
+    /// Delete keys with `UNLINK` instead of `DEL` (default: false)
+    ///
+    /// Redis then frees the memory of destroyed sessions in the background,
+    /// so deleting large sessions or clearing the store doesn't block other
+    /// commands. Needs Redis 4.0 or later.
+    pub fn with_unlink(mut self, unlink: bool) -> Self {
+        self.unlink = unlink;
+        self
+    }
+
+    /// Expire sessions at their cookie's `expires` instant (default: false)
+    ///
+    /// Relative TTLs are computed in whole seconds some time after the
+    /// cookie expiry was set, so the record and the cookie drift apart.
+    /// With this, saves and touches set the expiry with `PXAT` and
+    /// `PEXPIREAT` instead, like connect-redis. Sessions without an expiry,
+    /// and those whose TTL is cut short by an idle timeout or absolute
+    /// lifetime, keep relative TTLs, as do loads with
+    /// [`SessionStore::get_and_touch`]. Needs Redis 6.2 or later.
+    pub fn with_expire_at(mut self, expire_at: bool) -> Self {
+        self.expire_at = expire_at;
+        self
+    }
+
+    /// Run multi-step operations as Lua scripts, atomically and in one round-trip
+    ///
+    /// With scripts, [`SessionStore::get_and_touch`] works on servers
+    /// without GETEX (before Redis 6.2), and [`SessionStore::compare_and_set`]
+    /// and [`SessionStore::destroy`] update the user index in the same
+    /// step. The scripts are loaded with `SCRIPT LOAD` before first use, see
+    /// [`Self::load_scripts`]. The index keys they derive aren't declared
+    /// as script keys, so they need a single Redis node rather than a
+    /// cluster.
+    pub fn with_scripts(mut self, scripts: bool) -> Self {
+        self.scripts = scripts.then(|| Arc::new(AtomicBool::new(false)));
+        self
+    }
+
+    /// Load the store's Lua scripts into Redis now, in one round-trip
+    ///
+    /// Done automatically before their first use; call it at startup to
+    /// surface errors early. Scripts flushed later (e.g. by a failover) are
+    /// reloaded on demand.
+    pub async fn load_scripts(&self) -> Result<(), SessionError> {
+        let mut pipe = redis::pipe();
+        for code in [COMPARE_AND_SET_SCRIPT, GET_AND_TOUCH_SCRIPT, DESTROY_SCRIPT] {
+            pipe.cmd("SCRIPT").arg("LOAD").arg(code).ignore();
+        }
+        pipe.query_async::<()>(&mut self.connection()).await?;
+        if let Some(loaded) = &self.scripts {
+            loaded.store(true, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Whether to use scripts, loading them on first use
+    async fn use_scripts(&self) -> Result<bool, SessionError> {
+        match &self.scripts {
+            Some(loaded) if !loaded.load(Ordering::Relaxed) => {
+                self.load_scripts().await?;
+                Ok(true)
+            }
+            Some(_) => Ok(true),
+            None => Ok(false),
+        }
+    }
+
+    /// Connection for one store call, reporting whether Redis was reached
+    fn connection(&self) -> TrackedConnection {
+        TrackedConnection {
+            inner: (*self.conn).clone(),
+            status: Arc::clone(&self.status),
+        }
+    }
+
+    /// Command deleting keys, see [`Self::with_unlink`]
+    fn delete_command(&self) -> &'static str {
+        if self.unlink {
+            "UNLINK"
+        } else {
+            "DEL"
+        }
+    }
+
+    /// Command deleting `keys`
+    fn delete<K: redis::ToRedisArgs>(&self, keys: K) -> Cmd {
+        let mut cmd = redis::cmd(self.delete_command());
+        cmd.arg(keys);
+        cmd
+    }
+
     /// Make a storage key from session ID
     fn make_key(&self, sid: &str) -> String {
         format!("{}{}", self.prefix, sid)
     }
 
+    /// Add a saved session to its user's index set, if indexing
+    ///
+    /// `ttl` is None for sessions saved without expiry; 0 removes the session.
+    async fn index_session(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        ttl: Option<u64>,
+    ) -> Result<(), SessionError> {
+        let mut pipe = redis::pipe();
+        if self.index_commands(&mut pipe, sid, session, ttl) {
+            pipe.query_async::<()>(&mut self.connection()).await?;
+        }
+        Ok(())
+    }
+
+    /// Queue the commands indexing a saved session, returning whether there are any
+    fn index_commands(
+        &self,
+        pipe: &mut Pipeline,
+        sid: &str,
+        session: &SessionData,
+        ttl: Option<u64>,
+    ) -> bool {
+        let (Some(prefix), Some(uid)) = (&self.user_index, user_key(session)) else {
+            return false;
+        };
+        let key = format!("{}{}", prefix, uid);
+
+        match ttl {
+            None => pipe.sadd(&key, sid).ignore(),
+            Some(0) => pipe.srem(&key, sid).ignore(),
+            // The set lives as long as the user's most recently saved session
+            Some(ttl) => pipe
+                .sadd(&key, sid)
+                .ignore()
+                .expire(&key, ttl as i64)
+                .ignore(),
+        };
+        true
+    }
+
     /// Get the TTL to use
     fn get_ttl(&self, ttl_secs: Option<u64>) -> u64 {
         ttl_secs.unwrap_or(self.default_ttl)
     }
+
+    /// Unix time in milliseconds to expire `session` at instead of after
+    /// `ttl` seconds, see [`Self::with_expire_at`]
+    fn expire_at(&self, session: &SessionData, ttl: u64) -> Option<u64> {
+        self.expire_at
+            .then(|| cookie_expiry(session, ttl))
+            .flatten()
+    }
+
+    /// Get the TTL to save with, None when TTLs are disabled
+    fn write_ttl(&self, ttl_secs: Option<u64>) -> Option<u64> {
+        (!self.disable_ttl).then(|| self.get_ttl(ttl_secs))
+    }
+}
+
+/// Cookie expiry of `session` in Unix milliseconds, unless `ttl` ends before it
+fn cookie_expiry(session: &SessionData, ttl: u64) -> Option<u64> {
+    let expires = session.cookie.expires?;
+    // TTLs shortened below the cookie's lifetime stay relative
+    let remaining = (expires - Utc::now()).num_seconds();
+    (ttl as i64 >= remaining).then(|| expires.timestamp_millis().max(0) as u64)
+}
+
+/// Parse a stored session, treating one with an expired cookie as missing
+fn parse_session(data: Option<String>) -> Result<Option<SessionData>, SessionError> {
+    let Some(json) = data else {
+        return Ok(None);
+    };
+    let session = SessionData::from_json(json)?;
+
+    // Check if expired (connect-redis doesn't do this, but it's a safety check)
+    if session.cookie.is_expired() {
+        return Ok(None);
+    }
+
+    Ok(Some(session))
 }
 
 impl Clone for RedisStore {
     fn clone(&self) -> Self {
         Self {
             conn: Arc::clone(&self.conn),
+            status: Arc::clone(&self.status),
             prefix: self.prefix.clone(),
             default_ttl: self.default_ttl,
+            disable_ttl: self.disable_ttl,
+            disable_touch: self.disable_touch,
+            user_index: self.user_index.clone(),
+            unlink: self.unlink,
+            expire_at: self.expire_at,
+            scripts: self.scripts.clone(),
         }
     }
 }
@@ -121,23 +566,10 @@ impl Clone for RedisStore {
 impl SessionStore for RedisStore {
     async fn get(&self, sid: &str) -> Result<Option<SessionData>, SessionError> {
         let key = self.make_key(sid);
-        let mut conn = (*self.conn).clone();
+        let mut conn = self.connection();
 
         let data: Option<String> = conn.get(&key).await?;
-
-        match data {
-            Some(json) => {
-                let session: SessionData = serde_json::from_str(&json)?;
-
-                // Check if expired (connect-redis doesn't do this, but it's a safety check)
-                if session.cookie.is_expired() {
-                    return Ok(None);
-                }
-
-                Ok(Some(session))
-            }
-            None => Ok(None),
-        }
+        parse_session(data)
     }
 
     async fn set(
@@ -147,27 +579,143 @@ impl SessionStore for RedisStore {
         ttl_secs: Option<u64>,
     ) -> Result<(), SessionError> {
         let key = self.make_key(sid);
-        let mut conn = (*self.conn).clone();
+        let mut conn = self.connection();
 
-        let json = serde_json::to_string(session)?;
-        let ttl = self.get_ttl(ttl_secs);
+        // Unchanged sessions are written back as loaded
+        let json = session.to_json()?;
+        let json: &str = &json;
+        let ttl = self.write_ttl(ttl_secs);
 
-        if ttl > 0 {
-            // Set with expiration (EX = seconds)
-            conn.set_ex::<_, _, ()>(&key, &json, ttl).await?;
-        } else {
+        match ttl {
+            // Without TTLs, keys persist until destroyed
+            None => conn.set::<_, _, ()>(&key, json).await?,
             // If TTL is 0 or negative, the session should be destroyed
-            conn.del::<_, ()>(&key).await?;
+            Some(0) => self.delete(&key).query_async::<()>(&mut conn).await?,
+            // Set with expiration, at the cookie expiry or after `ttl` seconds
+            Some(ttl) => {
+                let expiry = match self.expire_at(session, ttl) {
+                    Some(at) => SetExpiry::PXAT(at),
+                    None => SetExpiry::EX(ttl),
+                };
+                let options = SetOptions::default().with_expiration(expiry);
+                conn.set_options::<_, _, ()>(&key, json, options).await?
+            }
+        }
+
+        self.index_session(sid, session, ttl).await
+    }
+
+    async fn get_many(&self, sids: &[String]) -> Result<Vec<Option<SessionData>>, SessionError> {
+        if sids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let keys: Vec<String> = sids.iter().map(|sid| self.make_key(sid)).collect();
+        let mut conn = self.connection();
+
+        // Explicit MGET: the `mget` helper sends GET for a single key
+        let values: Vec<Option<String>> =
+            redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+        values.into_iter().map(parse_session).collect()
+    }
+
+    async fn set_many(
+        &self,
+        sessions: &[(String, SessionData, Option<u64>)],
+    ) -> Result<(), SessionError> {
+        if sessions.is_empty() {
+            return Ok(());
         }
 
+        let mut pipe = redis::pipe();
+        for (sid, session, ttl_secs) in sessions {
+            let key = self.make_key(sid);
+            let json = session.to_json()?;
+            let ttl = self.write_ttl(*ttl_secs);
+            match ttl {
+                None => pipe.set(&key, json.as_ref()).ignore(),
+                Some(0) => pipe.add_command(self.delete(&key)).ignore(),
+                Some(ttl) => {
+                    let expiry = match self.expire_at(session, ttl) {
+                        Some(at) => SetExpiry::PXAT(at),
+                        None => SetExpiry::EX(ttl),
+                    };
+                    let options = SetOptions::default().with_expiration(expiry);
+                    pipe.set_options(&key, json.as_ref(), options).ignore()
+                }
+            };
+            self.index_commands(&mut pipe, sid, session, ttl);
+        }
+        pipe.query_async::<()>(&mut self.connection()).await?;
         Ok(())
     }
 
+    async fn compare_and_set(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        expected: Option<u64>,
+        ttl_secs: Option<u64>,
+    ) -> Result<bool, SessionError> {
+        let key = self.make_key(sid);
+        let mut conn = self.connection();
+
+        let json = session.to_json()?;
+        let expected = expected.map(|v| v.to_string()).unwrap_or_default();
+        let ttl = self.write_ttl(ttl_secs);
+        // Scripted stores index the session in the same step
+        let scripted = self.use_scripts().await?;
+        let index = match (scripted, &self.user_index) {
+            (true, Some(prefix)) => user_key(session).map(|uid| format!("{}{}", prefix, uid)),
+            _ => None,
+        };
+        let written: i32 = scripts()
+            .compare_and_set
+            .key(&key)
+            .arg(expected)
+            .arg(json.as_ref())
+            .arg(ttl.map(|ttl| ttl.to_string()).unwrap_or_default())
+            .arg(index.as_deref().unwrap_or_default())
+            .arg(sid)
+            .arg(self.delete_command())
+            .arg(
+                ttl.and_then(|ttl| self.expire_at(session, ttl))
+                    .map(|at| at.to_string())
+                    .unwrap_or_default(),
+            )
+            .invoke_async(&mut conn)
+            .await?;
+
+        if written == 1 && !scripted {
+            self.index_session(sid, session, ttl).await?;
+        }
+        Ok(written == 1)
+    }
+
     async fn destroy(&self, sid: &str) -> Result<(), SessionError> {
+        if let (true, Some(prefix)) = (self.use_scripts().await?, &self.user_index) {
+            let mut conn = self.connection();
+            scripts()
+                .destroy
+                .key(self.make_key(sid))
+                .arg(prefix)
+                .arg(sid)
+                .arg(self.delete_command())
+                .invoke_async::<()>(&mut conn)
+                .await?;
+            return Ok(());
+        }
+
+        // Find the user to unindex before the session is gone
+        if self.user_index.is_some() {
+            if let Some(session) = self.get(sid).await? {
+                self.index_session(sid, &session, Some(0)).await?;
+            }
+        }
+
         let key = self.make_key(sid);
-        let mut conn = (*self.conn).clone();
+        let mut conn = self.connection();
 
-        conn.del::<_, ()>(&key).await?;
+        self.delete(&key).query_async::<()>(&mut conn).await?;
         Ok(())
     }
 
@@ -177,24 +725,58 @@ impl SessionStore for RedisStore {
         session: &SessionData,
         ttl_secs: Option<u64>,
     ) -> Result<(), SessionError> {
+        // connect-redis skips touches when either option is set
+        if self.disable_touch || self.disable_ttl {
+            return Ok(());
+        }
+
         let key = self.make_key(sid);
-        let mut conn = (*self.conn).clone();
+        let mut conn = self.connection();
 
         let ttl = self.get_ttl(ttl_secs);
 
         // Just update the TTL without touching the data
         // This is what connect-redis does with EXPIRE
-        let _: bool = conn.expire(&key, ttl as i64).await?;
-
         // If EXPIRE returns false, the key doesn't exist, which is fine
         // connect-redis also doesn't check the return value
-        let _ = session; // Silence unused warning
+        let _: bool = match self.expire_at(session, ttl) {
+            Some(at) => conn.pexpire_at(&key, at as i64).await?,
+            None => conn.expire(&key, ttl as i64).await?,
+        };
 
         Ok(())
     }
 
+    async fn get_and_touch(
+        &self,
+        sid: &str,
+        ttl_secs: Option<u64>,
+    ) -> Result<Option<SessionData>, SessionError> {
+        if self.disable_touch || self.disable_ttl {
+            return self.get(sid).await;
+        }
+
+        let key = self.make_key(sid);
+        let mut conn = self.connection();
+
+        // GETEX (Redis 6.2+) reads and sets the TTL at once; unlike EXPIRE
+        // it rejects a zero TTL, so the shortest one is a second
+        let ttl = self.get_ttl(ttl_secs).max(1);
+        let data: Option<String> = if self.use_scripts().await? {
+            scripts()
+                .get_and_touch
+                .key(&key)
+                .arg(ttl)
+                .invoke_async(&mut conn)
+                .await?
+        } else {
+            conn.get_ex(&key, Expiry::EX(ttl)).await?
+        };
+        parse_session(data)
+    }
+
     async fn clear(&self) -> Result<(), SessionError> {
-        let mut conn = (*self.conn).clone();
+        let mut conn = self.connection();
 
         // Get all keys matching our prefix
         let pattern = format!("{}*", self.prefix);
@@ -204,14 +786,14 @@ impl SessionStore for RedisStore {
             .await?;
 
         if !keys.is_empty() {
-            conn.del::<_, ()>(keys).await?;
+            self.delete(keys).query_async::<()>(&mut conn).await?;
         }
 
         Ok(())
     }
 
     async fn length(&self) -> Result<usize, SessionError> {
-        let mut conn = (*self.conn).clone();
+        let mut conn = self.connection();
 
         let pattern = format!("{}*", self.prefix);
         let keys: Vec<String> = redis::cmd("KEYS")
@@ -223,7 +805,7 @@ impl SessionStore for RedisStore {
     }
 
     async fn ids(&self) -> Result<Vec<String>, SessionError> {
-        let mut conn = (*self.conn).clone();
+        let mut conn = self.connection();
 
         let pattern = format!("{}*", self.prefix);
         let keys: Vec<String> = redis::cmd("KEYS")
@@ -239,7 +821,7 @@ impl SessionStore for RedisStore {
     }
 
     async fn all(&self) -> Result<Vec<SessionData>, SessionError> {
-        let mut conn = (*self.conn).clone();
+        let mut conn = self.connection();
 
         let pattern = format!("{}*", self.prefix);
         let keys: Vec<String> = redis::cmd("KEYS")
@@ -255,12 +837,105 @@ impl SessionStore for RedisStore {
 
         let sessions: Vec<SessionData> = values
             .into_iter()
-            .filter_map(|v| v)
+            .flatten()
             .filter_map(|json| serde_json::from_str(&json).ok())
             .collect();
 
         Ok(sessions)
     }
+
+    async fn sessions_for_user(&self, uid: &str) -> Result<Vec<String>, SessionError> {
+        let Some(prefix) = &self.user_index else {
+            return scan_sessions_for_user(self, uid).await;
+        };
+        let key = format!("{}{}", prefix, uid);
+        let mut conn = self.connection();
+
+        let members: Vec<String> = conn.smembers(&key).await?;
+        let mut sids = Vec::new();
+        let mut stale = Vec::new();
+        for sid in members {
+            // Expired sessions and sessions since logged into another user linger in the set
+            match self.get(&sid).await? {
+                Some(session) if user_key(&session).as_deref() == Some(uid) => sids.push(sid),
+                _ => stale.push(sid),
+            }
+        }
+        if !stale.is_empty() {
+            conn.srem::<_, _, ()>(&key, stale).await?;
+        }
+        Ok(sids)
+    }
+
+    async fn ping(&self) -> Result<(), SessionError> {
+        let mut conn = self.connection();
+        redis::cmd("PING").query_async::<()>(&mut conn).await?;
+        Ok(())
+    }
+
+    fn status(&self) -> Option<watch::Receiver<StoreStatus>> {
+        Some(self.status.subscribe())
+    }
+}
+
+/// Connection manager wrapper that updates the store status after each command
+struct TrackedConnection {
+    inner: ConnectionManager,
+    status: Arc<watch::Sender<StoreStatus>>,
+}
+
+impl ConnectionLike for TrackedConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let result = self.inner.req_packed_command(cmd).await;
+            report_status(&self.status, &result);
+            result
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let result = self.inner.req_packed_commands(cmd, offset, count).await;
+            report_status(&self.status, &result);
+            result
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.inner.get_db()
+    }
+}
+
+/// Update the store status from a command result, notifying on changes only
+///
+/// Errors returned by the server (e.g. a failing script) still mean Redis
+/// was reached.
+fn report_status<T>(status: &watch::Sender<StoreStatus>, result: &RedisResult<T>) {
+    let reached = match result {
+        Ok(_) => true,
+        Err(e) => !(e.is_io_error() || e.is_connection_dropped() || e.is_timeout()),
+    };
+    let new = if reached {
+        StoreStatus::Connected
+    } else {
+        StoreStatus::Disconnected
+    };
+    status.send_if_modified(|current| {
+        if *current == new {
+            return false;
+        }
+        match new {
+            StoreStatus::Connected => tracing::info!("Redis session store reconnected"),
+            StoreStatus::Disconnected => tracing::warn!("Redis session store disconnected"),
+        }
+        *current = new;
+        true
+    });
 }
 
 #[cfg(test)]
@@ -270,6 +945,78 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_report_status() {
+        let status = watch::Sender::new(StoreStatus::Connected);
+        let mut rx = status.subscribe();
+        let io_error: RedisResult<()> =
+            Err(std::io::Error::from(std::io::ErrorKind::ConnectionReset).into());
+        let server_error: RedisResult<()> = Err((redis::ErrorKind::ResponseError, "ERR").into());
+
+        report_status(&status, &io_error);
+        assert!(rx.has_changed().unwrap());
+        assert_eq!(*rx.borrow_and_update(), StoreStatus::Disconnected);
+
+        report_status(&status, &io_error);
+        assert!(!rx.has_changed().unwrap());
+
+        report_status(&status, &server_error);
+        assert_eq!(*rx.borrow_and_update(), StoreStatus::Connected);
+    }
+
+    #[test]
+    fn test_cookie_expiry() {
+        let session = SessionData::new(3600);
+        let expires = session.cookie.expires.unwrap().timestamp_millis() as u64;
+        assert_eq!(cookie_expiry(&session, 3600), Some(expires));
+        // Cut short by an idle timeout
+        assert_eq!(cookie_expiry(&session, 600), None);
+        assert_eq!(
+            cookie_expiry(&SessionData::new_session_cookie(), 3600),
+            None
+        );
+    }
+
+    #[test]
+    fn test_timeouts_are_distinct() {
+        let timeout = redis::RedisError::from(std::io::Error::from(std::io::ErrorKind::TimedOut));
+        assert!(matches!(SessionError::from(timeout), SessionError::Timeout));
+        let refused =
+            redis::RedisError::from(std::io::Error::from(std::io::ErrorKind::ConnectionRefused));
+        assert!(matches!(
+            SessionError::from(refused),
+            SessionError::RedisError(_)
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_redis_store_timeouts() {
+        let options = RedisConnectOptions::new()
+            .with_response_timeout(Duration::from_millis(50))
+            .with_connect_timeout(Duration::from_millis(500))
+            .with_retries(1)
+            .with_max_backoff(Duration::from_millis(200));
+        let store = RedisStore::from_url_with_options("redis://127.0.0.1/", options)
+            .await
+            .unwrap();
+        store.ping().await.unwrap();
+
+        // A blocked server leaves commands unanswered
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let _: () = redis::cmd("CLIENT")
+            .arg("PAUSE")
+            .arg(300)
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.get("test-timeout").await,
+            Err(SessionError::Timeout)
+        ));
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_redis_store_basic() {
@@ -294,9 +1041,104 @@ mod tests {
         // Touch session
         store.touch("test-id", &data, Some(7200)).await.unwrap();
 
+        store.ping().await.unwrap();
+
         // Destroy session
         store.destroy("test-id").await.unwrap();
         let retrieved = store.get("test-id").await.unwrap();
         assert!(retrieved.is_none());
     }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_redis_store_disable_ttl() {
+        let store = RedisStore::from_url("redis://127.0.0.1/")
+            .await
+            .unwrap()
+            .with_disable_ttl(true);
+        store.clear().await.unwrap();
+
+        let data = SessionData::new(3600);
+        store.set("persistent", &data, Some(3600)).await.unwrap();
+        store.touch("persistent", &data, Some(60)).await.unwrap();
+
+        let mut conn = store.connection();
+        let ttl: i64 = conn.ttl("sess:persistent").await.unwrap();
+        assert_eq!(ttl, -1);
+        store.destroy("persistent").await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_redis_store_unlink() {
+        let store = RedisStore::from_url("redis://127.0.0.1/")
+            .await
+            .unwrap()
+            .with_unlink(true);
+        store.clear().await.unwrap();
+
+        let data = SessionData::new(3600);
+        store.set("a", &data, Some(3600)).await.unwrap();
+        store.set("b", &data, Some(3600)).await.unwrap();
+        store.destroy("a").await.unwrap();
+        assert!(store.get("a").await.unwrap().is_none());
+        store.clear().await.unwrap();
+        assert_eq!(store.length().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_redis_store_user_index() {
+        let store = RedisStore::from_url("redis://127.0.0.1/")
+            .await
+            .unwrap()
+            .with_user_index("user_sessions:");
+        store.clear().await.unwrap();
+
+        let mut data = SessionData::new(3600);
+        data.set("passport", serde_json::json!({ "user": 42 }));
+        store.set("laptop", &data, Some(3600)).await.unwrap();
+        store.set("phone", &data, Some(3600)).await.unwrap();
+
+        store.destroy("phone").await.unwrap();
+        assert_eq!(store.sessions_for_user("42").await.unwrap(), vec!["laptop"]);
+
+        assert_eq!(store.destroy_all_for_user("42").await.unwrap(), 1);
+        assert!(store.get("laptop").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_redis_store_scripts() {
+        let store = RedisStore::from_url("redis://127.0.0.1/")
+            .await
+            .unwrap()
+            .with_user_index("user_sessions:")
+            .with_scripts(true);
+        store.clear().await.unwrap();
+        store.load_scripts().await.unwrap();
+
+        let mut data = SessionData::new(3600);
+        data.set("passport", serde_json::json!({ "user": 42 }));
+        assert!(store
+            .compare_and_set("scripted", &data, None, Some(3600))
+            .await
+            .unwrap());
+        let mut conn = store.connection();
+        let members: Vec<String> = conn.smembers("user_sessions:42").await.unwrap();
+        assert_eq!(members, vec!["scripted"]);
+
+        assert!(store
+            .get_and_touch("scripted", Some(60))
+            .await
+            .unwrap()
+            .is_some());
+        let ttl: i64 = conn.ttl("sess:scripted").await.unwrap();
+        assert!(ttl <= 60);
+
+        store.destroy("scripted").await.unwrap();
+        assert!(store.get("scripted").await.unwrap().is_none());
+        let members: Vec<String> = conn.smembers("user_sessions:42").await.unwrap();
+        assert!(members.is_empty());
+    }
 }
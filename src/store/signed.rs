@@ -0,0 +1,334 @@
+//! Integrity-protected session records
+//!
+//! Cookies are signed, but the records they point to are trusted as stored:
+//! anyone with write access to Redis, or a compromised sibling app, could
+//! edit a session to log in as another user. [`SignedStore`] wraps a store
+//! and adds an HMAC-SHA256 of each record, verified on load, so such edits
+//! are detected:
+//!
+//! ```rust,ignore
+//! let store = SignedStore::new(RedisStore::from_url(&url).await?, "record-secret");
+//! let session_handler = ExpressSessionHandler::new(store, config);
+//! ```
+//!
+//! The MAC is stored as `sessionMac` next to the session's cookie, computed
+//! over the rest of the record with keys sorted. Records failing
+//! verification are treated as missing and logged. Node.js apps sharing the
+//! store don't maintain the MAC, so sessions they write are rejected unless
+//! [`SignedStore::with_accept_unsigned`] is set and they drop the field.
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::{Map, Value};
+use sha2::Sha256;
+use tokio::sync::watch;
+
+use super::{SessionStore, StoreStatus};
+use crate::error::SessionError;
+use crate::session::SessionData;
+
+/// [`SessionStore`] adding an integrity MAC to the records of another store
+pub struct SignedStore<S: SessionStore> {
+    inner: S,
+    secrets: Vec<String>,
+    accept_unsigned: bool,
+}
+
+impl<S: SessionStore> SignedStore<S> {
+    /// Sign the records of `inner` with `secret`
+    pub fn new<K: Into<String>>(inner: S, secret: K) -> Self {
+        Self {
+            inner,
+            secrets: vec![secret.into()],
+            accept_unsigned: false,
+        }
+    }
+
+    /// Set the secrets, for rotation: the first signs, all verify
+    pub fn with_secrets<I, K>(mut self, secrets: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        let secrets: Vec<String> = secrets.into_iter().map(Into::into).collect();
+        if !secrets.is_empty() {
+            self.secrets = secrets;
+        }
+        self
+    }
+
+    /// Set whether records without a MAC are accepted (default: false)
+    ///
+    /// Lets existing sessions survive enabling signing; they are signed on
+    /// their next save. Tampering that removes the MAC goes unnoticed while
+    /// this is set, so only enable it for the rollout.
+    pub fn with_accept_unsigned(mut self, accept: bool) -> Self {
+        self.accept_unsigned = accept;
+        self
+    }
+
+    /// Get a reference to the wrapped store
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// A copy of `session` carrying the MAC made with the first secret
+    fn sign(&self, session: &SessionData) -> Result<SessionData, SessionError> {
+        let mut signed = session.clone();
+        signed.mac = Some(mac(&self.secrets[0], session)?);
+        Ok(signed)
+    }
+
+    /// Check and strip the MAC of a loaded record
+    fn verify(&self, sid: &str, mut session: SessionData) -> Option<SessionData> {
+        let valid = match session.mac.take() {
+            Some(stored) => {
+                // The loaded JSON carries the MAC just removed
+                session.mark_changed();
+                let stored = URL_SAFE_NO_PAD.decode(stored).unwrap_or_default();
+                self.secrets.iter().any(|secret| {
+                    canonical(&session)
+                        .map(|payload| hmac(secret, &payload).verify_slice(&stored).is_ok())
+                        .unwrap_or(false)
+                })
+            }
+            None => self.accept_unsigned,
+        };
+        if !valid {
+            tracing::warn!(
+                session.id_hash = %crate::handler::hash_session_id(sid),
+                "Discarding session record with an invalid MAC"
+            );
+            return None;
+        }
+        Some(session)
+    }
+}
+
+/// HMAC-SHA256 over `payload`
+fn hmac(secret: &str, payload: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Base64url MAC of a session record, ignoring any MAC it carries
+fn mac(secret: &str, session: &SessionData) -> Result<String, SessionError> {
+    let payload = canonical(session)?;
+    Ok(URL_SAFE_NO_PAD.encode(hmac(secret, &payload).finalize().into_bytes()))
+}
+
+/// JSON of a record without its MAC, with object keys sorted at every level
+fn canonical(session: &SessionData) -> Result<String, SessionError> {
+    let mut value = serde_json::to_value(session)?;
+    if let Value::Object(map) = &mut value {
+        map.remove("sessionMac");
+    }
+    Ok(sorted(value).to_string())
+}
+
+/// Rebuild objects in key order, whatever map type serde_json uses
+fn sorted(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sorted(value)))
+                    .collect::<Map<String, Value>>(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sorted).collect()),
+        other => other,
+    }
+}
+
+#[async_trait]
+impl<S: SessionStore> SessionStore for SignedStore<S> {
+    async fn get(&self, sid: &str) -> Result<Option<SessionData>, SessionError> {
+        let session = self.inner.get(sid).await?;
+        Ok(session.and_then(|session| self.verify(sid, session)))
+    }
+
+    async fn set(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        ttl_secs: Option<u64>,
+    ) -> Result<(), SessionError> {
+        self.inner.set(sid, &self.sign(session)?, ttl_secs).await
+    }
+
+    async fn patch(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        changed: &[String],
+        ttl_secs: Option<u64>,
+    ) -> Result<(), SessionError> {
+        // The MAC covers the whole record, so the whole record is written
+        let _ = changed;
+        self.set(sid, session, ttl_secs).await
+    }
+
+    async fn compare_and_set(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        expected: Option<u64>,
+        ttl_secs: Option<u64>,
+    ) -> Result<bool, SessionError> {
+        self.inner
+            .compare_and_set(sid, &self.sign(session)?, expected, ttl_secs)
+            .await
+    }
+
+    async fn destroy(&self, sid: &str) -> Result<(), SessionError> {
+        self.inner.destroy(sid).await
+    }
+
+    async fn touch(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        ttl_secs: Option<u64>,
+    ) -> Result<(), SessionError> {
+        // Stores may rewrite the record on touch
+        self.inner.touch(sid, &self.sign(session)?, ttl_secs).await
+    }
+
+    async fn get_and_touch(
+        &self,
+        sid: &str,
+        ttl_secs: Option<u64>,
+    ) -> Result<Option<SessionData>, SessionError> {
+        let session = self.inner.get_and_touch(sid, ttl_secs).await?;
+        Ok(session.and_then(|session| self.verify(sid, session)))
+    }
+
+    async fn get_many(&self, sids: &[String]) -> Result<Vec<Option<SessionData>>, SessionError> {
+        let sessions = self.inner.get_many(sids).await?;
+        Ok(sids
+            .iter()
+            .zip(sessions)
+            .map(|(sid, session)| session.and_then(|session| self.verify(sid, session)))
+            .collect())
+    }
+
+    async fn set_many(
+        &self,
+        sessions: &[(String, SessionData, Option<u64>)],
+    ) -> Result<(), SessionError> {
+        let signed = sessions
+            .iter()
+            .map(|(sid, session, ttl)| Ok((sid.clone(), self.sign(session)?, *ttl)))
+            .collect::<Result<Vec<_>, SessionError>>()?;
+        self.inner.set_many(&signed).await
+    }
+
+    async fn clear(&self) -> Result<(), SessionError> {
+        self.inner.clear().await
+    }
+
+    async fn length(&self) -> Result<usize, SessionError> {
+        self.inner.length().await
+    }
+
+    async fn ids(&self) -> Result<Vec<String>, SessionError> {
+        self.inner.ids().await
+    }
+
+    async fn all(&self) -> Result<Vec<SessionData>, SessionError> {
+        // Records don't carry their ID, so invalid ones are logged without it
+        Ok(self
+            .inner
+            .all()
+            .await?
+            .into_iter()
+            .filter_map(|session| self.verify("", session))
+            .collect())
+    }
+
+    async fn sessions_for_user(&self, uid: &str) -> Result<Vec<String>, SessionError> {
+        self.inner.sessions_for_user(uid).await
+    }
+
+    async fn destroy_all_for_user(&self, uid: &str) -> Result<usize, SessionError> {
+        self.inner.destroy_all_for_user(uid).await
+    }
+
+    async fn prune_expired(
+        &self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, SessionError> {
+        self.inner.prune_expired(before, limit).await
+    }
+
+    async fn flush(&self) -> Result<(), SessionError> {
+        self.inner.flush().await
+    }
+
+    async fn ping(&self) -> Result<(), SessionError> {
+        self.inner.ping().await
+    }
+
+    fn status(&self) -> Option<watch::Receiver<StoreStatus>> {
+        self.inner.status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    #[tokio::test]
+    async fn test_tampering_is_detected() {
+        let store = SignedStore::new(MemoryStore::new(), "record-secret");
+        let mut data = SessionData::new(3600);
+        data.set("user", "alice");
+        data.set(
+            "nested",
+            serde_json::json!({"b": 1, "a": [{"y": 2, "x": 3}]}),
+        );
+        store.set("abc", &data, Some(3600)).await.unwrap();
+
+        let loaded = store.get("abc").await.unwrap().unwrap();
+        assert_eq!(loaded.get::<String>("user").as_deref(), Some("alice"));
+        assert!(loaded.mac.is_none());
+
+        // Edited behind the store's back
+        let mut stored = store.inner().get("abc").await.unwrap().unwrap();
+        assert!(stored.mac.is_some());
+        stored.set("user", "mallory");
+        store.inner().set("abc", &stored, Some(3600)).await.unwrap();
+        assert!(store.get("abc").await.unwrap().is_none());
+
+        // Written without a MAC
+        store.inner().set("abc", &data, Some(3600)).await.unwrap();
+        assert!(store.get("abc").await.unwrap().is_none());
+        let lenient =
+            SignedStore::new(MemoryStore::new(), "record-secret").with_accept_unsigned(true);
+        lenient.inner().set("abc", &data, Some(3600)).await.unwrap();
+        assert!(lenient.get("abc").await.unwrap().is_some());
+
+        // Rotated secrets still verify
+        store.set("abc", &data, Some(3600)).await.unwrap();
+        let stored = store.inner().get("abc").await.unwrap().unwrap();
+        let rotated =
+            SignedStore::new(MemoryStore::new(), "new").with_secrets(["new", "record-secret"]);
+        rotated
+            .inner()
+            .set("abc", &stored, Some(3600))
+            .await
+            .unwrap();
+        assert!(rotated.get("abc").await.unwrap().is_some());
+    }
+}
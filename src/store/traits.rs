@@ -1,8 +1,22 @@
 //! Session store trait
 
+use std::sync::Arc;
+
+use crate::auth::user_key;
 use crate::error::SessionError;
 use crate::session::SessionData;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tokio::sync::watch;
+
+/// Whether a store can reach its backend
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StoreStatus {
+    /// The last command reached the backend
+    Connected,
+    /// The last command failed to reach the backend
+    Disconnected,
+}
 
 /// Trait for session storage backends
 ///
@@ -26,6 +40,45 @@ pub trait SessionStore: Send + Sync + 'static {
         ttl_secs: Option<u64>,
     ) -> Result<(), SessionError>;
 
+    /// Update an existing session, persisting only the changed keys (optional)
+    ///
+    /// `changed` lists the user data keys set, changed or removed during the
+    /// request; a key missing from `session` was removed. The cookie,
+    /// `createdAt` and `lastAccess` aren't tracked per key and should always
+    /// be written. The default rewrites the whole session with [`Self::set`].
+    async fn patch(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        changed: &[String],
+        ttl_secs: Option<u64>,
+    ) -> Result<(), SessionError> {
+        let _ = changed;
+        self.set(sid, session, ttl_secs).await
+    }
+
+    /// Write a session only if its stored version is still `expected` (optional)
+    ///
+    /// `expected` is the `version` the session was loaded with; `None`
+    /// matches a missing session or one saved without a version. Returns
+    /// false, writing nothing, if another request saved the session in the
+    /// meantime. The default checks with [`Self::get`] before calling
+    /// [`Self::set`], which is not atomic; stores should override it.
+    async fn compare_and_set(
+        &self,
+        sid: &str,
+        session: &SessionData,
+        expected: Option<u64>,
+        ttl_secs: Option<u64>,
+    ) -> Result<bool, SessionError> {
+        let current = self.get(sid).await?.and_then(|stored| stored.version);
+        if current != expected {
+            return Ok(false);
+        }
+        self.set(sid, session, ttl_secs).await?;
+        Ok(true)
+    }
+
     /// Destroy/delete a session
     async fn destroy(&self, sid: &str) -> Result<(), SessionError>;
 
@@ -39,6 +92,48 @@ pub trait SessionStore: Send + Sync + 'static {
         ttl_secs: Option<u64>,
     ) -> Result<(), SessionError>;
 
+    /// Get a session and refresh its TTL, like [`Self::get`] then [`Self::touch`]
+    ///
+    /// Stores that can should do both in one round-trip; the default makes
+    /// both calls.
+    async fn get_and_touch(
+        &self,
+        sid: &str,
+        ttl_secs: Option<u64>,
+    ) -> Result<Option<SessionData>, SessionError> {
+        let session = self.get(sid).await?;
+        if let Some(session) = &session {
+            self.touch(sid, session, ttl_secs).await?;
+        }
+        Ok(session)
+    }
+
+    /// Get several sessions, in the order of `sids`, with None for missing ones
+    ///
+    /// Stores that can should fetch them in one round-trip; the default
+    /// calls [`Self::get`] for each.
+    async fn get_many(&self, sids: &[String]) -> Result<Vec<Option<SessionData>>, SessionError> {
+        let mut sessions = Vec::with_capacity(sids.len());
+        for sid in sids {
+            sessions.push(self.get(sid).await?);
+        }
+        Ok(sessions)
+    }
+
+    /// Save several sessions, each with its TTL as in [`Self::set`]
+    ///
+    /// Stores that can should save them in one round-trip; the default
+    /// calls [`Self::set`] for each.
+    async fn set_many(
+        &self,
+        sessions: &[(String, SessionData, Option<u64>)],
+    ) -> Result<(), SessionError> {
+        for (sid, session, ttl_secs) in sessions {
+            self.set(sid, session, *ttl_secs).await?;
+        }
+        Ok(())
+    }
+
     /// Clear all sessions (optional)
     async fn clear(&self) -> Result<(), SessionError> {
         Err(SessionError::StoreError(
@@ -62,4 +157,223 @@ pub trait SessionStore: Send + Sync + 'static {
     async fn all(&self) -> Result<Vec<SessionData>, SessionError> {
         Err(SessionError::StoreError("all not implemented".to_string()))
     }
+
+    /// Get the IDs of the sessions a user is logged into (optional)
+    ///
+    /// The user is the one stored by [`Session::login`](crate::Session::login)
+    /// (`passport.user`), with numeric IDs formatted as numbers. The default
+    /// loads every session listed by [`Self::ids`]; stores should keep an index.
+    async fn sessions_for_user(&self, uid: &str) -> Result<Vec<String>, SessionError> {
+        scan_sessions_for_user(self, uid).await
+    }
+
+    /// Destroy every session of a user, e.g. to log out everywhere (optional)
+    ///
+    /// Returns the number of sessions destroyed.
+    async fn destroy_all_for_user(&self, uid: &str) -> Result<usize, SessionError> {
+        let sids = self.sessions_for_user(uid).await?;
+        for sid in &sids {
+            self.destroy(sid).await?;
+        }
+        Ok(sids.len())
+    }
+
+    /// Remove up to `limit` sessions that expired before `before` (optional)
+    ///
+    /// Returns the number removed. Stores that can't expire keys natively
+    /// (files, SQL tables) implement this so a
+    /// [`SessionReaper`](crate::SessionReaper) keeps them bounded; the
+    /// default removes nothing.
+    async fn prune_expired(
+        &self,
+        before: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<usize, SessionError> {
+        let _ = (before, limit);
+        Ok(0)
+    }
+
+    /// Write out anything buffered before the process exits (optional)
+    ///
+    /// Called by [`ExpressSessionHandler::shutdown`](crate::ExpressSessionHandler::shutdown).
+    /// The default does nothing, for stores that write through.
+    async fn flush(&self) -> Result<(), SessionError> {
+        Ok(())
+    }
+
+    /// Check that the backend is reachable
+    ///
+    /// Stores without a network backend are always reachable.
+    async fn ping(&self) -> Result<(), SessionError> {
+        Ok(())
+    }
+
+    /// Watch connectivity changes, like express-session stores' `connect`
+    /// and `disconnect` events (optional)
+    ///
+    /// Stores without a network backend return None.
+    fn status(&self) -> Option<watch::Receiver<StoreStatus>> {
+        None
+    }
+
+    /// Name of the store, labeling its metrics
+    ///
+    /// Defaults to the type name without path or generics.
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        let name = name.split('<').next().unwrap_or(name);
+        name.rsplit("::").next().unwrap_or(name)
+    }
+}
+
+/// Find a user's sessions by loading every stored session
+pub(crate) async fn scan_sessions_for_user<S: SessionStore + ?Sized>(
+    store: &S,
+    uid: &str,
+) -> Result<Vec<String>, SessionError> {
+    let mut sids = Vec::new();
+    for sid in store.ids().await? {
+        let Some(data) = store.get(&sid).await? else {
+            continue;
+        };
+        if user_key(&data).as_deref() == Some(uid) {
+            sids.push(sid);
+        }
+    }
+    Ok(sids)
 }
+
+/// Sessions fetched or saved per [`SessionStore::get_many`] and
+/// [`SessionStore::set_many`] call by bulk operations
+pub(crate) const BATCH_SIZE: usize = 100;
+
+/// Forward the store interface through a smart pointer, so type-erased
+/// stores (`Box<dyn SessionStore>`, `Arc<dyn SessionStore>`) can be used
+/// wherever a store is expected
+macro_rules! forward_session_store {
+    ($ptr:ident) => {
+        #[async_trait]
+        impl<T: SessionStore + ?Sized> SessionStore for $ptr<T> {
+            async fn get(&self, sid: &str) -> Result<Option<SessionData>, SessionError> {
+                (**self).get(sid).await
+            }
+
+            async fn set(
+                &self,
+                sid: &str,
+                session: &SessionData,
+                ttl_secs: Option<u64>,
+            ) -> Result<(), SessionError> {
+                (**self).set(sid, session, ttl_secs).await
+            }
+
+            async fn patch(
+                &self,
+                sid: &str,
+                session: &SessionData,
+                changed: &[String],
+                ttl_secs: Option<u64>,
+            ) -> Result<(), SessionError> {
+                (**self).patch(sid, session, changed, ttl_secs).await
+            }
+
+            async fn compare_and_set(
+                &self,
+                sid: &str,
+                session: &SessionData,
+                expected: Option<u64>,
+                ttl_secs: Option<u64>,
+            ) -> Result<bool, SessionError> {
+                (**self)
+                    .compare_and_set(sid, session, expected, ttl_secs)
+                    .await
+            }
+
+            async fn destroy(&self, sid: &str) -> Result<(), SessionError> {
+                (**self).destroy(sid).await
+            }
+
+            async fn get_and_touch(
+                &self,
+                sid: &str,
+                ttl_secs: Option<u64>,
+            ) -> Result<Option<SessionData>, SessionError> {
+                (**self).get_and_touch(sid, ttl_secs).await
+            }
+
+            async fn get_many(
+                &self,
+                sids: &[String],
+            ) -> Result<Vec<Option<SessionData>>, SessionError> {
+                (**self).get_many(sids).await
+            }
+
+            async fn set_many(
+                &self,
+                sessions: &[(String, SessionData, Option<u64>)],
+            ) -> Result<(), SessionError> {
+                (**self).set_many(sessions).await
+            }
+
+            async fn touch(
+                &self,
+                sid: &str,
+                session: &SessionData,
+                ttl_secs: Option<u64>,
+            ) -> Result<(), SessionError> {
+                (**self).touch(sid, session, ttl_secs).await
+            }
+
+            async fn clear(&self) -> Result<(), SessionError> {
+                (**self).clear().await
+            }
+
+            async fn length(&self) -> Result<usize, SessionError> {
+                (**self).length().await
+            }
+
+            async fn ids(&self) -> Result<Vec<String>, SessionError> {
+                (**self).ids().await
+            }
+
+            async fn all(&self) -> Result<Vec<SessionData>, SessionError> {
+                (**self).all().await
+            }
+
+            async fn sessions_for_user(&self, uid: &str) -> Result<Vec<String>, SessionError> {
+                (**self).sessions_for_user(uid).await
+            }
+
+            async fn destroy_all_for_user(&self, uid: &str) -> Result<usize, SessionError> {
+                (**self).destroy_all_for_user(uid).await
+            }
+
+            async fn prune_expired(
+                &self,
+                before: DateTime<Utc>,
+                limit: usize,
+            ) -> Result<usize, SessionError> {
+                (**self).prune_expired(before, limit).await
+            }
+
+            async fn flush(&self) -> Result<(), SessionError> {
+                (**self).flush().await
+            }
+
+            async fn ping(&self) -> Result<(), SessionError> {
+                (**self).ping().await
+            }
+
+            fn status(&self) -> Option<watch::Receiver<StoreStatus>> {
+                (**self).status()
+            }
+
+            fn name(&self) -> &'static str {
+                (**self).name()
+            }
+        }
+    };
+}
+
+forward_session_store!(Box);
+forward_session_store!(Arc);
@@ -0,0 +1,200 @@
+//! Bulk export, import and migration between stores
+//!
+//! [`dump`] writes every session as one JSON object per line (JSONL):
+//!
+//! ```text
+//! {"sid":"abc","ttl":3541,"data":{"cookie":{...},"user":"alice"}}
+//! ```
+//!
+//! `ttl` is the remaining lifetime in seconds from the cookie expiry, or
+//! null for sessions without one, which [`restore`] saves with the target
+//! store's default TTL. [`migrate`] copies sessions directly between two
+//! stores, e.g. from Redis to a SQL store during a switch-over.
+
+use std::io::{BufRead, Write};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::{SessionStore, BATCH_SIZE};
+use crate::error::SessionError;
+use crate::session::SessionData;
+
+/// One line of a dump
+#[derive(Serialize, Deserialize)]
+struct DumpRecord {
+    sid: String,
+    ttl: Option<u64>,
+    data: SessionData,
+}
+
+/// Remaining lifetime of a session, None if it has already expired
+///
+/// Sessions without a cookie expiry have an unknown lifetime (`Some(None)`).
+fn remaining_ttl(data: &SessionData) -> Option<Option<u64>> {
+    match data.cookie.expires {
+        Some(expires) => {
+            let secs = (expires - Utc::now()).num_seconds();
+            (secs > 0).then_some(Some(secs as u64))
+        }
+        None => Some(None),
+    }
+}
+
+/// Write every unexpired session of `store` to `writer` as JSONL
+///
+/// Returns the number of sessions written. Needs a store implementing
+/// [`SessionStore::ids`].
+pub async fn dump<S, W>(store: &S, mut writer: W) -> Result<usize, SessionError>
+where
+    S: SessionStore + ?Sized,
+    W: Write,
+{
+    let mut written = 0;
+    for batch in store.ids().await?.chunks(BATCH_SIZE) {
+        for (sid, data) in batch.iter().zip(store.get_many(batch).await?) {
+            let Some(data) = data else {
+                continue;
+            };
+            let Some(ttl) = remaining_ttl(&data) else {
+                continue;
+            };
+            let sid = sid.clone();
+            let line = serde_json::to_string(&DumpRecord { sid, ttl, data })?;
+            writeln!(writer, "{}", line).map_err(io_error)?;
+            written += 1;
+        }
+    }
+    writer.flush().map_err(io_error)?;
+    Ok(written)
+}
+
+/// Save every session of a JSONL dump from `reader` into `store`
+///
+/// Blank lines are skipped. Returns the number of sessions saved.
+pub async fn restore<S, R>(store: &S, reader: R) -> Result<usize, SessionError>
+where
+    S: SessionStore + ?Sized,
+    R: BufRead,
+{
+    let mut restored = 0;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(io_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: DumpRecord = serde_json::from_str(&line)
+            .map_err(|e| SessionError::SerializationError(format!("line {}: {}", index + 1, e)))?;
+        batch.push((record.sid, record.data, record.ttl));
+        if batch.len() == BATCH_SIZE {
+            store.set_many(&batch).await?;
+            restored += batch.len();
+            batch.clear();
+        }
+    }
+    store.set_many(&batch).await?;
+    Ok(restored + batch.len())
+}
+
+/// Copy every unexpired session from one store to another
+///
+/// Sessions are copied in batches, keeping their IDs and remaining
+/// lifetime. `progress` is called for each session once its batch is
+/// saved, with the number copied so far and the number listed by the source. Returns the number
+/// copied; the source is left untouched.
+pub async fn migrate<F, T>(
+    from: &F,
+    to: &T,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<usize, SessionError>
+where
+    F: SessionStore + ?Sized,
+    T: SessionStore + ?Sized,
+{
+    let ids = from.ids().await?;
+    let total = ids.len();
+    let mut copied = 0;
+    for batch in ids.chunks(BATCH_SIZE) {
+        // Sessions can expire or be destroyed while migrating
+        let sessions: Vec<_> = batch
+            .iter()
+            .zip(from.get_many(batch).await?)
+            .filter_map(|(sid, data)| {
+                let data = data?;
+                let ttl = remaining_ttl(&data)?;
+                Some((sid.clone(), data, ttl))
+            })
+            .collect();
+        to.set_many(&sessions).await?;
+        for _ in &sessions {
+            copied += 1;
+            progress(copied, total);
+        }
+    }
+    Ok(copied)
+}
+
+/// Report an I/O failure of a dump or restore
+fn io_error(source: std::io::Error) -> SessionError {
+    SessionError::Io {
+        context: "Session dump I/O failed".to_string(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    #[tokio::test]
+    async fn test_dump_and_restore() {
+        let source = MemoryStore::new();
+        let mut data = SessionData::new(3600);
+        data.set("user", "alice");
+        source.set("a", &data, Some(3600)).await.unwrap();
+        source
+            .set("b", &SessionData::new_session_cookie(), None)
+            .await
+            .unwrap();
+        let mut expired = SessionData::new(3600);
+        expired.cookie.expires = Some(Utc::now() - chrono::Duration::minutes(1));
+        source.set("expired", &expired, None).await.unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(dump(&source, &mut out).await.unwrap(), 2);
+
+        let target = MemoryStore::new();
+        assert_eq!(restore(&target, out.as_slice()).await.unwrap(), 2);
+        let restored = target.get("a").await.unwrap().unwrap();
+        assert_eq!(restored.get::<String>("user"), Some("alice".to_string()));
+        assert!(target.get("b").await.unwrap().is_some());
+        assert!(target.get("expired").await.unwrap().is_none());
+
+        let invalid = restore(&target, "\n{not json}\n".as_bytes()).await;
+        assert!(
+            matches!(invalid, Err(SessionError::SerializationError(msg)) if msg.starts_with("line 2"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_migrate() {
+        let source = MemoryStore::new();
+        for sid in ["a", "b", "c"] {
+            source
+                .set(sid, &SessionData::new(3600), Some(3600))
+                .await
+                .unwrap();
+        }
+        let target = MemoryStore::with_prefix("new:");
+
+        let mut reports = Vec::new();
+        let copied = migrate(&source, &target, |done, total| reports.push((done, total)))
+            .await
+            .unwrap();
+        assert_eq!(copied, 3);
+        assert_eq!(reports, vec![(1, 3), (2, 3), (3, 3)]);
+        assert_eq!(target.length().await.unwrap(), 3);
+    }
+}
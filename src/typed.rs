@@ -0,0 +1,168 @@
+//! Typed views over the session data
+//!
+//! express-session stores user data flattened next to `cookie`. A typed view
+//! deserializes those fields into a struct, lets the handler work with plain
+//! field access, and writes the fields back when committed or dropped.
+//!
+//! ```rust,ignore
+//! #[derive(Serialize, Deserialize, Default)]
+//! struct Cart {
+//!     #[serde(default)]
+//!     items: Vec<String>,
+//! }
+//!
+//! let mut cart = session.as_typed::<Cart>()?;
+//! cart.items.push("book".to_string());
+//! cart.commit()?;
+//! ```
+
+use std::ops::{Deref, DerefMut};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::SessionError;
+use crate::session::Session;
+
+/// Guard giving typed access to the session data
+///
+/// Changes are written back by [`TypedSession::commit`], or on drop (where
+/// serialization errors can only be logged). Fields of the session that `T`
+/// doesn't know about are left untouched, and the session is only marked
+/// modified if the value actually changed.
+pub struct TypedSession<'a, T: Serialize> {
+    session: &'a Session,
+    value: Option<T>,
+    original: Value,
+}
+
+impl Session {
+    /// Deserialize the session data into `T` and return a write-back guard
+    pub fn as_typed<T: Serialize + DeserializeOwned>(
+        &self,
+    ) -> Result<TypedSession<'_, T>, SessionError> {
+        let data = Value::Object(self.read().data.clone().into_iter().collect());
+        let value: T = serde_json::from_value(data)?;
+        let original = serde_json::to_value(&value)?;
+
+        Ok(TypedSession {
+            session: self,
+            value: Some(value),
+            original,
+        })
+    }
+}
+
+impl<T: Serialize> TypedSession<'_, T> {
+    /// Write the value back to the session
+    pub fn commit(mut self) -> Result<(), SessionError> {
+        self.write_back()
+    }
+
+    /// Drop the guard without writing changes back
+    pub fn discard(mut self) {
+        self.value = None;
+    }
+
+    /// Merge the serialized fields into the session data if they changed
+    fn write_back(&mut self) -> Result<(), SessionError> {
+        let Some(value) = self.value.take() else {
+            return Ok(());
+        };
+
+        let value = serde_json::to_value(&value)?;
+        if value == self.original {
+            return Ok(());
+        }
+        let Value::Object(fields) = value else {
+            return Err(SessionError::SerializationError(
+                "Typed session value must serialize to a map".to_string(),
+            ));
+        };
+
+        let removed = match &self.original {
+            Value::Object(original) => original
+                .keys()
+                .filter(|key| !fields.contains_key(*key))
+                .cloned()
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let mut data = self.session.write();
+        for key in removed {
+            data.data.remove(&key);
+        }
+        data.data.extend(fields);
+        drop(data);
+
+        self.session.mark_modified();
+        Ok(())
+    }
+}
+
+impl<T: Serialize> Deref for TypedSession<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+            .as_ref()
+            .expect("typed session value is present until dropped")
+    }
+}
+
+impl<T: Serialize> DerefMut for TypedSession<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+            .as_mut()
+            .expect("typed session value is present until dropped")
+    }
+}
+
+impl<T: Serialize> Drop for TypedSession<'_, T> {
+    fn drop(&mut self) {
+        if let Err(e) = self.write_back() {
+            tracing::error!("Failed to write back typed session: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionData;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize)]
+    struct Cart {
+        #[serde(default)]
+        items: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        coupon: Option<String>,
+    }
+
+    #[test]
+    fn test_typed_write_back() {
+        let session = Session::new("sid".to_string(), SessionData::default(), false);
+        session.set("user", "alice");
+        session.set("coupon", "SAVE10");
+
+        {
+            let mut cart = session.as_typed::<Cart>().unwrap();
+            cart.items.push("book".to_string());
+            cart.coupon = None;
+        }
+
+        assert_eq!(session.get::<Vec<String>>("items").unwrap(), vec!["book"]);
+        assert_eq!(session.get::<String>("user").unwrap(), "alice");
+        assert!(!session.contains("coupon"));
+    }
+
+    #[test]
+    fn test_unchanged_is_not_modified() {
+        let session = Session::new("sid".to_string(), SessionData::default(), false);
+        session.as_typed::<Cart>().unwrap().commit().unwrap();
+        assert!(!session.is_modified());
+    }
+}
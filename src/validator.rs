@@ -0,0 +1,110 @@
+//! Application checks of loaded sessions
+//!
+//! A [`SessionValidator`] sees every session loaded from the store together
+//! with the request, and decides whether the request may use it: the
+//! extension point for anomaly detection, geo checks or tenant checks.
+//!
+//! ```rust,ignore
+//! struct SameTenant;
+//!
+//! #[async_trait]
+//! impl SessionValidator for SameTenant {
+//!     async fn validate(&self, req: &Request, _sid: &str, data: &SessionData) -> Validation {
+//!         let host = req.uri().host().unwrap_or_default();
+//!         match data.get::<String>("tenant") {
+//!             Some(tenant) if !host.starts_with(&tenant) => Validation::Reject,
+//!             _ => Validation::Accept,
+//!         }
+//!     }
+//! }
+//!
+//! let session_handler = ExpressSessionHandler::new(store, config).with_validator(SameTenant);
+//! ```
+//!
+//! Validators run before downstream handlers. Sessions are loaded eagerly
+//! while a validator is set, even with `lazy_load`.
+
+use async_trait::async_trait;
+use salvo_core::Request;
+
+use crate::session::SessionData;
+
+/// Decision of a [`SessionValidator`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Validation {
+    /// Use the session
+    Accept,
+    /// Treat the session as missing: the request gets a new one, and the
+    /// stored session is left alone
+    Reject,
+    /// Use the session's data under a new ID; the old one is destroyed
+    Regenerate,
+}
+
+/// Check run on every session loaded from the store
+#[async_trait]
+pub trait SessionValidator: Send + Sync + 'static {
+    /// Decide whether `req` may use the session `sid`
+    async fn validate(&self, req: &Request, sid: &str, data: &SessionData) -> Validation;
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_core::{handler, Depot, Router, Service};
+
+    use super::*;
+    use crate::config::SessionConfig;
+    use crate::depot_ext::SessionDepotExt;
+    use crate::handler::ExpressSessionHandler;
+    use crate::store::{MemoryStore, SessionStore};
+
+    /// Decides by the `x-validation` request header
+    struct ByHeader;
+
+    #[async_trait]
+    impl SessionValidator for ByHeader {
+        async fn validate(&self, req: &Request, _sid: &str, _data: &SessionData) -> Validation {
+            match req.header::<String>("x-validation").as_deref() {
+                Some("reject") => Validation::Reject,
+                Some("regenerate") => Validation::Regenerate,
+                _ => Validation::Accept,
+            }
+        }
+    }
+
+    #[handler]
+    async fn visit(depot: &mut Depot) -> String {
+        let session = depot.session_mut().unwrap();
+        let visits = session.get::<u64>("visits").unwrap_or(0) + 1;
+        session.set("visits", visits);
+        visits.to_string()
+    }
+
+    #[tokio::test]
+    async fn test_validation() {
+        let store = MemoryStore::new();
+        let config = SessionConfig::new("secret").with_lazy_load(true);
+        let handler = ExpressSessionHandler::new(store.clone(), config).with_validator(ByHeader);
+        let service = Service::new(Router::new().hoop(handler).get(visit));
+
+        let res = TestClient::get("http://127.0.0.1/").send(&service).await;
+        let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+
+        let request = |validation: &'static str| {
+            TestClient::get("http://127.0.0.1/")
+                .add_header("cookie", &cookie, true)
+                .add_header("x-validation", validation, true)
+                .send(&service)
+        };
+        assert_eq!(request("accept").await.take_string().await.unwrap(), "2");
+        assert_eq!(request("reject").await.take_string().await.unwrap(), "1");
+        assert_eq!(store.length().await.unwrap(), 2);
+
+        let mut res = request("regenerate").await;
+        let regenerated = res.cookie("connect.sid").unwrap().stripped().to_string();
+        assert_ne!(regenerated, cookie);
+        assert_eq!(res.take_string().await.unwrap(), "3");
+        assert_eq!(request("accept").await.take_string().await.unwrap(), "1");
+    }
+}
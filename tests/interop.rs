@@ -0,0 +1,165 @@
+//! Cross-language interop tests (requires the `interop-tests` feature)
+//!
+//! Starts Redis and the Node.js express-session app from `tests/e2e` in
+//! Docker containers through testcontainers, then round-trips sessions
+//! between it and an in-process Rust handler sharing the same Redis, in both
+//! directions:
+//!
+//! ```text
+//! cargo test --features interop-tests --test interop
+//! ```
+//!
+//! Needs a Docker daemon and network access to pull `redis:7-alpine` and
+//! `node:20-alpine`. Containers are removed when the test ends.
+
+#![cfg(feature = "interop-tests")]
+
+use std::path::Path;
+use std::time::Duration;
+
+use salvo::prelude::*;
+use salvo::test::{ResponseExt, TestClient};
+use salvo_express_session::{ExpressSessionHandler, RedisStore, SessionConfig, SessionDepotExt};
+use testcontainers::core::{IntoContainerPort, Mount, WaitFor};
+use testcontainers::runners::AsyncRunner;
+use testcontainers::{ContainerAsync, GenericImage, ImageExt};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Secret shared with `tests/e2e/server.js`
+const SECRET: &str = "e2e-test-secret-key";
+
+/// Host address of a container's published `port`
+async fn host_addr(container: &ContainerAsync<GenericImage>, port: u16) -> String {
+    let host = container.get_host().await.unwrap();
+    let port = container.get_host_port_ipv4(port.tcp()).await.unwrap();
+    format!("{}:{}", host, port)
+}
+
+/// Minimal HTTP/1.1 GET, returning the `connect.sid` cookie set and the body
+async fn get(addr: &str, path: &str, cookie: Option<&str>) -> (Option<String>, serde_json::Value) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let cookie = cookie
+        .map(|c| format!("Cookie: {}\r\n", c))
+        .unwrap_or_default();
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\n{}Connection: close\r\n\r\n",
+        path, addr, cookie
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let set_cookie = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(name, _)| name.eq_ignore_ascii_case("set-cookie"))
+        .filter_map(|(_, value)| value.trim().split(';').next())
+        .find(|pair| pair.starts_with("connect.sid="))
+        .map(String::from);
+    (set_cookie, serde_json::from_str(body).unwrap())
+}
+
+#[handler]
+async fn rust_set(req: &mut Request, depot: &mut Depot) -> String {
+    let key = req.query::<String>("key").unwrap();
+    let value = req.query::<String>("value").unwrap();
+    depot.session_mut().unwrap().set(&key, value);
+    "ok".to_string()
+}
+
+#[handler]
+async fn rust_get(req: &mut Request, depot: &mut Depot) -> String {
+    let key = req.query::<String>("key").unwrap();
+    let session = depot.session().unwrap();
+    session.get::<String>(&key).unwrap_or_default()
+}
+
+#[handler]
+async fn rust_counter(depot: &mut Depot) -> String {
+    let session = depot.session_mut().unwrap();
+    let counter = session.get::<i64>("counter").unwrap_or(0) + 1;
+    session.set("counter", counter);
+    counter.to_string()
+}
+
+#[tokio::test]
+async fn test_sessions_round_trip_with_express_session() {
+    // Containers on a private network reach each other by container name
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let network = format!("session-interop-{}", id);
+    let redis_name = format!("session-interop-redis-{}", id);
+
+    let redis = GenericImage::new("redis", "7-alpine")
+        .with_exposed_port(6379.tcp())
+        .with_wait_for(WaitFor::message_on_stdout("Ready to accept connections"))
+        .with_network(&network)
+        .with_container_name(&redis_name)
+        .start()
+        .await
+        .expect("failed to start Redis");
+    let redis_addr = host_addr(&redis, 6379).await;
+
+    let app_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/e2e");
+    let node = GenericImage::new("node", "20-alpine")
+        .with_exposed_port(3000.tcp())
+        .with_wait_for(WaitFor::message_on_stdout("E2E test server running"))
+        .with_network(&network)
+        .with_mount(Mount::bind_mount(app_dir.display().to_string(), "/app"))
+        .with_working_dir("/app")
+        .with_env_var("REDIS_URL", format!("redis://{}:6379", redis_name))
+        .with_env_var("SESSION_SECRET", SECRET)
+        .with_cmd(["sh", "-c", "npm ci --omit=dev && node server.js"])
+        .with_startup_timeout(Duration::from_secs(300))
+        .start()
+        .await
+        .expect("failed to start the Node.js app");
+    let node_addr = host_addr(&node, 3000).await;
+
+    let store = RedisStore::from_url(&format!("redis://{}/", redis_addr))
+        .await
+        .unwrap();
+    let config = SessionConfig::new(SECRET).with_max_age(86400);
+    let service = Service::new(
+        Router::new()
+            .hoop(ExpressSessionHandler::new(store, config))
+            .push(Router::with_path("set").get(rust_set))
+            .push(Router::with_path("get").get(rust_get))
+            .push(Router::with_path("counter").get(rust_counter)),
+    );
+
+    // Node.js -> Rust
+    let (cookie, _) = get(&node_addr, "/set?key=user&value=alice", None).await;
+    let cookie = cookie.expect("express-session set no cookie");
+    let user = TestClient::get("http://127.0.0.1/get?key=user")
+        .add_header("cookie", &cookie, true)
+        .send(&service)
+        .await
+        .take_string()
+        .await
+        .unwrap();
+    assert_eq!(user, "alice");
+
+    // Rust -> Node.js
+    let res = TestClient::get("http://127.0.0.1/set?key=user&value=bob")
+        .send(&service)
+        .await;
+    let cookie = res.cookie("connect.sid").unwrap().stripped().to_string();
+    let (_, body) = get(&node_addr, "/get?key=user", Some(&cookie)).await;
+    assert_eq!(body["value"], "bob");
+
+    // Alternating writes to one session
+    let (_, body) = get(&node_addr, "/counter", Some(&cookie)).await;
+    assert_eq!(body["counter"], 1);
+    let counter = TestClient::get("http://127.0.0.1/counter")
+        .add_header("cookie", &cookie, true)
+        .send(&service)
+        .await
+        .take_string()
+        .await
+        .unwrap();
+    assert_eq!(counter, "2");
+    let (_, body) = get(&node_addr, "/counter", Some(&cookie)).await;
+    assert_eq!(body["counter"], 3);
+}